@@ -0,0 +1,11 @@
+mod cfg;
+mod dominators;
+mod operands;
+mod register_types;
+mod verifier;
+
+pub use cfg::*;
+pub use dominators::*;
+pub(crate) use operands::*;
+pub use register_types::*;
+pub use verifier::*;
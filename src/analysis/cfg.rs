@@ -0,0 +1,386 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::raw::{
+    bytecode::{DecodeError, DecodedInstruction, Instruction, Opcode},
+    code_item::CodeItem,
+    uint, RawTypeIndex,
+};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum CfgError {
+    #[error("error decoding instructions: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("instruction at {0} targets {1}, which is not an instruction")]
+    InvalidTarget(uint, uint),
+    #[error("try block at {0} references a missing handler at offset {1}")]
+    InvalidHandler(uint, uint),
+    #[error("execution falls off the end of the code after {0}")]
+    FallsOffEnd(uint),
+}
+
+pub type BlockId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    Fallthrough,
+    /// A taken `goto`, `if-test` or `if-testz`.
+    Branch,
+    /// A case of a `packed-switch` or `sparse-switch`.
+    Switch,
+    /// An exception caught by a handler, with the index into the `type_ids` list
+    /// of the caught exception type, or `None` for a catch-all handler.
+    Exception(Option<RawTypeIndex>),
+    /// A `return` or `throw` to the exit block.
+    Exit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub block: BlockId,
+    pub kind: EdgeKind,
+}
+
+/// A maximal sequence of instructions that is only entered at its first instruction,
+/// and only left after its last one (or through an exception).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    /// Address of the first instruction in code units.
+    pub start: uint,
+    /// Address after the last instruction in code units.
+    pub end: uint,
+    /// Range of the instructions of this block in [`Cfg::instructions`].
+    pub instructions: std::ops::Range<usize>,
+    pub successors: Vec<Edge>,
+    pub predecessors: Vec<Edge>,
+}
+
+/// The control-flow graph of a method.
+///
+/// Payloads, and the `nop`s aligning them, aren't part of any block. There is a single synthetic exit block
+/// without instructions, which every `return` and every `throw` not covered by
+/// a catch-all handler has an [`EdgeKind::Exit`] edge to.
+/// Blocks covered by a try block have [`EdgeKind::Exception`] edges to each of its handlers.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    instructions: Vec<DecodedInstruction>,
+    blocks: Vec<BasicBlock>,
+}
+
+fn is_payload(insn: &DecodedInstruction) -> bool {
+    insn.instruction.opcode().is_none()
+}
+
+/// Whether the instruction at `idx` is a payload, or a `nop` padding the payload after it.
+fn is_data(instructions: &[DecodedInstruction], idx: usize) -> bool {
+    is_payload(&instructions[idx])
+        || instructions[idx].instruction.opcode() == Some(Opcode::Nop)
+            && instructions.get(idx + 1).is_some_and(is_payload)
+}
+
+impl Cfg {
+    pub fn new(code: &CodeItem) -> Result<Self, CfgError> {
+        let instructions = code.instructions().collect::<Result<Vec<_>, _>>()?;
+        let index_of = instructions
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !is_data(&instructions, *idx))
+            .map(|(idx, insn)| (insn.offset, idx))
+            .collect::<HashMap<_, _>>();
+        let check = |from: uint, target: uint| {
+            if index_of.contains_key(&target) {
+                Ok(target)
+            } else {
+                Err(CfgError::InvalidTarget(from, target))
+            }
+        };
+
+        // normal successors of each instruction ending a block
+        let mut targets = HashMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        for (_, insn) in instructions
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !is_data(&instructions, *idx))
+        {
+            let op = insn.instruction.opcode().unwrap();
+            let mut edges = Vec::new();
+            if let Some(offset) = insn.instruction.branch_offset() {
+                let target = insn.offset.wrapping_add_signed(offset);
+                edges.push((check(insn.offset, target)?, EdgeKind::Branch));
+            } else if op.is_switch() {
+                for target in code.switch_targets(insn)?.into_values() {
+                    edges.push((check(insn.offset, target)?, EdgeKind::Switch));
+                }
+            } else if op.can_continue() {
+                continue;
+            }
+            leaders.extend(edges.iter().map(|(target, _)| *target));
+            leaders.insert(insn.offset + insn.len);
+            targets.insert(insn.offset, edges);
+        }
+        // blocks must not straddle the boundaries of try blocks
+        let handlers = code.handlers.as_ref();
+        let mut tries = Vec::with_capacity(code.tries.len());
+        for try_item in &code.tries {
+            let end = try_item.start_addr + try_item.insn_count as uint;
+            let handler = handlers
+                .and_then(|handlers| handlers.handler_at(try_item.handler_off))
+                .ok_or(CfgError::InvalidHandler(
+                    try_item.start_addr,
+                    try_item.handler_off as uint,
+                ))?;
+            let mut edges = Vec::new();
+            for pair in &handler.handlers {
+                let target = check(try_item.start_addr, pair.addr as uint)?;
+                edges.push((
+                    target,
+                    EdgeKind::Exception(Some(pair.type_id as RawTypeIndex)),
+                ));
+            }
+            if let Some(addr) = handler.catch_all_addr {
+                let target = check(try_item.start_addr, addr as uint)?;
+                edges.push((target, EdgeKind::Exception(None)));
+            }
+            leaders.extend([try_item.start_addr, end]);
+            leaders.extend(edges.iter().map(|(target, _)| *target));
+            tries.push((
+                try_item.start_addr..end,
+                edges,
+                handler.catch_all_addr.is_some(),
+            ));
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (idx, insn) in instructions.iter().enumerate() {
+            if is_data(&instructions, idx) {
+                continue;
+            }
+            match blocks.last_mut() {
+                Some(block) if block.end == insn.offset && !leaders.contains(&insn.offset) => {
+                    block.end += insn.len;
+                    block.instructions.end = idx + 1;
+                }
+                _ => blocks.push(BasicBlock {
+                    id: blocks.len(),
+                    start: insn.offset,
+                    end: insn.offset + insn.len,
+                    instructions: idx..idx + 1,
+                    successors: Vec::new(),
+                    predecessors: Vec::new(),
+                }),
+            }
+        }
+        let exit = blocks.len();
+        let end = instructions.last().map_or(0, |insn| insn.offset + insn.len);
+        blocks.push(BasicBlock {
+            id: exit,
+            start: end,
+            end,
+            instructions: instructions.len()..instructions.len(),
+            successors: Vec::new(),
+            predecessors: Vec::new(),
+        });
+        let block_at = blocks
+            .iter()
+            .map(|block| (block.start, block.id))
+            .collect::<HashMap<_, _>>();
+
+        for id in 0..exit {
+            let block = &blocks[id];
+            let last = &instructions[block.instructions.end - 1];
+            let op = last.instruction.opcode().unwrap();
+            let mut edges = Vec::new();
+            for (target, kind) in targets.get(&last.offset).into_iter().flatten() {
+                edges.push((block_at[target], *kind));
+            }
+            if op.can_continue() {
+                // the next block has to start right after this one
+                match block_at.get(&block.end) {
+                    Some(next) if *next != exit => edges.push((*next, EdgeKind::Fallthrough)),
+                    _ => return Err(CfgError::FallsOffEnd(last.offset)),
+                }
+            }
+            let mut caught_all = false;
+            for (range, handlers, catch_all) in &tries {
+                if range.contains(&block.start) {
+                    edges.extend(
+                        handlers
+                            .iter()
+                            .map(|(target, kind)| (block_at[target], *kind)),
+                    );
+                    caught_all |= catch_all;
+                }
+            }
+            let throws = matches!(
+                last.instruction,
+                Instruction::Format11x {
+                    op: Opcode::Throw,
+                    ..
+                }
+            );
+            if op.is_return() || (throws && !caught_all) {
+                edges.push((exit, EdgeKind::Exit));
+            }
+            for (target, kind) in edges {
+                let edge = Edge {
+                    block: target,
+                    kind,
+                };
+                if !blocks[id].successors.contains(&edge) {
+                    blocks[id].successors.push(edge);
+                    blocks[target].predecessors.push(Edge { block: id, kind });
+                }
+            }
+        }
+        Ok(Self {
+            instructions,
+            blocks,
+        })
+    }
+
+    /// All decoded instructions of the method, including payloads.
+    pub fn instructions(&self) -> &[DecodedInstruction] {
+        &self.instructions
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id]
+    }
+
+    /// The instructions of the block `id`.
+    pub fn block_instructions(&self, id: BlockId) -> &[DecodedInstruction] {
+        &self.instructions[self.blocks[id].instructions.clone()]
+    }
+
+    /// The block at address 0.
+    pub fn entry(&self) -> BlockId {
+        0
+    }
+
+    /// The synthetic exit block.
+    pub fn exit(&self) -> BlockId {
+        self.blocks.len() - 1
+    }
+
+    pub fn successors(&self, id: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+        self.blocks[id].successors.iter().map(|edge| edge.block)
+    }
+
+    pub fn predecessors(&self, id: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+        self.blocks[id].predecessors.iter().map(|edge| edge.block)
+    }
+
+    /// Returns the block containing the instruction at `address`.
+    pub fn block_at(&self, address: uint) -> Option<BlockId> {
+        let idx = self
+            .blocks
+            .partition_point(|block| block.start <= address)
+            .checked_sub(1)?;
+        let block = &self.blocks[idx];
+        (address < block.end).then_some(block.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::bytecode::Assembler;
+
+    fn code(insns: Vec<u16>) -> CodeItem {
+        CodeItem {
+            registers_size: 2,
+            ins_size: 1,
+            outs_size: 0,
+            tries_size: 0,
+            debug_info_off: 0,
+            insns,
+            tries: Vec::new(),
+            handlers: None,
+        }
+    }
+
+    #[test]
+    fn diamond() {
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        asm.branch(Opcode::IfEqz, &[1], other)
+            .push(Instruction::Format11n {
+                op: Opcode::Const4,
+                a: 0,
+                lit: 1,
+            })
+            .branch(Opcode::Goto, &[], join)
+            .bind(other)
+            .push(Instruction::Format11n {
+                op: Opcode::Const4,
+                a: 0,
+                lit: 2,
+            })
+            .bind(join)
+            .push(Instruction::Format11x {
+                op: Opcode::Return,
+                a: 0,
+            });
+        let cfg = Cfg::new(&code(asm.assemble().unwrap())).unwrap();
+        let successors = |id| cfg.successors(id).collect::<Vec<_>>();
+        assert_eq!(cfg.blocks().len(), 5);
+        assert_eq!(successors(cfg.entry()), vec![2, 1]);
+        assert_eq!(successors(1), vec![3]);
+        assert_eq!(successors(2), vec![3]);
+        assert_eq!(successors(3), vec![cfg.exit()]);
+        assert_eq!(cfg.predecessors(3).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(cfg.block_at(3), Some(1));
+        assert_eq!(cfg.block_instructions(1).len(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        // const/4 v0, #0 falls off the end
+        assert_eq!(
+            Cfg::new(&code(vec![0x0012])).unwrap_err(),
+            CfgError::FallsOffEnd(0)
+        );
+        // goto +2 jumps into the middle of nowhere
+        assert_eq!(
+            Cfg::new(&code(vec![0x0228, 0x000e])).unwrap_err(),
+            CfgError::InvalidTarget(0, 2)
+        );
+    }
+
+    #[test]
+    fn dex() {
+        let dex = crate::t::dex!();
+        let mut exceptions = 0;
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                let cfg = Cfg::new(&code).unwrap();
+                assert_eq!(cfg.block(cfg.entry()).start, 0);
+                assert!(cfg.block(cfg.exit()).successors.is_empty());
+                for block in cfg.blocks() {
+                    for edge in &block.successors {
+                        assert!(cfg.predecessors(edge.block).any(|id| id == block.id));
+                        if let EdgeKind::Exception(_) = edge.kind {
+                            exceptions += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(exceptions > 0);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::BTreeSet;
+
+use crate::analysis::{BlockId, Cfg};
+
+/// Blocks reachable from `root` in reverse postorder,
+/// following successors or, if `reverse` is set, predecessors.
+fn reverse_postorder(cfg: &Cfg, root: BlockId, reverse: bool) -> Vec<BlockId> {
+    let next = |id: BlockId| -> Vec<BlockId> {
+        if reverse {
+            cfg.predecessors(id).collect()
+        } else {
+            cfg.successors(id).collect()
+        }
+    };
+    let mut visited = vec![false; cfg.blocks().len()];
+    let mut order = Vec::new();
+    let mut stack = vec![(root, next(root), 0)];
+    visited[root] = true;
+    while let Some((id, edges, idx)) = stack.last_mut() {
+        if let Some(&target) = edges.get(*idx) {
+            *idx += 1;
+            if !visited[target] {
+                visited[target] = true;
+                stack.push((target, next(target), 0));
+            }
+        } else {
+            order.push(*id);
+            stack.pop();
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// The dominator tree of a [`Cfg`], or its post-dominator tree.
+///
+/// Block `a` dominates `b` if every path from the entry to `b` goes through `a`,
+/// and post-dominates `b` if every path from `b` to the exit goes through `a`.
+/// Exception edges are taken into account like any other edge.
+/// Blocks that can't be reached from the root (the entry, or the exit for
+/// post-dominators) aren't part of the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominatorTree {
+    root: BlockId,
+    reverse: bool,
+    idom: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+    // position of each block in reverse postorder, used to walk up the tree
+    order: Vec<usize>,
+}
+
+impl DominatorTree {
+    /// Computes the dominators of `cfg`.
+    pub fn new(cfg: &Cfg) -> Self {
+        Self::compute(cfg, cfg.entry(), false)
+    }
+
+    /// Computes the post-dominators of `cfg`, which are rooted at [`Cfg::exit`].
+    pub fn post_dominators(cfg: &Cfg) -> Self {
+        Self::compute(cfg, cfg.exit(), true)
+    }
+
+    // See "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+    fn compute(cfg: &Cfg, root: BlockId, reverse: bool) -> Self {
+        let len = cfg.blocks().len();
+        let rpo = reverse_postorder(cfg, root, reverse);
+        let mut order = vec![usize::MAX; len];
+        for (idx, id) in rpo.iter().enumerate() {
+            order[*id] = idx;
+        }
+        let mut idom = vec![None; len];
+        idom[root] = Some(root);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in rpo.iter().skip(1) {
+                let preds: Vec<_> = if reverse {
+                    cfg.successors(id).collect()
+                } else {
+                    cfg.predecessors(id).collect()
+                };
+                let mut new_idom = None;
+                for pred in preds.into_iter().filter(|pred| idom[*pred].is_some()) {
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(mut other) => {
+                            let mut pred = pred;
+                            while pred != other {
+                                while order[pred] > order[other] {
+                                    pred = idom[pred].unwrap();
+                                }
+                                while order[other] > order[pred] {
+                                    other = idom[other].unwrap();
+                                }
+                            }
+                            pred
+                        }
+                    });
+                }
+                if new_idom != idom[id] {
+                    idom[id] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idom[root] = None;
+        let mut children = vec![Vec::new(); len];
+        for &id in &rpo {
+            if let Some(parent) = idom[id] {
+                children[parent].push(id);
+            }
+        }
+        Self {
+            root,
+            reverse,
+            idom,
+            children,
+            order,
+        }
+    }
+
+    /// The root of the tree, i.e. the entry or exit block.
+    pub fn root(&self) -> BlockId {
+        self.root
+    }
+
+    /// Whether `id` is reachable from the root.
+    pub fn contains(&self, id: BlockId) -> bool {
+        self.order[id] != usize::MAX
+    }
+
+    /// The immediate (post-)dominator of `id`,
+    /// or `None` for the root and unreachable blocks.
+    pub fn idom(&self, id: BlockId) -> Option<BlockId> {
+        self.idom[id]
+    }
+
+    /// The blocks immediately (post-)dominated by `id`.
+    pub fn children(&self, id: BlockId) -> &[BlockId] {
+        &self.children[id]
+    }
+
+    /// Whether `a` (post-)dominates `b`. Every block dominates itself.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.contains(a) || !self.contains(b) {
+            return false;
+        }
+        while self.order[b] > self.order[a] {
+            match self.idom[b] {
+                Some(parent) => b = parent,
+                None => return false,
+            }
+        }
+        a == b
+    }
+
+    /// Computes the dominance frontier of each block: the blocks where the
+    /// dominance of a block ends. For post-dominators, this is the set of blocks
+    /// each block is control dependent on.
+    pub fn frontiers(&self, cfg: &Cfg) -> Vec<BTreeSet<BlockId>> {
+        let mut frontiers = vec![BTreeSet::new(); self.idom.len()];
+        for id in (0..self.idom.len()).filter(|id| self.contains(*id)) {
+            let preds: Vec<_> = if self.reverse {
+                cfg.successors(id).collect()
+            } else {
+                cfg.predecessors(id).collect()
+            };
+            if preds.len() < 2 {
+                continue;
+            }
+            for mut runner in preds.into_iter().filter(|pred| self.contains(*pred)) {
+                while Some(runner) != self.idom[id] {
+                    frontiers[runner].insert(id);
+                    match self.idom[runner] {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+pub type LoopId = usize;
+
+/// A natural loop, consisting of all back edges to the same header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub id: LoopId,
+    /// The only block through which the loop can be entered.
+    pub header: BlockId,
+    /// Sources of the back edges to the header.
+    pub latches: Vec<BlockId>,
+    /// All blocks of the loop, including the header and any nested loops.
+    pub blocks: BTreeSet<BlockId>,
+    /// The innermost loop containing this loop.
+    pub parent: Option<LoopId>,
+    /// The loops directly nested in this loop.
+    pub children: Vec<LoopId>,
+    /// Nesting depth, starting at 1 for outermost loops.
+    pub depth: usize,
+}
+
+/// The natural loops of a [`Cfg`] and how they nest.
+///
+/// Loops are ordered outermost first, so parents always come before their children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopForest {
+    loops: Vec<Loop>,
+    innermost: Vec<Option<LoopId>>,
+}
+
+impl LoopForest {
+    pub fn new(cfg: &Cfg, dominators: &DominatorTree) -> Self {
+        // collect the back edges per header, whose target dominates their source
+        let mut found: Vec<(BlockId, Vec<BlockId>)> = Vec::new();
+        for block in cfg.blocks() {
+            for header in cfg.successors(block.id) {
+                if !dominators.dominates(header, block.id) {
+                    continue;
+                }
+                match found.iter_mut().find(|(h, _)| *h == header) {
+                    Some((_, latches)) => latches.push(block.id),
+                    None => found.push((header, vec![block.id])),
+                }
+            }
+        }
+
+        let mut loops = found
+            .into_iter()
+            .map(|(header, latches)| {
+                // everything reaching a latch without going through the header
+                let mut blocks = BTreeSet::from([header]);
+                let mut stack = latches.clone();
+                while let Some(id) = stack.pop() {
+                    if blocks.insert(id) {
+                        stack.extend(cfg.predecessors(id).filter(|p| dominators.contains(*p)));
+                    }
+                }
+                Loop {
+                    id: 0,
+                    header,
+                    latches,
+                    blocks,
+                    parent: None,
+                    children: Vec::new(),
+                    depth: 1,
+                }
+            })
+            .collect::<Vec<_>>();
+        // loops with distinct headers are either disjoint or nested
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.blocks.len()), l.header));
+
+        let mut innermost = vec![None; cfg.blocks().len()];
+        for id in 0..loops.len() {
+            loops[id].id = id;
+            // the last enclosing loop seen so far is the smallest one
+            let parent = (0..id)
+                .rev()
+                .find(|parent| loops[*parent].blocks.contains(&loops[id].header));
+            if let Some(parent) = parent {
+                loops[id].parent = Some(parent);
+                loops[id].depth = loops[parent].depth + 1;
+                loops[parent].children.push(id);
+            }
+            for block in &loops[id].blocks {
+                innermost[*block] = Some(id);
+            }
+        }
+        Self { loops, innermost }
+    }
+
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    pub fn get(&self, id: LoopId) -> &Loop {
+        &self.loops[id]
+    }
+
+    /// The outermost loops.
+    pub fn roots(&self) -> impl Iterator<Item = &Loop> {
+        self.loops.iter().filter(|l| l.parent.is_none())
+    }
+
+    /// The innermost loop containing `block`.
+    pub fn loop_of(&self, block: BlockId) -> Option<&Loop> {
+        self.innermost[block].map(|id| &self.loops[id])
+    }
+
+    /// Number of loops containing `block`.
+    pub fn depth(&self, block: BlockId) -> usize {
+        self.loop_of(block).map_or(0, |l| l.depth)
+    }
+
+    /// Whether `block` is the header of a loop.
+    pub fn is_header(&self, block: BlockId) -> bool {
+        self.loops.iter().any(|l| l.header == block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::{
+        bytecode::{Assembler, Instruction, Opcode},
+        code_item::CodeItem,
+    };
+
+    fn cfg(asm: &Assembler) -> Cfg {
+        Cfg::new(&CodeItem {
+            registers_size: 2,
+            ins_size: 2,
+            outs_size: 0,
+            tries_size: 0,
+            debug_info_off: 0,
+            insns: asm.assemble().unwrap(),
+            tries: Vec::new(),
+            handlers: None,
+        })
+        .unwrap()
+    }
+
+    fn nop() -> Instruction {
+        Instruction::Format10x { op: Opcode::Nop }
+    }
+
+    #[test]
+    fn diamond() {
+        // 0: if-eqz v0 -> 2; 1: nop, goto 3; 2: nop; 3: return-void
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        asm.branch(Opcode::IfEqz, &[0], other)
+            .push(nop())
+            .branch(Opcode::Goto, &[], join)
+            .bind(other)
+            .push(nop())
+            .bind(join)
+            .push(Instruction::Format10x {
+                op: Opcode::ReturnVoid,
+            });
+        let cfg = cfg(&asm);
+        let dom = DominatorTree::new(&cfg);
+        assert_eq!(dom.root(), 0);
+        assert_eq!(
+            (0..5).map(|id| dom.idom(id)).collect::<Vec<_>>(),
+            vec![None, Some(0), Some(0), Some(0), Some(3)]
+        );
+        assert!(dom.dominates(0, 3) && !dom.dominates(1, 3) && dom.dominates(2, 2));
+        let frontiers = dom.frontiers(&cfg);
+        assert_eq!(frontiers[1], BTreeSet::from([3]));
+        assert_eq!(frontiers[2], BTreeSet::from([3]));
+        assert!(frontiers[0].is_empty());
+
+        let pdom = DominatorTree::post_dominators(&cfg);
+        assert_eq!(pdom.root(), 4);
+        assert_eq!(pdom.idom(0), Some(3));
+        assert_eq!(pdom.idom(1), Some(3));
+        // the branches are control dependent on the entry
+        assert_eq!(pdom.frontiers(&cfg)[1], BTreeSet::from([0]));
+        assert!(LoopForest::new(&cfg, &dom).loops().is_empty());
+    }
+
+    #[test]
+    fn nested_loops() {
+        // outer: if-eqz v0 -> done
+        //   inner: if-eqz v1 -> outer
+        //     nop, goto inner
+        // done: return-void
+        let mut asm = Assembler::new();
+        let (outer, inner, done) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.bind(outer)
+            .branch(Opcode::IfEqz, &[0], done)
+            .bind(inner)
+            .branch(Opcode::IfEqz, &[1], outer)
+            .push(nop())
+            .branch(Opcode::Goto, &[], inner)
+            .bind(done)
+            .push(Instruction::Format10x {
+                op: Opcode::ReturnVoid,
+            });
+        let cfg = cfg(&asm);
+        let dom = DominatorTree::new(&cfg);
+        let loops = LoopForest::new(&cfg, &dom);
+        assert_eq!(loops.loops().len(), 2);
+        let (outer, inner) = (loops.get(0), loops.get(1));
+        assert_eq!(outer.header, 0);
+        assert_eq!(outer.blocks, BTreeSet::from([0, 1, 2]));
+        assert_eq!(outer.latches, vec![1]);
+        assert_eq!(outer.children, vec![1]);
+        assert_eq!(inner.header, 1);
+        assert_eq!(inner.blocks, BTreeSet::from([1, 2]));
+        assert_eq!(inner.parent, Some(0));
+        assert_eq!(loops.depth(2), 2);
+        assert_eq!(loops.depth(0), 1);
+        assert_eq!(loops.depth(3), 0);
+        assert!(loops.is_header(1) && !loops.is_header(2));
+        assert_eq!(loops.roots().count(), 1);
+    }
+
+    #[test]
+    fn dex() {
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                let cfg = Cfg::new(&code).unwrap();
+                let dom = DominatorTree::new(&cfg);
+                let pdom = DominatorTree::post_dominators(&cfg);
+                for block in cfg.blocks().iter().filter(|b| dom.contains(b.id)) {
+                    assert!(dom.dominates(cfg.entry(), block.id));
+                    if pdom.contains(block.id) {
+                        assert!(pdom.dominates(cfg.exit(), block.id));
+                    }
+                }
+                for l in LoopForest::new(&cfg, &dom).loops() {
+                    assert!(l.blocks.iter().all(|b| dom.dominates(l.header, *b)));
+                }
+            }
+        }
+    }
+}
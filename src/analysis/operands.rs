@@ -0,0 +1,85 @@
+use crate::{analysis::TypeCategory, raw::bytecode::Opcode};
+
+/// What each register operand of `op` holds, in operand order, for opcodes whose operand
+/// types follow from the opcode alone.
+///
+/// The first operand of an operation that produces a value is its destination;
+/// `/2addr` operations also read it as their first source.
+pub(crate) fn operand_types(op: Opcode) -> Option<&'static [TypeCategory]> {
+    use Opcode::*;
+    use TypeCategory::*;
+    Some(match op {
+        Move | MoveFrom16 | Move16 => &[Primitive, Primitive],
+        MoveWide | MoveWideFrom16 | MoveWide16 => &[Wide, Wide],
+        MoveObject | MoveObjectFrom16 | MoveObject16 => &[Object, Object],
+        MoveResult | Return | Const4 | Const16 | Const | ConstHigh16 => &[Primitive],
+        MoveResultWide | ReturnWide | ConstWide16 | ConstWide32 | ConstWide | ConstWideHigh16 => {
+            &[Wide]
+        }
+        MoveResultObject | MoveException | ReturnObject | ConstString | ConstStringJumbo
+        | ConstClass | ConstMethodHandle | ConstMethodType | MonitorEnter | MonitorExit
+        | CheckCast | NewInstance | Throw => &[Reference],
+        InstanceOf => &[Integer, Reference],
+        ArrayLength => &[Integer, Array],
+        NewArray => &[Array, Integer],
+        FillArrayData => &[Array],
+        PackedSwitch | SparseSwitch | IfLtz | IfGez | IfGtz | IfLez => &[Integer],
+        CmplFloat | CmpgFloat => &[Integer, Float, Float],
+        CmplDouble | CmpgDouble => &[Integer, Double, Double],
+        CmpLong => &[Integer, Long, Long],
+        IfLt | IfGe | IfGt | IfLe => &[Integer, Integer],
+        Aget | Aput => &[Primitive, Array, Integer],
+        AgetWide | AputWide => &[Wide, Array, Integer],
+        AgetObject | AputObject => &[Reference, Array, Integer],
+        AgetBoolean | AgetByte | AgetChar | AgetShort | AputBoolean | AputByte | AputChar
+        | AputShort => &[Integer, Array, Integer],
+        Iget | Iput => &[Primitive, Reference],
+        IgetWide | IputWide => &[Wide, Reference],
+        IgetObject | IputObject => &[Reference, Reference],
+        IgetBoolean | IgetByte | IgetChar | IgetShort | IputBoolean | IputByte | IputChar
+        | IputShort => &[Integer, Reference],
+        Sget | Sput => &[Primitive],
+        SgetWide | SputWide => &[Wide],
+        SgetObject | SputObject => &[Reference],
+        SgetBoolean | SgetByte | SgetChar | SgetShort | SputBoolean | SputByte | SputChar
+        | SputShort => &[Integer],
+        NegInt | NotInt | IntToByte | IntToChar | IntToShort => &[Integer, Integer],
+        NegLong | NotLong => &[Long, Long],
+        NegFloat => &[Float, Float],
+        NegDouble => &[Double, Double],
+        IntToLong => &[Long, Integer],
+        IntToFloat => &[Float, Integer],
+        IntToDouble => &[Double, Integer],
+        LongToInt => &[Integer, Long],
+        LongToFloat => &[Float, Long],
+        LongToDouble => &[Double, Long],
+        FloatToInt => &[Integer, Float],
+        FloatToLong => &[Long, Float],
+        FloatToDouble => &[Double, Float],
+        DoubleToInt => &[Integer, Double],
+        DoubleToLong => &[Long, Double],
+        DoubleToFloat => &[Float, Double],
+        op if (AddInt..=UshrInt).contains(&op) => &[Integer, Integer, Integer],
+        op if (ShlLong..=UshrLong).contains(&op) => &[Long, Long, Integer],
+        op if (AddLong..=XorLong).contains(&op) => &[Long, Long, Long],
+        op if (AddFloat..=RemFloat).contains(&op) => &[Float, Float, Float],
+        op if (AddDouble..=RemDouble).contains(&op) => &[Double, Double, Double],
+        op if (AddInt2Addr..=UshrInt2Addr).contains(&op) => &[Integer, Integer],
+        op if (ShlLong2Addr..=UshrLong2Addr).contains(&op) => &[Long, Integer],
+        op if (AddLong2Addr..=XorLong2Addr).contains(&op) => &[Long, Long],
+        op if (AddFloat2Addr..=RemFloat2Addr).contains(&op) => &[Float, Float],
+        op if (AddDouble2Addr..=RemDouble2Addr).contains(&op) => &[Double, Double],
+        op if (AddIntLit16..=UshrIntLit8).contains(&op) => &[Integer, Integer],
+        _ => return None,
+    })
+}
+
+/// Whether `op` is a `/2addr` operation, which reads its destination as well.
+pub(crate) fn is_two_addr(op: Opcode) -> bool {
+    (Opcode::AddInt2Addr..=Opcode::RemDouble2Addr).contains(&op)
+}
+
+/// Whether `op` is a unary, binary or literal arithmetic operation.
+pub(crate) fn is_arithmetic(op: Opcode) -> bool {
+    (Opcode::NegInt..=Opcode::UshrIntLit8).contains(&op)
+}
@@ -0,0 +1,711 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    analysis::{is_arithmetic, operand_types, BlockId, Cfg, EdgeKind, TypeCategory},
+    dex::{class::Method, strings::DexString, DexFile},
+    raw::{
+        bytecode::{DecodedInstruction, Instruction, Opcode},
+        code_item::CodeItem,
+        flags::AccessFlags,
+        uint, ushort,
+    },
+};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TypeInferenceError {
+    #[error("register v{0} used at offset {1} is out of bounds")]
+    RegisterOutOfBounds(uint, uint),
+    #[error("ins_size is {0}, but the prototype needs {1} registers")]
+    InsSizeMismatch(ushort, uint),
+    #[error("ins_size {0} is larger than registers_size {1}")]
+    TooManyIns(ushort, ushort),
+}
+
+/// The type of a register at a given instruction, as inferred by [`RegisterTypes`].
+///
+/// Wide values occupy two registers, the low half of which holds the `*Lo` type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RegisterType {
+    /// The register hasn't been assigned on every path.
+    Undefined,
+    /// The register holds incompatible types on different paths.
+    Conflict,
+    /// The constant `0`, which may be used as `null`, `false` or any numeric zero.
+    Zero,
+    /// A non-zero 32-bit constant, which may be used as any 32-bit primitive.
+    Constant,
+    Boolean,
+    Byte,
+    Short,
+    Char,
+    Integer,
+    Float,
+    LongLo,
+    LongHi,
+    DoubleLo,
+    DoubleHi,
+    /// A 64-bit constant, which may be used as a `long` or `double`.
+    WideConstantLo,
+    WideConstantHi,
+    /// A reference to an instance of the type with the given descriptor.
+    Reference(DexString),
+    /// `this` in a constructor, before the superclass constructor is called.
+    UninitializedThis(DexString),
+    /// The result of the `new-instance` at `address`, before its constructor is called.
+    Uninitialized {
+        ty: DexString,
+        address: uint,
+    },
+}
+
+impl RegisterType {
+    /// Returns the type of a value with the given descriptor, or `None` for `void`.
+    pub fn from_descriptor(descriptor: &DexString) -> Option<Self> {
+        Some(match descriptor.as_bytes().first()? {
+            b'V' => return None,
+            b'Z' => Self::Boolean,
+            b'B' => Self::Byte,
+            b'S' => Self::Short,
+            b'C' => Self::Char,
+            b'I' => Self::Integer,
+            b'F' => Self::Float,
+            b'J' => Self::LongLo,
+            b'D' => Self::DoubleLo,
+            _ => Self::Reference(descriptor.clone()),
+        })
+    }
+
+    fn reference(descriptor: &str) -> Self {
+        Self::Reference(DexString::new(descriptor.to_owned()))
+    }
+
+    /// The type of the high half, if this is the low half of a wide type.
+    pub fn high_half(&self) -> Option<Self> {
+        match self {
+            Self::LongLo => Some(Self::LongHi),
+            Self::DoubleLo => Some(Self::DoubleHi),
+            Self::WideConstantLo => Some(Self::WideConstantHi),
+            _ => None,
+        }
+    }
+
+    /// Whether this is `boolean`, `byte`, `short`, `char` or `int`.
+    pub fn is_integral(&self) -> bool {
+        matches!(
+            self,
+            Self::Boolean | Self::Byte | Self::Short | Self::Char | Self::Integer
+        )
+    }
+
+    /// Whether this is a reference, including uninitialized ones.
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Reference(_) | Self::UninitializedThis(_) | Self::Uninitialized { .. }
+        )
+    }
+
+    /// The element type, if this is an array reference.
+    pub fn component(&self) -> Option<Self> {
+        match self {
+            Self::Reference(ty) => {
+                Self::from_descriptor(&DexString::new(ty.strip_prefix('[')?.into()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The least upper bound of both types.
+    ///
+    /// Since the class hierarchy isn't known,
+    /// distinct references merge to `Ljava/lang/Object;`.
+    pub fn merge(&self, other: &Self) -> Self {
+        use RegisterType::*;
+        if self == other {
+            return self.clone();
+        }
+        match (self, other) {
+            (Zero, x) | (x, Zero)
+                if x.is_integral() || matches!(x, Constant | Float | Reference(_)) =>
+            {
+                x.clone()
+            }
+            (Constant, x) | (x, Constant) if x.is_integral() || *x == Float => x.clone(),
+            (WideConstantLo, x @ (LongLo | DoubleLo))
+            | (x @ (LongLo | DoubleLo), WideConstantLo) => x.clone(),
+            (WideConstantHi, x @ (LongHi | DoubleHi))
+            | (x @ (LongHi | DoubleHi), WideConstantHi) => x.clone(),
+            (Boolean, x) | (x, Boolean) if x.is_integral() => x.clone(),
+            (Byte, Short) | (Short, Byte) => Short,
+            (a, b) if a.is_integral() && b.is_integral() => Integer,
+            (Reference(_), Reference(_)) => Self::reference("Ljava/lang/Object;"),
+            _ => Conflict,
+        }
+    }
+}
+
+impl std::fmt::Display for RegisterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Undefined => f.write_str("undefined"),
+            Self::Conflict => f.write_str("conflict"),
+            Self::Zero => f.write_str("zero"),
+            Self::Constant => f.write_str("const"),
+            Self::Boolean => f.write_str("boolean"),
+            Self::Byte => f.write_str("byte"),
+            Self::Short => f.write_str("short"),
+            Self::Char => f.write_str("char"),
+            Self::Integer => f.write_str("int"),
+            Self::Float => f.write_str("float"),
+            Self::LongLo => f.write_str("long"),
+            Self::LongHi => f.write_str("long-hi"),
+            Self::DoubleLo => f.write_str("double"),
+            Self::DoubleHi => f.write_str("double-hi"),
+            Self::WideConstantLo => f.write_str("const-wide"),
+            Self::WideConstantHi => f.write_str("const-wide-hi"),
+            Self::Reference(ty) => f.write_str(ty),
+            Self::UninitializedThis(ty) => write!(f, "uninit-this {ty}"),
+            Self::Uninitialized { ty, address } => write!(f, "uninit@{address} {ty}"),
+        }
+    }
+}
+
+/// The register types at some point, plus the pending result of the last invoke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    registers: Vec<RegisterType>,
+    result: Option<RegisterType>,
+}
+
+impl Frame {
+    fn get(&self, reg: uint) -> RegisterType {
+        self.registers[reg as usize].clone()
+    }
+
+    fn set(&mut self, reg: uint, ty: RegisterType) {
+        let reg = reg as usize;
+        // overwriting one half of a wide value invalidates the other half
+        if reg > 0 && self.registers[reg - 1].high_half().is_some() {
+            self.registers[reg - 1] = RegisterType::Conflict;
+        }
+        if self.registers[reg].high_half().is_some() {
+            self.registers[reg + 1] = RegisterType::Conflict;
+        }
+        if let Some(high) = ty.high_half() {
+            self.registers[reg + 1] = high;
+        }
+        self.registers[reg] = ty;
+    }
+
+    /// Merges `other` into this frame, returning whether anything changed.
+    fn merge(&mut self, other: &Frame) -> bool {
+        let mut changed = false;
+        for (ty, other) in self.registers.iter_mut().zip(&other.registers) {
+            let merged = ty.merge(other);
+            changed |= merged != *ty;
+            *ty = merged;
+        }
+        if self.result != other.result && self.result.is_some() {
+            self.result = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Marks the uninitialized reference in `reg`, and all of its copies, as initialized.
+    fn initialize(&mut self, reg: uint) {
+        let (RegisterType::UninitializedThis(ty) | RegisterType::Uninitialized { ty, .. }) =
+            self.get(reg)
+        else {
+            return;
+        };
+        let uninitialized = self.get(reg);
+        for register in &mut self.registers {
+            if *register == uninitialized {
+                *register = RegisterType::Reference(ty.clone());
+            }
+        }
+    }
+}
+
+/// The inferred type of every register before every reachable instruction of a method.
+///
+/// Types are seeded from the prototype of the method, and refined by the
+/// instructions and the types of the fields, methods and types they reference.
+#[derive(Debug, Clone)]
+pub struct RegisterTypes {
+    cfg: Cfg,
+    // indexed like the instructions of the cfg, `None` if unreachable
+    states: Vec<Option<Vec<RegisterType>>>,
+}
+
+impl RegisterTypes {
+    pub fn new(method: &Method, code: &CodeItem) -> crate::Result<Self> {
+        let cfg = Cfg::new(code)?;
+        let dex = method.dex();
+        let inference = Inference {
+            dex,
+            cfg: &cfg,
+            registers_size: code.registers_size as uint,
+        };
+        let states = inference.run(method, code)?;
+        Ok(Self { cfg, states })
+    }
+
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    /// The types of all registers before the instruction at `address`,
+    /// or `None` if there's no reachable instruction there.
+    pub fn at(&self, address: uint) -> Option<&[RegisterType]> {
+        let idx = self
+            .cfg
+            .instructions()
+            .binary_search_by_key(&address, |insn| insn.offset)
+            .ok()?;
+        self.states[idx].as_deref()
+    }
+
+    /// The type of register `reg` before the instruction at `address`.
+    pub fn get(&self, address: uint, reg: uint) -> Option<&RegisterType> {
+        self.at(address)?.get(reg as usize)
+    }
+}
+
+struct Inference<'a> {
+    dex: &'a DexFile<'a>,
+    cfg: &'a Cfg,
+    registers_size: uint,
+}
+
+impl Inference<'_> {
+    fn run(
+        &self,
+        method: &Method,
+        code: &CodeItem,
+    ) -> crate::Result<Vec<Option<Vec<RegisterType>>>> {
+        let blocks = self.cfg.blocks();
+        let mut entries: Vec<Option<Frame>> = vec![None; blocks.len()];
+        entries[self.cfg.entry()] = Some(self.initial_frame(method, code)?);
+        let mut states = vec![None; self.cfg.instructions().len()];
+        let mut worklist = BTreeSet::from([self.cfg.entry()]);
+
+        while let Some(id) = worklist.pop_first() {
+            let block = &blocks[id];
+            let mut frame = entries[id].clone().unwrap();
+            let handlers = block
+                .successors
+                .iter()
+                .filter(|edge| matches!(edge.kind, EdgeKind::Exception(_)))
+                .map(|edge| edge.block)
+                .collect::<Vec<_>>();
+            let mut exception = self.exception_type(id)?;
+            for idx in block.instructions.clone() {
+                states[idx] = Some(frame.registers.clone());
+                // every instruction in a try block may throw before changing any register
+                for handler in &handlers {
+                    let state = Frame {
+                        registers: frame.registers.clone(),
+                        result: None,
+                    };
+                    if merge_into(&mut entries[*handler], &state) {
+                        worklist.insert(*handler);
+                    }
+                }
+                let insn = &self.cfg.instructions()[idx];
+                self.step(&mut frame, insn, exception.take())?;
+            }
+            for edge in &block.successors {
+                if matches!(
+                    edge.kind,
+                    EdgeKind::Fallthrough | EdgeKind::Branch | EdgeKind::Switch
+                ) && merge_into(&mut entries[edge.block], &frame)
+                {
+                    worklist.insert(edge.block);
+                }
+            }
+        }
+        Ok(states)
+    }
+
+    fn initial_frame(&self, method: &Method, code: &CodeItem) -> crate::Result<Frame> {
+        if code.ins_size > code.registers_size {
+            Err(TypeInferenceError::TooManyIns(
+                code.ins_size,
+                code.registers_size,
+            ))?;
+        }
+        let method_ref = method.method_ref()?;
+        let mut ins = Vec::new();
+        if !method.access_flags.contains(AccessFlags::Static) {
+            let class = method_ref.class.clone();
+            ins.push(
+                if method_ref.name.as_str() == "<init>" && class.as_str() != "Ljava/lang/Object;" {
+                    RegisterType::UninitializedThis(class)
+                } else {
+                    RegisterType::Reference(class)
+                },
+            );
+        }
+        for param in &method_ref.proto.parameters {
+            let ty = RegisterType::from_descriptor(param).unwrap_or(RegisterType::Conflict);
+            if let Some(high) = ty.high_half() {
+                ins.extend([ty, high]);
+            } else {
+                ins.push(ty);
+            }
+        }
+        if ins.len() != code.ins_size as usize {
+            Err(TypeInferenceError::InsSizeMismatch(
+                code.ins_size,
+                ins.len() as uint,
+            ))?;
+        }
+        let mut registers = vec![RegisterType::Undefined; self.registers_size as usize];
+        registers[(code.registers_size - code.ins_size) as usize..].clone_from_slice(&ins);
+        Ok(Frame {
+            registers,
+            result: None,
+        })
+    }
+
+    /// The type caught by `move-exception` at the start of block `id`.
+    fn exception_type(&self, id: BlockId) -> crate::Result<Option<RegisterType>> {
+        let mut caught: Option<RegisterType> = None;
+        for edge in &self.cfg.block(id).predecessors {
+            let EdgeKind::Exception(ty) = edge.kind else {
+                continue;
+            };
+            let ty = match ty {
+                Some(idx) => RegisterType::Reference(self.dex.types().get(idx)?),
+                None => RegisterType::reference("Ljava/lang/Throwable;"),
+            };
+            caught = Some(match caught {
+                Some(other) if other != ty => RegisterType::reference("Ljava/lang/Throwable;"),
+                _ => ty,
+            });
+        }
+        Ok(caught)
+    }
+
+    fn step(
+        &self,
+        frame: &mut Frame,
+        insn: &DecodedInstruction,
+        exception: Option<RegisterType>,
+    ) -> crate::Result<()> {
+        use RegisterType::*;
+        let instruction = &insn.instruction;
+        let op = instruction.opcode().unwrap();
+        let regs = instruction
+            .registers()
+            .into_iter()
+            .map(|reg| reg as uint)
+            .collect::<Vec<_>>();
+        let operands = operand_types(op).unwrap_or_default();
+        for (i, reg) in regs.iter().enumerate() {
+            // wide operands also use the next register
+            let wide = operands.get(i).is_some_and(|category| category.is_wide());
+            let last = if wide { reg + 1 } else { *reg };
+            if last >= self.registers_size {
+                Err(TypeInferenceError::RegisterOutOfBounds(last, insn.offset))?;
+            }
+        }
+        let index = instruction.index().unwrap_or_default();
+        let ty = |index| -> crate::Result<DexString> { Ok(self.dex.types().get(index)?) };
+        let set = |frame: &mut Frame, reg: uint, ty: RegisterType| {
+            if ty.high_half().is_some() && reg + 1 >= self.registers_size {
+                return Err(TypeInferenceError::RegisterOutOfBounds(
+                    reg + 1,
+                    insn.offset,
+                ));
+            }
+            frame.set(reg, ty);
+            Ok(())
+        };
+        let result = frame.result.take();
+
+        let value = match op {
+            Opcode::Move
+            | Opcode::MoveFrom16
+            | Opcode::Move16
+            | Opcode::MoveWide
+            | Opcode::MoveWideFrom16
+            | Opcode::MoveWide16
+            | Opcode::MoveObject
+            | Opcode::MoveObjectFrom16
+            | Opcode::MoveObject16 => Some(frame.get(regs[1])),
+            Opcode::MoveResult | Opcode::MoveResultWide | Opcode::MoveResultObject => {
+                Some(result.unwrap_or(Conflict))
+            }
+            Opcode::MoveException => Some(exception.unwrap_or(Conflict)),
+            Opcode::Const4 | Opcode::Const16 | Opcode::Const | Opcode::ConstHigh16 => {
+                Some(match instruction.literal() {
+                    Some(0) => Zero,
+                    _ => Constant,
+                })
+            }
+            Opcode::ConstWide16
+            | Opcode::ConstWide32
+            | Opcode::ConstWide
+            | Opcode::ConstWideHigh16 => Some(WideConstantLo),
+            Opcode::ConstString | Opcode::ConstStringJumbo => {
+                Some(RegisterType::reference("Ljava/lang/String;"))
+            }
+            Opcode::ConstClass => Some(RegisterType::reference("Ljava/lang/Class;")),
+            Opcode::ConstMethodHandle => {
+                Some(RegisterType::reference("Ljava/lang/invoke/MethodHandle;"))
+            }
+            Opcode::ConstMethodType => {
+                Some(RegisterType::reference("Ljava/lang/invoke/MethodType;"))
+            }
+            Opcode::CheckCast | Opcode::NewArray => Some(Reference(ty(index)?)),
+            Opcode::InstanceOf => Some(Boolean),
+            Opcode::ArrayLength => Some(Integer),
+            Opcode::NewInstance => Some(Uninitialized {
+                ty: ty(index)?,
+                address: insn.offset,
+            }),
+            Opcode::FilledNewArray | Opcode::FilledNewArrayRange => {
+                frame.result = Some(Reference(ty(index)?));
+                None
+            }
+            Opcode::CmplFloat
+            | Opcode::CmpgFloat
+            | Opcode::CmplDouble
+            | Opcode::CmpgDouble
+            | Opcode::CmpLong => Some(Integer),
+            Opcode::Aget
+            | Opcode::AgetWide
+            | Opcode::AgetObject
+            | Opcode::AgetBoolean
+            | Opcode::AgetByte
+            | Opcode::AgetChar
+            | Opcode::AgetShort => Some(match frame.get(regs[1]).component() {
+                // the untyped variants take their type from the array
+                Some(element)
+                    if matches!(op, Opcode::Aget | Opcode::AgetWide | Opcode::AgetObject) =>
+                {
+                    element
+                }
+                _ => match op {
+                    Opcode::Aget => Integer,
+                    Opcode::AgetWide => LongLo,
+                    Opcode::AgetObject => RegisterType::reference("Ljava/lang/Object;"),
+                    Opcode::AgetBoolean => Boolean,
+                    Opcode::AgetByte => Byte,
+                    Opcode::AgetChar => Char,
+                    _ => Short,
+                },
+            }),
+            Opcode::Iget
+            | Opcode::IgetWide
+            | Opcode::IgetObject
+            | Opcode::IgetBoolean
+            | Opcode::IgetByte
+            | Opcode::IgetChar
+            | Opcode::IgetShort
+            | Opcode::Sget
+            | Opcode::SgetWide
+            | Opcode::SgetObject
+            | Opcode::SgetBoolean
+            | Opcode::SgetByte
+            | Opcode::SgetChar
+            | Opcode::SgetShort => RegisterType::from_descriptor(&self.dex.fields().get(index)?.ty),
+            Opcode::InvokeVirtual
+            | Opcode::InvokeSuper
+            | Opcode::InvokeDirect
+            | Opcode::InvokeStatic
+            | Opcode::InvokeInterface
+            | Opcode::InvokeVirtualRange
+            | Opcode::InvokeSuperRange
+            | Opcode::InvokeDirectRange
+            | Opcode::InvokeStaticRange
+            | Opcode::InvokeInterfaceRange => {
+                let method = self.dex.methods().get(index)?;
+                if method.name.as_str() == "<init>" {
+                    if let Some(receiver) = regs.first() {
+                        frame.initialize(*receiver);
+                    }
+                }
+                frame.result = RegisterType::from_descriptor(&method.proto.return_type);
+                None
+            }
+            Opcode::InvokePolymorphic | Opcode::InvokePolymorphicRange => {
+                let proto = match *instruction {
+                    Instruction::Format45cc { proto, .. }
+                    | Instruction::Format4rcc { proto, .. } => proto,
+                    _ => unreachable!(),
+                };
+                frame.result =
+                    RegisterType::from_descriptor(&self.dex.protos().get(proto)?.return_type);
+                None
+            }
+            Opcode::InvokeCustom | Opcode::InvokeCustomRange => {
+                let call_site = self.dex.call_sites().get(index)?;
+                frame.result = RegisterType::from_descriptor(&call_site.method_type.return_type);
+                None
+            }
+            Opcode::NegInt
+            | Opcode::NotInt
+            | Opcode::LongToInt
+            | Opcode::FloatToInt
+            | Opcode::DoubleToInt => Some(Integer),
+            Opcode::NegLong
+            | Opcode::NotLong
+            | Opcode::IntToLong
+            | Opcode::FloatToLong
+            | Opcode::DoubleToLong => Some(LongLo),
+            Opcode::NegFloat | Opcode::IntToFloat | Opcode::LongToFloat | Opcode::DoubleToFloat => {
+                Some(Float)
+            }
+            Opcode::NegDouble
+            | Opcode::IntToDouble
+            | Opcode::LongToDouble
+            | Opcode::FloatToDouble => Some(DoubleLo),
+            Opcode::IntToByte => Some(Byte),
+            Opcode::IntToChar => Some(Char),
+            Opcode::IntToShort => Some(Short),
+            // and, or and xor of booleans stay boolean
+            Opcode::AndInt | Opcode::OrInt | Opcode::XorInt
+                if frame.get(regs[1]) == Boolean && frame.get(regs[2]) == Boolean =>
+            {
+                Some(Boolean)
+            }
+            Opcode::AndInt2Addr | Opcode::OrInt2Addr | Opcode::XorInt2Addr
+                if frame.get(regs[0]) == Boolean && frame.get(regs[1]) == Boolean =>
+            {
+                Some(Boolean)
+            }
+            Opcode::AndIntLit16
+            | Opcode::OrIntLit16
+            | Opcode::XorIntLit16
+            | Opcode::AndIntLit8
+            | Opcode::OrIntLit8
+            | Opcode::XorIntLit8
+                if frame.get(regs[1]) == Boolean
+                    && matches!(instruction.literal(), Some(0 | 1)) =>
+            {
+                Some(Boolean)
+            }
+            // the remaining arithmetic produces the type of its destination operand
+            op if is_arithmetic(op) => match operands.first() {
+                Some(TypeCategory::Long) => Some(LongLo),
+                Some(TypeCategory::Float) => Some(Float),
+                Some(TypeCategory::Double) => Some(DoubleLo),
+                _ => Some(Integer),
+            },
+            // everything else doesn't write a register
+            _ => None,
+        };
+        if let Some(value) = value {
+            set(frame, regs[0], value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Merges `frame` into the entry state of a block, returning whether it changed.
+fn merge_into(entry: &mut Option<Frame>, frame: &Frame) -> bool {
+    match entry {
+        Some(entry) => entry.merge(frame),
+        None => {
+            *entry = Some(frame.clone());
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::class::Method;
+
+    fn method<'a>(dex: &'a DexFile<'a>, class: &str, name: &str) -> Method<'a> {
+        let class = dex.class_by_descriptor(class).unwrap().unwrap();
+        class
+            .direct_methods()
+            .unwrap()
+            .into_iter()
+            .chain(class.virtual_methods().unwrap())
+            .find(|m| m.method_ref().unwrap().name.as_str() == name)
+            .unwrap()
+    }
+
+    fn types(method: &Method) -> RegisterTypes {
+        RegisterTypes::new(method, &method.code().unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn merge() {
+        use RegisterType::*;
+        let object = RegisterType::reference("Ljava/lang/Object;");
+        let string = RegisterType::reference("Ljava/lang/String;");
+        assert_eq!(Zero.merge(&Integer), Integer);
+        assert_eq!(Zero.merge(&string), string);
+        assert_eq!(Constant.merge(&Float), Float);
+        assert_eq!(Boolean.merge(&Byte), Byte);
+        assert_eq!(Byte.merge(&Char), Integer);
+        assert_eq!(Integer.merge(&Float), Conflict);
+        assert_eq!(WideConstantLo.merge(&DoubleLo), DoubleLo);
+        assert_eq!(string.merge(&RegisterType::reference("[I")), object);
+        assert_eq!(Undefined.merge(&Integer), Conflict);
+        assert_eq!(string.component(), None);
+        assert_eq!(RegisterType::reference("[I").component(), Some(Integer));
+    }
+
+    #[test]
+    fn dex() {
+        use RegisterType::*;
+        let dex = crate::t::dex!();
+
+        // this is uninitialized until the superclass constructor is called
+        let init = types(&method(&dex, "Lcom/example/Base;", "<init>"));
+        let base = DexString::new("Lcom/example/Base;".into());
+        assert_eq!(init.at(0).unwrap(), &[UninitializedThis(base.clone())]);
+        assert_eq!(init.at(3).unwrap(), &[Reference(base)]);
+
+        // the counter merges its initial zero with the result of add-int
+        let l = types(&method(&dex, "Lcom/example/Main;", "loop"));
+        assert_eq!(l.at(0).unwrap(), &[Undefined, Integer]);
+        assert_eq!(l.at(1).unwrap(), &[Integer, Integer]);
+        assert_eq!(l.at(7).unwrap(), &[Integer, Integer]);
+
+        let run = types(&method(&dex, "Lcom/example/Main;", "run"));
+        assert_eq!(run.get(4, 0), Some(&Integer));
+        assert_eq!(run.get(8, 0), Some(&Constant));
+        assert_eq!(
+            run.get(4, 2),
+            Some(&RegisterType::reference("Lcom/example/Main;"))
+        );
+
+        let try_catch = types(&method(&dex, "Lcom/example/Main;", "tryCatch"));
+        assert_eq!(
+            try_catch.get(9, 0),
+            Some(&RegisterType::reference("Ljava/io/PrintStream;"))
+        );
+        assert_eq!(
+            try_catch.get(14, 0),
+            Some(&RegisterType::reference("Ljava/lang/Exception;"))
+        );
+        assert_eq!(
+            try_catch.get(16, 0),
+            Some(&RegisterType::reference("Ljava/lang/Throwable;"))
+        );
+
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                if let Some(code) = method.code().unwrap() {
+                    RegisterTypes::new(&method, &code).unwrap();
+                }
+            }
+        }
+    }
+}
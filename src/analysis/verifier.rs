@@ -0,0 +1,745 @@
+use std::sync::Arc;
+
+use crate::{
+    analysis::{
+        is_two_addr, operand_types, CfgError, EdgeKind, RegisterType, RegisterTypes,
+        TypeInferenceError,
+    },
+    dex::{class::Method, methods::MethodRef, strings::DexString},
+    raw::{
+        bytecode::{DecodeError, DecodedInstruction, Instruction, Opcode},
+        code_item::CodeItem,
+        uint, ushort,
+    },
+};
+
+/// What an operand is expected to hold, as reported by [`VerifyErrorKind::TypeMismatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeCategory {
+    /// `boolean`, `byte`, `short`, `char` or `int`.
+    Integer,
+    Float,
+    /// Either an integer or a `float`.
+    Primitive,
+    Long,
+    Double,
+    /// Either a `long` or a `double`.
+    Wide,
+    /// An initialized reference or `null`.
+    Reference,
+    /// An array reference or `null`.
+    Array,
+    /// Any reference, including uninitialized ones.
+    Object,
+}
+
+impl TypeCategory {
+    fn of(descriptor: &str) -> Self {
+        match descriptor.as_bytes().first() {
+            Some(b'Z' | b'B' | b'S' | b'C' | b'I') => Self::Integer,
+            Some(b'F') => Self::Float,
+            Some(b'J') => Self::Long,
+            Some(b'D') => Self::Double,
+            _ => Self::Reference,
+        }
+    }
+
+    pub(crate) fn is_wide(self) -> bool {
+        matches!(self, Self::Long | Self::Double | Self::Wide)
+    }
+
+    /// Whether a register of type `ty`, followed by one of type `high`, fits this category.
+    fn accepts(self, ty: &RegisterType, high: Option<&RegisterType>) -> bool {
+        use RegisterType::*;
+        let integer = ty.is_integral() || matches!(ty, Zero | Constant);
+        let float = matches!(ty, Zero | Constant | Float);
+        let long = matches!(
+            (ty, high),
+            (LongLo | WideConstantLo, Some(LongHi | WideConstantHi))
+        );
+        let double = matches!(
+            (ty, high),
+            (DoubleLo | WideConstantLo, Some(DoubleHi | WideConstantHi))
+        );
+        match self {
+            Self::Integer => integer,
+            Self::Float => float,
+            Self::Primitive => integer || float,
+            Self::Long => long,
+            Self::Double => double,
+            Self::Wide => long || double,
+            Self::Reference => matches!(ty, Zero | Reference(_)),
+            Self::Array => match ty {
+                Zero => true,
+                Reference(ty) => ty.starts_with('['),
+                _ => false,
+            },
+            Self::Object => *ty == Zero || ty.is_reference(),
+        }
+    }
+}
+
+impl std::fmt::Display for TypeCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Integer => "int",
+            Self::Float => "float",
+            Self::Primitive => "int or float",
+            Self::Long => "long",
+            Self::Double => "double",
+            Self::Wide => "long or double",
+            Self::Reference => "reference",
+            Self::Array => "array",
+            Self::Object => "object",
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    #[error("{0}")]
+    Cfg(CfgError),
+    #[error("register v{0} is out of bounds")]
+    RegisterOutOfBounds(uint),
+    #[error("ins_size is {0}, but the prototype needs {1} registers")]
+    InsSizeMismatch(ushort, uint),
+    #[error("ins_size {0} is larger than registers_size {1}")]
+    TooManyIns(ushort, ushort),
+    #[error("invoke passes {0} registers, but outs_size is {1}")]
+    OutsSizeExceeded(uint, ushort),
+    #[error("invoke passes {0} registers, but the prototype needs {1}")]
+    ArgumentCountMismatch(uint, uint),
+    #[error("try block covering {0} code units is out of bounds")]
+    TryOutOfBounds(ushort),
+    #[error("try block doesn't start or end on an instruction")]
+    TryNotOnInstruction,
+    #[error("try blocks overlap or aren't sorted by address")]
+    OverlappingTries,
+    #[error("register v{0} holds {1}, but {2} is expected")]
+    TypeMismatch(uint, RegisterType, TypeCategory),
+    #[error("{0} doesn't match the return type {1}")]
+    ReturnMismatch(Opcode, DexString),
+    #[error("move-result doesn't directly follow an invoke or filled-new-array")]
+    MissingResult,
+    #[error("move-exception isn't the first instruction of an exception handler")]
+    MisplacedMoveException,
+    #[error("monitor-exit without a matching monitor-enter")]
+    UnbalancedMonitorExit,
+    #[error("paths with {0} and {1} held monitors merge")]
+    MonitorDepthMismatch(uint, uint),
+    #[error("returns while holding {0} monitors")]
+    MonitorHeldOnReturn(uint),
+}
+
+/// A reason why a method would be rejected by the runtime.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("{method} at offset {offset}: {kind}")]
+pub struct VerifyError {
+    pub method: Arc<MethodRef>,
+    /// Address of the offending instruction in code units.
+    pub offset: uint,
+    pub kind: VerifyErrorKind,
+}
+
+/// Checks `code` as the body of `method`, roughly the way ART's method verifier does,
+/// and returns everything that would make the runtime reject it.
+///
+/// Structural problems (invalid instructions, branch targets or registers)
+/// stop verification, since the remaining checks depend on them.
+/// The class hierarchy isn't known, so references are only checked to be references.
+pub fn verify_method(method: &Method, code: &CodeItem) -> crate::Result<Vec<VerifyError>> {
+    let mut verifier = Verifier {
+        method: method.method_ref()?,
+        code,
+        errors: Vec::new(),
+    };
+    verifier.run(method)?;
+    verifier.errors.sort_by_key(|e| e.offset);
+    Ok(verifier.errors)
+}
+
+struct Verifier<'a> {
+    method: Arc<MethodRef>,
+    code: &'a CodeItem,
+    errors: Vec<VerifyError>,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, offset: uint, kind: VerifyErrorKind) {
+        self.errors.push(VerifyError {
+            method: self.method.clone(),
+            offset,
+            kind,
+        });
+    }
+
+    fn run(&mut self, method: &Method) -> crate::Result<()> {
+        let types = match RegisterTypes::new(method, self.code) {
+            Ok(types) => types,
+            Err(crate::error::Error::Cfg(e)) => {
+                let offset = match e {
+                    CfgError::Decode(
+                        DecodeError::UnknownOpcode(_, offset)
+                        | DecodeError::UnknownPayload(_, offset)
+                        | DecodeError::Truncated(offset)
+                        | DecodeError::InvalidArgumentCount(_, offset)
+                        | DecodeError::InvalidElementWidth(_, offset)
+                        | DecodeError::InvalidPayloadReference(offset),
+                    )
+                    | CfgError::InvalidTarget(offset, _)
+                    | CfgError::InvalidHandler(offset, _)
+                    | CfgError::FallsOffEnd(offset) => offset,
+                };
+                self.error(offset, VerifyErrorKind::Cfg(e));
+                return Ok(());
+            }
+            Err(crate::error::Error::TypeInference(e)) => {
+                match e {
+                    TypeInferenceError::RegisterOutOfBounds(reg, offset) => {
+                        self.error(offset, VerifyErrorKind::RegisterOutOfBounds(reg))
+                    }
+                    TypeInferenceError::InsSizeMismatch(ins, expected) => {
+                        self.error(0, VerifyErrorKind::InsSizeMismatch(ins, expected))
+                    }
+                    TypeInferenceError::TooManyIns(ins, registers) => {
+                        self.error(0, VerifyErrorKind::TooManyIns(ins, registers))
+                    }
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        self.check_tries(&types);
+        self.check_monitors(&types);
+        for (idx, insn) in types.cfg().instructions().iter().enumerate() {
+            if let Some(registers) = types.at(insn.offset) {
+                self.check_instruction(method, &types, idx, insn, registers)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_tries(&mut self, types: &RegisterTypes) {
+        let cfg = types.cfg();
+        let is_boundary = |address: uint| {
+            address as usize == self.code.insns.len()
+                || cfg
+                    .instructions()
+                    .binary_search_by_key(&address, |insn| insn.offset)
+                    .is_ok()
+        };
+        let mut previous_end = 0;
+        for try_item in &self.code.tries {
+            let start = try_item.start_addr;
+            let end = start + try_item.insn_count as uint;
+            let error = if end as usize > self.code.insns.len() || try_item.insn_count == 0 {
+                Some(VerifyErrorKind::TryOutOfBounds(try_item.insn_count))
+            } else if !is_boundary(start) || !is_boundary(end) {
+                Some(VerifyErrorKind::TryNotOnInstruction)
+            } else if start < previous_end {
+                Some(VerifyErrorKind::OverlappingTries)
+            } else {
+                None
+            };
+            if let Some(kind) = error {
+                self.error(start, kind);
+            }
+            previous_end = end;
+        }
+    }
+
+    /// Checks that every path holds the same number of monitors at each instruction,
+    /// and that none are held when returning.
+    fn check_monitors(&mut self, types: &RegisterTypes) {
+        let cfg = types.cfg();
+        let mut entries: Vec<Option<uint>> = vec![None; cfg.blocks().len()];
+        entries[cfg.entry()] = Some(0);
+        let mut worklist = vec![cfg.entry()];
+        while let Some(id) = worklist.pop() {
+            let block = cfg.block(id);
+            let mut depth = entries[id].unwrap();
+            let mut targets = Vec::new();
+            for insn in cfg.block_instructions(id) {
+                // the depth before each instruction reaches the handlers
+                for edge in &block.successors {
+                    if let EdgeKind::Exception(_) = edge.kind {
+                        targets.push((edge.block, depth));
+                    }
+                }
+                match insn.instruction.opcode() {
+                    Some(Opcode::MonitorEnter) => depth += 1,
+                    Some(Opcode::MonitorExit) if depth == 0 => {
+                        self.error(insn.offset, VerifyErrorKind::UnbalancedMonitorExit)
+                    }
+                    Some(Opcode::MonitorExit) => depth -= 1,
+                    Some(op) if op.is_return() && depth > 0 => {
+                        self.error(insn.offset, VerifyErrorKind::MonitorHeldOnReturn(depth))
+                    }
+                    _ => {}
+                }
+            }
+            for edge in &block.successors {
+                if !matches!(edge.kind, EdgeKind::Exception(_) | EdgeKind::Exit) {
+                    targets.push((edge.block, depth));
+                }
+            }
+            for (target, depth) in targets {
+                match entries[target] {
+                    None => {
+                        entries[target] = Some(depth);
+                        worklist.push(target);
+                    }
+                    Some(other) if other != depth => {
+                        let offset = cfg.block(target).start;
+                        let kind = VerifyErrorKind::MonitorDepthMismatch(other, depth);
+                        if !self
+                            .errors
+                            .iter()
+                            .any(|e| e.offset == offset && e.kind == kind)
+                        {
+                            self.error(offset, kind);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn check_instruction(
+        &mut self,
+        method: &Method,
+        types: &RegisterTypes,
+        idx: usize,
+        insn: &DecodedInstruction,
+        registers: &[RegisterType],
+    ) -> crate::Result<()> {
+        use TypeCategory::*;
+        let dex = method.dex();
+        let instruction = &insn.instruction;
+        let Some(op) = instruction.opcode() else {
+            return Ok(());
+        };
+        let regs = instruction
+            .registers()
+            .into_iter()
+            .map(|reg| reg as uint)
+            .collect::<Vec<_>>();
+        let expect = |this: &mut Self, reg: uint, category: TypeCategory| {
+            let ty = &registers[reg as usize];
+            let high = registers.get(reg as usize + 1);
+            if category.is_wide() && high.is_none() {
+                this.error(insn.offset, VerifyErrorKind::RegisterOutOfBounds(reg + 1));
+            } else if !category.accepts(ty, high) {
+                this.error(
+                    insn.offset,
+                    VerifyErrorKind::TypeMismatch(reg, ty.clone(), category),
+                );
+            }
+        };
+        let index = instruction.index().unwrap_or_default();
+        match op {
+            Opcode::Move | Opcode::MoveFrom16 | Opcode::Move16 => expect(self, regs[1], Primitive),
+            Opcode::MoveWide | Opcode::MoveWideFrom16 | Opcode::MoveWide16 => {
+                expect(self, regs[1], Wide)
+            }
+            Opcode::MoveObject | Opcode::MoveObjectFrom16 | Opcode::MoveObject16 => {
+                expect(self, regs[1], Object)
+            }
+            Opcode::MoveResult | Opcode::MoveResultWide | Opcode::MoveResultObject => {
+                let previous = idx
+                    .checked_sub(1)
+                    .and_then(|idx| types.cfg().instructions()[idx].instruction.opcode());
+                let follows_invoke = previous.is_some_and(|op| {
+                    matches!(op, Opcode::FilledNewArray | Opcode::FilledNewArrayRange)
+                        || is_invoke(op)
+                });
+                if !follows_invoke {
+                    self.error(insn.offset, VerifyErrorKind::MissingResult);
+                }
+            }
+            Opcode::MoveException => {
+                let block = types
+                    .cfg()
+                    .block_at(insn.offset)
+                    .map(|id| types.cfg().block(id));
+                let is_handler = block.is_some_and(|block| {
+                    block.start == insn.offset
+                        && block
+                            .predecessors
+                            .iter()
+                            .any(|edge| matches!(edge.kind, EdgeKind::Exception(_)))
+                });
+                if !is_handler {
+                    self.error(insn.offset, VerifyErrorKind::MisplacedMoveException);
+                }
+            }
+            Opcode::ReturnVoid | Opcode::Return | Opcode::ReturnWide | Opcode::ReturnObject => {
+                let return_type = self.method.proto.return_type.clone();
+                let expected = match return_type.as_str() {
+                    "V" => Opcode::ReturnVoid,
+                    "J" | "D" => Opcode::ReturnWide,
+                    ty if TypeCategory::of(ty) == Reference => Opcode::ReturnObject,
+                    _ => Opcode::Return,
+                };
+                if op != expected {
+                    self.error(
+                        insn.offset,
+                        VerifyErrorKind::ReturnMismatch(op, return_type),
+                    );
+                } else if op != Opcode::ReturnVoid {
+                    expect(self, regs[0], TypeCategory::of(&return_type));
+                }
+            }
+            Opcode::MonitorEnter | Opcode::MonitorExit | Opcode::Throw | Opcode::CheckCast => {
+                expect(self, regs[0], Reference)
+            }
+            Opcode::InstanceOf => expect(self, regs[1], Reference),
+            Opcode::ArrayLength => expect(self, regs[1], Array),
+            Opcode::NewArray => expect(self, regs[1], Integer),
+            Opcode::FilledNewArray | Opcode::FilledNewArrayRange => {
+                let ty = dex.types().get(index)?;
+                let element = TypeCategory::of(ty.strip_prefix('[').unwrap_or_default());
+                for reg in regs {
+                    expect(self, reg, element);
+                }
+            }
+            Opcode::FillArrayData => expect(self, regs[0], Array),
+            Opcode::PackedSwitch | Opcode::SparseSwitch => expect(self, regs[0], Integer),
+            Opcode::CmplFloat | Opcode::CmpgFloat => {
+                expect(self, regs[1], Float);
+                expect(self, regs[2], Float);
+            }
+            Opcode::CmplDouble | Opcode::CmpgDouble => {
+                expect(self, regs[1], Double);
+                expect(self, regs[2], Double);
+            }
+            Opcode::CmpLong => {
+                expect(self, regs[1], Long);
+                expect(self, regs[2], Long);
+            }
+            Opcode::IfEq | Opcode::IfNe => {
+                let (a, b) = (&registers[regs[0] as usize], &registers[regs[1] as usize]);
+                let both =
+                    |category: TypeCategory| category.accepts(a, None) && category.accepts(b, None);
+                if !both(Integer) && !both(Reference) {
+                    let category = if Reference.accepts(a, None) {
+                        Reference
+                    } else {
+                        Integer
+                    };
+                    expect(self, regs[0], category);
+                    expect(self, regs[1], category);
+                }
+            }
+            Opcode::IfLt | Opcode::IfGe | Opcode::IfGt | Opcode::IfLe => {
+                expect(self, regs[0], Integer);
+                expect(self, regs[1], Integer);
+            }
+            Opcode::IfEqz | Opcode::IfNez => {
+                if !Reference.accepts(&registers[regs[0] as usize], None) {
+                    expect(self, regs[0], Integer);
+                }
+            }
+            Opcode::IfLtz | Opcode::IfGez | Opcode::IfGtz | Opcode::IfLez => {
+                expect(self, regs[0], Integer)
+            }
+            Opcode::Aget
+            | Opcode::AgetWide
+            | Opcode::AgetObject
+            | Opcode::AgetBoolean
+            | Opcode::AgetByte
+            | Opcode::AgetChar
+            | Opcode::AgetShort => {
+                expect(self, regs[1], Array);
+                expect(self, regs[2], Integer);
+            }
+            Opcode::Aput
+            | Opcode::AputWide
+            | Opcode::AputObject
+            | Opcode::AputBoolean
+            | Opcode::AputByte
+            | Opcode::AputChar
+            | Opcode::AputShort => {
+                let value = match op {
+                    Opcode::Aput => Primitive,
+                    Opcode::AputWide => Wide,
+                    Opcode::AputObject => Reference,
+                    _ => Integer,
+                };
+                expect(self, regs[0], value);
+                expect(self, regs[1], Array);
+                expect(self, regs[2], Integer);
+            }
+            Opcode::Iget
+            | Opcode::IgetWide
+            | Opcode::IgetObject
+            | Opcode::IgetBoolean
+            | Opcode::IgetByte
+            | Opcode::IgetChar
+            | Opcode::IgetShort => expect(self, regs[1], Reference),
+            Opcode::Iput
+            | Opcode::IputWide
+            | Opcode::IputObject
+            | Opcode::IputBoolean
+            | Opcode::IputByte
+            | Opcode::IputChar
+            | Opcode::IputShort => {
+                let field = dex.fields().get(index)?;
+                expect(self, regs[0], TypeCategory::of(&field.ty));
+                // constructors may initialize their own fields before calling super
+                if !matches!(
+                    registers[regs[1] as usize],
+                    RegisterType::UninitializedThis(_)
+                ) {
+                    expect(self, regs[1], Reference);
+                }
+            }
+            Opcode::Sput
+            | Opcode::SputWide
+            | Opcode::SputObject
+            | Opcode::SputBoolean
+            | Opcode::SputByte
+            | Opcode::SputChar
+            | Opcode::SputShort => {
+                let field = dex.fields().get(index)?;
+                expect(self, regs[0], TypeCategory::of(&field.ty));
+            }
+            op if is_invoke(op) => {
+                let count = regs.len() as uint;
+                if count > self.code.outs_size as uint {
+                    self.error(
+                        insn.offset,
+                        VerifyErrorKind::OutsSizeExceeded(count, self.code.outs_size),
+                    );
+                }
+                // only constructors may be called on uninitialized references
+                let (receiver, proto) = match op {
+                    Opcode::InvokeCustom | Opcode::InvokeCustomRange => {
+                        (None, dex.call_sites().get(index)?.method_type.clone())
+                    }
+                    Opcode::InvokePolymorphic | Opcode::InvokePolymorphicRange => {
+                        let proto = match *instruction {
+                            Instruction::Format45cc { proto, .. }
+                            | Instruction::Format4rcc { proto, .. } => proto,
+                            _ => unreachable!(),
+                        };
+                        (Some(Reference), dex.protos().get(proto)?)
+                    }
+                    _ => {
+                        let callee = dex.methods().get(index)?;
+                        let is_static =
+                            matches!(op, Opcode::InvokeStatic | Opcode::InvokeStaticRange);
+                        let receiver = match callee.name.as_str() {
+                            "<init>" => Object,
+                            _ => Reference,
+                        };
+                        ((!is_static).then_some(receiver), callee.proto.clone())
+                    }
+                };
+                let mut expected = receiver.into_iter().collect::<Vec<_>>();
+                expected.extend(proto.parameters.iter().map(|p| TypeCategory::of(p)));
+                let needed = expected
+                    .iter()
+                    .map(|category| if category.is_wide() { 2 } else { 1 })
+                    .sum();
+                if count != needed {
+                    self.error(
+                        insn.offset,
+                        VerifyErrorKind::ArgumentCountMismatch(count, needed),
+                    );
+                    return Ok(());
+                }
+                let mut args = regs.iter();
+                for category in expected {
+                    let reg = *args.next().unwrap();
+                    if category.is_wide() {
+                        args.next();
+                    }
+                    expect(self, reg, category);
+                }
+            }
+            _ => {
+                // the remaining operations, by the types of the operands they read
+                let operands = operand_types(op).unwrap_or_default();
+                // only /2addr variants read their destination
+                let skip = if is_two_addr(op) { 0 } else { 1 };
+                for (reg, category) in regs.iter().zip(operands).skip(skip) {
+                    expect(self, *reg, *category);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_invoke(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::InvokeVirtual
+            | Opcode::InvokeSuper
+            | Opcode::InvokeDirect
+            | Opcode::InvokeStatic
+            | Opcode::InvokeInterface
+            | Opcode::InvokeVirtualRange
+            | Opcode::InvokeSuperRange
+            | Opcode::InvokeDirectRange
+            | Opcode::InvokeStaticRange
+            | Opcode::InvokeInterfaceRange
+            | Opcode::InvokePolymorphic
+            | Opcode::InvokePolymorphicRange
+            | Opcode::InvokeCustom
+            | Opcode::InvokeCustomRange
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::DexFile;
+
+    fn add<'a>(dex: &'a DexFile<'a>) -> Method<'a> {
+        let class = dex
+            .class_by_descriptor("Lcom/example/Main;")
+            .unwrap()
+            .unwrap();
+        class
+            .direct_methods()
+            .unwrap()
+            .into_iter()
+            .find(|m| m.method_ref().unwrap().name.as_str() == "add")
+            .unwrap()
+    }
+
+    /// Verifies `insns` as the body of `add(II)I`, with `v1` and `v2` as its parameters.
+    fn verify(
+        dex: &DexFile,
+        outs_size: ushort,
+        insns: Vec<ushort>,
+    ) -> Vec<(uint, VerifyErrorKind)> {
+        let code = CodeItem {
+            registers_size: 3,
+            ins_size: 2,
+            outs_size,
+            tries_size: 0,
+            debug_info_off: 0,
+            insns,
+            tries: Vec::new(),
+            handlers: None,
+        };
+        verify_method(&add(dex), &code)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.offset, e.kind))
+            .collect()
+    }
+
+    #[test]
+    fn errors() {
+        let dex = crate::t::dex!();
+        // add-int v0, v1, v2; return v0
+        assert_eq!(verify(&dex, 0, vec![0x0090, 0x0201, 0x000f]), vec![]);
+        // add-int v0, v1, v2; return-wide v0
+        assert_eq!(
+            verify(&dex, 0, vec![0x0090, 0x0201, 0x0010]),
+            vec![(
+                2,
+                VerifyErrorKind::ReturnMismatch(Opcode::ReturnWide, DexString::new("I".into()))
+            )]
+        );
+        // return v3
+        assert_eq!(
+            verify(&dex, 0, vec![0x030f]),
+            vec![(0, VerifyErrorKind::RegisterOutOfBounds(3))]
+        );
+        // aget-wide v2, v1, v0; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x0245, 0x0001, 0x010f]),
+            vec![(0, VerifyErrorKind::RegisterOutOfBounds(3))]
+        );
+        // neg-long v0, v2; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x207d, 0x010f]),
+            vec![(0, VerifyErrorKind::RegisterOutOfBounds(3))]
+        );
+        // goto +2; return v1
+        assert!(matches!(
+            verify(&dex, 0, vec![0x0228, 0x010f])[..],
+            [(0, VerifyErrorKind::Cfg(CfgError::InvalidTarget(0, 2)))]
+        ));
+        // monitor-enter v1; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x011d, 0x010f]),
+            vec![
+                (
+                    0,
+                    VerifyErrorKind::TypeMismatch(
+                        1,
+                        RegisterType::Integer,
+                        TypeCategory::Reference
+                    )
+                ),
+                (1, VerifyErrorKind::MonitorHeldOnReturn(1))
+            ]
+        );
+        // const/4 v0, #0; monitor-enter v0; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x0012, 0x001d, 0x010f]),
+            vec![(2, VerifyErrorKind::MonitorHeldOnReturn(1))]
+        );
+        // rem-float/2addr v2, v1; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x12ca, 0x010f]),
+            vec![
+                (
+                    0,
+                    VerifyErrorKind::TypeMismatch(2, RegisterType::Integer, TypeCategory::Float)
+                ),
+                (
+                    0,
+                    VerifyErrorKind::TypeMismatch(1, RegisterType::Integer, TypeCategory::Float)
+                )
+            ]
+        );
+        // move-result v0; return v1
+        assert_eq!(
+            verify(&dex, 0, vec![0x000a, 0x010f]),
+            vec![(0, VerifyErrorKind::MissingResult)]
+        );
+
+        let add_idx = dex
+            .methods()
+            .find("Lcom/example/Main;", "add", "(II)I")
+            .unwrap() as ushort;
+        // invoke-static {v1, v2}, add; move-result v0; return v0
+        let insns = vec![0x2071, add_idx, 0x0021, 0x000a, 0x000f];
+        assert_eq!(verify(&dex, 2, insns.clone()), vec![]);
+        assert_eq!(
+            verify(&dex, 1, insns),
+            vec![(0, VerifyErrorKind::OutsSizeExceeded(2, 1))]
+        );
+        // invoke-static {v1}, add; return v1
+        assert_eq!(
+            verify(&dex, 2, vec![0x1071, add_idx, 0x0001, 0x010f]),
+            vec![(0, VerifyErrorKind::ArgumentCountMismatch(1, 2))]
+        );
+    }
+
+    #[test]
+    fn dex() {
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                if let Some(code) = method.code().unwrap() {
+                    assert_eq!(verify_method(&method, &code).unwrap(), vec![]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use scroll::Pread;
+
+use crate::{
+    raw::{
+        call_site::{CallSiteError, CallSiteItem},
+        encoded_value::EncodedValue,
+        simple::CallSiteId,
+        uint, RawCallSiteIndex, RawMethodHandleIndex,
+    },
+    utils::{nohash::BuildNoHashHasher, IntoArc},
+};
+
+use super::{
+    method_handles::{MethodHandleReadError, MethodHandleRef, MethodHandles},
+    protos::{ProtoReadError, Protos, Prototype},
+    section::Section,
+    strings::{DexString, StringReadError, Strings},
+};
+
+type Result<T> = std::result::Result<T, CallSiteReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CallSiteReadError {
+    #[error("call site index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("error reading call site item: {0}")]
+    CallSite(#[from] CallSiteError),
+    #[error("error reading method handle: {0}")]
+    MethodHandle(#[from] MethodHandleReadError),
+    #[error("error reading method name: {0}")]
+    MethodName(#[from] StringReadError),
+    #[error("error reading method type: {0}")]
+    MethodType(#[from] ProtoReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// A resolved `call_site_item`, as used by `invoke-custom`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSite {
+    /// Index into the `method_handles` list for the bootstrap linker method.
+    pub method_handle_idx: RawMethodHandleIndex,
+    /// The bootstrap linker method.
+    pub method_handle: Arc<MethodHandleRef>,
+    /// The name of the method to resolve.
+    pub method_name: DexString,
+    /// The type of the method to resolve.
+    pub method_type: Arc<Prototype>,
+    /// Additional static arguments passed to the bootstrap linker method.
+    pub extra_args: Vec<EncodedValue>,
+}
+
+pub struct CallSites<'a> {
+    src: &'a [u8],
+    // call site id section, not present in files without call sites
+    section: Option<Section<'a>>,
+    method_handles: Arc<MethodHandles<'a>>,
+    strings: Arc<Strings<'a>>,
+    protos: Arc<Protos<'a>>,
+    read_cache:
+        dashmap::DashMap<RawCallSiteIndex, Arc<CallSite>, BuildNoHashHasher<RawCallSiteIndex>>,
+}
+
+impl<'a> CallSites<'a> {
+    pub fn new(
+        src: &'a [u8],
+        section: Option<Section<'a>>,
+        method_handles: Arc<MethodHandles<'a>>,
+        strings: Arc<Strings<'a>>,
+        protos: Arc<Protos<'a>>,
+    ) -> Self {
+        Self {
+            src,
+            section,
+            method_handles,
+            strings,
+            protos,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.section
+            .as_ref()
+            .map_or(0, |section| section.len() as uint)
+    }
+
+    pub fn id_at(&self, index: RawCallSiteIndex) -> Result<CallSiteId> {
+        match &self.section {
+            Some(section) if index < self.len() => {
+                Ok(section.index(index as usize, section.endian())?)
+            }
+            _ => Err(CallSiteReadError::IndexOutOfBounds(index)),
+        }
+    }
+
+    /// Decodes and resolves the call site at `index`.
+    pub fn get(&self, index: RawCallSiteIndex) -> Result<Arc<CallSite>> {
+        if let Some(v) = self.read_cache.get(&index) {
+            return Ok(v.value().clone());
+        }
+        let id = self.id_at(index)?;
+        let item: CallSiteItem = self.src.pread_with(id.call_site_off as usize, ())?;
+        let call_site = CallSite {
+            method_handle_idx: item.method_handle,
+            method_handle: self.method_handles.get(item.method_handle)?,
+            method_name: self.strings.get_at(item.method_name)?,
+            method_type: self.protos.get(item.method_type)?,
+            extra_args: item.extra_args,
+        }
+        .into_arc();
+        self.read_cache.insert(index, call_site.clone());
+        Ok(call_site)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dex::method_handles::MethodHandleTarget, raw::method_handle::MethodHandleType};
+
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        for idx in 0..dex.call_sites().len() {
+            let call_site = dex.call_sites().get(idx).unwrap();
+            // bootstrap linker methods are always static
+            assert_eq!(call_site.method_handle.ty, MethodHandleType::InvokeStatic);
+            assert!(matches!(
+                call_site.method_handle.target,
+                MethodHandleTarget::Method(_)
+            ));
+        }
+        assert!(dex.call_sites().get(dex.call_sites().len()).is_err());
+    }
+}
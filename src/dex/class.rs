@@ -0,0 +1,374 @@
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+use scroll::Pread;
+
+use crate::raw::{
+    annotations::AnnotationsDirectory,
+    class_data::{ClassData, EncodedField, EncodedMethod},
+    classdef::ClassDef,
+    code_item::{CodeItem, DebugInfo, DebugInfoItem},
+    encoded_value::{EncodedArrayItem, EncodedValue},
+    flags::AccessFlags,
+    hiddenapi::Restriction,
+    type_list::TypeList,
+    uint, ulong, RawFieldIndex, RawMethodIndex, NO_INDEX,
+};
+
+use super::{fields::FieldRef, methods::MethodRef, strings::DexString, DexFile};
+
+/// A view over a single `class_def_item` of a [`DexFile`].
+/// The associated `class_data_item` is only decoded once it's first needed.
+pub struct Class<'a> {
+    dex: &'a DexFile<'a>,
+    index: uint,
+    def: ClassDef,
+    data: OnceCell<Option<ClassData>>,
+}
+
+impl<'a> Class<'a> {
+    pub(crate) fn new(dex: &'a DexFile<'a>, index: uint, def: ClassDef) -> Self {
+        Self {
+            dex,
+            index,
+            def,
+            data: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn dex(&self) -> &'a DexFile<'a> {
+        self.dex
+    }
+
+    /// Index of this class into the `class_defs` list.
+    pub fn index(&self) -> uint {
+        self.index
+    }
+
+    pub fn def(&self) -> &ClassDef {
+        &self.def
+    }
+
+    pub fn descriptor(&self) -> crate::Result<DexString> {
+        Ok(self.dex.types().get(self.def.class_idx)?)
+    }
+
+    pub fn access_flags(&self) -> AccessFlags {
+        self.def.access_flags
+    }
+
+    /// The descriptor of the superclass, or `None` if this is a root class.
+    pub fn superclass(&self) -> crate::Result<Option<DexString>> {
+        if self.def.superclass_idx == NO_INDEX {
+            return Ok(None);
+        }
+        Ok(Some(self.dex.types().get(self.def.superclass_idx)?))
+    }
+
+    pub fn interfaces(&self) -> crate::Result<Vec<DexString>> {
+        if self.def.interfaces_off == 0 {
+            return Ok(Vec::new());
+        }
+        let list: TypeList = self
+            .dex
+            .src
+            .pread_with(self.def.interfaces_off as usize, self.dex.header().endian())?;
+        let interfaces = list
+            .into_inner()
+            .into_iter()
+            .map(|item| self.dex.types().get(item.type_idx as uint))
+            .collect::<Result<_, _>>()?;
+        Ok(interfaces)
+    }
+
+    /// The name of the source file this class was compiled from, if known.
+    pub fn source_file(&self) -> crate::Result<Option<DexString>> {
+        if self.def.source_file_idx == NO_INDEX {
+            return Ok(None);
+        }
+        Ok(Some(self.dex.strings().get_at(self.def.source_file_idx)?))
+    }
+
+    /// The decoded `class_data_item`, or `None` if this class has no class data.
+    pub fn class_data(&self) -> crate::Result<Option<&ClassData>> {
+        let data = self.data.get_or_try_init(|| {
+            if self.def.class_data_off == 0 {
+                return Ok::<_, crate::error::Error>(None);
+            }
+            let data: ClassData = self
+                .dex
+                .src
+                .pread_with(self.def.class_data_off as usize, ())?;
+            let feature = "default and static interface methods";
+            let required = data.min_version(self.def.access_flags);
+            self.dex.header().version.require(required, feature)?;
+            Ok(Some(data))
+        })?;
+        Ok(data.as_ref())
+    }
+
+    /// The annotations of this class and its members, or `None` if it has none.
+    pub fn annotations(&self) -> crate::Result<Option<AnnotationsDirectory>> {
+        if self.def.annotations_off == 0 {
+            return Ok(None);
+        }
+        let directory = self.dex.src.pread_with(
+            self.def.annotations_off as usize,
+            self.dex.header().endian(),
+        )?;
+        Ok(Some(directory))
+    }
+
+    /// The initial values of the static fields, in [`Class::static_fields`] order.
+    /// May be shorter than the list of fields, in which case
+    /// the remaining ones are initialized to `0` or `null`.
+    pub fn static_values(&self) -> crate::Result<Vec<EncodedValue>> {
+        if self.def.static_values_off == 0 {
+            return Ok(Vec::new());
+        }
+        let item: EncodedArrayItem = self
+            .dex
+            .src
+            .pread_with(self.def.static_values_off as usize, ())?;
+        Ok(item.into_inner().into_inner())
+    }
+
+    /// The hidden API restrictions of all members of this class, in `class_data_item` order
+    /// (static fields, instance fields, direct methods, then virtual methods),
+    /// or `None` if this file has no restrictions for this class.
+    pub fn hiddenapi_flags(&self) -> crate::Result<Option<Vec<Restriction>>> {
+        let Some(hiddenapi) = self.dex.hiddenapi() else {
+            return Ok(None);
+        };
+        let members = self.class_data()?.map_or(0, |data| {
+            data.static_fields.len()
+                + data.instance_fields.len()
+                + data.direct_methods.len()
+                + data.virtual_methods.len()
+        });
+        Ok(hiddenapi.class_flags(self.index, members)?)
+    }
+
+    pub fn static_fields(&self) -> crate::Result<Vec<Field<'a>>> {
+        self.fields(|data| &data.static_fields)
+    }
+
+    pub fn instance_fields(&self) -> crate::Result<Vec<Field<'a>>> {
+        self.fields(|data| &data.instance_fields)
+    }
+
+    pub fn direct_methods(&self) -> crate::Result<Vec<Method<'a>>> {
+        self.methods(|data| &data.direct_methods)
+    }
+
+    pub fn virtual_methods(&self) -> crate::Result<Vec<Method<'a>>> {
+        self.methods(|data| &data.virtual_methods)
+    }
+
+    fn fields<F>(&self, list: F) -> crate::Result<Vec<Field<'a>>>
+    where
+        F: FnOnce(&ClassData) -> &Vec<EncodedField>,
+    {
+        let Some(data) = self.class_data()? else {
+            return Ok(Vec::new());
+        };
+        let mut index: ulong = 0;
+        Ok(list(data)
+            .iter()
+            .map(|field| {
+                // the index of each element is encoded as a difference from the previous one
+                index += field.field_idx_diff;
+                Field {
+                    dex: self.dex,
+                    index: index as RawFieldIndex,
+                    access_flags: field.access_flags,
+                }
+            })
+            .collect())
+    }
+
+    fn methods<F>(&self, list: F) -> crate::Result<Vec<Method<'a>>>
+    where
+        F: FnOnce(&ClassData) -> &Vec<EncodedMethod>,
+    {
+        let Some(data) = self.class_data()? else {
+            return Ok(Vec::new());
+        };
+        let mut index: ulong = 0;
+        Ok(list(data)
+            .iter()
+            .map(|method| {
+                // the index of each element is encoded as a difference from the previous one
+                index += method.method_idx_diff;
+                Method {
+                    dex: self.dex,
+                    index: index as RawMethodIndex,
+                    access_flags: method.access_flags,
+                    code_off: method.code_off as uint,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Identifies a field or method defined by a [`Class`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemberIndex {
+    Field(RawFieldIndex),
+    Method(RawMethodIndex),
+}
+
+impl From<&Field<'_>> for MemberIndex {
+    fn from(field: &Field<'_>) -> Self {
+        Self::Field(field.index)
+    }
+}
+
+impl From<&Method<'_>> for MemberIndex {
+    fn from(method: &Method<'_>) -> Self {
+        Self::Method(method.index)
+    }
+}
+
+/// A field defined by a [`Class`].
+#[derive(Clone, Copy)]
+pub struct Field<'a> {
+    dex: &'a DexFile<'a>,
+    /// Index into the `field_ids` list for this field.
+    pub index: RawFieldIndex,
+    /// Access flags for the field (`public`, `final`, etc.).
+    pub access_flags: AccessFlags,
+}
+
+impl<'a> Field<'a> {
+    /// Resolves the defining class, name and type of this field.
+    pub fn field_ref(&self) -> crate::Result<Arc<FieldRef>> {
+        Ok(self.dex.fields().get(self.index)?)
+    }
+}
+
+/// A method defined by a [`Class`].
+#[derive(Clone, Copy)]
+pub struct Method<'a> {
+    dex: &'a DexFile<'a>,
+    /// Index into the `method_ids` list for this method.
+    pub index: RawMethodIndex,
+    /// Access flags for the method (`public`, `final`, etc.).
+    pub access_flags: AccessFlags,
+    /// Offset from the start of the file to the code of this method,
+    /// or 0 if this method is either `abstract` or `native`.
+    pub code_off: uint,
+}
+
+impl<'a> Method<'a> {
+    pub(crate) fn dex(&self) -> &'a DexFile<'a> {
+        self.dex
+    }
+
+    /// Resolves the defining class, name and prototype of this method.
+    pub fn method_ref(&self) -> crate::Result<Arc<MethodRef>> {
+        Ok(self.dex.methods().get(self.index)?)
+    }
+
+    /// Decodes the code of this method, or `None` if it is `abstract` or `native`.
+    pub fn code(&self) -> crate::Result<Option<CodeItem>> {
+        if self.code_off == 0 {
+            return Ok(None);
+        }
+        let code = self.dex.code_item(self.index, self.code_off)?;
+        self.dex.check_code(self.code_off, &code)?;
+        Ok(Some(code))
+    }
+
+    /// Decodes the position and local variable tables of this method,
+    /// or `None` if it has no code or no debug info.
+    pub fn debug_info(&self) -> crate::Result<Option<DebugInfo>> {
+        let Some(code) = self.code()? else {
+            return Ok(None);
+        };
+        let Some(item) = self.debug_info_item(&code)? else {
+            return Ok(None);
+        };
+        Ok(Some(item.decode(code.insns.len() as uint)))
+    }
+
+    /// Reads the undecoded `debug_info_item` of `code`, or `None` if it has no debug info.
+    pub fn debug_info_item(&self, code: &CodeItem) -> crate::Result<Option<DebugInfoItem>> {
+        if code.debug_info_off == 0 {
+            return Ok(None);
+        }
+        let item = self.dex.src.pread_with(code.debug_info_off as usize, ())?;
+        Ok(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let descriptor = class.descriptor().unwrap();
+            let class_2 = dex.class_by_descriptor(&descriptor).unwrap().unwrap();
+            assert_eq!(class.index(), class_2.index());
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                assert_eq!(method.method_ref().unwrap().class, descriptor);
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                if let Some(info) = method.debug_info().unwrap() {
+                    let insns_size = code.insns.len() as u32;
+                    assert!(info
+                        .positions
+                        .windows(2)
+                        .all(|w| w[0].address <= w[1].address));
+                    assert!(info.positions.iter().all(|p| p.address < insns_size));
+                    assert!(info
+                        .locals
+                        .iter()
+                        .all(|l| l.start_address <= l.end_address && l.end_address <= insns_size));
+                }
+            }
+            for field in class
+                .static_fields()
+                .unwrap()
+                .into_iter()
+                .chain(class.instance_fields().unwrap())
+            {
+                assert_eq!(field.field_ref().unwrap().class, descriptor);
+            }
+        }
+        assert!(dex.class_by_descriptor("Lnot/a/Class;").unwrap().is_none());
+    }
+
+    #[test]
+    pub fn hiddenapi() {
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let Some(flags) = class.hiddenapi_flags().unwrap() else {
+                continue;
+            };
+            let fields = class
+                .static_fields()
+                .unwrap()
+                .into_iter()
+                .chain(class.instance_fields().unwrap())
+                .map(|field| dex.hiddenapi_flags(&class, &field).unwrap());
+            let methods = class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+                .map(|method| dex.hiddenapi_flags(&class, &method).unwrap());
+            let by_member = fields.chain(methods).collect::<Option<Vec<_>>>();
+            assert_eq!(by_member, Some(flags));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Dex containers, introduced in [`Version::V041`].
+//!
+//! A container is a sequence of dex files, each starting with its own header,
+//! which share a single data section. Offsets into the id sections are relative to
+//! the header of each file, while offsets into the data section are relative to the container.
+
+use scroll::Pread;
+
+use crate::raw::{
+    header::{self, Version, MAGIC_LEN},
+    ubyte, uint,
+};
+
+use super::DexFile;
+
+/// The dex files of a container. A file of an earlier version is treated as
+/// a container holding just that file.
+#[derive(Debug, Clone)]
+pub struct DexContainer<'a> {
+    src: &'a [u8],
+    headers: Vec<uint>,
+}
+
+impl<'a> DexContainer<'a> {
+    /// Finds the headers of all files in `src`, without parsing them.
+    pub fn new(src: &'a [u8]) -> crate::Result<Self> {
+        let version = Version::try_from(src.pread_with::<&[ubyte]>(0, MAGIC_LEN)?)?;
+        if version < Version::V041 {
+            return Ok(Self {
+                src,
+                headers: vec![0],
+            });
+        }
+        let mut headers = Vec::new();
+        let mut offset = 0;
+        while offset < src.len() {
+            headers.push(offset as uint);
+            offset += header::file_len(&src[offset..])?;
+        }
+        Ok(Self { src, headers })
+    }
+
+    pub fn src(&self) -> &'a [u8] {
+        self.src
+    }
+
+    /// Number of files in the container.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// The offset of the header of each file.
+    pub fn header_offsets(&self) -> &[uint] {
+        &self.headers
+    }
+
+    /// Opens the file at `index`, or `None` if it's out of bounds.
+    pub fn get(&self, index: usize) -> Option<crate::Result<DexFile<'a>>> {
+        let offset = *self.headers.get(index)?;
+        Some(DexFile::new_at(self.src, offset as usize))
+    }
+
+    /// Opens each file in order.
+    pub fn dex_files(&self) -> impl Iterator<Item = crate::Result<DexFile<'a>>> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+}
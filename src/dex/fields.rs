@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::{
+    raw::{header::Header, simple::FieldId, uint, RawFieldIndex},
+    utils::{nohash::BuildNoHashHasher, IntoArc},
+};
+
+use super::{
+    section::Section,
+    strings::{DexString, StringReadError, Strings},
+    types::{TypeReadError, Types},
+};
+
+type Result<T> = std::result::Result<T, FieldReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldReadError {
+    #[error("field not found")]
+    FieldNotFound,
+    #[error("field index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("error reading name: {0}")]
+    Name(#[from] StringReadError),
+    #[error("error reading type: {0}")]
+    Type(#[from] TypeReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// A resolved [`FieldId`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldRef {
+    /// The descriptor of the class defining this field.
+    pub class: DexString,
+    /// The name of this field.
+    pub name: DexString,
+    /// The descriptor of the type of this field.
+    pub ty: DexString,
+}
+
+impl std::fmt::Display for FieldRef {
+    /// Formats the field as `Lcom/example/Foo;->name:I`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}->{}:{}", self.class, self.name, self.ty)
+    }
+}
+
+pub struct Fields<'a> {
+    header: Header<'a>,
+    // field id section
+    section: Section<'a>,
+    strings: Arc<Strings<'a>>,
+    types: Arc<Types<'a>>,
+    read_cache: dashmap::DashMap<RawFieldIndex, Arc<FieldRef>, BuildNoHashHasher<RawFieldIndex>>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(
+        header: Header<'a>,
+        section: Section<'a>,
+        strings: Arc<Strings<'a>>,
+        types: Arc<Types<'a>>,
+    ) -> Self {
+        Self {
+            header,
+            section,
+            strings,
+            types,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.header.field_ids_size
+    }
+
+    pub fn id_at(&self, index: RawFieldIndex) -> Result<FieldId> {
+        if index >= self.len() {
+            return Err(FieldReadError::IndexOutOfBounds(index));
+        }
+        let id = self.section.index(index as usize, self.section.endian())?;
+        Ok(id)
+    }
+
+    /// Resolves the field at `index` into its defining class, name and type.
+    pub fn get(&self, index: RawFieldIndex) -> Result<Arc<FieldRef>> {
+        if let Some(v) = self.read_cache.get(&index) {
+            return Ok(v.value().clone());
+        }
+        let id = self.id_at(index)?;
+        let field = FieldRef {
+            class: self.types.get(id.class_idx as uint)?,
+            name: self.strings.get_at(id.name_idx)?,
+            ty: self.types.get(id.type_idx as uint)?,
+        }
+        .into_arc();
+        self.read_cache.insert(index, field.clone());
+        Ok(field)
+    }
+
+    /// Returns the index into the `field_ids` list of the field
+    /// defined by `class` with the given `name` and type descriptor `ty`.
+    pub fn find(&self, class: &str, name: &str, ty: &str) -> Result<RawFieldIndex> {
+        let find_type = |descriptor| match self.types.find(descriptor) {
+            Ok(idx) => Ok(idx),
+            Err(TypeReadError::TypeNotFound) => Err(FieldReadError::FieldNotFound),
+            Err(e) => Err(e.into()),
+        };
+        let name_idx = match self.strings.find_index(name) {
+            Ok(idx) => idx,
+            Err(StringReadError::StringNotFound) => return Err(FieldReadError::FieldNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let element = (find_type(class)?, name_idx, find_type(ty)?);
+        // field_ids are sorted by defining type, then by name, then by type
+        let index = self
+            .section
+            .binary_search(&element, self.section.endian(), |id: &FieldId, element| {
+                let item = (id.class_idx as uint, id.name_idx, id.type_idx as uint);
+                Ok::<_, FieldReadError>(element.cmp(&item))
+            })?
+            .ok_or(FieldReadError::FieldNotFound)?;
+        Ok(index as RawFieldIndex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        let fidx = dex.fields().len() / 2;
+        let field = dex.fields().get(fidx).unwrap();
+        let fidx_2 = dex
+            .fields()
+            .find(&field.class, &field.name, &field.ty)
+            .unwrap();
+        assert_eq!(fidx, fidx_2);
+        assert!(dex.fields().find(&field.class, &field.name, "V").is_err());
+    }
+}
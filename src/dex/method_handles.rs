@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use crate::{
+    raw::{
+        method_handle::{MethodHandle, MethodHandleError, MethodHandleType},
+        uint, RawMethodHandleIndex,
+    },
+    utils::{nohash::BuildNoHashHasher, IntoArc},
+};
+
+use super::{
+    fields::{FieldReadError, FieldRef, Fields},
+    methods::{MethodReadError, MethodRef, Methods},
+    section::Section,
+};
+
+type Result<T> = std::result::Result<T, MethodHandleReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MethodHandleReadError {
+    #[error("method handle index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("method handle of type {0:?} can't target {1}")]
+    InvalidTarget(MethodHandleType, Arc<MethodRef>),
+    #[error("error reading method handle: {0}")]
+    MethodHandle(#[from] MethodHandleError),
+    #[error("error reading field: {0}")]
+    Field(#[from] FieldReadError),
+    #[error("error reading method: {0}")]
+    Method(#[from] MethodReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// The field or method a [`MethodHandleRef`] points to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MethodHandleTarget {
+    /// Target of the accessor handle types (`static-put` to `instance-get`).
+    Field(Arc<FieldRef>),
+    /// Target of the invoker handle types (`invoke-static` to `invoke-interface`).
+    Method(Arc<MethodRef>),
+}
+
+/// A resolved [`MethodHandle`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodHandleRef {
+    pub ty: MethodHandleType,
+    pub target: MethodHandleTarget,
+}
+
+impl std::fmt::Display for MethodHandleRef {
+    /// Formats the method handle as `invoke-static@Lcom/example/Foo;->bar()V`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ty = match self.ty {
+            MethodHandleType::StaticPut => "static-put",
+            MethodHandleType::StaticGet => "static-get",
+            MethodHandleType::InstancePut => "instance-put",
+            MethodHandleType::InstanceGet => "instance-get",
+            MethodHandleType::InvokeStatic => "invoke-static",
+            MethodHandleType::InvokeInstance => "invoke-instance",
+            MethodHandleType::InvokeConstructor => "invoke-constructor",
+            MethodHandleType::InvokeDirect => "invoke-direct",
+            MethodHandleType::InvokeInterface => "invoke-interface",
+        };
+        match &self.target {
+            MethodHandleTarget::Field(field) => write!(f, "{ty}@{field}"),
+            MethodHandleTarget::Method(method) => write!(f, "{ty}@{method}"),
+        }
+    }
+}
+
+pub struct MethodHandles<'a> {
+    // method handle section, not present in files without method handles
+    section: Option<Section<'a>>,
+    fields: Arc<Fields<'a>>,
+    methods: Arc<Methods<'a>>,
+    read_cache: dashmap::DashMap<
+        RawMethodHandleIndex,
+        Arc<MethodHandleRef>,
+        BuildNoHashHasher<RawMethodHandleIndex>,
+    >,
+}
+
+impl<'a> MethodHandles<'a> {
+    pub fn new(
+        section: Option<Section<'a>>,
+        fields: Arc<Fields<'a>>,
+        methods: Arc<Methods<'a>>,
+    ) -> Self {
+        Self {
+            section,
+            fields,
+            methods,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.section
+            .as_ref()
+            .map_or(0, |section| section.len() as uint)
+    }
+
+    pub fn id_at(&self, index: RawMethodHandleIndex) -> Result<MethodHandle> {
+        match &self.section {
+            Some(section) if index < self.len() => {
+                Ok(section.index(index as usize, section.endian())?)
+            }
+            _ => Err(MethodHandleReadError::IndexOutOfBounds(index)),
+        }
+    }
+
+    /// Resolves the method handle at `index`, as referenced by call sites,
+    /// [`EncodedValue::MethodHandle`][crate::raw::encoded_value::EncodedValue::MethodHandle]
+    /// and `const-method-handle`.
+    pub fn get(&self, index: RawMethodHandleIndex) -> Result<Arc<MethodHandleRef>> {
+        if let Some(v) = self.read_cache.get(&index) {
+            return Ok(v.value().clone());
+        }
+        let handle = self.id_at(index)?;
+        let target_idx = handle.field_or_method_id as uint;
+        let target = if handle.is_accessor() {
+            MethodHandleTarget::Field(self.fields.get(target_idx)?)
+        } else {
+            let method = self.methods.get(target_idx)?;
+            // constructors can only be invoked through invoke-constructor handles,
+            // and class initializers can't be invoked at all
+            let valid = match (handle.ty, &**method.name) {
+                (MethodHandleType::InvokeConstructor, name) => name == "<init>",
+                (_, "<init>" | "<clinit>") => false,
+                _ => true,
+            };
+            if !valid {
+                return Err(MethodHandleReadError::InvalidTarget(handle.ty, method));
+            }
+            MethodHandleTarget::Method(method)
+        };
+        let handle = MethodHandleRef {
+            ty: handle.ty,
+            target,
+        }
+        .into_arc();
+        self.read_cache.insert(index, handle.clone());
+        Ok(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MethodHandleTarget;
+
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        for idx in 0..dex.method_handles().len() {
+            let handle = dex.method_handles().get(idx).unwrap();
+            let raw = dex.method_handles().id_at(idx).unwrap();
+            match handle.target {
+                MethodHandleTarget::Field(_) => assert!(raw.is_accessor()),
+                MethodHandleTarget::Method(_) => assert!(!raw.is_accessor()),
+            }
+        }
+        assert!(dex
+            .method_handles()
+            .get(dex.method_handles().len())
+            .is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use crate::{
+    raw::{header::Header, simple::MethodId, uint, RawMethodIndex},
+    utils::{nohash::BuildNoHashHasher, IntoArc},
+};
+
+use super::{
+    protos::{ProtoReadError, Protos, Prototype},
+    section::Section,
+    strings::{DexString, StringReadError, Strings},
+    types::{TypeReadError, Types},
+};
+
+type Result<T> = std::result::Result<T, MethodReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MethodReadError {
+    #[error("method not found")]
+    MethodNotFound,
+    #[error("method index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("error reading name: {0}")]
+    Name(#[from] StringReadError),
+    #[error("error reading type: {0}")]
+    Type(#[from] TypeReadError),
+    #[error("error reading prototype: {0}")]
+    Proto(#[from] ProtoReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// A resolved [`MethodId`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodRef {
+    /// The descriptor of the class defining this method.
+    pub class: DexString,
+    /// The name of this method.
+    pub name: DexString,
+    /// The prototype of this method.
+    pub proto: Arc<Prototype>,
+}
+
+impl std::fmt::Display for MethodRef {
+    /// Formats the method as `Lcom/example/Foo;->name(I)V`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}->{}{}", self.class, self.name, self.proto)
+    }
+}
+
+pub struct Methods<'a> {
+    header: Header<'a>,
+    // method id section
+    section: Section<'a>,
+    strings: Arc<Strings<'a>>,
+    types: Arc<Types<'a>>,
+    protos: Arc<Protos<'a>>,
+    read_cache: dashmap::DashMap<RawMethodIndex, Arc<MethodRef>, BuildNoHashHasher<RawMethodIndex>>,
+}
+
+impl<'a> Methods<'a> {
+    pub fn new(
+        header: Header<'a>,
+        section: Section<'a>,
+        strings: Arc<Strings<'a>>,
+        types: Arc<Types<'a>>,
+        protos: Arc<Protos<'a>>,
+    ) -> Self {
+        Self {
+            header,
+            section,
+            strings,
+            types,
+            protos,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.header.method_ids_size
+    }
+
+    pub fn id_at(&self, index: RawMethodIndex) -> Result<MethodId> {
+        if index >= self.len() {
+            return Err(MethodReadError::IndexOutOfBounds(index));
+        }
+        let id = self.section.index(index as usize, self.section.endian())?;
+        Ok(id)
+    }
+
+    /// Resolves the method at `index` into its defining class, name and prototype.
+    pub fn get(&self, index: RawMethodIndex) -> Result<Arc<MethodRef>> {
+        if let Some(v) = self.read_cache.get(&index) {
+            return Ok(v.value().clone());
+        }
+        let id = self.id_at(index)?;
+        let method = MethodRef {
+            class: self.types.get(id.class_idx as uint)?,
+            name: self.strings.get_at(id.name_idx)?,
+            proto: self.protos.get(id.proto_idx as uint)?,
+        }
+        .into_arc();
+        self.read_cache.insert(index, method.clone());
+        Ok(method)
+    }
+
+    /// Returns the index into the `method_ids` list of the method defined by `class`
+    /// with the given `name` and `signature`, e.g. `(ILjava/lang/String;)V`.
+    pub fn find(&self, class: &str, name: &str, signature: &str) -> Result<RawMethodIndex> {
+        let class_idx = match self.types.find(class) {
+            Ok(idx) => idx,
+            Err(TypeReadError::TypeNotFound) => return Err(MethodReadError::MethodNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let name_idx = match self.strings.find_index(name) {
+            Ok(idx) => idx,
+            Err(StringReadError::StringNotFound) => return Err(MethodReadError::MethodNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let proto_idx = match self.protos.find(signature) {
+            Ok(idx) => idx,
+            Err(ProtoReadError::ProtoNotFound) => return Err(MethodReadError::MethodNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let element = (class_idx, name_idx, proto_idx);
+        // method_ids are sorted by defining type, then by name, then by prototype
+        let index = self
+            .section
+            .binary_search(&element, self.section.endian(), |id: &MethodId, element| {
+                let item = (id.class_idx as uint, id.name_idx, id.proto_idx as uint);
+                Ok::<_, MethodReadError>(element.cmp(&item))
+            })?
+            .ok_or(MethodReadError::MethodNotFound)?;
+        Ok(index as RawMethodIndex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        let midx = dex.methods().len() / 2;
+        let method = dex.methods().get(midx).unwrap();
+        let midx_2 = dex
+            .methods()
+            .find(&method.class, &method.name, &method.proto.to_string())
+            .unwrap();
+        assert_eq!(midx, midx_2);
+        assert!(dex
+            .methods()
+            .find(&method.class, "<not a method>", &method.proto.to_string())
+            .is_err());
+    }
+}
@@ -1,45 +1,396 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dashmap::DashSet;
 use scroll::Pread;
 
-use crate::raw::{header::Header, map_list::MapList, tysize};
+use crate::{
+    raw::{
+        annotations::{Annotation, AnnotationSetItem, AnnotationSetRefList},
+        class_data::ClassData,
+        classdef::ClassDef,
+        code_item::CodeItem,
+        compact::{self, DebugInfoOffsets},
+        header::{self, Header, HeaderError, Version},
+        hiddenapi::{HiddenapiClassDataItem, Restriction},
+        map_list::{ItemType, MapList},
+        tysize, uint, ulong, RawTypeIndex,
+    },
+    utils::nohash::BuildNoHashHasher,
+    writer::DexWriter,
+};
+use call_sites::CallSites;
+use class::{Class, MemberIndex};
+use fields::Fields;
+use method_handles::MethodHandles;
+use methods::Methods;
+use protos::Protos;
 use strings::Strings;
+use types::Types;
 
+pub mod call_sites;
+pub mod class;
+pub mod container;
+pub mod fields;
+pub mod method_handles;
+pub mod methods;
+pub mod protos;
 pub(crate) mod section;
 pub mod strings;
+pub mod types;
 #[macro_use]
 mod utils;
 
 pub struct DexFile<'a> {
+    // the file itself, starting at its header, which the id sections are relative to
+    ids_src: &'a [u8],
+    // what data offsets are relative to: the whole container if the file is part of one,
+    // or only the data section of a compact file
     src: &'a [u8],
     header: Header<'a>,
     map_list: MapList,
-    strings: Strings<'a>,
+    strings: Arc<Strings<'a>>,
+    types: Arc<Types<'a>>,
+    protos: Arc<Protos<'a>>,
+    fields: Arc<Fields<'a>>,
+    methods: Arc<Methods<'a>>,
+    method_handles: Arc<MethodHandles<'a>>,
+    call_sites: CallSites<'a>,
+    // only present in files built against the Android platform
+    hiddenapi: Option<HiddenapiClassDataItem<'a>>,
+    // maps type indices to class_def indices
+    class_defs: HashMap<RawTypeIndex, uint, BuildNoHashHasher<RawTypeIndex>>,
+    // only present in compact files, whose code items don't contain them
+    debug_info_offsets: Option<DebugInfoOffsets<'a>>,
+    // offsets of the code items whose opcodes were checked against the version
+    checked_code: DashSet<uint, BuildNoHashHasher<uint>>,
 }
 
 impl<'a> DexFile<'a> {
     pub fn new(src: &'a [u8]) -> crate::Result<Self> {
-        let header: Header = src.pread_with(0, scroll::LE)?;
-        let map_list: MapList = src.pread_with(header.map_off as usize, scroll::LE)?;
-        let strings = Strings::new(
+        Self::new_at(src, 0)
+    }
+    /// Opens the file whose header is at `header_offset` in the container `src`,
+    /// see [`DexContainer`](container::DexContainer).
+    ///
+    /// Compact dex files are opened the same way, and `src` has to include their data section.
+    pub fn new_at(src: &'a [u8], header_offset: usize) -> crate::Result<Self> {
+        let header: Header = src.pread(header_offset)?;
+        let endian = header.endian();
+        if header.version >= Version::V041 {
+            if header.header_offset as usize != header_offset {
+                let err =
+                    HeaderError::HeaderOffsetMismatch(header_offset as uint, header.header_offset);
+                return Err(err.into());
+            }
+            if header.container_size as usize != src.len() {
+                let err = HeaderError::ContainerSizeMismatch(src.len(), header.container_size);
+                return Err(err.into());
+            }
+        }
+        // the id sections are relative to the header, the data section to the container
+        let ids_src = &src[header_offset..];
+        let src = match header.compact {
+            Some(_) => {
+                let start = header.data_off as usize;
+                let end = start + header.data_size as usize;
+                ids_src.get(start..end).ok_or(scroll::Error::TooBig {
+                    size: end,
+                    len: ids_src.len(),
+                })?
+            }
+            None => src,
+        };
+        let debug_info_offsets = match &header.compact {
+            Some(compact) => Some(DebugInfoOffsets::new(src, compact, endian)?),
+            None => None,
+        };
+        let map_list: MapList = src.pread_with(header.map_off as usize, endian)?;
+        for item_type in map_list.item_types() {
+            let feature = format_args!("{item_type:?} items");
+            header.version.require(item_type.min_version(), feature)?;
+        }
+        let strings = Arc::new(Strings::new(
+            src,
+            /* shallow clone */ header.clone(),
+            raw_string_ids_section(ids_src, &header)?,
+        ));
+        let types = Arc::new(Types::new(
+            /* shallow clone */ header.clone(),
+            raw_type_ids_section(ids_src, &header)?,
+            strings.clone(),
+        ));
+        let protos = Arc::new(Protos::new(
             src,
             /* shallow clone */ header.clone(),
-            raw_string_ids_section(src, &header)?,
+            raw_proto_ids_section(ids_src, &header)?,
+            strings.clone(),
+            types.clone(),
+        ));
+        let fields = Arc::new(Fields::new(
+            /* shallow clone */ header.clone(),
+            raw_field_ids_section(ids_src, &header)?,
+            strings.clone(),
+            types.clone(),
+        ));
+        let methods = Arc::new(Methods::new(
+            /* shallow clone */ header.clone(),
+            raw_method_ids_section(ids_src, &header)?,
+            strings.clone(),
+            types.clone(),
+            protos.clone(),
+        ));
+        // these sections are only present in files that need them
+        let method_handles = Arc::new(MethodHandles::new(
+            raw_method_handles_section(ids_src, &map_list, endian)?,
+            fields.clone(),
+            methods.clone(),
+        ));
+        let call_sites = CallSites::new(
+            src,
+            raw_call_site_ids_section(ids_src, &map_list, endian)?,
+            method_handles.clone(),
+            strings.clone(),
+            protos.clone(),
         );
+        let class_defs_section = raw_class_defs_section(ids_src, &header)?;
+        let mut class_defs =
+            HashMap::with_capacity_and_hasher(header.class_defs_size as usize, Default::default());
+        for index in 0..header.class_defs_size {
+            let def: ClassDef = class_defs_section.index(index as usize, endian)?;
+            class_defs.insert(def.class_idx, index);
+        }
+        let hiddenapi = match map_list.get_offset(ItemType::HiddenapiClassDataItem) {
+            Some(offset) => {
+                Some(src.pread_with(offset as usize, (endian, header.class_defs_size))?)
+            }
+            None => None,
+        };
         Ok(Self {
+            ids_src,
             src,
             header,
             map_list,
             strings,
+            types,
+            protos,
+            fields,
+            methods,
+            method_handles,
+            call_sites,
+            hiddenapi,
+            class_defs,
+            debug_info_offsets,
+            checked_code: Default::default(),
         })
     }
-    pub fn header(&self) -> &Header {
+    pub fn header(&self) -> &Header<'a> {
         &self.header
     }
+    /// Whether this is a compact dex file, see [`compact`](crate::raw::compact).
+    pub fn is_compact(&self) -> bool {
+        self.header.compact.is_some()
+    }
+    /// Fails if `code`, at `code_off`, uses opcodes the version of this file doesn't allow.
+    /// Each code item is only decoded once, and only in files before 039.
+    pub(crate) fn check_code(&self, code_off: uint, code: &CodeItem) -> crate::Result<()> {
+        // every opcode is allowed since 039, so later files don't need to be checked
+        let version = self.header.version;
+        if version >= Version::V039 || self.checked_code.contains(&code_off) {
+            return Ok(());
+        }
+        for insn in code.instructions() {
+            if let Some(opcode) = insn?.instruction.opcode() {
+                version.require(opcode.min_version(), opcode)?;
+            }
+        }
+        self.checked_code.insert(code_off);
+        Ok(())
+    }
+    /// Reads the code item at `code_off` of the method with the given index.
+    /// Compact code items are converted to the standard form.
+    pub(crate) fn code_item(&self, method_idx: uint, code_off: uint) -> crate::Result<CodeItem> {
+        match &self.debug_info_offsets {
+            Some(offsets) => {
+                let debug_info_off = offsets.get(method_idx)?;
+                let code = compact::read_code_item(
+                    self.src,
+                    code_off as usize,
+                    debug_info_off,
+                    self.header.endian(),
+                )?;
+                Ok(code)
+            }
+            None => Ok(self
+                .src
+                .pread_with(code_off as usize, self.header.endian())?),
+        }
+    }
+    /// Writes this file as a standard dex file, e.g. to convert a compact one.
+    /// Like [`DexWriter::from_dex`], items no class refers to are dropped.
+    pub fn to_standard(&self) -> crate::Result<Vec<u8>> {
+        self.to_endian(self.header.endian())
+    }
+    /// Like [`DexFile::to_standard`], but writes the file in the given byte order,
+    /// e.g. to convert a big-endian file to little-endian.
+    pub fn to_endian(&self, endian: scroll::Endian) -> crate::Result<Vec<u8>> {
+        let mut writer = DexWriter::from_dex(self)?;
+        if self.is_compact() {
+            writer.set_version(self.min_version()?);
+        }
+        writer.set_endian(endian);
+        writer.write()
+    }
+    /// The first version that allows everything this file contains,
+    /// which may be lower than the version in its header.
+    pub fn min_version(&self) -> crate::Result<Version> {
+        let mut version = self
+            .map_list
+            .item_types()
+            .map(ItemType::min_version)
+            .max()
+            .unwrap_or(Version::V035);
+        for class in self.classes() {
+            // not `Class::class_data` or `Method::code`,
+            // which fail if the header's version is too low
+            let def = *class?.def();
+            if def.class_data_off == 0 {
+                continue;
+            }
+            let data: ClassData = self.src.pread_with(def.class_data_off as usize, ())?;
+            version = version.max(data.min_version(def.access_flags));
+            for methods in [&data.direct_methods, &data.virtual_methods] {
+                let mut method_idx: ulong = 0;
+                for method in methods {
+                    method_idx += method.method_idx_diff;
+                    if method.code_off != 0 {
+                        let code = self.code_item(method_idx as uint, method.code_off as uint)?;
+                        version = version.max(code.min_version()?);
+                    }
+                }
+            }
+        }
+        Ok(version)
+    }
+    /// Checks the SHA-1 `signature` in the header against the contents of the file.
+    /// Unlike the checksum, it isn't checked by [`DexFile::new`], as hashing the whole file is slow.
+    pub fn verify_signature(&self) -> crate::Result<()> {
+        if header::signature(self.ids_src)? != self.header.signature {
+            return Err(HeaderError::InvalidSignature.into());
+        }
+        Ok(())
+    }
     pub fn map_list(&self) -> &MapList {
         &self.map_list
     }
-    pub fn strings(&self) -> &Strings {
+    pub fn strings(&self) -> &Strings<'a> {
         &self.strings
     }
+    pub fn types(&self) -> &Types<'a> {
+        &self.types
+    }
+    pub fn protos(&self) -> &Protos<'a> {
+        &self.protos
+    }
+    pub fn fields(&self) -> &Fields<'a> {
+        &self.fields
+    }
+    pub fn methods(&self) -> &Methods<'a> {
+        &self.methods
+    }
+    pub fn method_handles(&self) -> &MethodHandles<'a> {
+        &self.method_handles
+    }
+    pub fn call_sites(&self) -> &CallSites<'a> {
+        &self.call_sites
+    }
+    pub fn hiddenapi(&self) -> Option<&HiddenapiClassDataItem<'a>> {
+        self.hiddenapi.as_ref()
+    }
+
+    /// Returns the hidden API restriction of a field or method defined by `class`,
+    /// or `None` if this file has no restrictions for it.
+    pub fn hiddenapi_flags(
+        &self,
+        class: &Class<'_>,
+        member: impl Into<MemberIndex>,
+    ) -> crate::Result<Option<Restriction>> {
+        let Some(flags) = class.hiddenapi_flags()? else {
+            return Ok(None);
+        };
+        let mut fields = class
+            .static_fields()?
+            .into_iter()
+            .chain(class.instance_fields()?);
+        let methods = class
+            .direct_methods()?
+            .into_iter()
+            .chain(class.virtual_methods()?);
+        let position = match member.into() {
+            MemberIndex::Field(index) => fields.position(|field| field.index == index),
+            MemberIndex::Method(index) => {
+                // methods come after all fields
+                let field_count = fields.count();
+                methods
+                    .into_iter()
+                    .position(|method| method.index == index)
+                    .map(|position| field_count + position)
+            }
+        };
+        Ok(position.and_then(|position| flags.get(position).copied()))
+    }
+
+    /// Reads the `annotation_set_item` at `offset`, or an empty set if `offset` is 0.
+    pub fn annotation_set(&self, offset: uint) -> crate::Result<Vec<Annotation>> {
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let set: AnnotationSetItem = self.src.pread_with(offset as usize, self.header.endian())?;
+        let annotations = set
+            .into_inner()
+            .into_iter()
+            .map(|off| self.src.pread_with(off as usize, self.header.endian()))
+            .collect::<Result<_, _>>()?;
+        Ok(annotations)
+    }
+
+    /// Reads the `annotation_set_ref_list` at `offset`, with one set per method parameter.
+    pub fn annotation_set_ref_list(&self, offset: uint) -> crate::Result<Vec<Vec<Annotation>>> {
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let list: AnnotationSetRefList =
+            self.src.pread_with(offset as usize, self.header.endian())?;
+        list.into_inner()
+            .into_iter()
+            .map(|off| self.annotation_set(off))
+            .collect()
+    }
+
+    /// Returns the class at `index` in the `class_defs` list.
+    pub fn class_at(&self, index: uint) -> crate::Result<Class<'_>> {
+        let def = self
+            .class_defs_section()?
+            .index(index as usize, self.header.endian())?;
+        Ok(Class::new(self, index, def))
+    }
+
+    /// Iterates over all classes defined in this file, in `class_defs` order.
+    pub fn classes(&self) -> impl Iterator<Item = crate::Result<Class<'_>>> {
+        (0..self.header.class_defs_size).map(move |index| self.class_at(index))
+    }
+
+    /// Returns the class with the given descriptor, or `None` if this file doesn't define it.
+    pub fn class_by_descriptor(&self, descriptor: &str) -> crate::Result<Option<Class<'_>>> {
+        let type_idx = match self.types.find(descriptor) {
+            Ok(idx) => idx,
+            Err(types::TypeReadError::TypeNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match self.class_defs.get(&type_idx) {
+            Some(index) => Ok(Some(self.class_at(*index)?)),
+            None => Ok(None),
+        }
+    }
 }
 
 // sections
@@ -62,6 +413,250 @@ section!(
 
 #[cfg(test)]
 mod tests {
+    use crate::{
+        dex::section::Error as SectionError,
+        error::Error,
+        raw::header::{fix_checksums, HeaderError, Version, VersionError},
+        smali::disassemble,
+    };
+
+    use super::DexFile;
+
+    #[test]
+    fn big_endian() {
+        let dex = crate::t::dex!();
+        let src = dex.to_endian(scroll::BE).unwrap();
+        let be = DexFile::new(&src).unwrap();
+        assert_eq!(be.header().endian(), scroll::BE);
+        assert_eq!(src[0x28..0x2c], [0x12, 0x34, 0x56, 0x78]);
+        be.verify_signature().unwrap();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let descriptor = class.descriptor().unwrap();
+            let be_class = be.class_by_descriptor(&descriptor).unwrap().unwrap();
+            assert_eq!(
+                disassemble(&be_class).unwrap(),
+                disassemble(&class).unwrap()
+            );
+        }
+        // the byte order is kept when rewriting, and converting back restores the file
+        assert_eq!(be.to_standard().unwrap(), src);
+        let le = be.to_endian(scroll::LE).unwrap();
+        assert_eq!(le, dex.to_endian(scroll::LE).unwrap());
+        assert_eq!(DexFile::new(&le).unwrap().header().endian(), scroll::LE);
+
+        let mut src = src;
+        src[0x28..0x2c].fill(0);
+        assert!(matches!(
+            DexFile::new(&src),
+            Err(Error::Header(HeaderError::InvalidEndianTag(0)))
+        ));
+    }
+
+    #[test]
+    fn checksums() {
+        let mut src = crate::t::DEX.to_vec();
+        DexFile::new(&src).unwrap().verify_signature().unwrap();
+
+        // patch the last byte of the map list, the size of its last entry
+        let last = src.len() - 5;
+        src[last] ^= 1;
+        assert!(matches!(
+            DexFile::new(&src),
+            Err(Error::Header(HeaderError::InvalidChecksum))
+        ));
+        fix_checksums(&mut src).unwrap();
+        DexFile::new(&src).unwrap().verify_signature().unwrap();
+
+        // a wrong signature with a matching checksum
+        src[12..32].fill(0);
+        let checksum = adler32::adler32(&src[12..]).unwrap();
+        src[8..12].copy_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            DexFile::new(&src).unwrap().verify_signature(),
+            Err(Error::Header(HeaderError::InvalidSignature))
+        ));
+        fix_checksums(&mut src).unwrap();
+        DexFile::new(&src).unwrap().verify_signature().unwrap();
+
+        assert!(fix_checksums(&mut [0; 16]).is_err());
+    }
+
+    #[test]
+    fn map_sections() {
+        let source = r#"
+.class public LA;
+.super Ljava/lang/Object;
+
+.method public static f()V
+    .registers 1
+    const-method-handle v0, invoke-static@LA;->f()V
+    return-void
+.end method
+"#;
+        let mut src = crate::t::write(&[source]);
+        let dex = DexFile::new(&src).unwrap();
+        assert_eq!(dex.method_handles().len(), 1);
+        assert!(dex.call_site_ids_section().unwrap().is_none());
+
+        // a method handle section past the end of the file
+        let map_off = dex.header().map_off as usize;
+        let count = dex.map_list().item_types().count();
+        let entry = (0..count)
+            .map(|i| map_off + 4 + i * 12)
+            .find(|entry| src[*entry..*entry + 2] == [0x08, 0x00])
+            .unwrap();
+        src[entry + 4..entry + 8].copy_from_slice(&0x1000u32.to_le_bytes());
+        fix_checksums(&mut src).unwrap();
+        assert!(matches!(
+            DexFile::new(&src),
+            Err(Error::Section(SectionError::BadSection("method_handles")))
+        ));
+    }
+
+    #[test]
+    fn interface_methods() {
+        let source = r#"
+.class public interface abstract LI;
+.super Ljava/lang/Object;
+
+.method static constructor <clinit>()V
+    .registers 0
+    return-void
+.end method
+
+.method public abstract f()V
+.end method
+"#;
+        let src = crate::t::write(&[source]);
+        let dex = DexFile::new(&src).unwrap();
+        assert_eq!(dex.header().version, Version::V035);
+        assert_eq!(dex.min_version().unwrap(), Version::V035);
+
+        // default methods need 037
+        let source = r#"
+.class public interface abstract LI;
+.super Ljava/lang/Object;
+
+.method public g()V
+    .registers 1
+    return-void
+.end method
+"#;
+        let mut src = crate::t::write(&[source]);
+        let dex = DexFile::new(&src).unwrap();
+        assert_eq!(dex.header().version, Version::V037);
+        assert_eq!(dex.min_version().unwrap(), Version::V037);
+
+        // and are rejected in earlier files
+        src[4..7].copy_from_slice(b"035");
+        fix_checksums(&mut src).unwrap();
+        let dex = DexFile::new(&src).unwrap();
+        assert_eq!(dex.min_version().unwrap(), Version::V037);
+        let class = dex.classes().next().unwrap().unwrap();
+        assert!(matches!(
+            class.class_data(),
+            Err(Error::Version(VersionError::FeatureUnsupported(
+                _,
+                Version::V037,
+                Version::V035
+            )))
+        ));
+    }
+
+    #[test]
+    fn versions() {
+        let dex = crate::t::dex!();
+        assert!(dex.min_version().unwrap() <= dex.header().version);
+
+        let source = r#"
+.class public LA;
+.super Ljava/lang/Object;
+
+.method public static f()V
+    .registers 1
+    const-method-handle v0, invoke-static@LA;->f()V
+    return-void
+.end method
+"#;
+        let mut src = crate::t::write(&[source]);
+        let dex = DexFile::new(&src).unwrap();
+        assert_eq!(dex.header().version, Version::V039);
+        assert_eq!(dex.min_version().unwrap(), Version::V039);
+
+        let mut patch = |version: &[u8]| {
+            src[4..7].copy_from_slice(version);
+            fix_checksums(&mut src).unwrap();
+            src.clone()
+        };
+        // the method handle section is allowed, but not the instruction
+        let src_038 = patch(b"038");
+        let dex = DexFile::new(&src_038).unwrap();
+        assert_eq!(dex.min_version().unwrap(), Version::V039);
+        let class = dex.classes().next().unwrap().unwrap();
+        let method = class.direct_methods().unwrap()[0];
+        assert!(matches!(
+            method.code(),
+            Err(Error::Version(VersionError::FeatureUnsupported(
+                _,
+                Version::V039,
+                Version::V038
+            )))
+        ));
+        assert!(matches!(
+            DexFile::new(&patch(b"035")),
+            Err(Error::Version(VersionError::FeatureUnsupported(
+                _,
+                Version::V038,
+                Version::V035
+            )))
+        ));
+        assert!(matches!(
+            DexFile::new(&patch(b"036")),
+            Err(Error::Header(HeaderError::Version(
+                VersionError::Unsupported(_)
+            )))
+        ));
+        assert!(matches!(
+            DexFile::new(&patch(b"0x9")),
+            Err(Error::Header(HeaderError::InvalidMagic(
+                VersionError::InvalidMagic
+            )))
+        ));
+
+        // instructions that fail to decode aren't skipped by the check
+        let source = r#"
+.class public LA;
+.super Ljava/lang/Object;
+
+.method public static f()V
+    .registers 1
+    nop
+    const-method-handle v0, invoke-static@LA;->f()V
+    return-void
+.end method
+"#;
+        let mut src = crate::t::write(&[source]);
+        src[4..7].copy_from_slice(b"038");
+        fix_checksums(&mut src).unwrap();
+        let dex = DexFile::new(&src).unwrap();
+        let class = dex.classes().next().unwrap().unwrap();
+        let method = class.direct_methods().unwrap()[0];
+        assert!(matches!(
+            method.code(),
+            Err(Error::Version(VersionError::FeatureUnsupported(..)))
+        ));
+        // the `nop` becomes an unused opcode, right after the 16 bytes before `insns`
+        let insns = method.code_off as usize + 16;
+        src[insns] = 0x3e;
+        fix_checksums(&mut src).unwrap();
+        let dex = DexFile::new(&src).unwrap();
+        let class = dex.classes().next().unwrap().unwrap();
+        let method = class.direct_methods().unwrap()[0];
+        assert!(matches!(method.code(), Err(Error::Decode(_))));
+        assert!(matches!(dex.min_version(), Err(Error::Decode(_))));
+    }
+
     #[test]
     #[ignore = "debug"]
     pub fn header() {
@@ -0,0 +1,217 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use scroll::Pread;
+
+use crate::{
+    raw::{header::Header, simple::ProtoId, type_list::TypeList, uint, RawProtoIndex},
+    utils::{nohash::BuildNoHashHasher, IntoArc},
+};
+
+use super::{
+    section::Section,
+    strings::{DexString, StringReadError, Strings},
+    types::{TypeReadError, Types},
+};
+
+type Result<T> = std::result::Result<T, ProtoReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtoReadError {
+    #[error("prototype not found")]
+    ProtoNotFound,
+    #[error("prototype index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("invalid method signature: {0}")]
+    InvalidSignature(String),
+    #[error("error reading shorty: {0}")]
+    Shorty(#[from] StringReadError),
+    #[error("error reading type: {0}")]
+    Type(#[from] TypeReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// A resolved [`ProtoId`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Prototype {
+    /// The short-form descriptor of this prototype, e.g. `ILL`.
+    pub shorty: DexString,
+    /// The descriptor of the return type.
+    pub return_type: DexString,
+    /// The descriptors of the parameter types, in order.
+    pub parameters: Vec<DexString>,
+}
+
+impl std::fmt::Display for Prototype {
+    /// Formats the prototype as a method signature, e.g. `(ILjava/lang/String;)V`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "(")?;
+        for param in &self.parameters {
+            write!(f, "{param}")?;
+        }
+        write!(f, "){}", self.return_type)
+    }
+}
+
+pub struct Protos<'a> {
+    src: &'a [u8],
+    header: Header<'a>,
+    // proto id section
+    section: Section<'a>,
+    strings: Arc<Strings<'a>>,
+    types: Arc<Types<'a>>,
+    read_cache: dashmap::DashMap<RawProtoIndex, Arc<Prototype>, BuildNoHashHasher<RawProtoIndex>>,
+}
+
+impl<'a> Protos<'a> {
+    pub fn new(
+        src: &'a [u8],
+        header: Header<'a>,
+        section: Section<'a>,
+        strings: Arc<Strings<'a>>,
+        types: Arc<Types<'a>>,
+    ) -> Self {
+        Self {
+            src,
+            header,
+            section,
+            strings,
+            types,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.header.proto_ids_size
+    }
+
+    pub fn id_at(&self, index: RawProtoIndex) -> Result<ProtoId> {
+        if index >= self.len() {
+            return Err(ProtoReadError::IndexOutOfBounds(index));
+        }
+        let id = self.section.index(index as usize, self.section.endian())?;
+        Ok(id)
+    }
+
+    /// Returns the raw type indices of the parameters of the given [`ProtoId`].
+    pub fn parameter_type_indices(&self, id: &ProtoId) -> Result<Vec<uint>> {
+        if id.parameters_off == 0 {
+            return Ok(Vec::new());
+        }
+        let list: TypeList = self
+            .src
+            .pread_with(id.parameters_off as usize, self.header.endian())?;
+        Ok(list
+            .into_inner()
+            .into_iter()
+            .map(|item| item.type_idx as uint)
+            .collect())
+    }
+
+    /// Resolves the prototype at `index` into its shorty, return type and parameters.
+    pub fn get(&self, index: RawProtoIndex) -> Result<Arc<Prototype>> {
+        if let Some(v) = self.read_cache.get(&index) {
+            return Ok(v.value().clone());
+        }
+        let id = self.id_at(index)?;
+        let parameters = self
+            .parameter_type_indices(&id)?
+            .into_iter()
+            .map(|idx| self.types.get(idx))
+            .collect::<std::result::Result<_, _>>()?;
+        let proto = Prototype {
+            shorty: self.strings.get_at(id.shorty_idx)?,
+            return_type: self.types.get(id.return_type_idx)?,
+            parameters,
+        }
+        .into_arc();
+        self.read_cache.insert(index, proto.clone());
+        Ok(proto)
+    }
+
+    /// Returns the index into the `proto_ids` list of the prototype
+    /// matching the given signature, e.g. `(ILjava/lang/String;)V`.
+    pub fn find(&self, signature: &str) -> Result<RawProtoIndex> {
+        let (return_type, parameters) = parse_signature(signature)
+            .ok_or_else(|| ProtoReadError::InvalidSignature(signature.to_owned()))?;
+        let find_type = |descriptor| match self.types.find(descriptor) {
+            Ok(idx) => Ok(idx),
+            Err(TypeReadError::TypeNotFound) => Err(ProtoReadError::ProtoNotFound),
+            Err(e) => Err(e.into()),
+        };
+        let element = (
+            find_type(return_type)?,
+            parameters
+                .into_iter()
+                .map(find_type)
+                .collect::<Result<Vec<_>>>()?,
+        );
+        // proto_ids are sorted by return type, then by the argument list
+        let index = self
+            .section
+            .binary_search(
+                &element,
+                self.section.endian(),
+                |id: &ProtoId, (return_type, parameters)| {
+                    Ok::<_, ProtoReadError>(match return_type.cmp(&id.return_type_idx) {
+                        Ordering::Equal => parameters.cmp(&self.parameter_type_indices(id)?),
+                        ordering => ordering,
+                    })
+                },
+            )?
+            .ok_or(ProtoReadError::ProtoNotFound)?;
+        Ok(index as RawProtoIndex)
+    }
+}
+
+/// Splits a method signature like `(ILjava/lang/String;)V` into
+/// its return type and parameter type descriptors.
+pub(crate) fn parse_signature(signature: &str) -> Option<(&str, Vec<&str>)> {
+    let rest = signature.strip_prefix('(')?;
+    let (params, return_type) = rest.split_once(')')?;
+    let mut parameters = Vec::new();
+    let mut params = params;
+    while !params.is_empty() {
+        let len = descriptor_len(params)?;
+        parameters.push(&params[..len]);
+        params = &params[len..];
+    }
+    if descriptor_len(return_type)? != return_type.len() {
+        return None;
+    }
+    Some((return_type, parameters))
+}
+
+/// Returns the length of the type descriptor at the start of `s`.
+pub(crate) fn descriptor_len(s: &str) -> Option<usize> {
+    let dims = s.bytes().take_while(|b| *b == b'[').count();
+    let len = match s.as_bytes().get(dims)? {
+        b'V' | b'Z' | b'B' | b'S' | b'C' | b'I' | b'J' | b'F' | b'D' => 1,
+        b'L' => s[dims..].find(';')? + 1,
+        _ => return None,
+    };
+    Some(dims + len)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        let pidx = dex.protos().len() / 2;
+        let proto = dex.protos().get(pidx).unwrap();
+        assert_eq!(proto.parameters.len() + 1, proto.shorty.len());
+        let pidx_2 = dex.protos().find(&proto.to_string()).unwrap();
+        assert_eq!(pidx, pidx_2);
+    }
+
+    #[test]
+    pub fn parse_signature() {
+        let (ret, params) = super::parse_signature("(I[[JLjava/lang/String;)V").unwrap();
+        assert_eq!(ret, "V");
+        assert_eq!(params, ["I", "[[J", "Ljava/lang/String;"]);
+        assert!(super::parse_signature("(I)").is_none());
+        assert!(super::parse_signature("(Ljava/lang/String)V").is_none());
+    }
+}
@@ -10,11 +10,26 @@ pub enum Error {
 pub struct Section<'a> {
     inner: &'a [u8],
     type_size: usize,
+    endian: scroll::Endian,
 }
 
 impl<'a> Section<'a> {
-    pub(crate) fn new(inner: &'a [u8], type_size: usize) -> Self {
-        Section { inner, type_size }
+    pub(crate) fn new(inner: &'a [u8], type_size: usize, endian: scroll::Endian) -> Self {
+        Section {
+            inner,
+            type_size,
+            endian,
+        }
+    }
+
+    /// Byte order of the file this section belongs to.
+    pub(crate) fn endian(&self) -> scroll::Endian {
+        self.endian
+    }
+
+    /// Number of items in this section.
+    pub(crate) fn len(&self) -> usize {
+        self.inner.len() / self.type_size
     }
 
     pub(crate) fn index<Ctx: Copy, N>(&self, index: usize, ctx: Ctx) -> Result<N, N::Error>
@@ -65,6 +80,7 @@ impl<'a> Section<'a> {
             let result = predicate(&item, element)?;
             match result {
                 Ordering::Equal => return Ok(Some(mid)),
+                Ordering::Less if mid == 0 => return Ok(None),
                 Ordering::Less => end = mid - 1,
                 Ordering::Greater => start = mid + 1,
             }
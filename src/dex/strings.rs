@@ -64,7 +64,7 @@ impl<'a> Strings<'a> {
         if index >= self.len() {
             return Err(StringReadError::IndexOutOfBounds(index));
         }
-        let id = self.section.index(index as usize, scroll::LE)?;
+        let id = self.section.index(index as usize, self.section.endian())?;
         Ok(id)
     }
 
@@ -76,7 +76,9 @@ impl<'a> Strings<'a> {
                 if !self.header.in_data_section(data_offset) {
                     return Err(StringReadError::OffsetOutOfBounds(data_offset));
                 }
-                let data: StringData = self.src.pread_with(data_offset as usize, scroll::LE)?;
+                let data: StringData = self
+                    .src
+                    .pread_with(data_offset as usize, self.header.endian())?;
                 let str = from_java_cesu8(data.data)
                     .map_err(|e| StringReadError::Malformed(data_offset, e))?
                     .into_owned()
@@ -87,16 +89,32 @@ impl<'a> Strings<'a> {
         }
     }
 
+    /// Shorthand for [`Strings::id_at`] followed by [`Strings::get`].
+    pub fn get_at(&self, index: uint) -> Result<DexString> {
+        self.get(&self.id_at(index)?)
+    }
+
     pub fn find(&self, query: &str) -> Result<StringId> {
+        self.id_at(self.find_index(query)?)
+    }
+
+    /// Same as [`Strings::find`], but returns the index into the `string_ids` list instead.
+    pub fn find_index(&self, query: &str) -> Result<uint> {
         let element = to_java_cesu8(query);
         let index = self
             .section
-            .binary_search(&element, scroll::LE, move |offset: &uint, element: _| {
-                let data: StringData = self.src.pread_with(*offset as usize, scroll::LE)?;
-                Ok::<_, StringReadError>((**element).cmp(data.data))
-            })?
-            .ok_or_else(|| StringReadError::StringNotFound)?;
-        self.id_at(index as uint)
+            .binary_search(
+                &element,
+                self.section.endian(),
+                move |offset: &uint, element: _| {
+                    let data: StringData = self
+                        .src
+                        .pread_with(*offset as usize, self.header.endian())?;
+                    Ok::<_, StringReadError>((**element).cmp(data.data))
+                },
+            )?
+            .ok_or(StringReadError::StringNotFound)?;
+        Ok(index as uint)
     }
 
     // TODO: does this need to be parallelized?
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::{
+    raw::{header::Header, simple::TypeId, uint, RawTypeIndex},
+    utils::nohash::BuildNoHashHasher,
+};
+
+use super::{
+    section::Section,
+    strings::{DexString, StringReadError, Strings},
+};
+
+type Result<T> = std::result::Result<T, TypeReadError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TypeReadError {
+    #[error("type not found")]
+    TypeNotFound,
+    #[error("type index {0} is out of bounds")]
+    IndexOutOfBounds(uint),
+    #[error("error reading descriptor: {0}")]
+    Descriptor(#[from] StringReadError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+pub struct Types<'a> {
+    header: Header<'a>,
+    // type id section
+    section: Section<'a>,
+    strings: Arc<Strings<'a>>,
+    read_cache: dashmap::DashMap<RawTypeIndex, DexString, BuildNoHashHasher<RawTypeIndex>>,
+}
+
+impl<'a> Types<'a> {
+    pub fn new(header: Header<'a>, section: Section<'a>, strings: Arc<Strings<'a>>) -> Self {
+        Self {
+            header,
+            section,
+            strings,
+            read_cache: Default::default(),
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.header.type_ids_size
+    }
+
+    pub fn id_at(&self, index: RawTypeIndex) -> Result<TypeId> {
+        if index >= self.len() {
+            return Err(TypeReadError::IndexOutOfBounds(index));
+        }
+        let id = self.section.index(index as usize, self.section.endian())?;
+        Ok(id)
+    }
+
+    /// Resolves the type at `index` to its descriptor, e.g. `Ljava/lang/Object;`.
+    pub fn get(&self, index: RawTypeIndex) -> Result<DexString> {
+        match self.read_cache.get(&index) {
+            Some(v) => Ok(v.value().clone()),
+            None => {
+                let id = self.id_at(index)?;
+                let descriptor = self.strings.get_at(id.descriptor_idx)?;
+                self.read_cache.insert(index, descriptor.clone());
+                Ok(descriptor)
+            }
+        }
+    }
+
+    /// Returns the index into the `type_ids` list of the type with the given descriptor.
+    pub fn find(&self, descriptor: &str) -> Result<RawTypeIndex> {
+        let descriptor_idx = match self.strings.find_index(descriptor) {
+            Ok(idx) => idx,
+            Err(StringReadError::StringNotFound) => return Err(TypeReadError::TypeNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        // type_ids are sorted by string_id index
+        let index = self
+            .section
+            .binary_search(
+                &descriptor_idx,
+                self.section.endian(),
+                |id: &TypeId, element| Ok::<_, TypeReadError>(element.cmp(&id.descriptor_idx)),
+            )?
+            .ok_or(TypeReadError::TypeNotFound)?;
+        Ok(index as RawTypeIndex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test() {
+        let dex = crate::t::dex!();
+        let tidx = dex.types().len() / 2;
+        let descriptor = dex.types().get(tidx).unwrap();
+        assert_eq!(dex.types().find(&descriptor).unwrap(), tidx);
+        assert!(dex.types().find("Lnot/a/Type;").is_err());
+    }
+}
@@ -1,16 +1,69 @@
 use crate::{
-    dex::{section::Error as SectionError, strings::StringReadError},
-    raw::{header::HeaderError, map_list::MapListError},
+    analysis::{CfgError, TypeInferenceError},
+    dex::{
+        call_sites::CallSiteReadError, fields::FieldReadError,
+        method_handles::MethodHandleReadError, methods::MethodReadError, protos::ProtoReadError,
+        section::Error as SectionError, strings::StringReadError, types::TypeReadError,
+    },
+    raw::{
+        annotations::AnnotationError,
+        bytecode::DecodeError,
+        class_data::ClassDataError,
+        code_item::DebugInfoError,
+        encoded_value::EncodedValueError,
+        header::{HeaderError, VersionError},
+        hiddenapi::HiddenapiError,
+        map_list::MapListError,
+    },
+    smali::DisassembleError,
+    vdex::VdexError,
+    writer::WriteError,
 };
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("error parsing header: {0}")]
     Header(#[from] HeaderError),
+    #[error("error checking version: {0}")]
+    Version(#[from] VersionError),
     #[error("error parsing map_list: {0}")]
     MapList(#[from] MapListError),
     #[error("error reading string: {0}")]
     StringRead(#[from] StringReadError),
+    #[error("error reading type: {0}")]
+    TypeRead(#[from] TypeReadError),
+    #[error("error reading prototype: {0}")]
+    ProtoRead(#[from] ProtoReadError),
+    #[error("error reading field: {0}")]
+    FieldRead(#[from] FieldReadError),
+    #[error("error reading method: {0}")]
+    MethodRead(#[from] MethodReadError),
+    #[error("error reading method handle: {0}")]
+    MethodHandleRead(#[from] MethodHandleReadError),
+    #[error("error reading call site: {0}")]
+    CallSiteRead(#[from] CallSiteReadError),
+    #[error("error reading class data: {0}")]
+    ClassData(#[from] ClassDataError),
+    #[error("error reading encoded value: {0}")]
+    EncodedValue(#[from] EncodedValueError),
+    #[error("error reading annotation: {0}")]
+    Annotation(#[from] AnnotationError),
+    #[error("error reading debug info: {0}")]
+    DebugInfo(#[from] DebugInfoError),
+    #[error("error decoding instruction: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("error reading hiddenapi flags: {0}")]
+    Hiddenapi(#[from] HiddenapiError),
+    #[error("error building control-flow graph: {0}")]
+    Cfg(#[from] CfgError),
+    #[error("error inferring register types: {0}")]
+    TypeInference(#[from] TypeInferenceError),
+    #[error("error disassembling class: {0}")]
+    Disassemble(#[from] DisassembleError),
+    #[error("error writing dex file: {0}")]
+    Write(#[from] WriteError),
+    #[error("error reading vdex file: {0}")]
+    Vdex(#[from] VdexError),
     #[error("error reading from section: {0}")]
     Section(#[from] SectionError),
     #[error("read error: {0}")]
@@ -2,21 +2,39 @@ mod error;
 #[macro_use]
 pub(crate) mod utils;
 
+pub mod analysis;
 pub mod dex;
 pub mod raw;
+pub mod smali;
+pub mod vdex;
+pub mod writer;
 
 pub(crate) type Result<T> = std::result::Result<T, error::Error>;
 
 #[cfg(test)]
 pub(crate) mod t {
+    /// The dex file most tests are run against.
+    pub(crate) const DEX: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/classes.dex"));
+
     macro_rules! dex {
         () => {
-            crate::dex::DexFile::new(include_bytes!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/tests/classes.dex"
-            )))
-            .unwrap()
+            crate::dex::DexFile::new(crate::t::DEX).unwrap()
         };
     }
     pub(crate) use dex;
+
+    /// Assembles the smali `sources` into a dex file.
+    pub(crate) fn write(sources: &[&str]) -> Vec<u8> {
+        let mut pools = crate::writer::Pools::new();
+        let classes = sources
+            .iter()
+            .map(|source| crate::smali::assemble(source, &mut pools).unwrap())
+            .collect::<Vec<_>>();
+        let mut writer = crate::writer::DexWriter::new(pools);
+        for class in classes {
+            writer.add_class(class);
+        }
+        writer.write().unwrap()
+    }
 }
@@ -1,5 +1,5 @@
 use crate::raw::encoded_value::{EncodedAnnotation, EncodedValueError};
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{
@@ -38,7 +38,7 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for Annotation {
         let offset = &mut 0;
         let visibility_byte = src.gread_with(offset, ctx)?;
         let visibility = Visibility::from_u8(visibility_byte)
-            .ok_or_else(|| AnnotationError::InvalidVisibility(visibility_byte))?;
+            .ok_or(AnnotationError::InvalidVisibility(visibility_byte))?;
         let annotation = src.gread(offset)?;
         Ok((
             Self {
@@ -60,6 +60,12 @@ impl TryIntoCtx<scroll::Endian> for Annotation {
     }
 }
 
+impl EncodedLen for Annotation {
+    fn encoded_len(&self) -> usize {
+        std::mem::size_of::<ubyte>() + self.annotation.encoded_len()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AnnotationsDirectory {
     pub class_annotations_off: uint,
@@ -106,6 +112,16 @@ impl TryIntoCtx<scroll::Endian> for AnnotationsDirectory {
     }
 }
 
+impl EncodedLen for AnnotationsDirectory {
+    fn encoded_len(&self) -> usize {
+        let annotations = self.field_annotations.len()
+            + self.method_annotations.len()
+            + self.parameter_annotations.len();
+        // the class annotations offset and three sizes, then an index and offset per annotation
+        4 * std::mem::size_of::<uint>() + annotations * 2 * std::mem::size_of::<uint>()
+    }
+}
+
 #[derive(Debug, Clone, Copy, Pread, Pwrite)]
 pub struct FieldAnnotation {
     pub field_idx: uint,
@@ -124,7 +140,9 @@ pub struct ParameterAnnotation {
     pub annotations_off: uint,
 }
 
-#[derive(Debug, Default)]
+sized_encoded_len!(FieldAnnotation, MethodAnnotation, ParameterAnnotation);
+
+#[derive(Debug, Default, Clone)]
 pub struct AnnotationSetRefList(Vec<uint>); // inlined item into list
 
 impl AnnotationSetRefList {
@@ -133,6 +151,12 @@ impl AnnotationSetRefList {
     }
 }
 
+impl From<Vec<uint>> for AnnotationSetRefList {
+    fn from(offsets: Vec<uint>) -> Self {
+        Self(offsets)
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for AnnotationSetRefList {
     type Error = scroll::Error;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
@@ -153,7 +177,13 @@ impl TryIntoCtx<scroll::Endian> for AnnotationSetRefList {
     }
 }
 
-#[derive(Debug, Default)]
+impl EncodedLen for AnnotationSetRefList {
+    fn encoded_len(&self) -> usize {
+        (1 + self.0.len()) * std::mem::size_of::<uint>()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct AnnotationSetItem(Vec<uint>); // inlined offsets into item
 
 impl AnnotationSetItem {
@@ -162,6 +192,12 @@ impl AnnotationSetItem {
     }
 }
 
+impl From<Vec<uint>> for AnnotationSetItem {
+    fn from(offsets: Vec<uint>) -> Self {
+        Self(offsets)
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for AnnotationSetItem {
     type Error = scroll::Error;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
@@ -181,3 +217,9 @@ impl TryIntoCtx<scroll::Endian> for AnnotationSetItem {
         Ok(*offset)
     }
 }
+
+impl EncodedLen for AnnotationSetItem {
+    fn encoded_len(&self) -> usize {
+        (1 + self.0.len()) * std::mem::size_of::<uint>()
+    }
+}
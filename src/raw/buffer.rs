@@ -0,0 +1,186 @@
+//! Serialization of raw items into a growable buffer.
+//!
+//! The `TryIntoCtx` impls of the raw items write into a `&mut [u8]` that has to fit the item.
+//! [`DexBuffer`] sizes that slice with [`EncodedLen`], so callers never have to guess.
+
+use scroll::{ctx::TryIntoCtx, Pwrite};
+
+/// The number of bytes an item takes up once written.
+///
+/// This is exact, so that the layout of a file can be computed before anything is written.
+pub trait EncodedLen {
+    fn encoded_len(&self) -> usize;
+}
+
+impl<T: EncodedLen + ?Sized> EncodedLen for &T {
+    fn encoded_len(&self) -> usize {
+        (**self).encoded_len()
+    }
+}
+
+/// An append-only buffer that raw items are written into.
+#[derive(Debug, Clone)]
+pub struct DexBuffer {
+    buf: Vec<u8>,
+    endian: scroll::Endian,
+}
+
+impl Default for DexBuffer {
+    fn default() -> Self {
+        Self::new(scroll::LE)
+    }
+}
+
+impl DexBuffer {
+    pub fn new(endian: scroll::Endian) -> Self {
+        Self {
+            buf: Vec::new(),
+            endian,
+        }
+    }
+
+    /// The endianness items are written with by [`DexBuffer::write`].
+    pub fn endian(&self) -> scroll::Endian {
+        self.endian
+    }
+
+    /// The offset the next item will be written at, i.e. the number of bytes written so far.
+    pub fn offset(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Pads the buffer with zeros up to a multiple of `alignment` bytes,
+    /// and returns the new offset.
+    pub fn align(&mut self, alignment: usize) -> usize {
+        let len = self.buf.len().next_multiple_of(alignment);
+        self.buf.resize(len, 0);
+        len
+    }
+
+    /// Appends `len` zeros, e.g. to reserve space for items written later with
+    /// [`DexBuffer::write_at`], and returns their offset.
+    pub fn zeroed(&mut self, len: usize) -> usize {
+        let offset = self.buf.len();
+        self.buf.resize(offset + len, 0);
+        offset
+    }
+
+    /// Appends `item` with the endianness of this buffer, and returns its offset.
+    pub fn write<T>(&mut self, item: T) -> Result<usize, T::Error>
+    where
+        T: TryIntoCtx<scroll::Endian> + EncodedLen,
+    {
+        let endian = self.endian;
+        self.write_with(item, endian)
+    }
+
+    /// Appends `item`, and returns its offset.
+    pub fn write_with<T, C: Copy>(&mut self, item: T, ctx: C) -> Result<usize, T::Error>
+    where
+        T: TryIntoCtx<C> + EncodedLen,
+    {
+        let offset = self.buf.len();
+        let len = item.encoded_len();
+        self.buf.resize(offset + len, 0);
+        match item.try_into_ctx(&mut self.buf[offset..], ctx) {
+            Ok(written) => {
+                debug_assert_eq!(written, len, "encoded_len differs from the written length");
+                self.buf.truncate(offset + written);
+                Ok(offset)
+            }
+            Err(err) => {
+                self.buf.truncate(offset);
+                Err(err)
+            }
+        }
+    }
+
+    /// Overwrites the bytes at `offset` with `item`, which must already have been written
+    /// or reserved with [`DexBuffer::zeroed`].
+    pub fn write_at<T>(&mut self, offset: usize, item: T) -> Result<(), T::Error>
+    where
+        T: TryIntoCtx<scroll::Endian>,
+        T::Error: From<scroll::Error>,
+    {
+        self.buf.pwrite_with(item, offset, self.endian)?;
+        Ok(())
+    }
+
+    /// Discards everything written after `offset`.
+    pub fn truncate(&mut self, offset: usize) {
+        self.buf.truncate(offset);
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::{
+        encoded_value::{EncodedArrayItem, EncodedValue},
+        string::StringData,
+        type_list::{TypeItem, TypeList},
+        uint,
+    };
+
+    #[test]
+    fn append() {
+        let mut buf = DexBuffer::default();
+        let data = StringData {
+            size: 3,
+            data: b"abc",
+        };
+        assert_eq!(data.encoded_len(), 5);
+        assert_eq!(buf.write(data).unwrap(), 0);
+        assert_eq!(buf.align(4), 8);
+        let list = TypeList::from(vec![TypeItem { type_idx: 1 }, TypeItem { type_idx: 2 }]);
+        assert_eq!(buf.write(list).unwrap(), 8);
+        assert_eq!(buf.offset(), 16);
+        assert_eq!(buf.zeroed(4), 16);
+        buf.write_at::<uint>(16, 0x12345678).unwrap();
+        assert_eq!(
+            buf.as_slice(),
+            b"\x03abc\0\0\0\0\x02\0\0\0\x01\0\x02\0\x78\x56\x34\x12"
+        );
+        assert!(buf.write_at::<uint>(18, 0).is_err());
+    }
+
+    #[test]
+    fn encoded_values() {
+        let values = vec![
+            EncodedValue::Byte(-1),
+            EncodedValue::Short(0x80),
+            EncodedValue::Short(-0x80),
+            EncodedValue::Char(0xffff),
+            EncodedValue::Int(0),
+            EncodedValue::Int(-0x8001),
+            EncodedValue::Long(i64::MIN),
+            EncodedValue::Long(0x7f_ffff_ffff),
+            EncodedValue::Float(1.5),
+            EncodedValue::Double(-0.0),
+            EncodedValue::String(0x100),
+            EncodedValue::Type(0),
+            EncodedValue::Method(uint::MAX),
+            EncodedValue::Array(vec![EncodedValue::Null; 200]),
+            EncodedValue::Boolean(true),
+        ];
+        for value in values.iter().cloned() {
+            let mut buf = DexBuffer::default();
+            let len = value.encoded_len();
+            buf.write_with(value, ()).unwrap();
+            assert_eq!(buf.offset(), len);
+        }
+        let item = EncodedArrayItem::from(values);
+        let mut buf = DexBuffer::default();
+        let len = item.encoded_len();
+        buf.write_with(item, ()).unwrap();
+        assert_eq!(buf.offset(), len);
+    }
+}
@@ -0,0 +1,11 @@
+mod assembler;
+mod encode;
+mod instruction;
+mod opcode;
+mod payload;
+
+pub use assembler::*;
+pub use encode::*;
+pub use instruction::*;
+pub use opcode::*;
+pub use payload::*;
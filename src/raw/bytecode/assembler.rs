@@ -0,0 +1,721 @@
+use std::collections::HashMap;
+
+use crate::raw::{bytecode::*, *};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum AssembleError {
+    #[error("label {0} is not bound")]
+    UnboundLabel(String),
+    #[error("label {0} is bound more than once")]
+    DuplicateLabel(String),
+    #[error("{0} is not a branch")]
+    NotABranch(Opcode),
+    #[error("{0} doesn't reference a payload")]
+    NotAPayloadReference(Opcode),
+    #[error("switch payload at label {0} must be referenced by exactly one switch")]
+    InvalidPayloadReference(String),
+    #[error("error encoding instruction: {0}")]
+    Encode(#[from] EncodeError),
+}
+
+type Result<T> = std::result::Result<T, AssembleError>;
+
+/// A position in the code of an [`Assembler`], bound with [`Assembler::bind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+#[derive(Debug, Clone)]
+enum Item {
+    Label(Label),
+    Instruction(Instruction),
+    /// `goto`, `if-test` or `if-testz` to a label.
+    Branch {
+        op: Opcode,
+        regs: Vec<Reg>,
+        target: Label,
+    },
+    /// `packed-switch`, `sparse-switch` or `fill-array-data` referencing a payload at a label.
+    PayloadRef {
+        op: Opcode,
+        reg: Reg,
+        payload: Label,
+    },
+    PackedSwitchPayload {
+        first_key: int,
+        targets: Vec<Label>,
+    },
+    SparseSwitchPayload {
+        entries: Vec<(int, Label)>,
+    },
+    FillArrayDataPayload(ArrayData),
+}
+
+impl Item {
+    fn is_payload(&self) -> bool {
+        matches!(
+            self,
+            Item::PackedSwitchPayload { .. }
+                | Item::SparseSwitchPayload { .. }
+                | Item::FillArrayDataPayload(_)
+                | Item::Instruction(
+                    Instruction::PackedSwitchPayload(_)
+                        | Instruction::SparseSwitchPayload(_)
+                        | Instruction::FillArrayDataPayload(_)
+                )
+        )
+    }
+}
+
+/// Assembles instructions and labels into code units for [`CodeItem::insns`][crate::raw::code_item::CodeItem].
+///
+/// Branches and payload references are resolved to relative offsets,
+/// and `goto` is widened to `goto/16` or `goto/32` where its target is out of range.
+/// Likewise, moves and constants added with [`Assembler::push`] are widened to the first
+/// larger variant their registers and literal or index fit into,
+/// e.g. `const/4` to `const/16`, `const/high16` or `const`.
+/// Payloads are aligned to 4 bytes by inserting a `nop` before them where necessary.
+#[derive(Debug, Clone, Default)]
+pub struct Assembler {
+    items: Vec<Item>,
+    // payloads added through the switch and array helpers, placed after all other items
+    trailing: Vec<Item>,
+    // the name of each label, if it has one
+    labels: Vec<Option<String>>,
+    named: HashMap<String, Label>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, unbound label.
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Returns the label with the given name, creating it if it doesn't exist yet.
+    pub fn named_label(&mut self, name: &str) -> Label {
+        if let Some(label) = self.named.get(name) {
+            return *label;
+        }
+        self.labels.push(Some(name.to_owned()));
+        let label = Label(self.labels.len() - 1);
+        self.named.insert(name.to_owned(), label);
+        label
+    }
+
+    /// Binds `label` to the position of the next item.
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        self.items.push(Item::Label(label));
+        self
+    }
+
+    /// Appends an instruction with already resolved operands,
+    /// widening moves and constants whose operands don't fit their format.
+    pub fn push(&mut self, insn: Instruction) -> &mut Self {
+        self.items.push(Item::Instruction(widen(insn)));
+        self
+    }
+
+    /// Appends a branch to `target`.
+    ///
+    /// `op` is a `goto` variant, an `if-test` with two registers or an `if-testz` with one.
+    pub fn branch(&mut self, op: Opcode, regs: &[Reg], target: Label) -> &mut Self {
+        self.items.push(Item::Branch {
+            op,
+            regs: regs.to_vec(),
+            target,
+        });
+        self
+    }
+
+    /// Appends a `packed-switch`, `sparse-switch` or `fill-array-data` instruction
+    /// referencing the payload bound to `payload`.
+    pub fn payload_ref(&mut self, op: Opcode, reg: Reg, payload: Label) -> &mut Self {
+        self.items.push(Item::PayloadRef { op, reg, payload });
+        self
+    }
+
+    /// Appends a `packed-switch-payload`. Its targets are relative to the switch referencing it.
+    pub fn packed_switch_payload(&mut self, first_key: int, targets: Vec<Label>) -> &mut Self {
+        self.items
+            .push(Item::PackedSwitchPayload { first_key, targets });
+        self
+    }
+
+    /// Appends a `sparse-switch-payload`. Its targets are relative to the switch referencing it.
+    pub fn sparse_switch_payload(&mut self, entries: Vec<(int, Label)>) -> &mut Self {
+        self.items.push(Item::SparseSwitchPayload { entries });
+        self
+    }
+
+    /// Appends a `fill-array-data-payload`.
+    pub fn fill_array_data_payload(&mut self, data: ArrayData) -> &mut Self {
+        self.items.push(Item::FillArrayDataPayload(data));
+        self
+    }
+
+    /// Appends a `packed-switch` on `reg`, placing its payload after all other items.
+    pub fn packed_switch(&mut self, reg: Reg, first_key: int, targets: Vec<Label>) -> &mut Self {
+        let payload = self.trailing_payload(Item::PackedSwitchPayload { first_key, targets });
+        self.payload_ref(Opcode::PackedSwitch, reg, payload)
+    }
+
+    /// Appends a `sparse-switch` on `reg`, placing its payload after all other items.
+    pub fn sparse_switch(&mut self, reg: Reg, entries: Vec<(int, Label)>) -> &mut Self {
+        let payload = self.trailing_payload(Item::SparseSwitchPayload { entries });
+        self.payload_ref(Opcode::SparseSwitch, reg, payload)
+    }
+
+    /// Appends a `fill-array-data` of the array in `reg`, placing its payload after all other items.
+    pub fn fill_array_data(&mut self, reg: Reg, data: ArrayData) -> &mut Self {
+        let payload = self.trailing_payload(Item::FillArrayDataPayload(data));
+        self.payload_ref(Opcode::FillArrayData, reg, payload)
+    }
+
+    fn trailing_payload(&mut self, item: Item) -> Label {
+        let label = self.new_label();
+        self.trailing.push(Item::Label(label));
+        self.trailing.push(item);
+        label
+    }
+
+    fn label_name(&self, label: Label) -> String {
+        match &self.labels[label.0] {
+            Some(name) => name.clone(),
+            None => format!("L{}", label.0),
+        }
+    }
+
+    /// Assembles all items into code units.
+    pub fn assemble(&self) -> Result<Vec<ushort>> {
+        self.assemble_with_labels().map(|(insns, _)| insns)
+    }
+
+    /// Assembles all items into code units, also returning the address of each bound label.
+    pub fn assemble_with_labels(&self) -> Result<(Vec<ushort>, HashMap<Label, uint>)> {
+        let items = self.items.iter().chain(&self.trailing).collect::<Vec<_>>();
+
+        // index of the item each label is bound to
+        let mut bound = vec![None; self.labels.len()];
+        for (idx, item) in items.iter().enumerate() {
+            if let Item::Label(label) = item {
+                if bound[label.0].replace(idx).is_some() {
+                    return Err(AssembleError::DuplicateLabel(self.label_name(*label)));
+                }
+            }
+        }
+        let target = |label: Label| {
+            bound[label.0].ok_or_else(|| AssembleError::UnboundLabel(self.label_name(label)))
+        };
+
+        // opcode of each branch, as gotos may need to be widened
+        let mut ops = items
+            .iter()
+            .map(|item| match item {
+                Item::Branch { op, .. } => Some(*op),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut addresses = self.layout(&items, &ops);
+        loop {
+            let mut widened = false;
+            for (idx, item) in items.iter().enumerate() {
+                let Item::Branch { target: label, .. } = item else {
+                    continue;
+                };
+                let offset = addresses[target(*label)?] as long - addresses[idx] as long;
+                let op = ops[idx].unwrap();
+                let needed = match op {
+                    Opcode::Goto if offset != 0 && byte::try_from(offset).is_ok() => op,
+                    Opcode::Goto | Opcode::Goto16
+                        if offset != 0 && short::try_from(offset).is_ok() =>
+                    {
+                        Opcode::Goto16
+                    }
+                    Opcode::Goto | Opcode::Goto16 => Opcode::Goto32,
+                    _ => op,
+                };
+                if needed != op {
+                    ops[idx] = Some(needed);
+                    widened = true;
+                }
+            }
+            if !widened {
+                break;
+            }
+            addresses = self.layout(&items, &ops);
+        }
+
+        let mut out = Vec::with_capacity(*addresses.last().unwrap() as usize);
+        for (idx, item) in items.iter().enumerate() {
+            let address = addresses[idx];
+            while (out.len() as uint) < address {
+                out.push(Opcode::Nop as ushort);
+            }
+            let relative = |label: Label, from: uint| -> Result<int> {
+                Ok(addresses[target(label)?].wrapping_sub(from) as int)
+            };
+            let insn = match item {
+                Item::Label(_) => continue,
+                Item::Instruction(insn) => insn.clone(),
+                Item::Branch {
+                    regs,
+                    target: label,
+                    ..
+                } => {
+                    let op = ops[idx].unwrap();
+                    let offset = relative(*label, address)?;
+                    match (op.format(), regs.as_slice()) {
+                        (Format::Format10t, []) => Instruction::Format10t { op, offset },
+                        (Format::Format20t, []) => Instruction::Format20t { op, offset },
+                        (Format::Format30t, []) => Instruction::Format30t { op, offset },
+                        (Format::Format21t, [a]) => Instruction::Format21t { op, a: *a, offset },
+                        (Format::Format22t, [a, b]) => Instruction::Format22t {
+                            op,
+                            a: *a,
+                            b: *b,
+                            offset,
+                        },
+                        (
+                            Format::Format10t
+                            | Format::Format20t
+                            | Format::Format30t
+                            | Format::Format21t
+                            | Format::Format22t,
+                            _,
+                        ) => return Err(EncodeError::FormatMismatch(op, op.format()).into()),
+                        _ => return Err(AssembleError::NotABranch(op)),
+                    }
+                }
+                Item::PayloadRef { op, reg, payload } => match op {
+                    Opcode::PackedSwitch | Opcode::SparseSwitch | Opcode::FillArrayData => {
+                        Instruction::Format31t {
+                            op: *op,
+                            a: *reg,
+                            offset: relative(*payload, address)?,
+                        }
+                    }
+                    _ => return Err(AssembleError::NotAPayloadReference(*op)),
+                },
+                Item::PackedSwitchPayload { first_key, targets } => {
+                    let switch = self.switch_address(&items, &addresses, idx)?;
+                    Instruction::PackedSwitchPayload(PackedSwitchPayload {
+                        first_key: *first_key,
+                        targets: targets
+                            .iter()
+                            .map(|label| relative(*label, switch))
+                            .collect::<Result<_>>()?,
+                    })
+                }
+                Item::SparseSwitchPayload { entries } => {
+                    let switch = self.switch_address(&items, &addresses, idx)?;
+                    Instruction::SparseSwitchPayload(SparseSwitchPayload {
+                        keys: entries.iter().map(|(key, _)| *key).collect(),
+                        targets: entries
+                            .iter()
+                            .map(|(_, label)| relative(*label, switch))
+                            .collect::<Result<_>>()?,
+                    })
+                }
+                Item::FillArrayDataPayload(data) => {
+                    Instruction::FillArrayDataPayload(FillArrayDataPayload { data: data.clone() })
+                }
+            };
+            insn.encode(&mut out)?;
+        }
+        let labels = bound
+            .iter()
+            .enumerate()
+            .filter_map(|(label, idx)| Some((Label(label), addresses[(*idx)?])))
+            .collect();
+        Ok((out, labels))
+    }
+
+    /// Computes the address of each item, plus the end address, given the opcode of each branch.
+    fn layout(&self, items: &[&Item], ops: &[Option<Opcode>]) -> Vec<uint> {
+        let mut addresses = Vec::with_capacity(items.len() + 1);
+        let mut address: uint = 0;
+        for (idx, item) in items.iter().enumerate() {
+            // payloads have to be 4-byte aligned, i.e. start at an even code unit
+            if item.is_payload() && !address.is_multiple_of(2) {
+                address += 1;
+            }
+            addresses.push(address);
+            address += match item {
+                Item::Label(_) => 0,
+                Item::Instruction(insn) => insn.len(),
+                Item::Branch { .. } => ops[idx].unwrap().format().len(),
+                Item::PayloadRef { .. } => Format::Format31t.len(),
+                Item::PackedSwitchPayload { targets, .. } => targets.len() as uint * 2 + 4,
+                Item::SparseSwitchPayload { entries } => entries.len() as uint * 4 + 2,
+                Item::FillArrayDataPayload(data) => {
+                    (data.len() * data.element_width() as uint).div_ceil(2) + 4
+                }
+            };
+        }
+        addresses.push(address);
+        // labels point at the (aligned) item following them
+        for idx in (0..items.len()).rev() {
+            if let Item::Label(_) = items[idx] {
+                addresses[idx] = addresses[idx + 1];
+            }
+        }
+        addresses
+    }
+
+    /// The address of the only switch referencing the payload at `idx`.
+    fn switch_address(&self, items: &[&Item], addresses: &[uint], idx: usize) -> Result<uint> {
+        // all labels bound to this payload
+        let labels = items[..idx]
+            .iter()
+            .rev()
+            .map_while(|item| match item {
+                Item::Label(label) => Some(*label),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut switches = items.iter().enumerate().filter(|(_, item)| {
+            matches!(item, Item::PayloadRef { op: Opcode::PackedSwitch | Opcode::SparseSwitch, payload, .. } if labels.contains(payload))
+        });
+        match (switches.next(), switches.next()) {
+            (Some((switch, _)), None) => Ok(addresses[switch]),
+            _ => Err(AssembleError::InvalidPayloadReference(
+                labels
+                    .first()
+                    .map_or_else(|| "<unlabeled>".to_owned(), |label| self.label_name(*label)),
+            )),
+        }
+    }
+}
+
+/// Variants of the same instruction, from the narrowest to the widest format.
+const FAMILIES: &[&[Opcode]] = &[
+    &[Opcode::Move, Opcode::MoveFrom16, Opcode::Move16],
+    &[Opcode::MoveWide, Opcode::MoveWideFrom16, Opcode::MoveWide16],
+    &[
+        Opcode::MoveObject,
+        Opcode::MoveObjectFrom16,
+        Opcode::MoveObject16,
+    ],
+    &[
+        Opcode::Const4,
+        Opcode::Const16,
+        Opcode::ConstHigh16,
+        Opcode::Const,
+    ],
+    &[
+        Opcode::ConstWide16,
+        Opcode::ConstWide32,
+        Opcode::ConstWideHigh16,
+        Opcode::ConstWide,
+    ],
+    &[Opcode::ConstString, Opcode::ConstStringJumbo],
+];
+
+/// Replaces the opcode of `insn` with the first variant, starting at its own,
+/// that can encode its operands. Other instructions are returned unchanged.
+fn widen(insn: Instruction) -> Instruction {
+    let Some(op) = insn.opcode() else {
+        return insn;
+    };
+    let Some(family) = FAMILIES.iter().find(|family| family.contains(&op)) else {
+        return insn;
+    };
+    let start = family.iter().position(|variant| *variant == op).unwrap();
+    let (regs, lit, index) = (insn.registers(), insn.literal(), insn.index());
+    family[start..]
+        .iter()
+        .filter_map(|op| {
+            let op = *op;
+            Some(match (op.format(), regs.as_slice()) {
+                (Format::Format12x, [a, b]) => Instruction::Format12x { op, a: *a, b: *b },
+                (Format::Format22x, [a, b]) => Instruction::Format22x { op, a: *a, b: *b },
+                (Format::Format32x, [a, b]) => Instruction::Format32x { op, a: *a, b: *b },
+                (Format::Format11n, [a]) => Instruction::Format11n {
+                    op,
+                    a: *a,
+                    lit: lit?,
+                },
+                (Format::Format21s, [a]) => Instruction::Format21s {
+                    op,
+                    a: *a,
+                    lit: lit?,
+                },
+                (Format::Format21h, [a]) => Instruction::Format21h {
+                    op,
+                    a: *a,
+                    lit: lit?,
+                },
+                (Format::Format31i, [a]) => Instruction::Format31i {
+                    op,
+                    a: *a,
+                    lit: lit?,
+                },
+                (Format::Format51l, [a]) => Instruction::Format51l {
+                    op,
+                    a: *a,
+                    lit: lit?,
+                },
+                (Format::Format21c, [a]) => Instruction::Format21c {
+                    op,
+                    a: *a,
+                    index: index?,
+                },
+                (Format::Format31c, [a]) => Instruction::Format31c {
+                    op,
+                    a: *a,
+                    index: index?,
+                },
+                _ => return None,
+            })
+        })
+        .find(|variant| variant.encode(&mut Vec::new()).is_ok())
+        // leave it to assemble() to report operands that don't fit any variant
+        .unwrap_or(insn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(insns: &[ushort]) -> Vec<DecodedInstruction> {
+        Decoder::new(insns)
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn branches() {
+        let mut asm = Assembler::new();
+        let start = asm.named_label("start");
+        let end = asm.named_label("end");
+        asm.bind(start)
+            .push(Instruction::Format11n {
+                op: Opcode::Const4,
+                a: 0,
+                lit: 1,
+            })
+            .branch(Opcode::IfEqz, &[0], end);
+        for _ in 0..200 {
+            asm.push(Instruction::Format10x { op: Opcode::Nop });
+        }
+        asm.branch(Opcode::Goto, &[], start)
+            .bind(end)
+            .push(Instruction::Format10x {
+                op: Opcode::ReturnVoid,
+            });
+        let (insns, labels) = asm.assemble_with_labels().unwrap();
+        assert_eq!(labels[&start], 0);
+        assert_eq!(labels[&end], 205);
+        let insns = decode(&insns);
+        assert_eq!(
+            insns[1].instruction,
+            Instruction::Format21t {
+                op: Opcode::IfEqz,
+                a: 0,
+                offset: 204
+            }
+        );
+        // the goto is widened since its target is more than 127 code units away
+        assert_eq!(
+            insns[202].instruction,
+            Instruction::Format20t {
+                op: Opcode::Goto16,
+                offset: -203
+            }
+        );
+    }
+
+    #[test]
+    fn payloads() {
+        let mut asm = Assembler::new();
+        let (a, b) = (asm.new_label(), asm.new_label());
+        asm.packed_switch(0, 5, vec![a, b])
+            .sparse_switch(0, vec![(-1, b), (100, a)])
+            .fill_array_data(1, ArrayData::Short(vec![1, -1, 3]))
+            .bind(a)
+            .push(Instruction::Format10x {
+                op: Opcode::ReturnVoid,
+            })
+            .bind(b)
+            .push(Instruction::Format10x {
+                op: Opcode::ReturnVoid,
+            });
+        let code = crate::raw::code_item::CodeItem {
+            registers_size: 2,
+            ins_size: 0,
+            outs_size: 0,
+            tries_size: 0,
+            debug_info_off: 0,
+            insns: asm.assemble().unwrap(),
+            tries: Vec::new(),
+            handlers: None,
+        };
+        let insns = decode(&code.insns);
+        assert_eq!(
+            code.switch_targets(&insns[0])
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![(5, 9), (6, 10)]
+        );
+        assert_eq!(
+            code.switch_targets(&insns[1])
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![(-1, 10), (100, 9)]
+        );
+        assert_eq!(
+            code.array_data(&insns[2]).unwrap(),
+            ArrayData::Short(vec![1, -1, 3])
+        );
+        // payloads start at even addresses, padded with a nop after the last return-void
+        assert_eq!(
+            insns[5].instruction,
+            Instruction::Format10x { op: Opcode::Nop }
+        );
+        for insn in &insns[6..] {
+            assert_eq!(insn.offset % 2, 0);
+        }
+    }
+
+    #[test]
+    fn widening() {
+        use Instruction::*;
+        let widened = |insn: Instruction| {
+            let insns = Assembler::new().push(insn).assemble().unwrap();
+            decode(&insns).remove(0).instruction
+        };
+        let mov = |op, a, b| widened(Format12x { op, a, b });
+        assert_eq!(
+            mov(Opcode::Move, 1, 2),
+            Format12x {
+                op: Opcode::Move,
+                a: 1,
+                b: 2
+            }
+        );
+        assert_eq!(
+            mov(Opcode::MoveWide, 16, 2),
+            Format22x {
+                op: Opcode::MoveWideFrom16,
+                a: 16,
+                b: 2
+            }
+        );
+        assert_eq!(
+            mov(Opcode::MoveObject, 256, 2),
+            Format32x {
+                op: Opcode::MoveObject16,
+                a: 256,
+                b: 2
+            }
+        );
+
+        let constant = |op, a, lit| widened(Format11n { op, a, lit });
+        let op = |lit| match constant(Opcode::Const4, 0, lit) {
+            Format11n { op, .. }
+            | Format21s { op, .. }
+            | Format21h { op, .. }
+            | Format31i { op, .. } => op,
+            insn => panic!("unexpected {insn:?}"),
+        };
+        assert_eq!(op(-8), Opcode::Const4);
+        assert_eq!(op(8), Opcode::Const16);
+        assert_eq!(op(0x10000), Opcode::ConstHigh16);
+        assert_eq!(op(0x10001), Opcode::Const);
+        assert_eq!(
+            constant(Opcode::Const4, 16, 0),
+            Format21s {
+                op: Opcode::Const16,
+                a: 16,
+                lit: 0
+            }
+        );
+        let wide = |lit| match widened(Format21s {
+            op: Opcode::ConstWide16,
+            a: 0,
+            lit,
+        }) {
+            Format21s { op, .. }
+            | Format21h { op, .. }
+            | Format31i { op, .. }
+            | Format51l { op, .. } => op,
+            insn => panic!("unexpected {insn:?}"),
+        };
+        assert_eq!(wide(-1), Opcode::ConstWide16);
+        assert_eq!(wide(0x10000), Opcode::ConstWide32);
+        assert_eq!(wide(1 << 48), Opcode::ConstWideHigh16);
+        assert_eq!(wide(1 << 32 | 1), Opcode::ConstWide);
+        // variants are only ever widened
+        assert_eq!(
+            widened(Format21h {
+                op: Opcode::ConstHigh16,
+                a: 0,
+                lit: 1
+            }),
+            Format31i {
+                op: Opcode::Const,
+                a: 0,
+                lit: 1
+            }
+        );
+
+        assert_eq!(
+            widened(Format21c {
+                op: Opcode::ConstString,
+                a: 0,
+                index: 0x10000
+            }),
+            Format31c {
+                op: Opcode::ConstStringJumbo,
+                a: 0,
+                index: 0x10000
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut asm = Assembler::new();
+        let label = asm.named_label("missing");
+        asm.branch(Opcode::Goto, &[], label);
+        assert_eq!(
+            asm.assemble(),
+            Err(AssembleError::UnboundLabel("missing".to_owned()))
+        );
+
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.bind(label).branch(Opcode::IfEq, &[16, 0], label);
+        assert_eq!(
+            asm.assemble(),
+            Err(AssembleError::Encode(EncodeError::RegisterOutOfRange(
+                Opcode::IfEq,
+                16
+            )))
+        );
+
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.bind(label).branch(Opcode::Move, &[], label);
+        assert_eq!(asm.assemble(), Err(AssembleError::NotABranch(Opcode::Move)));
+
+        // a goto to itself can only be encoded as goto/32
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.bind(label).branch(Opcode::Goto, &[], label);
+        let insns = decode(&asm.assemble().unwrap());
+        assert_eq!(
+            insns[0].instruction,
+            Instruction::Format30t {
+                op: Opcode::Goto32,
+                offset: 0
+            }
+        );
+    }
+}
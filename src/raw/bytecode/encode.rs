@@ -0,0 +1,451 @@
+use crate::raw::{bytecode::*, *};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum EncodeError {
+    #[error("{0} has format {1:?}, which doesn't match the instruction")]
+    FormatMismatch(Opcode, Format),
+    #[error("register v{1} is out of range for {0}")]
+    RegisterOutOfRange(Opcode, uint),
+    #[error("literal {1} is out of range for {0}")]
+    LiteralOutOfRange(Opcode, long),
+    #[error("branch offset {1} is out of range for {0}")]
+    OffsetOutOfRange(Opcode, long),
+    #[error("index {1} is out of range for {0}")]
+    IndexOutOfRange(Opcode, uint),
+    #[error("{0} takes at most 5 argument registers, got {1}")]
+    TooManyArguments(Opcode, usize),
+    #[error("payload has too many elements: {0}")]
+    PayloadTooLarge(usize),
+    #[error("sparse-switch-payload has {0} keys but {1} targets")]
+    SwitchLengthMismatch(usize, usize),
+}
+
+type Result<T> = std::result::Result<T, EncodeError>;
+
+/// Range checks for the operands of an instruction.
+struct Operands(Opcode);
+
+impl Operands {
+    fn reg(&self, reg: Reg, bits: u32) -> Result<ushort> {
+        if (reg as uint) >> bits != 0 {
+            return Err(EncodeError::RegisterOutOfRange(self.0, reg as uint));
+        }
+        Ok(reg)
+    }
+
+    fn lit(&self, lit: long, bits: u32) -> Result<ushort> {
+        let min = -(1 << (bits - 1));
+        let max = (1 << (bits - 1)) - 1;
+        if !(min..=max).contains(&lit) {
+            return Err(EncodeError::LiteralOutOfRange(self.0, lit));
+        }
+        Ok(lit as ushort)
+    }
+
+    /// Branch offsets narrower than 32 bits must not be 0.
+    fn offset(&self, offset: int, bits: u32) -> Result<ushort> {
+        let min = -(1 << (bits - 1));
+        let max = (1 << (bits - 1)) - 1;
+        if offset == 0 || !(min..=max).contains(&offset) {
+            return Err(EncodeError::OffsetOutOfRange(self.0, offset as long));
+        }
+        Ok(offset as ushort)
+    }
+
+    fn index(&self, index: uint) -> Result<ushort> {
+        ushort::try_from(index).map_err(|_| EncodeError::IndexOutOfRange(self.0, index))
+    }
+
+    /// The first code unit, with `a` in the high byte.
+    fn op_aa(&self, a: Reg) -> Result<ushort> {
+        Ok(self.0 as ushort | self.reg(a, 8)? << 8)
+    }
+
+    /// The first code unit, with `a` and `b` in the high byte.
+    fn op_ba(&self, a: Reg, b: ushort) -> Result<ushort> {
+        Ok(self.0 as ushort | self.reg(a, 4)? << 8 | b << 12)
+    }
+
+    /// The first and third code unit of the 35c and 45cc formats.
+    fn args(&self, args: &[Reg]) -> Result<(ushort, ushort)> {
+        if args.len() > 5 {
+            return Err(EncodeError::TooManyArguments(self.0, args.len()));
+        }
+        let mut regs = [0; 5];
+        for (i, arg) in args.iter().enumerate() {
+            regs[i] = self.reg(*arg, 4)?;
+        }
+        let first = self.0 as ushort | regs[4] << 8 | (args.len() as ushort) << 12;
+        let third = regs[0] | regs[1] << 4 | regs[2] << 8 | regs[3] << 12;
+        Ok((first, third))
+    }
+
+    /// The first code unit of the 3rc and 4rcc formats, checking the whole register range.
+    fn range(&self, first: Reg, count: ushort) -> Result<ushort> {
+        let last = first as uint + (count as uint).saturating_sub(1);
+        if last > ushort::MAX as uint {
+            return Err(EncodeError::RegisterOutOfRange(self.0, last));
+        }
+        if count > ubyte::MAX as ushort {
+            return Err(EncodeError::TooManyArguments(self.0, count as usize));
+        }
+        Ok(self.0 as ushort | count << 8)
+    }
+}
+
+fn split(value: uint) -> [ushort; 2] {
+    [value as ushort, (value >> 16) as ushort]
+}
+
+impl Instruction {
+    /// The format of this instruction, or `None` if this is a payload.
+    pub fn format(&self) -> Option<Format> {
+        use Instruction::*;
+        Some(match self {
+            Format10x { .. } => Format::Format10x,
+            Format12x { .. } => Format::Format12x,
+            Format11n { .. } => Format::Format11n,
+            Format11x { .. } => Format::Format11x,
+            Format10t { .. } => Format::Format10t,
+            Format20t { .. } => Format::Format20t,
+            Format22x { .. } => Format::Format22x,
+            Format21t { .. } => Format::Format21t,
+            Format21s { .. } => Format::Format21s,
+            Format21h { .. } => Format::Format21h,
+            Format21c { .. } => Format::Format21c,
+            Format23x { .. } => Format::Format23x,
+            Format22b { .. } => Format::Format22b,
+            Format22t { .. } => Format::Format22t,
+            Format22s { .. } => Format::Format22s,
+            Format22c { .. } => Format::Format22c,
+            Format32x { .. } => Format::Format32x,
+            Format30t { .. } => Format::Format30t,
+            Format31t { .. } => Format::Format31t,
+            Format31i { .. } => Format::Format31i,
+            Format31c { .. } => Format::Format31c,
+            Format35c { .. } => Format::Format35c,
+            Format3rc { .. } => Format::Format3rc,
+            Format45cc { .. } => Format::Format45cc,
+            Format4rcc { .. } => Format::Format4rcc,
+            Format51l { .. } => Format::Format51l,
+            PackedSwitchPayload(_) | SparseSwitchPayload(_) | FillArrayDataPayload(_) => {
+                return None
+            }
+        })
+    }
+
+    /// Encodes this instruction and appends its code units to `out`.
+    ///
+    /// Fails if the opcode doesn't have the format of the variant,
+    /// or if an operand doesn't fit into the format.
+    pub fn encode(&self, out: &mut Vec<ushort>) -> Result<()> {
+        use Instruction::*;
+        if let (Some(op), Some(format)) = (self.opcode(), self.format()) {
+            if op.format() != format {
+                return Err(EncodeError::FormatMismatch(op, op.format()));
+            }
+        }
+        match *self {
+            Format10x { op } => out.push(op as ushort),
+            Format12x { op, a, b } => {
+                let o = Operands(op);
+                out.push(o.op_ba(a, o.reg(b, 4)?)?);
+            }
+            Format11n { op, a, lit } => {
+                let o = Operands(op);
+                out.push(o.op_ba(a, o.lit(lit, 4)? & 0xf)?);
+            }
+            Format11x { op, a } => out.push(Operands(op).op_aa(a)?),
+            Format10t { op, offset } => {
+                let o = Operands(op);
+                out.push(op as ushort | (o.offset(offset, 8)? & 0xff) << 8);
+            }
+            Format20t { op, offset } => {
+                let o = Operands(op);
+                out.extend([op as ushort, o.offset(offset, 16)?]);
+            }
+            Format22x { op, a, b } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.reg(b, 16)?]);
+            }
+            Format21t { op, a, offset } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.offset(offset, 16)?]);
+            }
+            Format21s { op, a, lit } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.lit(lit, 16)?]);
+            }
+            Format21h { op, a, lit } => {
+                let o = Operands(op);
+                // only the high 16 bits may be set
+                let shift = match op {
+                    Opcode::ConstWideHigh16 => 48,
+                    _ => 16,
+                };
+                if lit & ((1 << shift) - 1) != 0 || (shift == 16 && o.lit(lit, 32).is_err()) {
+                    return Err(EncodeError::LiteralOutOfRange(op, lit));
+                }
+                out.extend([o.op_aa(a)?, (lit >> shift) as ushort]);
+            }
+            Format21c { op, a, index } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.index(index)?]);
+            }
+            Format23x { op, a, b, c } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.reg(b, 8)? | o.reg(c, 8)? << 8]);
+            }
+            Format22b { op, a, b, lit } => {
+                let o = Operands(op);
+                out.extend([o.op_aa(a)?, o.reg(b, 8)? | (o.lit(lit, 8)? & 0xff) << 8]);
+            }
+            Format22t { op, a, b, offset } => {
+                let o = Operands(op);
+                out.extend([o.op_ba(a, o.reg(b, 4)?)?, o.offset(offset, 16)?]);
+            }
+            Format22s { op, a, b, lit } => {
+                let o = Operands(op);
+                out.extend([o.op_ba(a, o.reg(b, 4)?)?, o.lit(lit, 16)?]);
+            }
+            Format22c { op, a, b, index } => {
+                let o = Operands(op);
+                out.extend([o.op_ba(a, o.reg(b, 4)?)?, o.index(index)?]);
+            }
+            Format32x { op, a, b } => {
+                let o = Operands(op);
+                out.extend([op as ushort, o.reg(a, 16)?, o.reg(b, 16)?]);
+            }
+            Format30t { op, offset } => {
+                out.push(op as ushort);
+                out.extend(split(offset as uint));
+            }
+            Format31t { op, a, offset } => {
+                out.push(Operands(op).op_aa(a)?);
+                out.extend(split(offset as uint));
+            }
+            Format31i { op, a, lit } => {
+                let o = Operands(op);
+                o.lit(lit, 32)?;
+                out.push(o.op_aa(a)?);
+                out.extend(split(lit as uint));
+            }
+            Format31c { op, a, index } => {
+                out.push(Operands(op).op_aa(a)?);
+                out.extend(split(index));
+            }
+            Format35c {
+                op,
+                ref args,
+                index,
+            } => {
+                let o = Operands(op);
+                let (first, third) = o.args(args)?;
+                out.extend([first, o.index(index)?, third]);
+            }
+            Format3rc {
+                op,
+                first,
+                count,
+                index,
+            } => {
+                let o = Operands(op);
+                out.extend([o.range(first, count)?, o.index(index)?, first]);
+            }
+            Format45cc {
+                op,
+                ref args,
+                index,
+                proto,
+            } => {
+                let o = Operands(op);
+                let (first, third) = o.args(args)?;
+                out.extend([first, o.index(index)?, third, o.index(proto)?]);
+            }
+            Format4rcc {
+                op,
+                first,
+                count,
+                index,
+                proto,
+            } => {
+                let o = Operands(op);
+                out.extend([
+                    o.range(first, count)?,
+                    o.index(index)?,
+                    first,
+                    o.index(proto)?,
+                ]);
+            }
+            Format51l { op, a, lit } => {
+                out.push(Operands(op).op_aa(a)?);
+                out.extend(split(lit as uint));
+                out.extend(split((lit as ulong >> 32) as uint));
+            }
+            PackedSwitchPayload(ref payload) => payload.encode(out)?,
+            SparseSwitchPayload(ref payload) => payload.encode(out)?,
+            FillArrayDataPayload(ref payload) => payload.encode(out)?,
+        }
+        Ok(())
+    }
+}
+
+impl PackedSwitchPayload {
+    pub fn encode(&self, out: &mut Vec<ushort>) -> Result<()> {
+        let size = ushort::try_from(self.targets.len())
+            .map_err(|_| EncodeError::PayloadTooLarge(self.targets.len()))?;
+        out.extend([PACKED_SWITCH_PAYLOAD, size]);
+        out.extend(split(self.first_key as uint));
+        for target in &self.targets {
+            out.extend(split(*target as uint));
+        }
+        Ok(())
+    }
+}
+
+impl SparseSwitchPayload {
+    pub fn encode(&self, out: &mut Vec<ushort>) -> Result<()> {
+        if self.keys.len() != self.targets.len() {
+            return Err(EncodeError::SwitchLengthMismatch(
+                self.keys.len(),
+                self.targets.len(),
+            ));
+        }
+        let size = ushort::try_from(self.keys.len())
+            .map_err(|_| EncodeError::PayloadTooLarge(self.keys.len()))?;
+        out.extend([SPARSE_SWITCH_PAYLOAD, size]);
+        for value in self.keys.iter().chain(&self.targets) {
+            out.extend(split(*value as uint));
+        }
+        Ok(())
+    }
+}
+
+impl FillArrayDataPayload {
+    pub fn encode(&self, out: &mut Vec<ushort>) -> Result<()> {
+        let size = self.data.len();
+        out.extend([FILL_ARRAY_DATA_PAYLOAD, self.data.element_width()]);
+        out.extend(split(size));
+        // the data is padded to a whole number of code units
+        out.extend(
+            self.data
+                .to_bytes()
+                .chunks(2)
+                .map(|c| ushort::from_le_bytes([c[0], c.get(1).copied().unwrap_or(0)])),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(insn: Instruction) -> Result<Vec<ushort>> {
+        let mut out = Vec::new();
+        insn.encode(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn encode_dex() {
+        // decoding and re-encoding every method has to be lossless
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                let mut out = Vec::new();
+                for insn in code.instructions() {
+                    insn.unwrap().instruction.encode(&mut out).unwrap();
+                }
+                assert_eq!(out, code.insns);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_wide() {
+        let insns = [
+            Instruction::Format51l {
+                op: Opcode::ConstWide,
+                a: 200,
+                lit: -0x123456789,
+            },
+            Instruction::Format21h {
+                op: Opcode::ConstWideHigh16,
+                a: 1,
+                lit: (-2i64) << 48,
+            },
+            Instruction::Format4rcc {
+                op: Opcode::InvokePolymorphicRange,
+                first: 300,
+                count: 3,
+                index: 7,
+                proto: 8,
+            },
+        ];
+        for insn in insns {
+            let units = encode(insn.clone()).unwrap();
+            assert_eq!(Instruction::decode(&units, 0), Ok(insn));
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let op = Opcode::Move;
+        assert_eq!(
+            encode(Instruction::Format12x { op, a: 16, b: 0 }),
+            Err(EncodeError::RegisterOutOfRange(op, 16))
+        );
+        let op = Opcode::Const4;
+        assert_eq!(
+            encode(Instruction::Format11n { op, a: 0, lit: 8 }),
+            Err(EncodeError::LiteralOutOfRange(op, 8))
+        );
+        let op = Opcode::Goto;
+        assert_eq!(
+            encode(Instruction::Format10t { op, offset: 0 }),
+            Err(EncodeError::OffsetOutOfRange(op, 0))
+        );
+        let op = Opcode::ConstString;
+        assert_eq!(
+            encode(Instruction::Format21c {
+                op,
+                a: 0,
+                index: 0x10000
+            }),
+            Err(EncodeError::IndexOutOfRange(op, 0x10000))
+        );
+        let op = Opcode::ConstHigh16;
+        assert_eq!(
+            encode(Instruction::Format21h { op, a: 0, lit: 1 }),
+            Err(EncodeError::LiteralOutOfRange(op, 1))
+        );
+        let op = Opcode::InvokeStatic;
+        assert_eq!(
+            encode(Instruction::Format35c {
+                op,
+                args: vec![0; 6],
+                index: 0
+            }),
+            Err(EncodeError::TooManyArguments(op, 6))
+        );
+        assert_eq!(
+            encode(Instruction::Format23x {
+                op: Opcode::Move,
+                a: 0,
+                b: 0,
+                c: 0
+            }),
+            Err(EncodeError::FormatMismatch(Opcode::Move, Format::Format12x))
+        );
+    }
+}
@@ -0,0 +1,637 @@
+use crate::raw::{bytecode::*, *};
+
+/// A register number. Depending on the format, only the lower 4, 8 or all 16 bits may be used.
+pub type Reg = ushort;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("unknown opcode {0:#04x} at offset {1}")]
+    UnknownOpcode(ubyte, uint),
+    #[error("unknown payload {0:#06x} at offset {1}")]
+    UnknownPayload(ushort, uint),
+    #[error("truncated instruction at offset {0}")]
+    Truncated(uint),
+    #[error("invalid argument count {0} at offset {1}")]
+    InvalidArgumentCount(ushort, uint),
+    #[error("invalid array element width {0} at offset {1}")]
+    InvalidElementWidth(ushort, uint),
+    #[error("instruction at offset {0} doesn't reference a matching payload")]
+    InvalidPayloadReference(uint),
+}
+
+/// A single Dalvik instruction, as stored in [`CodeItem::insns`][crate::raw::code_item::CodeItem].
+///
+/// There is one variant per [`Format`], and the operands are named after the spec.
+/// Branch offsets are signed and relative to the address of the instruction,
+/// in code units. Literals are sign-extended, and for the `21h` format already shifted into place.
+///
+/// For more information, click [here][1].
+///
+/// [1]: https://source.android.com/docs/core/runtime/instruction-formats
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `op`
+    Format10x { op: Opcode },
+    /// `op vA, vB`
+    Format12x { op: Opcode, a: Reg, b: Reg },
+    /// `op vA, #+B`
+    Format11n { op: Opcode, a: Reg, lit: long },
+    /// `op vAA`
+    Format11x { op: Opcode, a: Reg },
+    /// `op +AA`
+    Format10t { op: Opcode, offset: int },
+    /// `op +AAAA`
+    Format20t { op: Opcode, offset: int },
+    /// `op vAA, vBBBB`
+    Format22x { op: Opcode, a: Reg, b: Reg },
+    /// `op vAA, +BBBB`
+    Format21t { op: Opcode, a: Reg, offset: int },
+    /// `op vAA, #+BBBB`
+    Format21s { op: Opcode, a: Reg, lit: long },
+    /// `op vAA, #+BBBB0000` or `op vAA, #+BBBB000000000000`
+    Format21h { op: Opcode, a: Reg, lit: long },
+    /// `op vAA, kind@BBBB`
+    Format21c { op: Opcode, a: Reg, index: uint },
+    /// `op vAA, vBB, vCC`
+    Format23x { op: Opcode, a: Reg, b: Reg, c: Reg },
+    /// `op vAA, vBB, #+CC`
+    Format22b {
+        op: Opcode,
+        a: Reg,
+        b: Reg,
+        lit: long,
+    },
+    /// `op vA, vB, +CCCC`
+    Format22t {
+        op: Opcode,
+        a: Reg,
+        b: Reg,
+        offset: int,
+    },
+    /// `op vA, vB, #+CCCC`
+    Format22s {
+        op: Opcode,
+        a: Reg,
+        b: Reg,
+        lit: long,
+    },
+    /// `op vA, vB, kind@CCCC`
+    Format22c {
+        op: Opcode,
+        a: Reg,
+        b: Reg,
+        index: uint,
+    },
+    /// `op vAAAA, vBBBB`
+    Format32x { op: Opcode, a: Reg, b: Reg },
+    /// `op +AAAAAAAA`
+    Format30t { op: Opcode, offset: int },
+    /// `op vAA, +BBBBBBBB`
+    Format31t { op: Opcode, a: Reg, offset: int },
+    /// `op vAA, #+BBBBBBBB`
+    Format31i { op: Opcode, a: Reg, lit: long },
+    /// `op vAA, kind@BBBBBBBB`
+    Format31c { op: Opcode, a: Reg, index: uint },
+    /// `op {vC, vD, vE, vF, vG}, kind@BBBB`, with up to 5 argument registers.
+    Format35c {
+        op: Opcode,
+        args: Vec<Reg>,
+        index: uint,
+    },
+    /// `op {vCCCC .. vNNNN}, kind@BBBB`, where `NNNN = CCCC + count - 1`.
+    Format3rc {
+        op: Opcode,
+        first: Reg,
+        count: ushort,
+        index: uint,
+    },
+    /// `op {vC, vD, vE, vF, vG}, meth@BBBB, proto@HHHH`
+    Format45cc {
+        op: Opcode,
+        args: Vec<Reg>,
+        index: uint,
+        proto: uint,
+    },
+    /// `op {vCCCC .. vNNNN}, meth@BBBB, proto@HHHH`
+    Format4rcc {
+        op: Opcode,
+        first: Reg,
+        count: ushort,
+        index: uint,
+        proto: uint,
+    },
+    /// `op vAA, #+BBBBBBBBBBBBBBBB`
+    Format51l { op: Opcode, a: Reg, lit: long },
+    /// Payload of `packed-switch`.
+    PackedSwitchPayload(PackedSwitchPayload),
+    /// Payload of `sparse-switch`.
+    SparseSwitchPayload(SparseSwitchPayload),
+    /// Payload of `fill-array-data`.
+    FillArrayDataPayload(FillArrayDataPayload),
+}
+
+impl Instruction {
+    /// Decodes the instruction at `offset` in `insns`, both in code units.
+    pub fn decode(insns: &[ushort], offset: uint) -> Result<Self, DecodeError> {
+        let start = offset as usize;
+        let first = *insns.get(start).ok_or(DecodeError::Truncated(offset))?;
+        let byte = (first & 0xff) as ubyte;
+        // payloads share their low byte with `nop`
+        if byte == 0 && first != 0 {
+            let len = Self::payload_len(insns, offset)?;
+            let units = &insns[start..start + len];
+            return Ok(match first {
+                PACKED_SWITCH_PAYLOAD => {
+                    Self::PackedSwitchPayload(PackedSwitchPayload::from_units(units))
+                }
+                SPARSE_SWITCH_PAYLOAD => {
+                    Self::SparseSwitchPayload(SparseSwitchPayload::from_units(units))
+                }
+                _ => Self::FillArrayDataPayload(FillArrayDataPayload::from_units(units, offset)?),
+            });
+        }
+        let op = Opcode::from_u8(byte).ok_or(DecodeError::UnknownOpcode(byte, offset))?;
+        let u = insns
+            .get(start..start + op.format().len() as usize)
+            .ok_or(DecodeError::Truncated(offset))?;
+        let aa = u[0] >> 8;
+        let a = (u[0] >> 8) & 0xf;
+        let b = u[0] >> 12;
+        let wide = |i: usize| u[i] as uint | (u[i + 1] as uint) << 16;
+        // the argument registers of the 35c and 45cc formats, in order
+        let args = || {
+            let count = u[0] >> 12;
+            if count > 5 {
+                return Err(DecodeError::InvalidArgumentCount(count, offset));
+            }
+            let regs = [
+                u[2] & 0xf,
+                (u[2] >> 4) & 0xf,
+                (u[2] >> 8) & 0xf,
+                u[2] >> 12,
+                (u[0] >> 8) & 0xf,
+            ];
+            Ok(regs[..count as usize].to_vec())
+        };
+        Ok(match op.format() {
+            Format::Format10x => Self::Format10x { op },
+            Format::Format12x => Self::Format12x { op, a, b },
+            Format::Format11n => Self::Format11n {
+                op,
+                a,
+                lit: (u[0] as short >> 12) as long,
+            },
+            Format::Format11x => Self::Format11x { op, a: aa },
+            Format::Format10t => Self::Format10t {
+                op,
+                offset: (u[0] as short >> 8) as int,
+            },
+            Format::Format20t => Self::Format20t {
+                op,
+                offset: u[1] as short as int,
+            },
+            Format::Format22x => Self::Format22x { op, a: aa, b: u[1] },
+            Format::Format21t => Self::Format21t {
+                op,
+                a: aa,
+                offset: u[1] as short as int,
+            },
+            Format::Format21s => Self::Format21s {
+                op,
+                a: aa,
+                lit: u[1] as short as long,
+            },
+            Format::Format21h => Self::Format21h {
+                op,
+                a: aa,
+                lit: match op {
+                    Opcode::ConstWideHigh16 => ((u[1] as ulong) << 48) as long,
+                    _ => ((u[1] as uint) << 16) as int as long,
+                },
+            },
+            Format::Format21c => Self::Format21c {
+                op,
+                a: aa,
+                index: u[1] as uint,
+            },
+            Format::Format23x => Self::Format23x {
+                op,
+                a: aa,
+                b: u[1] & 0xff,
+                c: u[1] >> 8,
+            },
+            Format::Format22b => Self::Format22b {
+                op,
+                a: aa,
+                b: u[1] & 0xff,
+                lit: (u[1] as short >> 8) as long,
+            },
+            Format::Format22t => Self::Format22t {
+                op,
+                a,
+                b,
+                offset: u[1] as short as int,
+            },
+            Format::Format22s => Self::Format22s {
+                op,
+                a,
+                b,
+                lit: u[1] as short as long,
+            },
+            Format::Format22c => Self::Format22c {
+                op,
+                a,
+                b,
+                index: u[1] as uint,
+            },
+            Format::Format32x => Self::Format32x {
+                op,
+                a: u[1],
+                b: u[2],
+            },
+            Format::Format30t => Self::Format30t {
+                op,
+                offset: wide(1) as int,
+            },
+            Format::Format31t => Self::Format31t {
+                op,
+                a: aa,
+                offset: wide(1) as int,
+            },
+            Format::Format31i => Self::Format31i {
+                op,
+                a: aa,
+                lit: wide(1) as int as long,
+            },
+            Format::Format31c => Self::Format31c {
+                op,
+                a: aa,
+                index: wide(1),
+            },
+            Format::Format35c => Self::Format35c {
+                op,
+                args: args()?,
+                index: u[1] as uint,
+            },
+            Format::Format3rc => Self::Format3rc {
+                op,
+                first: u[2],
+                count: aa,
+                index: u[1] as uint,
+            },
+            Format::Format45cc => Self::Format45cc {
+                op,
+                args: args()?,
+                index: u[1] as uint,
+                proto: u[3] as uint,
+            },
+            Format::Format4rcc => Self::Format4rcc {
+                op,
+                first: u[2],
+                count: aa,
+                index: u[1] as uint,
+                proto: u[3] as uint,
+            },
+            Format::Format51l => Self::Format51l {
+                op,
+                a: aa,
+                lit: (wide(1) as ulong | (wide(3) as ulong) << 32) as long,
+            },
+        })
+    }
+
+    /// Branch offset of this instruction, relative to its address,
+    /// if it's a `goto`, `if-test` or `if-testz`.
+    pub fn branch_offset(&self) -> Option<int> {
+        match *self {
+            Self::Format10t { offset, .. }
+            | Self::Format20t { offset, .. }
+            | Self::Format30t { offset, .. }
+            | Self::Format21t { offset, .. }
+            | Self::Format22t { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// All register operands of this instruction, in the order of the spec.
+    /// Ranges are expanded, so `{v4 .. v6}` yields `v4`, `v5` and `v6`.
+    pub fn registers(&self) -> Vec<Reg> {
+        match self {
+            Self::Format11n { a, .. }
+            | Self::Format11x { a, .. }
+            | Self::Format21t { a, .. }
+            | Self::Format21s { a, .. }
+            | Self::Format21h { a, .. }
+            | Self::Format21c { a, .. }
+            | Self::Format31t { a, .. }
+            | Self::Format31i { a, .. }
+            | Self::Format31c { a, .. }
+            | Self::Format51l { a, .. } => vec![*a],
+            Self::Format12x { a, b, .. }
+            | Self::Format22x { a, b, .. }
+            | Self::Format22b { a, b, .. }
+            | Self::Format22t { a, b, .. }
+            | Self::Format22s { a, b, .. }
+            | Self::Format22c { a, b, .. }
+            | Self::Format32x { a, b, .. } => vec![*a, *b],
+            Self::Format23x { a, b, c, .. } => vec![*a, *b, *c],
+            Self::Format35c { args, .. } | Self::Format45cc { args, .. } => args.clone(),
+            Self::Format3rc { first, count, .. } | Self::Format4rcc { first, count, .. } => {
+                (0..*count).map(|i| first.wrapping_add(i)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The index into a constant pool (`string@`, `type@`, `field@`, `meth@`,
+    /// `call_site@`, etc.) referenced by this instruction, if any.
+    pub fn index(&self) -> Option<uint> {
+        match *self {
+            Self::Format21c { index, .. }
+            | Self::Format22c { index, .. }
+            | Self::Format31c { index, .. }
+            | Self::Format35c { index, .. }
+            | Self::Format3rc { index, .. }
+            | Self::Format45cc { index, .. }
+            | Self::Format4rcc { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The literal operand of this instruction, if any.
+    pub fn literal(&self) -> Option<long> {
+        match *self {
+            Self::Format11n { lit, .. }
+            | Self::Format21s { lit, .. }
+            | Self::Format21h { lit, .. }
+            | Self::Format22b { lit, .. }
+            | Self::Format22s { lit, .. }
+            | Self::Format31i { lit, .. }
+            | Self::Format51l { lit, .. } => Some(lit),
+            _ => None,
+        }
+    }
+
+    /// Address of the payload referenced by this instruction, if it's located at `address`.
+    /// Only `packed-switch`, `sparse-switch` and `fill-array-data` reference payloads.
+    pub fn payload_address(&self, address: uint) -> Option<uint> {
+        match *self {
+            Self::Format31t {
+                op: Opcode::PackedSwitch | Opcode::SparseSwitch | Opcode::FillArrayData,
+                offset,
+                ..
+            } => Some(address.wrapping_add_signed(offset)),
+            _ => None,
+        }
+    }
+
+    /// Size of the payload at `offset` in code units, including its identifying code unit.
+    fn payload_len(insns: &[ushort], offset: uint) -> Result<usize, DecodeError> {
+        let start = offset as usize;
+        let unit = |i: usize| {
+            insns
+                .get(start + i)
+                .map(|unit| *unit as usize)
+                .ok_or(DecodeError::Truncated(offset))
+        };
+        let len = match insns[start] {
+            PACKED_SWITCH_PAYLOAD => unit(1)? * 2 + 4,
+            SPARSE_SWITCH_PAYLOAD => unit(1)? * 4 + 2,
+            FILL_ARRAY_DATA_PAYLOAD => {
+                let element_width = unit(1)?;
+                let size = unit(2)? | unit(3)? << 16;
+                (size * element_width).div_ceil(2) + 4
+            }
+            ident => return Err(DecodeError::UnknownPayload(ident, offset)),
+        };
+        if start + len > insns.len() {
+            return Err(DecodeError::Truncated(offset));
+        }
+        Ok(len)
+    }
+
+    /// The opcode of this instruction, or `None` if this is a payload.
+    pub fn opcode(&self) -> Option<Opcode> {
+        use Instruction::*;
+        match *self {
+            Format10x { op }
+            | Format12x { op, .. }
+            | Format11n { op, .. }
+            | Format11x { op, .. }
+            | Format10t { op, .. }
+            | Format20t { op, .. }
+            | Format22x { op, .. }
+            | Format21t { op, .. }
+            | Format21s { op, .. }
+            | Format21h { op, .. }
+            | Format21c { op, .. }
+            | Format23x { op, .. }
+            | Format22b { op, .. }
+            | Format22t { op, .. }
+            | Format22s { op, .. }
+            | Format22c { op, .. }
+            | Format32x { op, .. }
+            | Format30t { op, .. }
+            | Format31t { op, .. }
+            | Format31i { op, .. }
+            | Format31c { op, .. }
+            | Format35c { op, .. }
+            | Format3rc { op, .. }
+            | Format45cc { op, .. }
+            | Format4rcc { op, .. }
+            | Format51l { op, .. } => Some(op),
+            PackedSwitchPayload(_) | SparseSwitchPayload(_) | FillArrayDataPayload(_) => None,
+        }
+    }
+
+    /// Size of this instruction in code units.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        match self {
+            Self::PackedSwitchPayload(payload) => payload.len(),
+            Self::SparseSwitchPayload(payload) => payload.len(),
+            Self::FillArrayDataPayload(payload) => payload.len(),
+            _ => self.opcode().map_or(0, |op| op.format().len()),
+        }
+    }
+}
+
+/// An [`Instruction`] along with its location in [`CodeItem::insns`][crate::raw::code_item::CodeItem].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DecodedInstruction {
+    /// Offset in code units from the start of the method's instructions.
+    pub offset: uint,
+    /// Size of the instruction in code units.
+    pub len: uint,
+    pub instruction: Instruction,
+}
+
+/// Decodes the instructions of a method in order. Stops after the first error.
+pub struct Decoder<'a> {
+    insns: &'a [ushort],
+    offset: uint,
+    failed: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(insns: &'a [ushort]) -> Self {
+        Self {
+            insns,
+            offset: 0,
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<DecodedInstruction, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset as usize >= self.insns.len() {
+            return None;
+        }
+        match Instruction::decode(self.insns, self.offset) {
+            Ok(instruction) => {
+                let offset = self.offset;
+                let len = instruction.len();
+                self.offset += len;
+                Some(Ok(DecodedInstruction {
+                    offset,
+                    len,
+                    instruction,
+                }))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let insns = [
+            0xf012, // const/4 v0, #-1
+            0x2071, 0x0003, 0x0021, // invoke-static {v1, v2}, meth@3
+            0x0115, 0x8000, // const/high16 v1, #0x80000000
+            0x0000, // nop
+            0xfd28, // goto -3
+        ];
+        let decoded = Decoder::new(&insns)
+            .map(|insn| insn.map(|insn| (insn.offset, insn.instruction)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            decoded,
+            vec![
+                (
+                    0,
+                    Instruction::Format11n {
+                        op: Opcode::Const4,
+                        a: 0,
+                        lit: -1
+                    }
+                ),
+                (
+                    1,
+                    Instruction::Format35c {
+                        op: Opcode::InvokeStatic,
+                        args: vec![1, 2],
+                        index: 3
+                    }
+                ),
+                (
+                    4,
+                    Instruction::Format21h {
+                        op: Opcode::ConstHigh16,
+                        a: 1,
+                        lit: i32::MIN as long
+                    }
+                ),
+                (6, Instruction::Format10x { op: Opcode::Nop }),
+                (
+                    7,
+                    Instruction::Format10t {
+                        op: Opcode::Goto,
+                        offset: -3
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_payload() {
+        let insns = [
+            0x0100, 0x0002, 0x000a, 0x0000, 0x0003, 0x0000, 0x0005,
+            0x0000, // packed-switch-payload
+            0x0300, 0x0001, 0x0003, 0x0000, 0x0201, 0x0003, // fill-array-data-payload
+        ];
+        let lens = Decoder::new(&insns)
+            .map(|insn| insn.unwrap().len)
+            .collect::<Vec<_>>();
+        assert_eq!(lens, vec![8, 6]);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let decode = |insns: &[ushort]| Decoder::new(insns).collect::<Result<Vec<_>, _>>();
+        assert_eq!(decode(&[0x003e]), Err(DecodeError::UnknownOpcode(0x3e, 0)));
+        assert_eq!(
+            decode(&[0x0000, 0x0014, 0x0001]),
+            Err(DecodeError::Truncated(1))
+        );
+        assert_eq!(
+            decode(&[0x0100, 0x0002, 0x0000]),
+            Err(DecodeError::Truncated(0))
+        );
+        assert_eq!(
+            decode(&[0x0400]),
+            Err(DecodeError::UnknownPayload(0x0400, 0))
+        );
+        assert_eq!(
+            decode(&[0x606e, 0x0000, 0x0000]),
+            Err(DecodeError::InvalidArgumentCount(6, 0))
+        );
+    }
+
+    #[test]
+    fn decode_dex() {
+        let dex = crate::t::dex!();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            for method in class
+                .direct_methods()
+                .unwrap()
+                .into_iter()
+                .chain(class.virtual_methods().unwrap())
+            {
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                let insns = code.instructions().collect::<Result<Vec<_>, _>>().unwrap();
+                let len: uint = insns.iter().map(|insn| insn.len).sum();
+                assert_eq!(len as usize, code.insns.len());
+                for insn in &insns {
+                    match insn.instruction.opcode() {
+                        Some(Opcode::PackedSwitch | Opcode::SparseSwitch) => {
+                            // every branch target has to be an instruction
+                            for target in code.switch_targets(insn).unwrap().into_values() {
+                                assert!(insns.iter().any(|insn| insn.offset == target));
+                            }
+                        }
+                        Some(Opcode::FillArrayData) => {
+                            code.array_data(insn).unwrap();
+                        }
+                        _ => assert!(code.switch_targets(insn).is_err()),
+                    }
+                }
+            }
+        }
+    }
+}
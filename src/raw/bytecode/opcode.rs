@@ -0,0 +1,405 @@
+use crate::raw::{header::Version, *};
+
+/// Instruction formats, named after their size in code units, register count and operand type.
+///
+/// For more information, click [here][1].
+///
+/// [1]: https://source.android.com/docs/core/runtime/instruction-formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Format10x,
+    Format12x,
+    Format11n,
+    Format11x,
+    Format10t,
+    Format20t,
+    Format22x,
+    Format21t,
+    Format21s,
+    Format21h,
+    Format21c,
+    Format23x,
+    Format22b,
+    Format22t,
+    Format22s,
+    Format22c,
+    Format32x,
+    Format30t,
+    Format31t,
+    Format31i,
+    Format31c,
+    Format35c,
+    Format3rc,
+    Format45cc,
+    Format4rcc,
+    Format51l,
+}
+
+impl Format {
+    /// Size of an instruction of this format in code units.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(self) -> uint {
+        use Format::*;
+        match self {
+            Format10x | Format12x | Format11n | Format11x | Format10t => 1,
+            Format20t | Format22x | Format21t | Format21s | Format21h | Format21c | Format23x
+            | Format22b | Format22t | Format22s | Format22c => 2,
+            Format32x | Format30t | Format31t | Format31i | Format31c | Format35c | Format3rc => 3,
+            Format45cc | Format4rcc => 4,
+            Format51l => 5,
+        }
+    }
+}
+
+macro_rules! opcodes {
+    ($($value:literal => $name:ident, $mnemonic:literal, $format:ident;)*) => {
+        /// See https://source.android.com/docs/core/runtime/dalvik-bytecode
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $value,)*
+        }
+
+        impl Opcode {
+            /// Returns the opcode with the given value, or `None` if it's unused.
+            pub fn from_u8(value: ubyte) -> Option<Self> {
+                match value {
+                    $($value => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// The mnemonic of this opcode, e.g. `move-result-object`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$name => $mnemonic,)*
+                }
+            }
+
+            pub fn format(self) -> Format {
+                match self {
+                    $(Self::$name => Format::$format,)*
+                }
+            }
+
+            /// Returns the opcode with the given mnemonic.
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($mnemonic => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    0x00 => Nop, "nop", Format10x;
+    0x01 => Move, "move", Format12x;
+    0x02 => MoveFrom16, "move/from16", Format22x;
+    0x03 => Move16, "move/16", Format32x;
+    0x04 => MoveWide, "move-wide", Format12x;
+    0x05 => MoveWideFrom16, "move-wide/from16", Format22x;
+    0x06 => MoveWide16, "move-wide/16", Format32x;
+    0x07 => MoveObject, "move-object", Format12x;
+    0x08 => MoveObjectFrom16, "move-object/from16", Format22x;
+    0x09 => MoveObject16, "move-object/16", Format32x;
+    0x0a => MoveResult, "move-result", Format11x;
+    0x0b => MoveResultWide, "move-result-wide", Format11x;
+    0x0c => MoveResultObject, "move-result-object", Format11x;
+    0x0d => MoveException, "move-exception", Format11x;
+    0x0e => ReturnVoid, "return-void", Format10x;
+    0x0f => Return, "return", Format11x;
+    0x10 => ReturnWide, "return-wide", Format11x;
+    0x11 => ReturnObject, "return-object", Format11x;
+    0x12 => Const4, "const/4", Format11n;
+    0x13 => Const16, "const/16", Format21s;
+    0x14 => Const, "const", Format31i;
+    0x15 => ConstHigh16, "const/high16", Format21h;
+    0x16 => ConstWide16, "const-wide/16", Format21s;
+    0x17 => ConstWide32, "const-wide/32", Format31i;
+    0x18 => ConstWide, "const-wide", Format51l;
+    0x19 => ConstWideHigh16, "const-wide/high16", Format21h;
+    0x1a => ConstString, "const-string", Format21c;
+    0x1b => ConstStringJumbo, "const-string/jumbo", Format31c;
+    0x1c => ConstClass, "const-class", Format21c;
+    0x1d => MonitorEnter, "monitor-enter", Format11x;
+    0x1e => MonitorExit, "monitor-exit", Format11x;
+    0x1f => CheckCast, "check-cast", Format21c;
+    0x20 => InstanceOf, "instance-of", Format22c;
+    0x21 => ArrayLength, "array-length", Format12x;
+    0x22 => NewInstance, "new-instance", Format21c;
+    0x23 => NewArray, "new-array", Format22c;
+    0x24 => FilledNewArray, "filled-new-array", Format35c;
+    0x25 => FilledNewArrayRange, "filled-new-array/range", Format3rc;
+    0x26 => FillArrayData, "fill-array-data", Format31t;
+    0x27 => Throw, "throw", Format11x;
+    0x28 => Goto, "goto", Format10t;
+    0x29 => Goto16, "goto/16", Format20t;
+    0x2a => Goto32, "goto/32", Format30t;
+    0x2b => PackedSwitch, "packed-switch", Format31t;
+    0x2c => SparseSwitch, "sparse-switch", Format31t;
+    0x2d => CmplFloat, "cmpl-float", Format23x;
+    0x2e => CmpgFloat, "cmpg-float", Format23x;
+    0x2f => CmplDouble, "cmpl-double", Format23x;
+    0x30 => CmpgDouble, "cmpg-double", Format23x;
+    0x31 => CmpLong, "cmp-long", Format23x;
+    0x32 => IfEq, "if-eq", Format22t;
+    0x33 => IfNe, "if-ne", Format22t;
+    0x34 => IfLt, "if-lt", Format22t;
+    0x35 => IfGe, "if-ge", Format22t;
+    0x36 => IfGt, "if-gt", Format22t;
+    0x37 => IfLe, "if-le", Format22t;
+    0x38 => IfEqz, "if-eqz", Format21t;
+    0x39 => IfNez, "if-nez", Format21t;
+    0x3a => IfLtz, "if-ltz", Format21t;
+    0x3b => IfGez, "if-gez", Format21t;
+    0x3c => IfGtz, "if-gtz", Format21t;
+    0x3d => IfLez, "if-lez", Format21t;
+    0x44 => Aget, "aget", Format23x;
+    0x45 => AgetWide, "aget-wide", Format23x;
+    0x46 => AgetObject, "aget-object", Format23x;
+    0x47 => AgetBoolean, "aget-boolean", Format23x;
+    0x48 => AgetByte, "aget-byte", Format23x;
+    0x49 => AgetChar, "aget-char", Format23x;
+    0x4a => AgetShort, "aget-short", Format23x;
+    0x4b => Aput, "aput", Format23x;
+    0x4c => AputWide, "aput-wide", Format23x;
+    0x4d => AputObject, "aput-object", Format23x;
+    0x4e => AputBoolean, "aput-boolean", Format23x;
+    0x4f => AputByte, "aput-byte", Format23x;
+    0x50 => AputChar, "aput-char", Format23x;
+    0x51 => AputShort, "aput-short", Format23x;
+    0x52 => Iget, "iget", Format22c;
+    0x53 => IgetWide, "iget-wide", Format22c;
+    0x54 => IgetObject, "iget-object", Format22c;
+    0x55 => IgetBoolean, "iget-boolean", Format22c;
+    0x56 => IgetByte, "iget-byte", Format22c;
+    0x57 => IgetChar, "iget-char", Format22c;
+    0x58 => IgetShort, "iget-short", Format22c;
+    0x59 => Iput, "iput", Format22c;
+    0x5a => IputWide, "iput-wide", Format22c;
+    0x5b => IputObject, "iput-object", Format22c;
+    0x5c => IputBoolean, "iput-boolean", Format22c;
+    0x5d => IputByte, "iput-byte", Format22c;
+    0x5e => IputChar, "iput-char", Format22c;
+    0x5f => IputShort, "iput-short", Format22c;
+    0x60 => Sget, "sget", Format21c;
+    0x61 => SgetWide, "sget-wide", Format21c;
+    0x62 => SgetObject, "sget-object", Format21c;
+    0x63 => SgetBoolean, "sget-boolean", Format21c;
+    0x64 => SgetByte, "sget-byte", Format21c;
+    0x65 => SgetChar, "sget-char", Format21c;
+    0x66 => SgetShort, "sget-short", Format21c;
+    0x67 => Sput, "sput", Format21c;
+    0x68 => SputWide, "sput-wide", Format21c;
+    0x69 => SputObject, "sput-object", Format21c;
+    0x6a => SputBoolean, "sput-boolean", Format21c;
+    0x6b => SputByte, "sput-byte", Format21c;
+    0x6c => SputChar, "sput-char", Format21c;
+    0x6d => SputShort, "sput-short", Format21c;
+    0x6e => InvokeVirtual, "invoke-virtual", Format35c;
+    0x6f => InvokeSuper, "invoke-super", Format35c;
+    0x70 => InvokeDirect, "invoke-direct", Format35c;
+    0x71 => InvokeStatic, "invoke-static", Format35c;
+    0x72 => InvokeInterface, "invoke-interface", Format35c;
+    0x74 => InvokeVirtualRange, "invoke-virtual/range", Format3rc;
+    0x75 => InvokeSuperRange, "invoke-super/range", Format3rc;
+    0x76 => InvokeDirectRange, "invoke-direct/range", Format3rc;
+    0x77 => InvokeStaticRange, "invoke-static/range", Format3rc;
+    0x78 => InvokeInterfaceRange, "invoke-interface/range", Format3rc;
+    0x7b => NegInt, "neg-int", Format12x;
+    0x7c => NotInt, "not-int", Format12x;
+    0x7d => NegLong, "neg-long", Format12x;
+    0x7e => NotLong, "not-long", Format12x;
+    0x7f => NegFloat, "neg-float", Format12x;
+    0x80 => NegDouble, "neg-double", Format12x;
+    0x81 => IntToLong, "int-to-long", Format12x;
+    0x82 => IntToFloat, "int-to-float", Format12x;
+    0x83 => IntToDouble, "int-to-double", Format12x;
+    0x84 => LongToInt, "long-to-int", Format12x;
+    0x85 => LongToFloat, "long-to-float", Format12x;
+    0x86 => LongToDouble, "long-to-double", Format12x;
+    0x87 => FloatToInt, "float-to-int", Format12x;
+    0x88 => FloatToLong, "float-to-long", Format12x;
+    0x89 => FloatToDouble, "float-to-double", Format12x;
+    0x8a => DoubleToInt, "double-to-int", Format12x;
+    0x8b => DoubleToLong, "double-to-long", Format12x;
+    0x8c => DoubleToFloat, "double-to-float", Format12x;
+    0x8d => IntToByte, "int-to-byte", Format12x;
+    0x8e => IntToChar, "int-to-char", Format12x;
+    0x8f => IntToShort, "int-to-short", Format12x;
+    0x90 => AddInt, "add-int", Format23x;
+    0x91 => SubInt, "sub-int", Format23x;
+    0x92 => MulInt, "mul-int", Format23x;
+    0x93 => DivInt, "div-int", Format23x;
+    0x94 => RemInt, "rem-int", Format23x;
+    0x95 => AndInt, "and-int", Format23x;
+    0x96 => OrInt, "or-int", Format23x;
+    0x97 => XorInt, "xor-int", Format23x;
+    0x98 => ShlInt, "shl-int", Format23x;
+    0x99 => ShrInt, "shr-int", Format23x;
+    0x9a => UshrInt, "ushr-int", Format23x;
+    0x9b => AddLong, "add-long", Format23x;
+    0x9c => SubLong, "sub-long", Format23x;
+    0x9d => MulLong, "mul-long", Format23x;
+    0x9e => DivLong, "div-long", Format23x;
+    0x9f => RemLong, "rem-long", Format23x;
+    0xa0 => AndLong, "and-long", Format23x;
+    0xa1 => OrLong, "or-long", Format23x;
+    0xa2 => XorLong, "xor-long", Format23x;
+    0xa3 => ShlLong, "shl-long", Format23x;
+    0xa4 => ShrLong, "shr-long", Format23x;
+    0xa5 => UshrLong, "ushr-long", Format23x;
+    0xa6 => AddFloat, "add-float", Format23x;
+    0xa7 => SubFloat, "sub-float", Format23x;
+    0xa8 => MulFloat, "mul-float", Format23x;
+    0xa9 => DivFloat, "div-float", Format23x;
+    0xaa => RemFloat, "rem-float", Format23x;
+    0xab => AddDouble, "add-double", Format23x;
+    0xac => SubDouble, "sub-double", Format23x;
+    0xad => MulDouble, "mul-double", Format23x;
+    0xae => DivDouble, "div-double", Format23x;
+    0xaf => RemDouble, "rem-double", Format23x;
+    0xb0 => AddInt2Addr, "add-int/2addr", Format12x;
+    0xb1 => SubInt2Addr, "sub-int/2addr", Format12x;
+    0xb2 => MulInt2Addr, "mul-int/2addr", Format12x;
+    0xb3 => DivInt2Addr, "div-int/2addr", Format12x;
+    0xb4 => RemInt2Addr, "rem-int/2addr", Format12x;
+    0xb5 => AndInt2Addr, "and-int/2addr", Format12x;
+    0xb6 => OrInt2Addr, "or-int/2addr", Format12x;
+    0xb7 => XorInt2Addr, "xor-int/2addr", Format12x;
+    0xb8 => ShlInt2Addr, "shl-int/2addr", Format12x;
+    0xb9 => ShrInt2Addr, "shr-int/2addr", Format12x;
+    0xba => UshrInt2Addr, "ushr-int/2addr", Format12x;
+    0xbb => AddLong2Addr, "add-long/2addr", Format12x;
+    0xbc => SubLong2Addr, "sub-long/2addr", Format12x;
+    0xbd => MulLong2Addr, "mul-long/2addr", Format12x;
+    0xbe => DivLong2Addr, "div-long/2addr", Format12x;
+    0xbf => RemLong2Addr, "rem-long/2addr", Format12x;
+    0xc0 => AndLong2Addr, "and-long/2addr", Format12x;
+    0xc1 => OrLong2Addr, "or-long/2addr", Format12x;
+    0xc2 => XorLong2Addr, "xor-long/2addr", Format12x;
+    0xc3 => ShlLong2Addr, "shl-long/2addr", Format12x;
+    0xc4 => ShrLong2Addr, "shr-long/2addr", Format12x;
+    0xc5 => UshrLong2Addr, "ushr-long/2addr", Format12x;
+    0xc6 => AddFloat2Addr, "add-float/2addr", Format12x;
+    0xc7 => SubFloat2Addr, "sub-float/2addr", Format12x;
+    0xc8 => MulFloat2Addr, "mul-float/2addr", Format12x;
+    0xc9 => DivFloat2Addr, "div-float/2addr", Format12x;
+    0xca => RemFloat2Addr, "rem-float/2addr", Format12x;
+    0xcb => AddDouble2Addr, "add-double/2addr", Format12x;
+    0xcc => SubDouble2Addr, "sub-double/2addr", Format12x;
+    0xcd => MulDouble2Addr, "mul-double/2addr", Format12x;
+    0xce => DivDouble2Addr, "div-double/2addr", Format12x;
+    0xcf => RemDouble2Addr, "rem-double/2addr", Format12x;
+    0xd0 => AddIntLit16, "add-int/lit16", Format22s;
+    0xd1 => RsubInt, "rsub-int", Format22s;
+    0xd2 => MulIntLit16, "mul-int/lit16", Format22s;
+    0xd3 => DivIntLit16, "div-int/lit16", Format22s;
+    0xd4 => RemIntLit16, "rem-int/lit16", Format22s;
+    0xd5 => AndIntLit16, "and-int/lit16", Format22s;
+    0xd6 => OrIntLit16, "or-int/lit16", Format22s;
+    0xd7 => XorIntLit16, "xor-int/lit16", Format22s;
+    0xd8 => AddIntLit8, "add-int/lit8", Format22b;
+    0xd9 => RsubIntLit8, "rsub-int/lit8", Format22b;
+    0xda => MulIntLit8, "mul-int/lit8", Format22b;
+    0xdb => DivIntLit8, "div-int/lit8", Format22b;
+    0xdc => RemIntLit8, "rem-int/lit8", Format22b;
+    0xdd => AndIntLit8, "and-int/lit8", Format22b;
+    0xde => OrIntLit8, "or-int/lit8", Format22b;
+    0xdf => XorIntLit8, "xor-int/lit8", Format22b;
+    0xe0 => ShlIntLit8, "shl-int/lit8", Format22b;
+    0xe1 => ShrIntLit8, "shr-int/lit8", Format22b;
+    0xe2 => UshrIntLit8, "ushr-int/lit8", Format22b;
+    0xfa => InvokePolymorphic, "invoke-polymorphic", Format45cc;
+    0xfb => InvokePolymorphicRange, "invoke-polymorphic/range", Format4rcc;
+    0xfc => InvokeCustom, "invoke-custom", Format35c;
+    0xfd => InvokeCustomRange, "invoke-custom/range", Format3rc;
+    0xfe => ConstMethodHandle, "const-method-handle", Format21c;
+    0xff => ConstMethodType, "const-method-type", Format21c;
+}
+
+impl std::fmt::Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Opcode {
+    /// Whether execution can continue with the next instruction,
+    /// which isn't the case for `goto`, `return` and `throw`.
+    pub fn can_continue(self) -> bool {
+        !matches!(
+            self,
+            Opcode::Goto | Opcode::Goto16 | Opcode::Goto32 | Opcode::Throw
+        ) && !self.is_return()
+    }
+
+    pub fn is_return(self) -> bool {
+        matches!(
+            self,
+            Opcode::ReturnVoid | Opcode::Return | Opcode::ReturnWide | Opcode::ReturnObject
+        )
+    }
+
+    /// Whether this is a `goto`, `if-test` or `if-testz`.
+    pub fn is_branch(self) -> bool {
+        matches!(
+            self.format(),
+            Format::Format10t
+                | Format::Format20t
+                | Format::Format30t
+                | Format::Format21t
+                | Format::Format22t
+        )
+    }
+
+    pub fn is_switch(self) -> bool {
+        matches!(self, Opcode::PackedSwitch | Opcode::SparseSwitch)
+    }
+}
+
+/// The constant pool an index operand refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    String,
+    Type,
+    Field,
+    Method,
+    Proto,
+    MethodHandle,
+    CallSite,
+}
+
+impl Opcode {
+    /// The constant pool the index operand of this opcode refers to, if it has one.
+    /// The second index operand of `invoke-polymorphic` always refers to a [`IndexKind::Proto`].
+    pub fn index_kind(self) -> Option<IndexKind> {
+        use Opcode::*;
+        Some(match self {
+            ConstString | ConstStringJumbo => IndexKind::String,
+            ConstClass | CheckCast | InstanceOf | NewInstance | NewArray | FilledNewArray
+            | FilledNewArrayRange => IndexKind::Type,
+            op if (Iget..=SputShort).contains(&op) => IndexKind::Field,
+            op if (InvokeVirtual..=InvokeInterfaceRange).contains(&op) => IndexKind::Method,
+            InvokePolymorphic | InvokePolymorphicRange => IndexKind::Method,
+            InvokeCustom | InvokeCustomRange => IndexKind::CallSite,
+            ConstMethodHandle => IndexKind::MethodHandle,
+            ConstMethodType => IndexKind::Proto,
+            _ => return None,
+        })
+    }
+
+    /// The first version that allows this opcode.
+    pub fn min_version(self) -> Version {
+        use Opcode::*;
+        match self {
+            InvokePolymorphic | InvokePolymorphicRange | InvokeCustom | InvokeCustomRange => {
+                Version::V038
+            }
+            ConstMethodHandle | ConstMethodType => Version::V039,
+            _ => Version::V035,
+        }
+    }
+}
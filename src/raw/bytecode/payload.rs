@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use crate::raw::{bytecode::*, *};
+
+/// The identifying code units of the payload pseudo-instructions.
+pub(crate) const PACKED_SWITCH_PAYLOAD: ushort = 0x0100;
+pub(crate) const SPARSE_SWITCH_PAYLOAD: ushort = 0x0200;
+pub(crate) const FILL_ARRAY_DATA_PAYLOAD: ushort = 0x0300;
+
+fn wide(units: &[ushort], i: usize) -> uint {
+    units[i] as uint | (units[i + 1] as uint) << 16
+}
+
+/// Resolves `target`, relative to the switch instruction at `switch_address`.
+fn absolute(switch_address: uint, target: int) -> uint {
+    switch_address.wrapping_add_signed(target)
+}
+
+/// See https://source.android.com/docs/core/runtime/dalvik-bytecode#packed-switch
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackedSwitchPayload {
+    /// First (and lowest) switch case value.
+    pub first_key: int,
+    /// Branch targets relative to the address of the switch instruction,
+    /// for the keys `first_key`, `first_key + 1`, etc.
+    pub targets: Vec<int>,
+}
+
+impl PackedSwitchPayload {
+    pub(crate) fn from_units(units: &[ushort]) -> Self {
+        let size = units[1] as usize;
+        Self {
+            first_key: wide(units, 2) as int,
+            targets: (0..size).map(|i| wide(units, 4 + i * 2) as int).collect(),
+        }
+    }
+
+    /// Size of this payload in code units.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.targets.len() as uint * 2 + 4
+    }
+
+    /// Maps each key to its absolute branch target,
+    /// given the address of the referencing switch instruction.
+    pub fn targets(&self, switch_address: uint) -> BTreeMap<int, uint> {
+        self.targets
+            .iter()
+            .enumerate()
+            .map(|(i, target)| {
+                let key = self.first_key.wrapping_add(i as int);
+                (key, absolute(switch_address, *target))
+            })
+            .collect()
+    }
+}
+
+/// See https://source.android.com/docs/core/runtime/dalvik-bytecode#sparse-switch
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SparseSwitchPayload {
+    /// Switch case values, sorted low-to-high.
+    pub keys: Vec<int>,
+    /// Branch targets relative to the address of the switch instruction, one per key.
+    pub targets: Vec<int>,
+}
+
+impl SparseSwitchPayload {
+    pub(crate) fn from_units(units: &[ushort]) -> Self {
+        let size = units[1] as usize;
+        let read = |start: usize| {
+            (0..size)
+                .map(|i| wide(units, start + i * 2) as int)
+                .collect()
+        };
+        Self {
+            keys: read(2),
+            targets: read(2 + size * 2),
+        }
+    }
+
+    /// Size of this payload in code units.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        self.keys.len() as uint * 4 + 2
+    }
+
+    /// Maps each key to its absolute branch target,
+    /// given the address of the referencing switch instruction.
+    pub fn targets(&self, switch_address: uint) -> BTreeMap<int, uint> {
+        self.keys
+            .iter()
+            .zip(&self.targets)
+            .map(|(key, target)| (*key, absolute(switch_address, *target)))
+            .collect()
+    }
+}
+
+/// The elements of a [`FillArrayDataPayload`], typed by their width.
+///
+/// Elements are stored as signed integers;
+/// `char`, `float` and `double` arrays have to be reinterpreted by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArrayData {
+    Byte(Vec<byte>),
+    Short(Vec<short>),
+    Int(Vec<int>),
+    Long(Vec<long>),
+}
+
+impl ArrayData {
+    /// Number of bytes in each element.
+    pub fn element_width(&self) -> ushort {
+        match self {
+            ArrayData::Byte(_) => 1,
+            ArrayData::Short(_) => 2,
+            ArrayData::Int(_) => 4,
+            ArrayData::Long(_) => 8,
+        }
+    }
+
+    /// Number of elements.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        (match self {
+            ArrayData::Byte(v) => v.len(),
+            ArrayData::Short(v) => v.len(),
+            ArrayData::Int(v) => v.len(),
+            ArrayData::Long(v) => v.len(),
+        }) as uint
+    }
+
+    /// The elements as little-endian bytes, as stored in the payload.
+    pub fn to_bytes(&self) -> Vec<ubyte> {
+        match self {
+            ArrayData::Byte(v) => v.iter().map(|e| *e as ubyte).collect(),
+            ArrayData::Short(v) => v.iter().flat_map(|e| e.to_le_bytes()).collect(),
+            ArrayData::Int(v) => v.iter().flat_map(|e| e.to_le_bytes()).collect(),
+            ArrayData::Long(v) => v.iter().flat_map(|e| e.to_le_bytes()).collect(),
+        }
+    }
+}
+
+/// See https://source.android.com/docs/core/runtime/dalvik-bytecode#fill-array
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FillArrayDataPayload {
+    pub data: ArrayData,
+}
+
+impl FillArrayDataPayload {
+    pub(crate) fn from_units(units: &[ushort], offset: uint) -> Result<Self, DecodeError> {
+        let element_width = units[1];
+        let size = wide(units, 2) as usize;
+        let bytes = units[4..]
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        let data = match element_width {
+            1 => ArrayData::Byte(bytes[..size].iter().map(|b| *b as byte).collect()),
+            2 => ArrayData::Short(
+                bytes[..size * 2]
+                    .chunks_exact(2)
+                    .map(|c| short::from_le_bytes([c[0], c[1]]))
+                    .collect(),
+            ),
+            4 => ArrayData::Int(
+                bytes[..size * 4]
+                    .chunks_exact(4)
+                    .map(|c| int::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            8 => ArrayData::Long(
+                bytes[..size * 8]
+                    .chunks_exact(8)
+                    .map(|c| long::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            _ => return Err(DecodeError::InvalidElementWidth(element_width, offset)),
+        };
+        Ok(Self { data })
+    }
+
+    /// Size of this payload in code units.
+    #[allow(clippy::len_without_is_empty)] // no need for that here
+    pub fn len(&self) -> uint {
+        (self.data.len() * self.data.element_width() as uint).div_ceil(2) + 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_switch() {
+        let insns = [
+            0x0100, 0x0003, 0xfffe, 0xffff, // size 3, first_key -2
+            0x0005, 0x0000, 0xfffc, 0xffff, 0x0000, 0x0001,
+        ];
+        let Instruction::PackedSwitchPayload(payload) = Instruction::decode(&insns, 0).unwrap()
+        else {
+            panic!("expected packed-switch-payload");
+        };
+        assert_eq!(payload.len(), insns.len() as uint);
+        assert_eq!(
+            payload.targets(10).into_iter().collect::<Vec<_>>(),
+            vec![(-2, 15), (-1, 6), (0, 10 + 0x10000)]
+        );
+
+        // the last key is the highest there is
+        let payload = PackedSwitchPayload {
+            first_key: int::MAX,
+            targets: vec![4],
+        };
+        assert_eq!(
+            payload.targets(10).into_iter().collect::<Vec<_>>(),
+            vec![(int::MAX, 14)]
+        );
+    }
+
+    #[test]
+    fn sparse_switch() {
+        let insns = [
+            0x0200, 0x0002, // size 2
+            0xffff, 0xffff, 0x0000, 0x0001, // keys -1, 0x10000
+            0x0003, 0x0000, 0xfffd, 0xffff, // targets 3, -3
+        ];
+        let Instruction::SparseSwitchPayload(payload) = Instruction::decode(&insns, 0).unwrap()
+        else {
+            panic!("expected sparse-switch-payload");
+        };
+        assert_eq!(payload.len(), insns.len() as uint);
+        assert_eq!(
+            payload.targets(4).into_iter().collect::<Vec<_>>(),
+            vec![(-1, 7), (0x10000, 1)]
+        );
+    }
+
+    #[test]
+    fn fill_array_data() {
+        let insns = [0x0300, 0x0001, 0x0003, 0x0000, 0x0201, 0x00ff];
+        let Instruction::FillArrayDataPayload(payload) = Instruction::decode(&insns, 0).unwrap()
+        else {
+            panic!("expected fill-array-data-payload");
+        };
+        assert_eq!(payload.len(), insns.len() as uint);
+        assert_eq!(payload.data, ArrayData::Byte(vec![1, 2, -1]));
+        assert_eq!(payload.data.to_bytes(), vec![1, 2, 0xff]);
+
+        let insns = [0x0300, 0x0004, 0x0001, 0x0000, 0xfffe, 0x7fff];
+        let Instruction::FillArrayDataPayload(payload) = Instruction::decode(&insns, 0).unwrap()
+        else {
+            panic!("expected fill-array-data-payload");
+        };
+        assert_eq!(payload.data, ArrayData::Int(vec![0x7ffffffe]));
+
+        let insns = [0x0300, 0x0003, 0x0001, 0x0000, 0x0000, 0x0000];
+        assert_eq!(
+            Instruction::decode(&insns, 0),
+            Err(DecodeError::InvalidElementWidth(3, 0))
+        );
+    }
+}
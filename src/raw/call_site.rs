@@ -0,0 +1,139 @@
+use crate::raw::{
+    buffer::EncodedLen,
+    encoded_value::{EncodedArrayItem, EncodedValue, EncodedValueError},
+    *,
+};
+use scroll::{
+    ctx::{TryFromCtx, TryIntoCtx},
+    Pread, Pwrite,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CallSiteError {
+    #[error("call site has {0} arguments, expected at least 3")]
+    TooFewArguments(usize),
+    #[error("call site argument {0} has an unexpected type: {1:?}")]
+    InvalidArgument(usize, EncodedValue),
+    #[error("error reading encoded array: {0}")]
+    EncodedValue(#[from] EncodedValueError),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// See https://source.android.com/docs/core/runtime/dex-format#call-site-item
+///
+/// Stored as an `encoded_array_item`, of which the first three elements
+/// are the arguments to the bootstrap linker method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSiteItem {
+    /// Index into the `method_handles` list for the bootstrap linker method.
+    pub method_handle: RawMethodHandleIndex,
+    /// Index into the `string_ids` list for the name of the method to resolve.
+    pub method_name: RawStringIndex,
+    /// Index into the `proto_ids` list for the type of the method to resolve.
+    pub method_type: RawProtoIndex,
+    /// Additional static arguments passed to the bootstrap linker method.
+    pub extra_args: Vec<EncodedValue>,
+}
+
+impl<'a> TryFromCtx<'a> for CallSiteItem {
+    type Error = CallSiteError;
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let item: EncodedArrayItem = src.gread(offset)?;
+        let mut values = item.into_inner().into_inner();
+        if values.len() < 3 {
+            return Err(CallSiteError::TooFewArguments(values.len()));
+        }
+        let method_handle = match &values[0] {
+            EncodedValue::MethodHandle(idx) => *idx,
+            v => return Err(CallSiteError::InvalidArgument(0, v.clone())),
+        };
+        let method_name = match &values[1] {
+            EncodedValue::String(idx) => *idx,
+            v => return Err(CallSiteError::InvalidArgument(1, v.clone())),
+        };
+        let method_type = match &values[2] {
+            EncodedValue::MethodType(idx) => *idx,
+            v => return Err(CallSiteError::InvalidArgument(2, v.clone())),
+        };
+        Ok((
+            Self {
+                method_handle,
+                method_name,
+                method_type,
+                extra_args: values.split_off(3),
+            },
+            *offset,
+        ))
+    }
+}
+
+impl TryIntoCtx for CallSiteItem {
+    type Error = CallSiteError;
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        dst.gwrite(EncodedArrayItem::from(self), offset)?;
+        Ok(*offset)
+    }
+}
+
+impl EncodedLen for CallSiteItem {
+    fn encoded_len(&self) -> usize {
+        let values = 3 + self.extra_args.len();
+        uleb128::len_of(values as ulong)
+            + EncodedValue::MethodHandle(self.method_handle).encoded_len()
+            + EncodedValue::String(self.method_name).encoded_len()
+            + EncodedValue::MethodType(self.method_type).encoded_len()
+            + self
+                .extra_args
+                .iter()
+                .map(EncodedValue::encoded_len)
+                .sum::<usize>()
+    }
+}
+
+impl From<CallSiteItem> for EncodedArrayItem {
+    fn from(item: CallSiteItem) -> Self {
+        let mut values = Vec::with_capacity(3 + item.extra_args.len());
+        values.push(EncodedValue::MethodHandle(item.method_handle));
+        values.push(EncodedValue::String(item.method_name));
+        values.push(EncodedValue::MethodType(item.method_type));
+        values.extend(item.extra_args);
+        EncodedArrayItem::from(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_site() {
+        let v = CallSiteItem {
+            method_handle: 1,
+            method_name: 256,
+            method_type: 3,
+            extra_args: vec![EncodedValue::Int(7), EncodedValue::Type(2)],
+        };
+        let mut buf = [0u8; 1024];
+        let len = TryIntoCtx::try_into_ctx(v.clone(), &mut buf, ()).unwrap();
+        let (v2, _) = CallSiteItem::try_from_ctx(&buf[..len], ()).unwrap();
+        assert_eq!(v, v2);
+    }
+
+    #[test]
+    fn invalid_call_site() {
+        let mut buf = [0u8; 1024];
+        let item = EncodedArrayItem::from(vec![
+            EncodedValue::String(1),
+            EncodedValue::String(2),
+            EncodedValue::MethodType(3),
+        ]);
+        let len = TryIntoCtx::try_into_ctx(item, &mut buf[..], ()).unwrap();
+        assert!(matches!(
+            CallSiteItem::try_from_ctx(&buf[..len], ()),
+            Err(CallSiteError::InvalidArgument(0, _))
+        ));
+    }
+}
@@ -1,10 +1,10 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
 };
 
-use super::flags::AccessFlags;
+use super::{flags::AccessFlags, header::Version};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ClassDataError {
@@ -12,7 +12,7 @@ pub enum ClassDataError {
     Scroll(#[from] scroll::Error),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClassData {
     /// The number of static fields defined in this item.
     pub static_fields_size: ulong,
@@ -38,6 +38,27 @@ pub struct ClassData {
     pub virtual_methods: Vec<EncodedMethod>,
 }
 
+impl ClassData {
+    /// The first version that allows the methods of a class with the given access flags.
+    /// Interfaces can only have methods other than `abstract` ones and `<clinit>`
+    /// (the static constructor) since [`Version::V037`].
+    pub fn min_version(&self, class_flags: AccessFlags) -> Version {
+        let clinit = AccessFlags::Static | AccessFlags::Constructor;
+        let default_or_static =
+            self.direct_methods
+                .iter()
+                .chain(&self.virtual_methods)
+                .any(|method| {
+                    !method.access_flags.contains(AccessFlags::Abstract)
+                        && !method.access_flags.contains(clinit)
+                });
+        match class_flags.contains(AccessFlags::Interface) && default_or_static {
+            true => Version::V037,
+            false => Version::V035,
+        }
+    }
+}
+
 impl<'a> TryFromCtx<'a> for ClassData {
     type Error = ClassDataError;
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
@@ -82,7 +103,36 @@ impl TryIntoCtx for ClassData {
     }
 }
 
-#[derive(Debug)]
+impl EncodedLen for ClassData {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.static_fields_size)
+            + uleb128::len_of(self.instance_fields_size)
+            + uleb128::len_of(self.direct_methods_size)
+            + uleb128::len_of(self.virtual_methods_size)
+            + self
+                .static_fields
+                .iter()
+                .map(EncodedField::encoded_len)
+                .sum::<usize>()
+            + self
+                .instance_fields
+                .iter()
+                .map(EncodedField::encoded_len)
+                .sum::<usize>()
+            + self
+                .direct_methods
+                .iter()
+                .map(EncodedMethod::encoded_len)
+                .sum::<usize>()
+            + self
+                .virtual_methods
+                .iter()
+                .map(EncodedMethod::encoded_len)
+                .sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct EncodedField {
     /// Index into the `field_ids` list for the identity of this field (includes the name and descriptor),
     /// represented as a difference from the index of previous element in the list.
@@ -119,7 +169,13 @@ impl TryIntoCtx for EncodedField {
     }
 }
 
-#[derive(Debug)]
+impl EncodedLen for EncodedField {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.field_idx_diff) + self.access_flags.uleb128_len()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct EncodedMethod {
     /// Index into the `method_ids` list for the identity of this field (includes the name and descriptor),
     /// represented as a difference from the index of previous element in the list.
@@ -162,3 +218,11 @@ impl TryIntoCtx for EncodedMethod {
         Ok(*offset)
     }
 }
+
+impl EncodedLen for EncodedMethod {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.method_idx_diff)
+            + self.access_flags.uleb128_len()
+            + uleb128::len_of(self.code_off)
+    }
+}
@@ -3,7 +3,7 @@ use scroll::{Pread, Pwrite};
 
 use super::flags::AccessFlags;
 
-#[derive(Debug, Pread, Pwrite)]
+#[derive(Debug, Clone, Copy, Pread, Pwrite)]
 pub struct ClassDef {
     /// Index into the `type_ids` list for this class.
     /// This must be a class type, and not an array or primitive type.
@@ -43,3 +43,5 @@ pub struct ClassDef {
     /// then the leftover fields are initialized with a type-appropriate 0 or `null`.
     pub static_values_off: uint,
 }
+
+sized_encoded_len!(ClassDef);
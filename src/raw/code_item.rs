@@ -1,4 +1,13 @@
-use crate::raw::{encoded_value::EncodedCatchHandlerList, simple::TryItem, *};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::raw::{
+    buffer::EncodedLen,
+    bytecode::{ArrayData, DecodeError, DecodedInstruction, Decoder, Instruction, Opcode},
+    encoded_value::EncodedCatchHandlerList,
+    header::Version,
+    simple::TryItem,
+    *,
+};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{
@@ -8,7 +17,7 @@ use scroll::{
 
 type TriesPadding = ushort;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CodeItem {
     pub registers_size: ushort,
     pub ins_size: ushort,
@@ -20,6 +29,60 @@ pub struct CodeItem {
     pub handlers: Option<EncodedCatchHandlerList>,
 }
 
+impl CodeItem {
+    /// Decodes [`CodeItem::insns`] into instructions, in order.
+    pub fn instructions(&self) -> Decoder<'_> {
+        Decoder::new(&self.insns)
+    }
+
+    /// The first version that allows every instruction of this code.
+    pub fn min_version(&self) -> Result<Version, DecodeError> {
+        let mut version = Version::V035;
+        for insn in self.instructions() {
+            if let Some(opcode) = insn?.instruction.opcode() {
+                version = version.max(opcode.min_version());
+            }
+        }
+        Ok(version)
+    }
+
+    /// Maps each key of the `packed-switch` or `sparse-switch` instruction `switch`
+    /// to its absolute branch target.
+    pub fn switch_targets(
+        &self,
+        switch: &DecodedInstruction,
+    ) -> Result<BTreeMap<int, uint>, DecodeError> {
+        match self.payload(switch)? {
+            Instruction::PackedSwitchPayload(payload) => Ok(payload.targets(switch.offset)),
+            Instruction::SparseSwitchPayload(payload) => Ok(payload.targets(switch.offset)),
+            _ => Err(DecodeError::InvalidPayloadReference(switch.offset)),
+        }
+    }
+
+    /// The elements the `fill-array-data` instruction `insn` fills its array with.
+    pub fn array_data(&self, insn: &DecodedInstruction) -> Result<ArrayData, DecodeError> {
+        match self.payload(insn)? {
+            Instruction::FillArrayDataPayload(payload) => Ok(payload.data),
+            _ => Err(DecodeError::InvalidPayloadReference(insn.offset)),
+        }
+    }
+
+    fn payload(&self, insn: &DecodedInstruction) -> Result<Instruction, DecodeError> {
+        let address = insn
+            .instruction
+            .payload_address(insn.offset)
+            .ok_or(DecodeError::InvalidPayloadReference(insn.offset))?;
+        let payload = Instruction::decode(&self.insns, address)?;
+        // the payload has to match the instruction referencing it
+        match (insn.instruction.opcode(), &payload) {
+            (Some(Opcode::PackedSwitch), Instruction::PackedSwitchPayload(_))
+            | (Some(Opcode::SparseSwitch), Instruction::SparseSwitchPayload(_))
+            | (Some(Opcode::FillArrayData), Instruction::FillArrayDataPayload(_)) => Ok(payload),
+            _ => Err(DecodeError::InvalidPayloadReference(insn.offset)),
+        }
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for CodeItem {
     type Error = scroll::Error;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
@@ -33,7 +96,7 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for CodeItem {
         let insns = try_gread_vec_with!(src, offset, insns_size, ctx);
         // 2 bytes of padding to make `tries` four-byte aligned.
         // This element is only present if `tries_size` is non-zero and `insns_size` is odd.
-        if insns_size % 2 != 0 && tries_size != 0 {
+        if !insns_size.is_multiple_of(2) && tries_size != 0 {
             src.gread_with::<TriesPadding>(offset, ctx)?;
         }
         let tries = try_gread_vec_with!(src, offset, tries_size, ctx);
@@ -71,7 +134,7 @@ impl TryIntoCtx<scroll::Endian> for CodeItem {
         try_gwrite_vec_with!(dst, offset, &self.insns, ctx);
         // 2 bytes of padding to make `tries` four-byte aligned.
         // This element is only present if `tries_size` is non-zero and `insns_size` is odd.
-        if self.insns.len() % 2 != 0 && self.tries_size != 0 {
+        if !self.insns.len().is_multiple_of(2) && self.tries_size != 0 {
             dst.gwrite_with::<TriesPadding>(0, offset, ctx)?;
         }
         try_gwrite_vec_with!(dst, offset, self.tries, ctx);
@@ -82,6 +145,26 @@ impl TryIntoCtx<scroll::Endian> for CodeItem {
     }
 }
 
+impl EncodedLen for CodeItem {
+    fn encoded_len(&self) -> usize {
+        let padding = if !self.insns.len().is_multiple_of(2) && self.tries_size != 0 {
+            std::mem::size_of::<TriesPadding>()
+        } else {
+            0
+        };
+        // four `ushort` sizes, then the debug info offset and `insns_size`
+        4 * std::mem::size_of::<ushort>()
+            + 2 * std::mem::size_of::<uint>()
+            + self.insns.len() * std::mem::size_of::<ushort>()
+            + padding
+            + self.tries.len() * tysize::TRY_ITEM
+            + self
+                .handlers
+                .as_ref()
+                .map_or(0, EncodedCatchHandlerList::encoded_len)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DebugInfoError {
     #[error("invalid operation in debug info: {0}")]
@@ -90,6 +173,7 @@ pub enum DebugInfoError {
     Scroll(#[from] scroll::Error),
 }
 
+/// See https://source.android.com/docs/core/runtime/dex-format#debug-info-item
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DebugInfoItem {
     /// The line number where the information in this item starts.
@@ -97,6 +181,9 @@ pub struct DebugInfoItem {
     /// The list of parameter names for this method.
     /// `Some` means the parameter has a name, `None` means it doesn't.
     pub parameter_names: Vec<Option<ulong>>,
+    /// The operations of the state machine, excluding the final `DBG_END_SEQUENCE`.
+    /// Use [`DebugInfoItem::decode`] to run them.
+    pub ops: Vec<DebugInfoOp>,
 }
 
 /// See https://source.android.com/docs/core/runtime/dex-format#debug-info-item
@@ -115,6 +202,231 @@ pub enum DebugInfoOperation {
     SetFile = 0x09,
 }
 const SPECIAL: std::ops::RangeInclusive<u8> = 0x0a..=0xff;
+const SPECIAL_FIRST: u8 = *SPECIAL.start();
+const LINE_BASE: int = -4;
+const LINE_RANGE: u8 = 15;
+
+/// A single operation of the debug info state machine, with its arguments.
+///
+/// All string and type indices are `None` if the encoded `uleb128p1` value is `NO_INDEX`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DebugInfoOp {
+    /// Advances the address register without emitting a position entry.
+    AdvancePc(uint),
+    /// Advances the line register without emitting a position entry.
+    AdvanceLine(int),
+    /// Introduces a local variable at the current address.
+    StartLocal {
+        register: uint,
+        name: Option<RawStringIndex>,
+        ty: Option<RawTypeIndex>,
+    },
+    /// Introduces a local variable with a type signature at the current address.
+    StartLocalExtended {
+        register: uint,
+        name: Option<RawStringIndex>,
+        ty: Option<RawTypeIndex>,
+        signature: Option<RawStringIndex>,
+    },
+    /// Marks the local variable in the register as out of scope at the current address.
+    EndLocal(uint),
+    /// Re-introduces the last local variable in the register at the current address.
+    RestartLocal(uint),
+    /// The next position entry is the end of the method prologue.
+    SetPrologueEnd,
+    /// The next position entry is the beginning of a method epilogue.
+    SetEpilogueBegin,
+    /// All subsequent position entries refer to this source file.
+    SetFile(Option<RawStringIndex>),
+    /// Advances both the line and address registers, then emits a position entry.
+    /// Contains the raw opcode, which is always in the range `0x0a..=0xff`.
+    Special(ubyte),
+}
+
+impl DebugInfoOp {
+    /// Builds the special opcode advancing the line and address registers by the given amounts,
+    /// or `None` if they can't be encoded in a single special opcode.
+    pub fn special(line_diff: int, address_diff: uint) -> Option<Self> {
+        if !(LINE_BASE..LINE_BASE + LINE_RANGE as int).contains(&line_diff) {
+            return None;
+        }
+        let opcode = address_diff
+            .checked_mul(LINE_RANGE as uint)?
+            .checked_add((line_diff - LINE_BASE) as uint + SPECIAL_FIRST as uint)?;
+        ubyte::try_from(opcode).ok().map(Self::Special)
+    }
+
+    /// How far this operation advances the address and line registers.
+    /// A [`DebugInfoOp::Special`] with an opcode below `0x0a` is invalid, and doesn't advance them.
+    pub fn advance(&self) -> (uint, int) {
+        match *self {
+            Self::AdvancePc(diff) => (diff, 0),
+            Self::AdvanceLine(diff) => (0, diff),
+            Self::Special(opcode) => {
+                let Some(adjusted) = opcode.checked_sub(SPECIAL_FIRST) else {
+                    return (0, 0);
+                };
+                (
+                    (adjusted / LINE_RANGE) as uint,
+                    LINE_BASE + (adjusted % LINE_RANGE) as int,
+                )
+            }
+            _ => (0, 0),
+        }
+    }
+}
+
+fn read_uleb128p1(src: &[u8], offset: &mut usize) -> Result<Option<uint>, scroll::Error> {
+    Ok(match uleb128::read(src, offset)? {
+        0 => None,
+        idx => Some((idx - 1) as uint),
+    })
+}
+
+fn write_uleb128p1(dst: &mut [u8], offset: &mut usize, idx: Option<uint>) -> scroll::Result<()> {
+    uleb128::write(dst, offset, idx.map_or(0, |idx| idx as ulong + 1))
+}
+
+fn uleb128p1_len(idx: Option<uint>) -> usize {
+    uleb128::len_of(idx.map_or(0, |idx| idx as ulong + 1))
+}
+
+impl<'a> TryFromCtx<'a> for DebugInfoOp {
+    type Error = DebugInfoError;
+    /// Fails with [`DebugInfoError::InvalidOperation`] on `DBG_END_SEQUENCE`.
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let byte = src.gread_with::<u8>(offset, scroll::LE)?;
+        let op = match DebugInfoOperation::from_u8(byte) {
+            Some(DebugInfoOperation::EndSequence) => {
+                return Err(DebugInfoError::InvalidOperation(byte))
+            }
+            Some(DebugInfoOperation::AdvancePc) => {
+                DebugInfoOp::AdvancePc(uleb128::read(src, offset)? as uint)
+            }
+            Some(DebugInfoOperation::AdvanceLine) => {
+                DebugInfoOp::AdvanceLine(sleb128::read(src, offset)? as int)
+            }
+            Some(DebugInfoOperation::StartLocal) => DebugInfoOp::StartLocal {
+                register: uleb128::read(src, offset)? as uint,
+                name: read_uleb128p1(src, offset)?,
+                ty: read_uleb128p1(src, offset)?,
+            },
+            Some(DebugInfoOperation::StartLocalExtended) => DebugInfoOp::StartLocalExtended {
+                register: uleb128::read(src, offset)? as uint,
+                name: read_uleb128p1(src, offset)?,
+                ty: read_uleb128p1(src, offset)?,
+                signature: read_uleb128p1(src, offset)?,
+            },
+            Some(DebugInfoOperation::EndLocal) => {
+                DebugInfoOp::EndLocal(uleb128::read(src, offset)? as uint)
+            }
+            Some(DebugInfoOperation::RestartLocal) => {
+                DebugInfoOp::RestartLocal(uleb128::read(src, offset)? as uint)
+            }
+            Some(DebugInfoOperation::SetPrologueEnd) => DebugInfoOp::SetPrologueEnd,
+            Some(DebugInfoOperation::SetEpilogueBegin) => DebugInfoOp::SetEpilogueBegin,
+            Some(DebugInfoOperation::SetFile) => DebugInfoOp::SetFile(read_uleb128p1(src, offset)?),
+            None if SPECIAL.contains(&byte) => DebugInfoOp::Special(byte),
+            None => return Err(DebugInfoError::InvalidOperation(byte)),
+        };
+        Ok((op, *offset))
+    }
+}
+
+impl TryIntoCtx for DebugInfoOp {
+    type Error = DebugInfoError;
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        let opcode = |dst: &mut [u8], op: DebugInfoOperation, offset: &mut usize| {
+            dst.gwrite_with::<u8>(op as u8, offset, scroll::LE)
+        };
+        match self {
+            DebugInfoOp::AdvancePc(diff) => {
+                opcode(dst, DebugInfoOperation::AdvancePc, offset)?;
+                uleb128::write(dst, offset, diff as ulong)?;
+            }
+            DebugInfoOp::AdvanceLine(diff) => {
+                opcode(dst, DebugInfoOperation::AdvanceLine, offset)?;
+                sleb128::write(dst, offset, diff as long)?;
+            }
+            DebugInfoOp::StartLocal { register, name, ty } => {
+                opcode(dst, DebugInfoOperation::StartLocal, offset)?;
+                uleb128::write(dst, offset, register as ulong)?;
+                write_uleb128p1(dst, offset, name)?;
+                write_uleb128p1(dst, offset, ty)?;
+            }
+            DebugInfoOp::StartLocalExtended {
+                register,
+                name,
+                ty,
+                signature,
+            } => {
+                opcode(dst, DebugInfoOperation::StartLocalExtended, offset)?;
+                uleb128::write(dst, offset, register as ulong)?;
+                write_uleb128p1(dst, offset, name)?;
+                write_uleb128p1(dst, offset, ty)?;
+                write_uleb128p1(dst, offset, signature)?;
+            }
+            DebugInfoOp::EndLocal(register) => {
+                opcode(dst, DebugInfoOperation::EndLocal, offset)?;
+                uleb128::write(dst, offset, register as ulong)?;
+            }
+            DebugInfoOp::RestartLocal(register) => {
+                opcode(dst, DebugInfoOperation::RestartLocal, offset)?;
+                uleb128::write(dst, offset, register as ulong)?;
+            }
+            DebugInfoOp::SetPrologueEnd => {
+                opcode(dst, DebugInfoOperation::SetPrologueEnd, offset)?;
+            }
+            DebugInfoOp::SetEpilogueBegin => {
+                opcode(dst, DebugInfoOperation::SetEpilogueBegin, offset)?;
+            }
+            DebugInfoOp::SetFile(name) => {
+                opcode(dst, DebugInfoOperation::SetFile, offset)?;
+                write_uleb128p1(dst, offset, name)?;
+            }
+            DebugInfoOp::Special(byte) => {
+                if !SPECIAL.contains(&byte) {
+                    return Err(DebugInfoError::InvalidOperation(byte));
+                }
+                dst.gwrite_with::<u8>(byte, offset, scroll::LE)?;
+            }
+        }
+        Ok(*offset)
+    }
+}
+
+impl EncodedLen for DebugInfoOp {
+    fn encoded_len(&self) -> usize {
+        // the opcode, then its arguments
+        1 + match *self {
+            DebugInfoOp::AdvancePc(diff) => uleb128::len_of(diff as ulong),
+            DebugInfoOp::AdvanceLine(diff) => sleb128::len_of(diff as long),
+            DebugInfoOp::StartLocal { register, name, ty } => {
+                uleb128::len_of(register as ulong) + uleb128p1_len(name) + uleb128p1_len(ty)
+            }
+            DebugInfoOp::StartLocalExtended {
+                register,
+                name,
+                ty,
+                signature,
+            } => {
+                uleb128::len_of(register as ulong)
+                    + uleb128p1_len(name)
+                    + uleb128p1_len(ty)
+                    + uleb128p1_len(signature)
+            }
+            DebugInfoOp::EndLocal(register) | DebugInfoOp::RestartLocal(register) => {
+                uleb128::len_of(register as ulong)
+            }
+            DebugInfoOp::SetFile(name) => uleb128p1_len(name),
+            DebugInfoOp::SetPrologueEnd
+            | DebugInfoOp::SetEpilogueBegin
+            | DebugInfoOp::Special(_) => 0,
+        }
+    }
+}
 
 impl<'a> TryFromCtx<'a> for DebugInfoItem {
     type Error = DebugInfoError;
@@ -124,62 +436,18 @@ impl<'a> TryFromCtx<'a> for DebugInfoItem {
         let parameters_size = uleb128::read(src, offset)?;
         let mut parameter_names = Vec::with_capacity(parameters_size as usize);
         for _ in 0..parameters_size {
-            let idx = uleb128::read(src, offset)?;
-            parameter_names.push(if idx != NO_INDEX.into() {
-                Some(idx + 1) // uleb128p1
-            } else {
-                None
-            });
-        }
-        // TODO: Implement DWARF3 state machine.
-        // Currently, we just discard the debug info.
-        loop {
-            let byte = src.gread_with::<u8>(offset, scroll::LE)?;
-            match DebugInfoOperation::from_u8(byte) {
-                Some(op) => match op {
-                    DebugInfoOperation::EndSequence => {
-                        break;
-                    }
-                    DebugInfoOperation::AdvancePc => {
-                        uleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::AdvanceLine => {
-                        sleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::StartLocal => {
-                        uleb128::read(src, offset)?;
-                        uleb128::read(src, offset)?;
-                        uleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::StartLocalExtended => {
-                        uleb128::read(src, offset)?;
-                        uleb128::read(src, offset)?;
-                        uleb128::read(src, offset)?;
-                        uleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::EndLocal => {
-                        uleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::RestartLocal => {
-                        uleb128::read(src, offset)?;
-                    }
-                    DebugInfoOperation::SetPrologueEnd => {}
-                    DebugInfoOperation::SetEpilogueBegin => {}
-                    DebugInfoOperation::SetFile => {
-                        uleb128::read(src, offset)?;
-                    }
-                },
-                None => {
-                    if !SPECIAL.contains(&byte) {
-                        return Err(DebugInfoError::InvalidOperation(byte));
-                    }
-                }
-            }
+            parameter_names.push(read_uleb128p1(src, offset)?.map(ulong::from));
         }
+        let mut ops = Vec::new();
+        while src.pread_with::<u8>(*offset, scroll::LE)? != DebugInfoOperation::EndSequence as u8 {
+            ops.push(src.gread(offset)?);
+        }
+        *offset += 1; // DBG_END_SEQUENCE
         Ok((
             Self {
                 line_start,
                 parameter_names,
+                ops,
             },
             *offset,
         ))
@@ -193,30 +461,294 @@ impl TryIntoCtx for DebugInfoItem {
         uleb128::write(dst, offset, self.line_start)?;
         uleb128::write(dst, offset, self.parameter_names.len() as u64)?;
         for idx in self.parameter_names {
-            if let Some(idx) = idx {
-                uleb128::write(dst, offset, idx - 1)?; // uleb128p1
-            } else {
-                uleb128::write(dst, offset, NO_INDEX.into())?;
-            }
+            write_uleb128p1(dst, offset, idx.map(|idx| idx as uint))?;
+        }
+        for op in self.ops {
+            dst.gwrite(op, offset)?;
         }
-        // TODO: Implement DWARF3 state machine.
-        // Currently, we just immediately end the sequence.
         dst.gwrite_with::<u8>(DebugInfoOperation::EndSequence as u8, offset, scroll::LE)?;
         Ok(*offset)
     }
 }
 
+impl EncodedLen for DebugInfoItem {
+    fn encoded_len(&self) -> usize {
+        let parameter_names = self
+            .parameter_names
+            .iter()
+            .map(|idx| uleb128p1_len(idx.map(|idx| idx as uint)))
+            .sum::<usize>();
+        // terminated by `DBG_END_SEQUENCE`
+        uleb128::len_of(self.line_start)
+            + uleb128::len_of(self.parameter_names.len() as ulong)
+            + parameter_names
+            + self.ops.iter().map(DebugInfoOp::encoded_len).sum::<usize>()
+            + 1
+    }
+}
+
+/// An entry of the position table, mapping an address to a source line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionEntry {
+    /// Offset in code units from the start of the method's instructions.
+    pub address: uint,
+    pub line: uint,
+    /// Index into the `string_ids` list for the source file, as set by `DBG_SET_FILE`.
+    /// `None` means the `source_file_idx` of the defining class applies.
+    pub source_file: Option<RawStringIndex>,
+    /// This entry marks the end of the method prologue.
+    pub prologue_end: bool,
+    /// This entry marks the beginning of a method epilogue.
+    pub epilogue_begin: bool,
+}
+
+/// An entry of the local variable table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalEntry {
+    pub register: uint,
+    /// Index into the `string_ids` list for the name of the variable.
+    pub name: Option<RawStringIndex>,
+    /// Index into the `type_ids` list for the type of the variable.
+    pub ty: Option<RawTypeIndex>,
+    /// Index into the `string_ids` list for the type signature of the variable.
+    pub signature: Option<RawStringIndex>,
+    /// Address at which the variable comes into scope, inclusive.
+    pub start_address: uint,
+    /// Address at which the variable goes out of scope, exclusive.
+    pub end_address: uint,
+}
+
+/// The decoded tables of a [`DebugInfoItem`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// Position entries, in increasing address order.
+    pub positions: Vec<PositionEntry>,
+    /// Local variables, in the order they came into scope.
+    pub locals: Vec<LocalEntry>,
+}
+
+impl DebugInfo {
+    /// Returns the source line of the instruction at `address`, if known.
+    pub fn line_at(&self, address: uint) -> Option<uint> {
+        let idx = self
+            .positions
+            .partition_point(|entry| entry.address <= address);
+        idx.checked_sub(1).map(|idx| self.positions[idx].line)
+    }
+
+    /// Returns the local variables in scope at `address`.
+    pub fn locals_at(&self, address: uint) -> impl Iterator<Item = &LocalEntry> {
+        self.locals
+            .iter()
+            .filter(move |local| (local.start_address..local.end_address).contains(&address))
+    }
+}
+
+impl DebugInfoItem {
+    /// Runs the state machine over [`DebugInfoItem::ops`].
+    ///
+    /// `insns_size` is the size of the method's instructions in code units;
+    /// local variables still in scope at the end of the sequence are live until then.
+    /// Method parameters aren't included in the local variable table,
+    /// see [`DebugInfoItem::parameter_names`] for those.
+    pub fn decode(&self, insns_size: uint) -> DebugInfo {
+        let mut info = DebugInfo::default();
+        let mut address: uint = 0;
+        let mut line = self.line_start as uint;
+        let mut source_file = None;
+        let mut prologue_end = false;
+        let mut epilogue_begin = false;
+        // index into `info.locals` of the variable currently in scope in each register,
+        // and of the variable last introduced in each register
+        let mut live: HashMap<uint, usize> = HashMap::new();
+        let mut last: HashMap<uint, usize> = HashMap::new();
+
+        for op in &self.ops {
+            let local = match *op {
+                DebugInfoOp::StartLocal { register, name, ty } => Some(LocalEntry {
+                    register,
+                    name,
+                    ty,
+                    signature: None,
+                    start_address: address,
+                    end_address: insns_size,
+                }),
+                DebugInfoOp::StartLocalExtended {
+                    register,
+                    name,
+                    ty,
+                    signature,
+                } => Some(LocalEntry {
+                    register,
+                    name,
+                    ty,
+                    signature,
+                    start_address: address,
+                    end_address: insns_size,
+                }),
+                // restarting a variable that is still in scope has no effect
+                DebugInfoOp::RestartLocal(register) if !live.contains_key(&register) => {
+                    last.get(&register).map(|&idx| LocalEntry {
+                        start_address: address,
+                        end_address: insns_size,
+                        ..info.locals[idx]
+                    })
+                }
+                DebugInfoOp::RestartLocal(_) => None,
+                DebugInfoOp::EndLocal(register) => {
+                    if let Some(idx) = live.remove(&register) {
+                        info.locals[idx].end_address = address;
+                    }
+                    None
+                }
+                DebugInfoOp::AdvancePc(diff) => {
+                    address = address.wrapping_add(diff);
+                    None
+                }
+                DebugInfoOp::AdvanceLine(diff) => {
+                    line = line.wrapping_add_signed(diff);
+                    None
+                }
+                DebugInfoOp::SetPrologueEnd => {
+                    prologue_end = true;
+                    None
+                }
+                DebugInfoOp::SetEpilogueBegin => {
+                    epilogue_begin = true;
+                    None
+                }
+                DebugInfoOp::SetFile(name) => {
+                    source_file = name;
+                    None
+                }
+                DebugInfoOp::Special(_) => {
+                    let (address_diff, line_diff) = op.advance();
+                    line = line.wrapping_add_signed(line_diff);
+                    address = address.wrapping_add(address_diff);
+                    info.positions.push(PositionEntry {
+                        address,
+                        line,
+                        source_file,
+                        prologue_end,
+                        epilogue_begin,
+                    });
+                    prologue_end = false;
+                    epilogue_begin = false;
+                    None
+                }
+            };
+            if let Some(local) = local {
+                // a new variable in a register ends the scope of the previous one
+                if let Some(idx) = live.remove(&local.register) {
+                    info.locals[idx].end_address = local.start_address;
+                }
+                live.insert(local.register, info.locals.len());
+                last.insert(local.register, info.locals.len());
+                info.locals.push(local);
+            }
+        }
+        info
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn debug_info() {
-        let v = super::DebugInfoItem {
+        let v = DebugInfoItem {
             line_start: 256,
-            parameter_names: vec![Some(256), None, Some(1)],
+            parameter_names: vec![Some(256), None, Some(0)],
+            ops: vec![
+                DebugInfoOp::SetPrologueEnd,
+                DebugInfoOp::StartLocalExtended {
+                    register: 3,
+                    name: Some(1),
+                    ty: None,
+                    signature: Some(0),
+                },
+                DebugInfoOp::AdvanceLine(-300),
+                DebugInfoOp::AdvancePc(1000),
+                DebugInfoOp::SetFile(None),
+                DebugInfoOp::Special(0xff),
+            ],
         };
         let mut buf = [0u8; 1024];
-        let len = scroll::ctx::TryIntoCtx::try_into_ctx(v.clone(), &mut buf, ()).unwrap();
-        let (v2, _) = scroll::ctx::TryFromCtx::try_from_ctx(&buf[..len], ()).unwrap();
+        let len = TryIntoCtx::try_into_ctx(v.clone(), &mut buf, ()).unwrap();
+        let (v2, len2) = DebugInfoItem::try_from_ctx(&buf[..len], ()).unwrap();
         assert_eq!(v, v2);
+        assert_eq!(len, len2);
+    }
+
+    #[test]
+    fn special() {
+        assert_eq!(
+            DebugInfoOp::special(-4, 0),
+            Some(DebugInfoOp::Special(0x0a))
+        );
+        assert_eq!(
+            DebugInfoOp::special(1, 16),
+            Some(DebugInfoOp::Special(0xff))
+        );
+        assert_eq!(DebugInfoOp::special(11, 0), None);
+        assert_eq!(DebugInfoOp::special(2, 16), None);
+        assert_eq!(DebugInfoOp::special(0, uint::MAX), None);
+    }
+
+    #[test]
+    fn advance() {
+        assert_eq!(DebugInfoOp::Special(0x0a).advance(), (0, -4));
+        assert_eq!(DebugInfoOp::Special(0xff).advance(), (16, 1));
+        assert_eq!(DebugInfoOp::Special(0x00).advance(), (0, 0));
+        assert_eq!(DebugInfoOp::AdvanceLine(-3).advance(), (0, -3));
+    }
+
+    #[test]
+    fn state_machine() {
+        let v = DebugInfoItem {
+            line_start: 10,
+            parameter_names: vec![],
+            ops: vec![
+                DebugInfoOp::SetPrologueEnd,
+                DebugInfoOp::special(0, 0).unwrap(),
+                DebugInfoOp::StartLocal {
+                    register: 0,
+                    name: Some(5),
+                    ty: Some(1),
+                },
+                DebugInfoOp::special(2, 3).unwrap(),
+                DebugInfoOp::EndLocal(0),
+                DebugInfoOp::SetFile(Some(7)),
+                DebugInfoOp::AdvancePc(2),
+                DebugInfoOp::RestartLocal(0),
+                DebugInfoOp::special(-1, 1).unwrap(),
+            ],
+        };
+        let info = v.decode(10);
+        assert_eq!(
+            info.positions
+                .iter()
+                .map(|entry| (entry.address, entry.line, entry.source_file))
+                .collect::<Vec<_>>(),
+            vec![(0, 10, None), (3, 12, None), (6, 11, Some(7))]
+        );
+        assert!(info.positions[0].prologue_end);
+        assert!(!info.positions[1].prologue_end);
+        assert_eq!(
+            info.locals
+                .iter()
+                .map(|local| (
+                    local.register,
+                    local.name,
+                    local.start_address,
+                    local.end_address
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, Some(5), 0, 3), (0, Some(5), 5, 10)]
+        );
+        assert_eq!(info.line_at(4), Some(12));
+        assert_eq!(info.locals_at(4).count(), 0);
+        assert_eq!(DebugInfoOp::special(20, 0), None);
     }
 }
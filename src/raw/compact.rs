@@ -0,0 +1,444 @@
+//! Raw items of ART's compact dex format (`cdex`), as found inside vdex files.
+//!
+//! A compact dex file has the layout of a standard one, but its header is extended with
+//! [`CompactHeader`], all data offsets are relative to the `data` section (which may be shared
+//! by several files), and code items are stored in a smaller form, see [`read_code_item`].
+//! Debug info offsets are moved out of the code items into [`DebugInfoOffsets`].
+
+use crate::raw::{buffer::EncodedLen, code_item::CodeItem, *};
+use scroll::{
+    ctx::{TryFromCtx, TryIntoCtx},
+    Pread, Pwrite,
+};
+
+/// `cdex001\0`, the only version there is.
+pub(crate) const COMPACT_MAGIC: &[ubyte; 8] = b"cdex001\0";
+/// Size of the header of compact dex files, including [`CompactHeader`].
+pub(crate) const COMPACT_HEADER_SIZE: uint = 0x88;
+
+bitflags::bitflags! {
+  /// Features a compact dex file uses.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+  pub struct FeatureFlags: u32 {
+      const DefaultMethods = 0x1;
+  }
+}
+
+/// The fields compact dex files add to the end of the standard [`Header`][super::header::Header].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactHeader {
+    pub feature_flags: FeatureFlags,
+    /// Offset from the start of the `data` section to the [`DebugInfoOffsets`] table.
+    pub debug_info_offsets_pos: uint,
+    /// Offset from the start of the debug info offsets to their lookup table.
+    pub debug_info_offsets_table_offset: uint,
+    /// The lowest debug info offset, which all others are stored relative to.
+    pub debug_info_base: uint,
+    /// Range of the shared `data` section owned by this file.
+    pub owned_data_begin: uint,
+    pub owned_data_end: uint,
+}
+
+impl<'a> TryFromCtx<'a, scroll::Endian> for CompactHeader {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let feature_flags = FeatureFlags::from_bits_retain(src.gread_with(offset, ctx)?);
+        Ok((
+            Self {
+                feature_flags,
+                debug_info_offsets_pos: src.gread_with(offset, ctx)?,
+                debug_info_offsets_table_offset: src.gread_with(offset, ctx)?,
+                debug_info_base: src.gread_with(offset, ctx)?,
+                owned_data_begin: src.gread_with(offset, ctx)?,
+                owned_data_end: src.gread_with(offset, ctx)?,
+            },
+            *offset,
+        ))
+    }
+}
+
+impl TryIntoCtx<scroll::Endian> for CompactHeader {
+    type Error = scroll::Error;
+    fn try_into_ctx(self, dst: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        dst.gwrite_with(self.feature_flags.bits(), offset, ctx)?;
+        dst.gwrite_with(self.debug_info_offsets_pos, offset, ctx)?;
+        dst.gwrite_with(self.debug_info_offsets_table_offset, offset, ctx)?;
+        dst.gwrite_with(self.debug_info_base, offset, ctx)?;
+        dst.gwrite_with(self.owned_data_begin, offset, ctx)?;
+        dst.gwrite_with(self.owned_data_end, offset, ctx)?;
+        Ok(*offset)
+    }
+}
+
+impl EncodedLen for CompactHeader {
+    fn encoded_len(&self) -> usize {
+        6 * std::mem::size_of::<uint>()
+    }
+}
+
+// `fields` holds four sizes of 4 bits each, `insns_count_and_flags` the number of
+// instructions in its upper 11 bits, and whether a size is extended by a preheader in the rest
+const REGISTERS_SIZE_SHIFT: u32 = 12;
+const INS_SIZE_SHIFT: u32 = 8;
+const OUTS_SIZE_SHIFT: u32 = 4;
+const TRIES_SIZE_SHIFT: u32 = 0;
+const INSNS_SIZE_SHIFT: u32 = 5;
+const PREHEADER_REGISTERS_SIZE: ushort = 0x1;
+const PREHEADER_INS_SIZE: ushort = 0x2;
+const PREHEADER_OUTS_SIZE: ushort = 0x4;
+const PREHEADER_TRIES_SIZE: ushort = 0x8;
+const PREHEADER_INSNS_SIZE: ushort = 0x10;
+
+/// Reads the compact code item at `offset` in the `data` section `src`,
+/// and returns it in the standard form with the given `debug_info_off`.
+///
+/// The code item may be preceded by a preheader, which extends the sizes that don't fit
+/// into its 4 bytes. It's read backwards from `offset`.
+pub fn read_code_item(
+    src: &[u8],
+    offset: usize,
+    debug_info_off: uint,
+    ctx: scroll::Endian,
+) -> scroll::Result<CodeItem> {
+    let mut cursor = offset;
+    let fields: ushort = src.gread_with(&mut cursor, ctx)?;
+    let insns_count_and_flags: ushort = src.gread_with(&mut cursor, ctx)?;
+    let field = |shift: u32| (fields >> shift) & 0xf;
+    let mut registers_size = field(REGISTERS_SIZE_SHIFT);
+    let mut ins_size = field(INS_SIZE_SHIFT);
+    let mut outs_size = field(OUTS_SIZE_SHIFT);
+    let mut tries_size = field(TRIES_SIZE_SHIFT);
+    let mut insns_size = (insns_count_and_flags >> INSNS_SIZE_SHIFT) as uint;
+
+    let mut preheader = offset;
+    let mut previous = || -> scroll::Result<ushort> {
+        preheader = preheader
+            .checked_sub(std::mem::size_of::<ushort>())
+            .ok_or(scroll::Error::BadOffset(offset))?;
+        src.pread_with(preheader, ctx)
+    };
+    if insns_count_and_flags & PREHEADER_INSNS_SIZE != 0 {
+        insns_size += previous()? as uint;
+        insns_size += (previous()? as uint) << 16;
+    }
+    if insns_count_and_flags & PREHEADER_REGISTERS_SIZE != 0 {
+        registers_size += previous()?;
+    }
+    if insns_count_and_flags & PREHEADER_INS_SIZE != 0 {
+        ins_size += previous()?;
+    }
+    if insns_count_and_flags & PREHEADER_OUTS_SIZE != 0 {
+        outs_size += previous()?;
+    }
+    if insns_count_and_flags & PREHEADER_TRIES_SIZE != 0 {
+        tries_size += previous()?;
+    }
+    // the registers size doesn't include the incoming arguments
+    registers_size += ins_size;
+
+    let offset = &mut cursor;
+    let insns = try_gread_vec_with!(src, offset, insns_size, ctx);
+    let (tries, handlers) = if tries_size != 0 {
+        // the tries are four-byte aligned, even though the code item is only two-byte aligned
+        *offset = offset.next_multiple_of(4);
+        let tries = try_gread_vec_with!(src, offset, tries_size, ctx);
+        (tries, Some(src.gread(offset)?))
+    } else {
+        (Vec::new(), None)
+    };
+    Ok(CodeItem {
+        registers_size,
+        ins_size,
+        outs_size,
+        tries_size,
+        debug_info_off,
+        insns,
+        tries,
+        handlers,
+    })
+}
+
+/// The debug info offsets of all methods, which compact code items don't contain.
+///
+/// Offsets are stored in blocks of 16 methods: a big-endian bit mask of the methods
+/// that have debug info, followed by the `uleb128` difference of each offset to the previous one.
+/// A table of `uint`s follows, with the offset of each block.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugInfoOffsets<'a> {
+    /// Starts at the first block.
+    src: &'a [u8],
+    base: uint,
+    table_offset: uint,
+    // of the lookup table, the masks are always big-endian
+    endian: scroll::Endian,
+}
+
+const METHODS_PER_BLOCK: uint = 16;
+
+impl<'a> DebugInfoOffsets<'a> {
+    /// `src` is the `data` section of a file with the given header and byte order.
+    pub fn new(
+        src: &'a [u8],
+        header: &CompactHeader,
+        endian: scroll::Endian,
+    ) -> scroll::Result<Self> {
+        let pos = header.debug_info_offsets_pos as usize;
+        let src = src.get(pos..).ok_or(scroll::Error::BadOffset(pos))?;
+        Ok(Self {
+            src,
+            base: header.debug_info_base,
+            table_offset: header.debug_info_offsets_table_offset,
+            endian,
+        })
+    }
+
+    /// The debug info offset of the method with the given index, or 0 if it has none.
+    pub fn get(&self, method_idx: RawMethodIndex) -> scroll::Result<uint> {
+        let ctx = self.endian;
+        let entry = self.table_offset as usize
+            + (method_idx / METHODS_PER_BLOCK) as usize * std::mem::size_of::<uint>();
+        let offset = &mut (self.src.pread_with::<uint>(entry, ctx)? as usize);
+        let mask = self.src.gread_with::<ushort>(offset, scroll::BE)?;
+        let bit = method_idx % METHODS_PER_BLOCK;
+        if mask & (1 << bit) == 0 {
+            return Ok(0);
+        }
+        // one difference for each method up to and including this one
+        let count = (mask & (u16::MAX >> (15 - bit))).count_ones();
+        let mut debug_info_off = self.base;
+        for _ in 0..count {
+            debug_info_off = debug_info_off.wrapping_add(uleb128::read(self.src, offset)? as uint);
+        }
+        Ok(debug_info_off)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dex::DexFile,
+        error::Error,
+        raw::{
+            buffer::DexBuffer,
+            header::{fix_checksums, HeaderError, Version, ENDIAN_CONSTANT, HEADER_SIZE, SIG_LEN},
+            map_list::{ItemType, MapItem, MapList},
+            uleb128,
+        },
+        smali::disassemble,
+    };
+
+    /// Converts a standard dex file to a compact one. The data section is kept as is,
+    /// except for the code items, which are rewritten in place as they only get smaller.
+    fn compact(src: &[u8]) -> Vec<u8> {
+        let dex = DexFile::new(src).unwrap();
+        let mut data = src.to_vec();
+        let mut debug_info = vec![0; dex.header().method_ids_size as usize];
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let methods = [
+                class.direct_methods().unwrap(),
+                class.virtual_methods().unwrap(),
+            ];
+            for method in methods.iter().flatten() {
+                let Some(code) = method.code().unwrap() else {
+                    continue;
+                };
+                debug_info[method.index as usize] = code.debug_info_off;
+                let locals = code.registers_size - code.ins_size;
+                let sizes = [locals, code.ins_size, code.outs_size, code.tries_size];
+                assert!(sizes.iter().all(|size| *size < 16) && code.insns.len() < 2048);
+                let fields = locals << 12 | code.ins_size << 8 | code.outs_size << 4;
+                let fields = fields | code.tries_size;
+                // the instructions, tries and handlers keep their alignment, 12 bytes earlier
+                let mut buf = DexBuffer::default();
+                buf.write(code.clone()).unwrap();
+                let mut item = fields.to_le_bytes().to_vec();
+                item.extend(((code.insns.len() as u16) << 5).to_le_bytes());
+                item.extend(&buf.as_slice()[16..]);
+                let offset = method.code_off as usize;
+                data[offset..offset + item.len()].copy_from_slice(&item);
+            }
+        }
+
+        // the debug info offsets, encoded like ART does
+        data.resize(data.len().next_multiple_of(4), 0);
+        let pos = data.len();
+        let base = debug_info.iter().copied().filter(|off| *off != 0).min();
+        let base = base.unwrap_or_default();
+        let mut offsets = Vec::new();
+        let mut table = Vec::new();
+        for block in debug_info.chunks(16) {
+            table.push(offsets.len() as u32);
+            let mask = (0..block.len())
+                .filter(|i| block[*i] != 0)
+                .fold(0u16, |mask, i| mask | 1 << i);
+            offsets.extend(mask.to_be_bytes());
+            let mut previous = base;
+            for off in block.iter().copied().filter(|off| *off != 0) {
+                let mut leb = [0; 5];
+                let len = &mut 0;
+                uleb128::write(&mut leb, len, off.wrapping_sub(previous) as u64).unwrap();
+                offsets.extend(&leb[..*len]);
+                previous = off;
+            }
+        }
+        offsets.resize(offsets.len().next_multiple_of(4), 0);
+        let table_offset = offsets.len() as u32;
+        offsets.extend(table.iter().flat_map(|entry| entry.to_le_bytes()));
+        data.extend(offsets);
+
+        // the id sections move by the size of the extra header fields
+        let header = dex.header();
+        let shift = COMPACT_HEADER_SIZE - HEADER_SIZE;
+        let map = dex.map_list();
+        let items = map.item_types().map(|item_type| {
+            let offset = map.get_offset(item_type).unwrap();
+            let offset = match item_type {
+                ItemType::StringIdItem
+                | ItemType::TypeIdItem
+                | ItemType::ProtoIdItem
+                | ItemType::FieldIdItem
+                | ItemType::MethodIdItem
+                | ItemType::ClassDefItem
+                | ItemType::CallSiteIdItem
+                | ItemType::MethodHandleItem => offset + shift,
+                _ => offset,
+            };
+            MapItem::new(item_type, map.get_len(item_type).unwrap(), offset)
+        });
+        let map = MapList::from(items.collect::<Vec<_>>());
+        data.pwrite_with(map, header.map_off as usize, scroll::LE)
+            .unwrap();
+        let ids = &src[HEADER_SIZE as usize..header.data_off as usize];
+        let mut cdex = vec![0; COMPACT_HEADER_SIZE as usize];
+        cdex.extend(ids);
+        let data_off = cdex.len() as u32;
+        let mut compact = header.clone();
+        compact.version = Version::COMPACT;
+        compact.signature = &[0; SIG_LEN];
+        compact.file_size = data_off;
+        compact.header_size = COMPACT_HEADER_SIZE;
+        compact.endian_tag = ENDIAN_CONSTANT;
+        for off in [
+            &mut compact.string_ids_off,
+            &mut compact.type_ids_off,
+            &mut compact.proto_ids_off,
+            &mut compact.field_ids_off,
+            &mut compact.method_ids_off,
+            &mut compact.class_defs_off,
+        ] {
+            *off += shift;
+        }
+        compact.data_off = data_off;
+        compact.data_size = data.len() as u32;
+        compact.compact = Some(CompactHeader {
+            debug_info_offsets_pos: pos as u32,
+            debug_info_offsets_table_offset: table_offset,
+            debug_info_base: base,
+            owned_data_begin: 0,
+            owned_data_end: data.len() as u32,
+            ..Default::default()
+        });
+        cdex.pwrite_with(compact, 0, scroll::LE).unwrap();
+        cdex.extend(data);
+        fix_checksums(&mut cdex).unwrap();
+        cdex
+    }
+
+    #[test]
+    fn compact_dex() {
+        let dex = crate::t::dex!();
+        let src = compact(crate::t::DEX);
+        let cdex = DexFile::new(&src).unwrap();
+        assert!(cdex.is_compact());
+        assert_eq!(cdex.header().version, Version::COMPACT);
+        let standard = cdex.to_standard().unwrap();
+        let standard = DexFile::new(&standard).unwrap();
+        assert!(!standard.is_compact());
+        assert_eq!(standard.header().version, dex.min_version().unwrap());
+        standard.verify_signature().unwrap();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let expected = disassemble(&class).unwrap();
+            let descriptor = class.descriptor().unwrap();
+            for file in [&cdex, &standard] {
+                let copy = file.class_by_descriptor(&descriptor).unwrap().unwrap();
+                assert_eq!(disassemble(&copy).unwrap(), expected);
+                assert_eq!(
+                    copy.hiddenapi_flags().unwrap(),
+                    class.hiddenapi_flags().unwrap()
+                );
+            }
+        }
+
+        // default methods need 037, even though the compact version is higher
+        let source = r#"
+.class public interface abstract LI;
+.super Ljava/lang/Object;
+
+.method public g()V
+    .registers 1
+    return-void
+.end method
+"#;
+        let cdex = compact(&crate::t::write(&[source]));
+        let standard = DexFile::new(&cdex).unwrap().to_standard().unwrap();
+        let standard = DexFile::new(&standard).unwrap();
+        assert_eq!(standard.header().version, Version::V037);
+
+        // the checksum covers the data section
+        let mut src = src;
+        let last = src.len() - 1;
+        src[last] ^= 1;
+        assert!(matches!(
+            DexFile::new(&src),
+            Err(Error::Header(HeaderError::InvalidChecksum))
+        ));
+    }
+
+    #[test]
+    fn code_item() {
+        // 17 registers (16 locals and 1 argument), 1 in, 2 out, no tries, 2 instructions,
+        // preceded by a preheader extending the number of locals
+        let src = [
+            0x0f, 0x00, // 15 more locals
+            0x20, 0x11, // 1 local, 1 in, 2 out, no tries
+            0x41, 0x00, // 2 instructions, locals extended by the preheader
+            0x0e, 0x00, 0x0e, 0x00, // return-void, twice
+        ];
+        let code = read_code_item(&src, 2, 0x42, scroll::LE).unwrap();
+        assert_eq!(code.registers_size, 17);
+        assert_eq!(code.ins_size, 1);
+        assert_eq!(code.outs_size, 2);
+        assert_eq!(code.insns, [0x0e, 0x0e]);
+        assert_eq!(code.debug_info_off, 0x42);
+        assert!(read_code_item(&src, 0, 0, scroll::LE).is_err());
+    }
+
+    #[test]
+    fn debug_info_offsets() {
+        let header = CompactHeader {
+            debug_info_offsets_pos: 2,
+            debug_info_offsets_table_offset: 6,
+            debug_info_base: 0x100,
+            ..Default::default()
+        };
+        let src = [
+            0xff, 0xff, // unrelated data
+            0x80, 0x05, // methods 0, 2 and 15 have debug info
+            0x00, 0x10, 0x7f, // at 0x100, 0x110 and 0x18f
+            0x00, // padding
+            0x00, 0x00, 0x00, 0x00, // the only block
+        ];
+        let offsets = DebugInfoOffsets::new(&src, &header, scroll::LE).unwrap();
+        // the table is relative to the first block
+        let expected = [(0, 0x100), (1, 0), (2, 0x110), (3, 0), (15, 0x18f)];
+        for (method_idx, offset) in expected {
+            assert_eq!(offsets.get(method_idx).unwrap(), offset);
+        }
+        assert!(offsets.get(16).is_err());
+    }
+}
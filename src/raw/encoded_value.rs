@@ -1,4 +1,4 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{
@@ -56,6 +56,39 @@ pub enum ValueType {
     Boolean = 0x1f,
 }
 
+impl EncodedValue {
+    /// The value a field of the type `descriptor` holds when it isn't initialized.
+    pub fn default_for(descriptor: &str) -> Self {
+        match descriptor {
+            "Z" => EncodedValue::Boolean(false),
+            "B" => EncodedValue::Byte(0),
+            "S" => EncodedValue::Short(0),
+            "C" => EncodedValue::Char(0),
+            "I" => EncodedValue::Int(0),
+            "J" => EncodedValue::Long(0),
+            "F" => EncodedValue::Float(0.0),
+            "D" => EncodedValue::Double(0.0),
+            _ => EncodedValue::Null,
+        }
+    }
+
+    /// Whether this is the value a field holds when it isn't initialized, i.e. 0, `false` or `null`.
+    pub fn is_default(&self) -> bool {
+        match self {
+            EncodedValue::Byte(0)
+            | EncodedValue::Short(0)
+            | EncodedValue::Char(0)
+            | EncodedValue::Int(0)
+            | EncodedValue::Long(0)
+            | EncodedValue::Null
+            | EncodedValue::Boolean(false) => true,
+            EncodedValue::Float(v) => v.to_bits() == 0,
+            EncodedValue::Double(v) => v.to_bits() == 0,
+            _ => false,
+        }
+    }
+}
+
 impl ValueType {
     fn from_value(value: &EncodedValue) -> Self {
         // This is painful, but it'll do for now.
@@ -140,7 +173,7 @@ impl<'a> TryFromCtx<'a> for EncodedValue {
         let value_arg = (header >> 5) as usize;
         let value_type = 0x1f & header;
         let value_type = ValueType::from_u8(value_type)
-            .ok_or_else(|| EncodedValueError::InvalidValueType(value_type))?;
+            .ok_or(EncodedValueError::InvalidValueType(value_type))?;
         let value = match value_type {
             ValueType::Byte => {
                 debug_assert_eq!(value_arg, 0);
@@ -364,8 +397,45 @@ impl TryIntoCtx for EncodedValue {
     }
 }
 
+/// The number of bytes `w_enc_int` and `w_enc_long` write for `v`.
+fn enc_int_len(v: long) -> usize {
+    // the magnitude, plus the sign bit
+    let magnitude = if v < 0 { !v } else { v };
+    ((long::BITS - magnitude.leading_zeros() + 1) as usize).div_ceil(8)
+}
+
+/// The number of bytes `w_enc_uint` writes for `v`.
+fn enc_uint_len(v: uint) -> usize {
+    ((uint::BITS - v.leading_zeros()).max(1) as usize).div_ceil(8)
+}
+
+impl EncodedLen for EncodedValue {
+    fn encoded_len(&self) -> usize {
+        // the value type and argument, then the value itself
+        1 + match self {
+            EncodedValue::Byte(v) => enc_int_len(*v as long),
+            EncodedValue::Short(v) => enc_int_len(*v as long),
+            EncodedValue::Char(v) => enc_uint_len(*v as uint),
+            EncodedValue::Int(v) => enc_int_len(*v as long),
+            EncodedValue::Long(v) => enc_int_len(*v),
+            EncodedValue::Float(_) => std::mem::size_of::<f32>(),
+            EncodedValue::Double(_) => std::mem::size_of::<f64>(),
+            EncodedValue::MethodType(v)
+            | EncodedValue::MethodHandle(v)
+            | EncodedValue::String(v)
+            | EncodedValue::Type(v)
+            | EncodedValue::Field(v)
+            | EncodedValue::Method(v)
+            | EncodedValue::Enum(v) => enc_uint_len(*v),
+            EncodedValue::Array(values) => encoded_array_len(values),
+            EncodedValue::Annotation(annotation) => annotation.encoded_len(),
+            EncodedValue::Null | EncodedValue::Boolean(_) => 0,
+        }
+    }
+}
+
 /// An array of [`EncodedValue`]s.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct EncodedArray(Vec<EncodedValue>);
 
 impl EncodedArray {
@@ -374,6 +444,12 @@ impl EncodedArray {
     }
 }
 
+impl From<Vec<EncodedValue>> for EncodedArray {
+    fn from(values: Vec<EncodedValue>) -> Self {
+        Self(values)
+    }
+}
+
 impl<'a> TryFromCtx<'a> for EncodedArray {
     type Error = EncodedValueError;
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
@@ -394,8 +470,19 @@ impl TryIntoCtx for EncodedArray {
     }
 }
 
+fn encoded_array_len(values: &[EncodedValue]) -> usize {
+    uleb128::len_of(values.len() as ulong)
+        + values.iter().map(EncodedValue::encoded_len).sum::<usize>()
+}
+
+impl EncodedLen for EncodedArray {
+    fn encoded_len(&self) -> usize {
+        encoded_array_len(&self.0)
+    }
+}
+
 /// An [`EncodedArray`] written as a single item.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct EncodedArrayItem(EncodedArray);
 
 impl EncodedArrayItem {
@@ -404,6 +491,12 @@ impl EncodedArrayItem {
     }
 }
 
+impl From<Vec<EncodedValue>> for EncodedArrayItem {
+    fn from(values: Vec<EncodedValue>) -> Self {
+        Self(EncodedArray(values))
+    }
+}
+
 impl<'a> TryFromCtx<'a> for EncodedArrayItem {
     type Error = EncodedValueError;
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
@@ -422,6 +515,12 @@ impl TryIntoCtx for EncodedArrayItem {
     }
 }
 
+impl EncodedLen for EncodedArrayItem {
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
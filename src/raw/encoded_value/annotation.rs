@@ -1,4 +1,4 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
@@ -42,6 +42,18 @@ impl TryIntoCtx for EncodedAnnotation {
     }
 }
 
+impl EncodedLen for EncodedAnnotation {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.type_idx)
+            + uleb128::len_of(self.size)
+            + self
+                .elements
+                .iter()
+                .map(AnnotationElement::encoded_len)
+                .sum::<usize>()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct AnnotationElement {
     pub name_idx: ulong,
@@ -67,3 +79,9 @@ impl TryIntoCtx for AnnotationElement {
         Ok(*offset)
     }
 }
+
+impl EncodedLen for AnnotationElement {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.name_idx) + self.value.encoded_len()
+    }
+}
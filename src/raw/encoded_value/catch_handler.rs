@@ -1,16 +1,50 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
 };
 
 /// An array of [`EncodedCatchHandler`]s.
-#[derive(Debug, Default)]
-pub struct EncodedCatchHandlerList(Vec<EncodedCatchHandler>);
+#[derive(Debug, Default, Clone)]
+pub struct EncodedCatchHandlerList {
+    handlers: Vec<EncodedCatchHandler>,
+    // offset in bytes of each handler from the start of the list
+    offsets: Vec<usize>,
+}
 
 impl EncodedCatchHandlerList {
     pub(crate) fn into_inner(self) -> Vec<EncodedCatchHandler> {
-        self.0
+        self.handlers
+    }
+
+    pub fn handlers(&self) -> &[EncodedCatchHandler] {
+        &self.handlers
+    }
+
+    /// The offset in bytes of the handler at `idx` from the start of this list,
+    /// as stored in [`TryItem::handler_off`][crate::raw::simple::TryItem].
+    pub fn handler_off(&self, idx: usize) -> Option<ushort> {
+        self.offsets.get(idx).map(|offset| *offset as ushort)
+    }
+
+    /// Returns the handler at `handler_off` bytes from the start of this list,
+    /// as referenced by [`TryItem::handler_off`][crate::raw::simple::TryItem].
+    pub fn handler_at(&self, handler_off: ushort) -> Option<&EncodedCatchHandler> {
+        let idx = self.offsets.binary_search(&(handler_off as usize)).ok()?;
+        self.handlers.get(idx)
+    }
+}
+
+impl From<Vec<EncodedCatchHandler>> for EncodedCatchHandlerList {
+    fn from(handlers: Vec<EncodedCatchHandler>) -> Self {
+        // each handler is preceded by the size of the list and all handlers before it
+        let mut offset = uleb128::len_of(handlers.len() as u64);
+        let mut offsets = Vec::with_capacity(handlers.len());
+        for handler in &handlers {
+            offsets.push(offset);
+            offset += handler.encoded_len();
+        }
+        Self { handlers, offsets }
     }
 }
 
@@ -19,8 +53,13 @@ impl<'a> TryFromCtx<'a> for EncodedCatchHandlerList {
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
         let size = uleb128::read(src, offset)?;
-        let handlers = try_gread_vec_with!(src, offset, size, ());
-        Ok((Self(handlers), *offset))
+        let mut handlers = Vec::with_capacity(size as usize);
+        let mut offsets = Vec::with_capacity(size as usize);
+        for _ in 0..size {
+            offsets.push(*offset);
+            handlers.push(src.gread(offset)?);
+        }
+        Ok((Self { handlers, offsets }, *offset))
     }
 }
 
@@ -28,12 +67,23 @@ impl TryIntoCtx for EncodedCatchHandlerList {
     type Error = scroll::Error;
     fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, Self::Error> {
         let offset = &mut 0;
-        uleb128::write(dst, offset, self.0.len() as u64)?;
-        try_gwrite_vec_with!(dst, offset, self.0, ());
+        uleb128::write(dst, offset, self.handlers.len() as u64)?;
+        try_gwrite_vec_with!(dst, offset, self.handlers, ());
         Ok(*offset)
     }
 }
 
+impl EncodedLen for EncodedCatchHandlerList {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.handlers.len() as u64)
+            + self
+                .handlers
+                .iter()
+                .map(EncodedCatchHandler::encoded_len)
+                .sum::<usize>()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EncodedCatchHandler {
     pub size: i64,
@@ -46,8 +96,9 @@ impl<'a> TryFromCtx<'a> for EncodedCatchHandler {
     fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
         let size = sleb128::read(src, offset)?;
-        let handlers = try_gread_vec_with!(src, offset, size.abs(), ());
-        let catch_all_addr = if size < 0 {
+        let handlers = try_gread_vec_with!(src, offset, size.unsigned_abs(), ());
+        // a non-positive size means a catch-all handler follows the typed ones
+        let catch_all_addr = if size <= 0 {
             Some(uleb128::read(src, offset)?)
         } else {
             None
@@ -76,6 +127,18 @@ impl TryIntoCtx for EncodedCatchHandler {
     }
 }
 
+impl EncodedLen for EncodedCatchHandler {
+    fn encoded_len(&self) -> usize {
+        sleb128::len_of(self.size)
+            + self
+                .handlers
+                .iter()
+                .map(EncodedTypeAddrPair::encoded_len)
+                .sum::<usize>()
+            + self.catch_all_addr.map_or(0, uleb128::len_of)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct EncodedTypeAddrPair {
     pub type_id: ulong,
@@ -101,3 +164,9 @@ impl TryIntoCtx for EncodedTypeAddrPair {
         Ok(*offset)
     }
 }
+
+impl EncodedLen for EncodedTypeAddrPair {
+    fn encoded_len(&self) -> usize {
+        uleb128::len_of(self.type_id) + uleb128::len_of(self.addr)
+    }
+}
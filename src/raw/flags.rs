@@ -1,4 +1,4 @@
-use crate::raw::uleb128;
+use crate::raw::{buffer::EncodedLen, uleb128};
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
@@ -46,6 +46,11 @@ impl AccessFlags {
         uleb128::write(dst, offset, self.bits() as u64)?;
         Ok(())
     }
+
+    /// The length of these flags when written with [`AccessFlags::try_into_uleb128`].
+    pub fn uleb128_len(&self) -> usize {
+        uleb128::len_of(self.bits() as u64)
+    }
 }
 
 impl<'a> TryFromCtx<'a, scroll::Endian> for AccessFlags {
@@ -57,7 +62,7 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for AccessFlags {
     }
 }
 
-impl<'a> TryIntoCtx<scroll::Endian> for &'a AccessFlags {
+impl TryIntoCtx<scroll::Endian> for &AccessFlags {
     type Error = scroll::Error;
     fn try_into_ctx(self, dst: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
         let offset = &mut 0;
@@ -72,3 +77,9 @@ impl TryIntoCtx<scroll::Endian> for AccessFlags {
         (&self).try_into_ctx(dst, ctx)
     }
 }
+
+impl EncodedLen for AccessFlags {
+    fn encoded_len(&self) -> usize {
+        std::mem::size_of::<u32>()
+    }
+}
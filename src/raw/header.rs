@@ -1,4 +1,8 @@
-use crate::raw::*;
+use crate::raw::{
+    buffer::EncodedLen,
+    compact::{CompactHeader, COMPACT_HEADER_SIZE, COMPACT_MAGIC},
+    *,
+};
 use adler32::adler32;
 use derivative::Derivative;
 use scroll::{
@@ -6,18 +10,45 @@ use scroll::{
     Pread, Pwrite,
 };
 
-const MAGIC_LEN: usize = 8;
-const SIG_LEN: usize = 20;
-const ENDIAN_CONSTANT: uint = 0x12345678;
+pub(crate) const MAGIC_LEN: usize = 8;
+pub(crate) const SIG_LEN: usize = 20;
+pub(crate) const ENDIAN_CONSTANT: uint = 0x12345678;
+/// [`ENDIAN_CONSTANT`] as read from a big-endian file.
+pub(crate) const REVERSE_ENDIAN_CONSTANT: uint = 0x78563412;
+/// Size of the header, which is also the offset of the first `string_id_item`.
+pub(crate) const HEADER_SIZE: uint = 0x70;
+/// Size of the header of files in a container, see [`Version::V041`].
+pub(crate) const CONTAINER_HEADER_SIZE: uint = 0x78;
+/// The checksum covers everything after itself.
+const CHECKSUM_OFF: usize = MAGIC_LEN;
+/// The signature covers everything after itself.
+const SIGNATURE_OFF: usize = CHECKSUM_OFF + 4;
+pub(crate) const FILE_SIZE_OFF: usize = SIGNATURE_OFF + SIG_LEN;
+const ENDIAN_TAG_OFF: usize = FILE_SIZE_OFF + 8;
+const DATA_SIZE_OFF: usize = HEADER_SIZE as usize - 8;
+const DATA_OFF_OFF: usize = HEADER_SIZE as usize - 4;
+/// Offset of `container_size`, the first field after the classic header.
+pub(crate) const CONTAINER_SIZE_OFF: usize = HEADER_SIZE as usize;
 
 #[derive(Debug, thiserror::Error)]
 pub enum HeaderError {
+    /// The magic is malformed, see [`HeaderError::Version`] for valid but unsupported versions.
     #[error("invalid magic: {0}")]
-    InvalidMagic(#[from] VersionError),
-    #[error("invalid endian tag {0:#x}, only LE is supported for now")]
+    InvalidMagic(VersionError),
+    #[error("{0}")]
+    Version(VersionError),
+    #[error("invalid endian tag {0:#x}")]
     InvalidEndianTag(u32),
     #[error("invalid checksum")]
     InvalidChecksum,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("invalid file size {0}, the header alone is larger")]
+    InvalidFileSize(uint),
+    #[error("header is at offset {0}, but claims to be at {1}")]
+    HeaderOffsetMismatch(uint, uint),
+    #[error("container is {0} bytes, but claims to be {1} bytes")]
+    ContainerSizeMismatch(usize, uint),
     #[error("read error: {0}")]
     Scroll(#[from] scroll::Error),
     #[error("io error: {0}")]
@@ -37,11 +68,12 @@ pub struct Header<'a> {
     #[derivative(Debug = "ignore")]
     pub signature: &'a [ubyte],
     /// Size of the entire file (including the header), in bytes.
+    /// In a container, only the size of this file, which is followed by the next one.
     pub file_size: uint,
     /// Size of the header (this entire section), in bytes.
     pub header_size: uint,
-    /// Specifies the endianness of the dex file.
-    /// Currently, only little-endian is supported.
+    /// Specifies the endianness of the dex file, see [`Header::endian`].
+    /// It's always read and written as little-endian, so it's `0x78563412` in big-endian files.
     /// Click [here][endian-constant] for more information.
     ///
     /// [endian-constant]: https://source.android.com/docs/core/runtime/dex-format#endian-constant
@@ -107,16 +139,44 @@ pub struct Header<'a> {
     /// or 0 if `class_defs_size == 0` (admittedly a strange edge case).
     /// The offset, if non-zero, should be to the start of the `class_defs` section.
     ///
-    /// TODO: Implement this.
+    /// Implemented as [`ClassDef`][super::classdef::ClassDef].
     pub class_defs_off: uint,
     /// Size of `data` section in bytes. Must be an even multiple of [`sizeof(uint)`][std::mem::size_of].
     pub data_size: uint,
     /// Offset from the start of the file to the start of the `data` section.
     pub data_off: uint,
+    /// Size of the entire container, including other dex files, in bytes.
+    /// Only present since [`Version::V041`], where all offsets are relative to the container.
+    pub container_size: uint,
+    /// Offset from the start of the container to this header.
+    /// Only present since [`Version::V041`].
+    pub header_offset: uint,
+    /// The extra fields of a compact dex file, which has the magic `cdex001\0` instead of
+    /// a version. Such files are given [`Version::COMPACT`].
+    pub compact: Option<CompactHeader>,
 }
 
 impl<'a> Header<'a> {
+    /// The byte order of every fixed-size field in the file.
+    /// `uleb128` values and [`EncodedValue`](super::encoded_value::EncodedValue)s are
+    /// sequences of bytes, and stored the same way in either byte order.
+    pub fn endian(&self) -> scroll::Endian {
+        match self.endian_tag {
+            REVERSE_ENDIAN_CONSTANT => scroll::BE,
+            _ => scroll::LE,
+        }
+    }
+
+    /// The range of offsets data items can be at.
+    /// In a container, files can share data, so it is the entire container.
     pub fn data_section(&self) -> std::ops::Range<uint> {
+        // data offsets of compact files are relative to the data section
+        if self.compact.is_some() {
+            return 0..self.data_size;
+        }
+        if self.version >= Version::V041 {
+            return 0..self.container_size;
+        }
         self.data_off..self.data_off + self.data_size
     }
 
@@ -127,9 +187,11 @@ impl<'a> Header<'a> {
 
 impl<'a> Clone for Header<'a> {
     /// The [`Clone`] implementation for [`Header`] is a shallow clone,
-    /// and only clones offsets and sizes.
+    /// and only clones the version, offsets and sizes.
     fn clone(&self) -> Self {
         Self {
+            version: self.version,
+            endian_tag: self.endian_tag,
             link_size: self.link_size,
             link_off: self.link_off,
             map_off: self.map_off,
@@ -147,19 +209,30 @@ impl<'a> Clone for Header<'a> {
             class_defs_off: self.class_defs_off,
             data_size: self.data_size,
             data_off: self.data_off,
+            container_size: self.container_size,
+            header_offset: self.header_offset,
+            compact: self.compact,
             ..Default::default()
         }
     }
 }
 
-impl<'a> TryFromCtx<'a, scroll::Endian> for Header<'a> {
+impl<'a> TryFromCtx<'a> for Header<'a> {
     type Error = HeaderError;
-    fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
+    /// The byte order of the file is detected from its `endian_tag`.
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
+        let ctx = endian_of(src)?;
 
-        let version = src.gread_with::<&[ubyte]>(offset, MAGIC_LEN)?.try_into()?;
+        let magic = src.gread_with::<&[ubyte]>(offset, MAGIC_LEN)?;
+        let is_compact = magic == COMPACT_MAGIC;
+        let version = match is_compact {
+            true => Version::COMPACT,
+            false => magic.try_into()?,
+        };
         let checksum = src.gread_with(offset, ctx)?;
-        if checksum != adler32(&src[*offset..])? {
+        // the checksum of compact files also covers their data section, which follows the header
+        if !is_compact && checksum != adler32(&src[*offset..file_len(src)?])? {
             return Err(HeaderError::InvalidChecksum);
         }
 
@@ -167,11 +240,7 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for Header<'a> {
         let file_size = src.gread_with(offset, ctx)?;
         let header_size = src.gread_with(offset, ctx)?;
 
-        let endian_tag = src.gread_with(offset, ctx)?;
-        match endian_tag {
-            ENDIAN_CONSTANT => {}                                 // LE, ok
-            _ => Err(HeaderError::InvalidEndianTag(endian_tag))?, // BE or unknown, not ok
-        }
+        let endian_tag = src.gread_with(offset, scroll::LE)?;
 
         let link_size = src.gread_with(offset, ctx)?;
         let link_off = src.gread_with(offset, ctx)?;
@@ -190,6 +259,18 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for Header<'a> {
         let class_defs_off = src.gread_with(offset, ctx)?;
         let data_size = src.gread_with(offset, ctx)?;
         let data_off = src.gread_with(offset, ctx)?;
+        let (container_size, header_offset) = if version >= Version::V041 {
+            (src.gread_with(offset, ctx)?, src.gread_with(offset, ctx)?)
+        } else {
+            (0, 0)
+        };
+        let compact = match is_compact {
+            true => Some(src.gread_with(offset, ctx)?),
+            false => None,
+        };
+        if is_compact && checksum != compact_checksum(src)? {
+            return Err(HeaderError::InvalidChecksum);
+        }
 
         Ok((
             Header {
@@ -216,6 +297,9 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for Header<'a> {
                 class_defs_off,
                 data_size,
                 data_off,
+                container_size,
+                header_offset,
+                compact,
             },
             *offset,
         ))
@@ -226,12 +310,15 @@ impl<'a> TryIntoCtx<scroll::Endian> for Header<'a> {
     type Error = HeaderError;
     fn try_into_ctx(self, dst: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
         let offset = &mut 0;
-        dst.gwrite_with(self.version, offset, ctx)?;
+        match self.compact {
+            Some(_) => dst.gwrite_with(COMPACT_MAGIC.as_slice(), offset, ())?,
+            None => dst.gwrite_with(self.version, offset, ctx)?,
+        };
         dst.gwrite_with(self.checksum, offset, ctx)?;
         dst.gwrite_with(self.signature, offset, ())?;
         dst.gwrite_with(self.file_size, offset, ctx)?;
         dst.gwrite_with(self.header_size, offset, ctx)?;
-        dst.gwrite_with(self.endian_tag, offset, ctx)?;
+        dst.gwrite_with(self.endian_tag, offset, scroll::LE)?;
         dst.gwrite_with(self.link_size, offset, ctx)?;
         dst.gwrite_with(self.link_off, offset, ctx)?;
         dst.gwrite_with(self.map_off, offset, ctx)?;
@@ -249,13 +336,193 @@ impl<'a> TryIntoCtx<scroll::Endian> for Header<'a> {
         dst.gwrite_with(self.class_defs_off, offset, ctx)?;
         dst.gwrite_with(self.data_size, offset, ctx)?;
         dst.gwrite_with(self.data_off, offset, ctx)?;
+        if self.version >= Version::V041 {
+            dst.gwrite_with(self.container_size, offset, ctx)?;
+            dst.gwrite_with(self.header_offset, offset, ctx)?;
+        }
+        if let Some(compact) = self.compact {
+            dst.gwrite_with(compact, offset, ctx)?;
+        }
         Ok(*offset)
     }
 }
 
-#[derive(Default)]
+impl<'a> EncodedLen for Header<'a> {
+    fn encoded_len(&self) -> usize {
+        // every field after the signature is a `uint`
+        let fields = if self.version >= Version::V041 {
+            22
+        } else {
+            20
+        };
+        self.version.encoded_len()
+            + std::mem::size_of::<uint>()
+            + self.signature.len()
+            + fields * std::mem::size_of::<uint>()
+            + self.compact.as_ref().map_or(0, EncodedLen::encoded_len)
+    }
+}
+
+/// The byte order of the dex file whose header is at the start of `src`.
+pub(crate) fn endian_of(src: &[u8]) -> Result<scroll::Endian, HeaderError> {
+    match src.pread_with(ENDIAN_TAG_OFF, scroll::LE)? {
+        ENDIAN_CONSTANT => Ok(scroll::LE),
+        REVERSE_ENDIAN_CONSTANT => Ok(scroll::BE),
+        tag => Err(HeaderError::InvalidEndianTag(tag)),
+    }
+}
+
+/// The `endian_tag` of files with the given byte order, as read by [`endian_of`].
+pub(crate) fn endian_tag(endian: scroll::Endian) -> uint {
+    match endian {
+        scroll::Endian::Little => ENDIAN_CONSTANT,
+        scroll::Endian::Big => REVERSE_ENDIAN_CONSTANT,
+    }
+}
+
+/// The length of the dex file whose header is at the start of `src`.
+/// That's all of `src`, unless the file is part of a container and followed by other files.
+/// Files with an invalid version are never considered part of a container.
+pub(crate) fn file_len(src: &[u8]) -> Result<usize, HeaderError> {
+    let magic = src.pread_with::<&[ubyte]>(0, MAGIC_LEN)?;
+    if !Version::try_from(magic).is_ok_and(|version| version >= Version::V041) {
+        return Ok(src.len());
+    }
+    let file_size: uint = src.pread_with(FILE_SIZE_OFF, endian_of(src)?)?;
+    if file_size < CONTAINER_HEADER_SIZE {
+        return Err(HeaderError::InvalidFileSize(file_size));
+    }
+    if file_size as usize > src.len() {
+        return Err(scroll::Error::TooBig {
+            size: file_size as usize,
+            len: src.len(),
+        }
+        .into());
+    }
+    Ok(file_size as usize)
+}
+
+/// The SHA-1 `signature` of the dex file whose header is at the start of `src`,
+/// as stored in its header.
+pub fn signature(src: &[u8]) -> Result<[ubyte; SIG_LEN], HeaderError> {
+    let len = file_len(src)?;
+    let data = src
+        .get(SIGNATURE_OFF + SIG_LEN..len)
+        .ok_or(scroll::Error::TooBig {
+            size: SIGNATURE_OFF + SIG_LEN,
+            len,
+        })?;
+    Ok(sha1_smol::Sha1::from(data).digest().bytes())
+}
+
+/// Recomputes the `signature` and `checksum` of the dex file `src` in place,
+/// or of every file if `src` is a container.
+///
+/// This is meant for patched files, so the current values are ignored.
+/// It also repairs files that fail to parse with [`HeaderError::InvalidChecksum`].
+pub fn fix_checksums(src: &mut [u8]) -> Result<(), HeaderError> {
+    let mut offset = 0;
+    // the files of a container follow each other
+    while offset == 0 || offset < src.len() {
+        let file = &mut src[offset..];
+        let len = file_len(file)?;
+        let signature = signature(file)?;
+        file[SIGNATURE_OFF..SIGNATURE_OFF + SIG_LEN].copy_from_slice(&signature);
+        let checksum = match file.get(..MAGIC_LEN) == Some(COMPACT_MAGIC) {
+            true => compact_checksum(file)?,
+            false => adler32(&file[SIGNATURE_OFF..len])?,
+        };
+        file.pwrite_with(checksum, CHECKSUM_OFF, endian_of(file)?)?;
+        offset += len;
+    }
+    Ok(())
+}
+
+/// The `checksum` of the compact dex file whose header is at the start of `src`.
+/// Unlike that of standard files, it also covers the `data` section, wherever it is.
+fn compact_checksum(src: &[u8]) -> Result<uint, HeaderError> {
+    let ctx = endian_of(src)?;
+    let header_size = COMPACT_HEADER_SIZE as usize;
+    let file_size: uint = src.pread_with(FILE_SIZE_OFF, ctx)?;
+    let data_size: uint = src.pread_with(DATA_SIZE_OFF, ctx)?;
+    let data_off: uint = src.pread_with(DATA_OFF_OFF, ctx)?;
+    if file_size < COMPACT_HEADER_SIZE {
+        return Err(HeaderError::InvalidFileSize(file_size));
+    }
+    let range = |start: usize, len: usize| {
+        src.get(start..start + len).ok_or(scroll::Error::TooBig {
+            size: start + len,
+            len: src.len(),
+        })
+    };
+    // the checksum itself and the location of the data section are excluded
+    let mut header = range(0, header_size)?.to_vec();
+    for field in [CHECKSUM_OFF, DATA_SIZE_OFF, DATA_OFF_OFF] {
+        header.pwrite_with::<uint>(0, field, ctx)?;
+    }
+    let mut checksum = adler32(header.as_slice())?;
+    let rest = range(header_size, file_size as usize - header_size)?;
+    checksum = checksum.wrapping_mul(31) ^ adler32(rest)?;
+    let data = range(data_off as usize, data_size as usize)?;
+    checksum = checksum.wrapping_mul(31) ^ adler32(data)?;
+    Ok(checksum)
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version(uint, uint, uint);
 
+impl Version {
+    pub const V035: Self = Self(0, 3, 5);
+    /// Android 7.0, adds default and static interface methods.
+    pub const V037: Self = Self(0, 3, 7);
+    /// Android 8.0, adds call sites and method handles.
+    pub const V038: Self = Self(0, 3, 8);
+    /// Android 9.0, adds `const-method-handle` and `const-method-type`.
+    pub const V039: Self = Self(0, 3, 9);
+    /// Android 10.0, only lifts restrictions on identifiers.
+    pub const V040: Self = Self(0, 4, 0);
+    /// Android 15.0, adds the container format.
+    pub const V041: Self = Self(0, 4, 1);
+
+    /// Every version this crate can read, in ascending order.
+    pub const SUPPORTED: [Self; 6] = [
+        Self::V035,
+        Self::V037,
+        Self::V038,
+        Self::V039,
+        Self::V040,
+        Self::V041,
+    ];
+
+    /// Compact dex files aren't versioned like standard ones. ART only produced them
+    /// while 039 was the latest version, which they're treated as.
+    pub const COMPACT: Self = Self::V039;
+
+    /// The size of the header of files of this version.
+    pub(crate) fn header_size(self) -> uint {
+        match self >= Version::V041 {
+            true => CONTAINER_HEADER_SIZE,
+            false => HEADER_SIZE,
+        }
+    }
+
+    /// Fails if `feature` needs a version later than this one.
+    pub(crate) fn require(
+        self,
+        required: Version,
+        feature: impl std::fmt::Display,
+    ) -> Result<(), VersionError> {
+        if self < required {
+            return Err(VersionError::FeatureUnsupported(
+                feature.to_string(),
+                required,
+                self,
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}{}{}", self.0, self.1, self.2)
@@ -274,12 +541,25 @@ pub enum VersionError {
     InvalidLength(usize),
     #[error("invalid magic bytes while parsing")]
     InvalidMagic,
-    #[error("invalid version: {0}")]
-    InvalidVersion(uint),
+    #[error("unsupported version: {0}")]
+    Unsupported(Version),
+    #[error("{0} requires version {1} or later, but the file is version {2}")]
+    FeatureUnsupported(String, Version, Version),
     #[error("read error: {0}")]
     Scroll(#[from] scroll::Error),
 }
 
+impl From<VersionError> for HeaderError {
+    fn from(err: VersionError) -> Self {
+        match err {
+            VersionError::Unsupported(_) | VersionError::FeatureUnsupported(..) => {
+                HeaderError::Version(err)
+            }
+            _ => HeaderError::InvalidMagic(err),
+        }
+    }
+}
+
 // dex\nXXX\0
 const MAGIC_START: &[ubyte; 4] = b"dex\n";
 const MAGIC_END: ubyte = 0;
@@ -296,16 +576,19 @@ impl TryFrom<&[ubyte]> for Version {
         if magic[MAGIC_LEN - 1] != MAGIC_END {
             return Err(VersionError::InvalidMagic);
         }
-        // Maybe not the most optimal solution, but it'll do for now.
-        let mut version = std::str::from_utf8(&magic[4..=6])
-            .map_err(|_| VersionError::InvalidMagic)?
-            .chars()
-            .map(|c| c.to_digit(10).unwrap());
-        Ok(Version(
-            version.next().unwrap(),
-            version.next().unwrap(),
-            version.next().unwrap(),
-        ))
+        // each digit is stored as an ASCII character
+        let mut digits = [0; 3];
+        for (digit, c) in digits.iter_mut().zip(&magic[4..=6]) {
+            if !c.is_ascii_digit() {
+                return Err(VersionError::InvalidMagic);
+            }
+            *digit = (c - b'0') as uint;
+        }
+        let version = Version(digits[0], digits[1], digits[2]);
+        if !Version::SUPPORTED.contains(&version) {
+            return Err(VersionError::Unsupported(version));
+        }
+        Ok(version)
     }
 }
 
@@ -314,10 +597,17 @@ impl TryIntoCtx<scroll::Endian> for Version {
     fn try_into_ctx(self, dst: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
         let offset = &mut 0;
         dst.gwrite_with(MAGIC_START.as_slice(), offset, ())?;
-        dst.gwrite_with(self.0, offset, ctx)?;
-        dst.gwrite_with(self.1, offset, ctx)?;
-        dst.gwrite_with(self.2, offset, ctx)?;
+        // each digit is stored as an ASCII character
+        for digit in [self.0, self.1, self.2] {
+            dst.gwrite_with(b'0' + digit as ubyte, offset, ctx)?;
+        }
         dst.gwrite_with(MAGIC_END, offset, ctx)?;
         Ok(*offset)
     }
 }
+
+impl EncodedLen for Version {
+    fn encoded_len(&self) -> usize {
+        MAGIC_LEN
+    }
+}
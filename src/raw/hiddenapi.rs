@@ -1,25 +1,75 @@
-use crate::raw::uleb128;
+use std::borrow::Cow;
+
+use crate::raw::{buffer::EncodedLen, *};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use scroll::{
+    ctx::{TryFromCtx, TryIntoCtx},
+    Pread, Pwrite,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HiddenapiError {
+    #[error("invalid hiddenapi flags: {0:#x}")]
+    InvalidFlags(ulong),
+    #[error("class_def index {0} is out of bounds")]
+    ClassOutOfBounds(uint),
+    #[error("item size {0} is smaller than its header")]
+    InvalidSize(uint),
+    #[error("flags offset {0} is out of bounds")]
+    OffsetOutOfBounds(uint),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+/// The API list a hidden member is on, stored in the low bits of its flags.
+///
+/// For more information, click [here][1].
+///
+/// [1]: https://source.android.com/docs/core/runtime/dex-format#hiddenapi-class-data-item
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum ApiList {
+    Whitelist = 0,
+    Greylist = 1,
+    Blacklist = 2,
+    GreylistMaxO = 3,
+    GreylistMaxP = 4,
+    GreylistMaxQ = 5,
+    GreylistMaxR = 6,
+    GreylistMaxS = 7,
+}
 
 bitflags::bitflags! {
-  /// For more information, click [here][1].
-  ///
-  /// [1]: https://source.android.com/docs/core/runtime/dex-format#hiddenapi-class-data-item
+  /// Domain-specific API bits, stored above the [`ApiList`] value.
   #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-  pub struct RestrictionFlag: u64 {
-      const Whitelist = 0;
-      const Greylist = 1;
-      const Blacklist = 2;
-      const GreylistMaxO = 3;
-      const GreylistMaxP = 4;
-      const GreylistMaxQ = 5;
-      const GreylistMaxR = 6;
+  pub struct DomainFlags: u32 {
+      const CorePlatformApi = 1 << API_LIST_BITS;
+      const TestApi = 1 << (API_LIST_BITS + 1);
   }
 }
 
-impl RestrictionFlag {
-    pub fn try_from_uleb128(src: &[u8], offset: &mut usize) -> Result<Self, scroll::Error> {
+/// Number of low bits the [`ApiList`] value takes up.
+const API_LIST_BITS: u32 = 3;
+const API_LIST_MASK: ulong = (1 << API_LIST_BITS) - 1;
+
+/// The hidden API restriction of a single field or method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Restriction {
+    pub api_list: ApiList,
+    pub domains: DomainFlags,
+}
+
+impl Restriction {
+    pub fn try_from_uleb128(src: &[u8], offset: &mut usize) -> Result<Self, HiddenapiError> {
         let flags = uleb128::read(src, offset)?;
-        Ok(RestrictionFlag::from_bits_truncate(flags))
+        let api_list =
+            ApiList::from_u64(flags & API_LIST_MASK).ok_or(HiddenapiError::InvalidFlags(flags))?;
+        let domains = u32::try_from(flags & !API_LIST_MASK)
+            .ok()
+            .and_then(DomainFlags::from_bits)
+            .ok_or(HiddenapiError::InvalidFlags(flags))?;
+        Ok(Self { api_list, domains })
     }
 
     pub fn try_into_uleb128(
@@ -27,7 +77,187 @@ impl RestrictionFlag {
         dst: &mut [u8],
         offset: &mut usize,
     ) -> Result<(), scroll::Error> {
-        uleb128::write(dst, offset, self.bits())?;
+        let flags = self.api_list as ulong | self.domains.bits() as ulong;
+        uleb128::write(dst, offset, flags)?;
         Ok(())
     }
+
+    /// The length of this restriction when written with [`Restriction::try_into_uleb128`].
+    pub fn uleb128_len(&self) -> usize {
+        uleb128::len_of(self.api_list as ulong | self.domains.bits() as ulong)
+    }
+}
+
+/// See https://source.android.com/docs/core/runtime/dex-format#hiddenapi-class-data-item
+///
+/// The flags of each class are only decoded on request,
+/// since their count depends on the `class_data_item` of that class.
+#[derive(Debug, Clone)]
+pub struct HiddenapiClassDataItem<'a> {
+    /// Offsets from the start of this item to the flags of each class, indexed by `class_def` index.
+    /// An offset of 0 means the class has no flags.
+    pub offsets: Vec<uint>,
+    /// The entire item, including `size` and `offsets`.
+    data: Cow<'a, [ubyte]>,
+}
+
+impl HiddenapiClassDataItem<'static> {
+    /// Encodes the flags of each class, indexed by `class_def` index.
+    pub fn new(classes: &[Option<&[Restriction]>]) -> Result<Self, HiddenapiError> {
+        let header_len = (classes.len() + 1) * std::mem::size_of::<uint>();
+        // `size` and `offsets` are written from the fields, so they're left zeroed here
+        let mut data = vec![0; header_len];
+        let mut offsets = Vec::with_capacity(classes.len());
+        for flags in classes {
+            let Some(flags) = flags else {
+                offsets.push(0);
+                continue;
+            };
+            offsets.push(data.len() as uint);
+            let start = data.len();
+            data.resize(
+                start + flags.iter().map(Restriction::uleb128_len).sum::<usize>(),
+                0,
+            );
+            let mut offset = start;
+            for restriction in *flags {
+                restriction.try_into_uleb128(&mut data, &mut offset)?;
+            }
+        }
+        Ok(Self {
+            offsets,
+            data: Cow::Owned(data),
+        })
+    }
+}
+
+impl<'a> HiddenapiClassDataItem<'a> {
+    /// Decodes the flags of the class at `class_def_idx`, which has `members` fields and methods,
+    /// in the order they appear in its `class_data_item`.
+    pub fn class_flags(
+        &self,
+        class_def_idx: uint,
+        members: usize,
+    ) -> Result<Option<Vec<Restriction>>, HiddenapiError> {
+        let offset = match self.offsets.get(class_def_idx as usize) {
+            Some(0) => return Ok(None),
+            Some(offset) => &mut (*offset as usize),
+            None => return Err(HiddenapiError::ClassOutOfBounds(class_def_idx)),
+        };
+        let mut flags = Vec::with_capacity(members);
+        for _ in 0..members {
+            flags.push(Restriction::try_from_uleb128(&self.data, offset)?);
+        }
+        Ok(Some(flags))
+    }
+}
+
+/// The context is the endianness and the number of classes in the file (`class_defs_size`).
+impl<'a> TryFromCtx<'a, (scroll::Endian, uint)> for HiddenapiClassDataItem<'a> {
+    type Error = HiddenapiError;
+    fn try_from_ctx(
+        src: &'a [u8],
+        (ctx, class_defs_size): (scroll::Endian, uint),
+    ) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let size: uint = src.gread_with(offset, ctx)?;
+        let header_len = (class_defs_size as usize + 1) * std::mem::size_of::<uint>();
+        if (size as usize) < header_len {
+            return Err(HiddenapiError::InvalidSize(size));
+        }
+        let offsets: Vec<uint> = try_gread_vec_with!(src, offset, class_defs_size, ctx);
+        // the flags of each class are after the header, 0 means there are none
+        if let Some(offset) = offsets.iter().find(|offset| {
+            **offset != 0 && !(header_len..=size as usize).contains(&(**offset as usize))
+        }) {
+            return Err(HiddenapiError::OffsetOutOfBounds(*offset));
+        }
+        let data = src.pread_with::<&[ubyte]>(0, size as usize)?;
+        let data = Cow::Borrowed(data);
+        Ok((Self { offsets, data }, size as usize))
+    }
+}
+
+impl<'a> TryIntoCtx<scroll::Endian> for HiddenapiClassDataItem<'a> {
+    type Error = HiddenapiError;
+    fn try_into_ctx(self, dst: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        dst.gwrite_with(self.data.len() as uint, offset, ctx)?;
+        try_gwrite_vec_with!(dst, offset, self.offsets, ctx);
+        // the flags themselves are copied verbatim
+        let flags = self
+            .data
+            .get(*offset..)
+            .ok_or(HiddenapiError::InvalidSize(self.data.len() as uint))?;
+        dst.gwrite(flags, offset)?;
+        Ok(*offset)
+    }
+}
+
+impl<'a> EncodedLen for HiddenapiClassDataItem<'a> {
+    fn encoded_len(&self) -> usize {
+        // `size` and `offsets` are part of `data`
+        self.data.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restriction() {
+        let v = Restriction {
+            api_list: ApiList::GreylistMaxQ,
+            domains: DomainFlags::CorePlatformApi | DomainFlags::TestApi,
+        };
+        let mut buf = [0u8; 8];
+        v.try_into_uleb128(&mut buf, &mut 0).unwrap();
+        assert_eq!(buf[0], 0b11_101);
+        let v2 = Restriction::try_from_uleb128(&buf, &mut 0).unwrap();
+        assert_eq!(v, v2);
+        let max_s = Restriction::try_from_uleb128(&[0b111], &mut 0).unwrap();
+        assert_eq!(max_s.api_list, ApiList::GreylistMaxS);
+        assert!(Restriction::try_from_uleb128(&[0b100_000], &mut 0).is_err());
+    }
+
+    #[test]
+    fn class_data_item() {
+        let restriction = |api_list| Restriction {
+            api_list,
+            domains: DomainFlags::empty(),
+        };
+        let flags = [
+            restriction(ApiList::Whitelist),
+            restriction(ApiList::Blacklist),
+        ];
+        let item = HiddenapiClassDataItem::new(&[None, Some(&flags[..]), Some(&[])]).unwrap();
+        assert_eq!(item.offsets, [0, 16, 18]);
+        let mut buf = vec![0; item.encoded_len()];
+        item.try_into_ctx(&mut buf, scroll::BE).unwrap();
+        assert_eq!(buf[..8], [0, 0, 0, 18, 0, 0, 0, 0]);
+        let (item, len) = HiddenapiClassDataItem::try_from_ctx(&buf, (scroll::BE, 3)).unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(item.class_flags(0, 0).unwrap(), None);
+        assert_eq!(item.class_flags(1, 2).unwrap().unwrap(), flags);
+        assert_eq!(item.class_flags(2, 0).unwrap().unwrap(), []);
+
+        let parse = |buf: &[u8]| HiddenapiClassDataItem::try_from_ctx(buf, (scroll::BE, 3)).err();
+        // a size that doesn't cover the offsets
+        let mut malformed = buf.clone();
+        malformed[3] = 12;
+        assert!(matches!(
+            parse(&malformed),
+            Some(HiddenapiError::InvalidSize(12))
+        ));
+        // flags inside the header, and past the end of the item
+        for offset in [4, 19] {
+            let mut malformed = buf.clone();
+            malformed[11] = offset;
+            assert!(matches!(
+                parse(&malformed),
+                Some(HiddenapiError::OffsetOutOfBounds(o)) if o == offset as uint
+            ));
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, header::Version, *};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{
@@ -16,9 +16,15 @@ pub enum MapListError {
 
 /// List of the entire contents of a file, in order. A given type must appear at most
 /// once in a map, entries must be ordered by initial offset and must not overlap.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MapList(Vec<MapItem>);
 
+impl From<Vec<MapItem>> for MapList {
+    fn from(items: Vec<MapItem>) -> Self {
+        Self(items)
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for MapList {
     type Error = MapListError;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
@@ -39,6 +45,12 @@ impl TryIntoCtx<scroll::Endian> for MapList {
     }
 }
 
+impl EncodedLen for MapList {
+    fn encoded_len(&self) -> usize {
+        std::mem::size_of::<uint>() + self.0.iter().map(MapItem::encoded_len).sum::<usize>()
+    }
+}
+
 impl MapList {
     /// Returns the `MapItem` corresponding to the [`ItemType`].
     pub fn get(&self, item_type: ItemType) -> Option<MapItem> {
@@ -57,10 +69,15 @@ impl MapList {
     pub fn get_len(&self, item_type: ItemType) -> Option<uint> {
         self.get(item_type).map(|map_item| map_item.size)
     }
+
+    /// The types of all items in the file, in the order they're listed.
+    pub fn item_types(&self) -> impl Iterator<Item = ItemType> + '_ {
+        self.0.iter().map(|map_item| map_item.item_type)
+    }
 }
 
 /// Items that can be found in the MapList.
-#[derive(FromPrimitive, Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(FromPrimitive, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[repr(u16)] // ushort
 pub enum ItemType {
     HeaderItem = 0x0,
@@ -86,6 +103,16 @@ pub enum ItemType {
     HiddenapiClassDataItem = 0xF000,
 }
 
+impl ItemType {
+    /// The first version that allows items of this type.
+    pub fn min_version(self) -> Version {
+        match self {
+            ItemType::CallSiteIdItem | ItemType::MethodHandleItem => Version::V038,
+            _ => Version::V035,
+        }
+    }
+}
+
 /// Single item of the MapList.
 #[derive(Debug, Clone, Copy)]
 pub struct MapItem {
@@ -97,12 +124,22 @@ pub struct MapItem {
     offset: uint,
 }
 
+impl MapItem {
+    pub fn new(item_type: ItemType, size: uint, offset: uint) -> Self {
+        Self {
+            item_type,
+            size,
+            offset,
+        }
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for MapItem {
     type Error = MapListError;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
         let ty: ushort = src.gread_with(offset, ctx)?;
-        let item_type = ItemType::from_u16(ty).ok_or_else(|| MapListError::InvalidTypeId(ty))?;
+        let item_type = ItemType::from_u16(ty).ok_or(MapListError::InvalidTypeId(ty))?;
         let __reserved: ushort = src.gread_with(offset, ctx)?;
         debug_assert_eq!(__reserved, RESERVED_VALUE as ushort);
         let size: uint = src.gread_with(offset, ctx)?;
@@ -129,3 +166,10 @@ impl TryIntoCtx<scroll::Endian> for MapItem {
         Ok(*offset)
     }
 }
+
+impl EncodedLen for MapItem {
+    fn encoded_len(&self) -> usize {
+        // type, unused, size and offset
+        2 * std::mem::size_of::<ushort>() + 2 * std::mem::size_of::<uint>()
+    }
+}
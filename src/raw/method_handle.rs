@@ -1,4 +1,4 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{Pread, Pwrite};
@@ -11,7 +11,7 @@ pub enum MethodHandleError {
     Scroll(#[from] scroll::Error),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MethodHandle {
     pub ty: MethodHandleType,
     pub field_or_method_id: ushort,
@@ -34,8 +34,7 @@ impl<'a> scroll::ctx::TryFromCtx<'a, scroll::Endian> for MethodHandle {
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
         let ty: ushort = src.gread_with(offset, ctx)?;
-        let ty =
-            MethodHandleType::from_u16(ty).ok_or_else(|| MethodHandleError::InvalidType(ty))?;
+        let ty = MethodHandleType::from_u16(ty).ok_or(MethodHandleError::InvalidType(ty))?;
         let __unused: ushort = src.gread_with(offset, ctx)?;
         debug_assert_eq!(__unused, RESERVED_VALUE as ushort);
         let field_or_method_id: ushort = src.gread_with(offset, ctx)?;
@@ -63,7 +62,13 @@ impl scroll::ctx::TryIntoCtx<scroll::Endian> for MethodHandle {
     }
 }
 
-#[derive(FromPrimitive, Debug, Clone, Copy, Eq, PartialEq)]
+impl EncodedLen for MethodHandle {
+    fn encoded_len(&self) -> usize {
+        tysize::METHOD_HANDLE
+    }
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[repr(u16)] // ushort
 pub enum MethodHandleType {
     /// Method handle is a static field setter (accessor)
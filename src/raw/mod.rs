@@ -1,9 +1,14 @@
 #![allow(non_camel_case_types, dead_code)] // TODO: remove dead_code
 
 pub mod annotations;
+pub mod buffer;
+/// Dalvik bytecode: [docs](https://source.android.com/docs/core/runtime/dalvik-bytecode)
+pub mod bytecode;
+pub mod call_site;
 pub mod class_data;
 pub mod classdef;
 pub mod code_item;
+pub mod compact;
 pub mod encoded_value;
 pub mod flags;
 pub mod header;
@@ -41,6 +46,7 @@ pub(crate) type RawProtoIndex = uint;
 pub(crate) type RawFieldIndex = uint;
 pub(crate) type RawMethodIndex = uint;
 pub(crate) type RawMethodHandleIndex = uint;
+pub(crate) type RawCallSiteIndex = uint;
 
 pub mod tysize {
     pub const STRING_ID: usize = 0x04;
@@ -51,6 +57,7 @@ pub mod tysize {
     pub const CLASS_DEF: usize = 0x20;
     pub const CALL_SITE_ID: usize = 0x04;
     pub const METHOD_HANDLE: usize = 0x08;
+    pub const TRY_ITEM: usize = 0x08;
 
     #[cfg(debug_assertions)]
     use super::*;
@@ -63,5 +70,6 @@ pub mod tysize {
         METHOD_ID; simple::MethodId
         CLASS_DEF; classdef::ClassDef
         CALL_SITE_ID; simple::CallSiteId
+        TRY_ITEM; simple::TryItem
     );
 }
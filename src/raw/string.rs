@@ -1,4 +1,4 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use derivative::Derivative;
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
@@ -14,6 +14,12 @@ impl StringId {
     }
 }
 
+impl From<uint> for StringId {
+    fn from(offset: uint) -> Self {
+        Self(offset)
+    }
+}
+
 impl From<StringId> for uint {
     fn from(id: StringId) -> Self {
         id.0
@@ -38,7 +44,13 @@ impl TryIntoCtx<scroll::Endian> for StringId {
     }
 }
 
-#[derive(Derivative)]
+impl EncodedLen for StringId {
+    fn encoded_len(&self) -> usize {
+        tysize::STRING_ID
+    }
+}
+
+#[derive(Derivative, Clone, Copy)]
 #[derivative(Debug)]
 pub struct StringData<'a> {
     /// Size of this string in UTF-16 code units (which is the "string length" in many systems).
@@ -63,6 +75,7 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for StringData<'a> {
         // for that, we read until we find a 0 byte
         let encoded_len = count_delim!(src, offset, b'\0');
         let data = src.gread_with::<&[ubyte]>(offset, encoded_len)?;
+        src.gread_with::<ubyte>(offset, scroll::LE)?; // terminating 0 byte
         Ok((Self { size, data }, *offset))
     }
 }
@@ -73,6 +86,14 @@ impl<'a> TryIntoCtx<scroll::Endian> for StringData<'a> {
         let offset = &mut 0;
         uleb128::write(dst, offset, self.size)?;
         dst.gwrite(self.data, offset)?;
+        dst.gwrite_with::<ubyte>(0, offset, scroll::LE)?;
         Ok(*offset)
     }
 }
+
+impl<'a> EncodedLen for StringData<'a> {
+    fn encoded_len(&self) -> usize {
+        // including the terminating 0 byte
+        uleb128::len_of(self.size) + self.data.len() + 1
+    }
+}
@@ -1,12 +1,24 @@
-use crate::raw::*;
+use crate::raw::{buffer::EncodedLen, *};
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Pread, Pwrite,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypeList(Vec<TypeItem>);
 
+impl TypeList {
+    pub(crate) fn into_inner(self) -> Vec<TypeItem> {
+        self.0
+    }
+}
+
+impl From<Vec<TypeItem>> for TypeList {
+    fn from(items: Vec<TypeItem>) -> Self {
+        Self(items)
+    }
+}
+
 impl<'a> TryFromCtx<'a, scroll::Endian> for TypeList {
     type Error = scroll::Error;
     fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
@@ -27,8 +39,16 @@ impl TryIntoCtx<scroll::Endian> for TypeList {
     }
 }
 
+impl EncodedLen for TypeList {
+    fn encoded_len(&self) -> usize {
+        std::mem::size_of::<uint>() + self.0.len() * std::mem::size_of::<TypeItem>()
+    }
+}
+
 #[derive(Debug, Clone, Copy, Pread, Pwrite)]
 pub struct TypeItem {
     /// Index into the `type_ids` list.
-    type_idx: ushort,
+    pub type_idx: ushort,
 }
+
+sized_encoded_len!(TypeItem);
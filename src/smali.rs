@@ -0,0 +1,5 @@
+mod assembler;
+mod disassembler;
+
+pub use assembler::*;
+pub use disassembler::*;
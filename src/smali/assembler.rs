@@ -0,0 +1,1817 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    raw::{
+        annotations::{Annotation, Visibility},
+        bytecode::{
+            ArrayData, AssembleError, Assembler, EncodeError, Format, IndexKind, Instruction,
+            Label, Opcode, Reg,
+        },
+        call_site::CallSiteItem,
+        class_data::{ClassData, EncodedField, EncodedMethod},
+        classdef::ClassDef,
+        code_item::{CodeItem, DebugInfoItem, DebugInfoOp},
+        encoded_value::{
+            AnnotationElement, EncodedAnnotation, EncodedCatchHandler, EncodedCatchHandlerList,
+            EncodedTypeAddrPair, EncodedValue,
+        },
+        flags::AccessFlags,
+        int, long,
+        method_handle::MethodHandleType,
+        simple::TryItem,
+        uint, ulong, ushort, RawFieldIndex, RawMethodIndex, RawProtoIndex, RawStringIndex,
+        RawTypeIndex, NO_INDEX,
+    },
+    writer::{ClassAnnotations, ClassItem, MethodCode, MethodHandleEntry, Pools},
+};
+
+/// An error in smali source, at a 1-based line and column.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("{line}:{column}: {kind}")]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    #[error("expected {0}, found end of input")]
+    UnexpectedEnd(&'static str),
+    #[error("expected {0}, found {1:?}")]
+    Expected(&'static str, String),
+    #[error("unknown directive {0}")]
+    UnknownDirective(String),
+    #[error("unknown instruction {0}")]
+    UnknownInstruction(String),
+    #[error("unknown access flag {0}")]
+    UnknownAccessFlag(String),
+    #[error("invalid literal {0}")]
+    InvalidLiteral(String),
+    #[error("invalid escape sequence")]
+    InvalidEscape,
+    #[error("register {0} is out of range")]
+    InvalidRegister(String),
+    #[error("registers must be declared with .registers or .locals first")]
+    MissingRegisters,
+    #[error("label {0} is not defined")]
+    UndefinedLabel(String),
+    #[error("label {0} is defined more than once")]
+    DuplicateLabel(String),
+    #[error("invalid try block range")]
+    InvalidTryRange,
+    #[error("try block overlaps another one")]
+    OverlappingTries,
+    #[error("missing .class directive")]
+    MissingClass,
+    #[error("error encoding instruction: {0}")]
+    Encode(EncodeError),
+    #[error("error assembling method: {0}")]
+    Assemble(AssembleError),
+}
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// Parses the smali source of a single class, interning everything it references into `pools`.
+///
+/// This accepts the output of [`disassemble`][super::disassemble] and the common subset of the syntax
+/// understood by `smali`. Labels and debug directives are resolved to addresses,
+/// and `pN` registers to the last registers of the method.
+pub fn assemble(source: &str, pools: &mut Pools) -> Result<ClassItem> {
+    Parser {
+        src: source,
+        pos: 0,
+        pools,
+    }
+    .class()
+}
+
+fn access_flag(name: &str) -> Option<AccessFlags> {
+    Some(match name {
+        "public" => AccessFlags::Public,
+        "private" => AccessFlags::Private,
+        "protected" => AccessFlags::Protected,
+        "static" => AccessFlags::Static,
+        "final" => AccessFlags::Final,
+        "synchronized" => AccessFlags::Synchronized,
+        "volatile" => AccessFlags::Volatile,
+        "bridge" => AccessFlags::Bridge,
+        "transient" => AccessFlags::Transient,
+        "varargs" => AccessFlags::Varargs,
+        "native" => AccessFlags::Native,
+        "interface" => AccessFlags::Interface,
+        "abstract" => AccessFlags::Abstract,
+        "strictfp" => AccessFlags::Strict,
+        "synthetic" => AccessFlags::Synthetic,
+        "annotation" => AccessFlags::Annotation,
+        "enum" => AccessFlags::Enum,
+        "constructor" => AccessFlags::Constructor,
+        "declared-synchronized" => AccessFlags::DeclaredSynchronized,
+        _ => return None,
+    })
+}
+
+fn method_handle_type(name: &str) -> Option<MethodHandleType> {
+    Some(match name {
+        "static-put" => MethodHandleType::StaticPut,
+        "static-get" => MethodHandleType::StaticGet,
+        "instance-put" => MethodHandleType::InstancePut,
+        "instance-get" => MethodHandleType::InstanceGet,
+        "invoke-static" => MethodHandleType::InvokeStatic,
+        "invoke-instance" => MethodHandleType::InvokeInstance,
+        "invoke-constructor" => MethodHandleType::InvokeConstructor,
+        "invoke-direct" => MethodHandleType::InvokeDirect,
+        "invoke-interface" => MethodHandleType::InvokeInterface,
+        _ => return None,
+    })
+}
+
+fn is_wide(descriptor: &str) -> bool {
+    descriptor == "J" || descriptor == "D"
+}
+
+/// A numeric literal, before it's narrowed to the type it's used as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    /// An integer, with its `t`, `s` or `L` suffix, if any.
+    Integer(long, Option<char>),
+    Float(f32),
+    Double(f64),
+}
+
+fn parse_number(text: &str) -> Option<Number> {
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let sign = if negative { -1.0 } else { 1.0 };
+    let special = match rest.trim_end_matches(['f', 'd']) {
+        "NaN" => Some(f64::NAN),
+        "Infinity" => Some(f64::INFINITY),
+        _ => None,
+    };
+    if let Some(value) = special {
+        return Some(match rest.ends_with('f') {
+            true => Number::Float((sign * value) as f32),
+            false => Number::Double(sign * value),
+        });
+    }
+    let integer = |digits: &str, radix: u32, suffix: Option<char>| {
+        let magnitude = u64::from_str_radix(digits, radix).ok()?;
+        let value = match negative {
+            true if magnitude <= 1 << 63 => (magnitude as long).wrapping_neg(),
+            true => return None,
+            false => magnitude as long,
+        };
+        Some(Number::Integer(value, suffix))
+    };
+    let (body, suffix) = match rest.char_indices().last()? {
+        (i, c @ ('t' | 's')) => (&rest[..i], Some(c)),
+        (i, 'L' | 'l') => (&rest[..i], Some('L')),
+        _ => (rest, None),
+    };
+    if let Some(hex) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+        return integer(hex, 16, suffix);
+    }
+    if suffix.is_some() {
+        return integer(body, 10, suffix);
+    }
+    if !rest.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    if let Some(float) = rest.strip_suffix(['f', 'F']) {
+        return float
+            .parse::<f32>()
+            .ok()
+            .map(|v| Number::Float(sign as f32 * v));
+    }
+    if let Some(double) = rest.strip_suffix(['d', 'D']) {
+        return double.parse::<f64>().ok().map(|v| Number::Double(sign * v));
+    }
+    if rest.contains(['.', 'e', 'E']) {
+        return rest.parse::<f64>().ok().map(|v| Number::Double(sign * v));
+    }
+    integer(rest, 10, None)
+}
+
+/// Narrows `value` to a signed integer of `bits` bits,
+/// also accepting its unsigned form, e.g. `0xff` for a `byte`.
+fn narrow(value: long, bits: u32) -> Option<long> {
+    if bits >= 64 {
+        return Some(value);
+    }
+    let min = -(1 << (bits - 1));
+    let max = (1 << (bits - 1)) - 1;
+    if (min..=max).contains(&value) {
+        Some(value)
+    } else if (0..1 << bits).contains(&value) {
+        Some(value - (1 << bits))
+    } else {
+        None
+    }
+}
+
+/// A reference to a field or method, like `Lcom/example/Foo;->bar:I`.
+enum Member {
+    Field(RawFieldIndex),
+    Method(RawMethodIndex),
+}
+
+/// A debug directive, bound to the address of the next instruction.
+enum DebugEvent {
+    Line(ulong),
+    Op(DebugInfoOp),
+}
+
+/// A `.catch` or `.catchall` directive.
+struct Catch {
+    at: usize,
+    ty: Option<RawTypeIndex>,
+    start: Label,
+    end: Label,
+    handler: Label,
+}
+
+/// The state of the method being parsed.
+struct MethodBuilder {
+    at: usize,
+    index: RawMethodIndex,
+    access_flags: AccessFlags,
+    /// The offset of each parameter from the first `p` register, skipping `this`.
+    parameter_registers: Vec<ushort>,
+    ins_size: ushort,
+    registers_size: Option<ushort>,
+    outs_size: ushort,
+    asm: Assembler,
+    /// Where each label is defined.
+    labels: HashMap<String, usize>,
+    /// Where each label is referenced.
+    references: Vec<(String, usize)>,
+    catches: Vec<Catch>,
+    debug: Vec<(Label, DebugEvent)>,
+    parameter_names: Vec<Option<RawStringIndex>>,
+    parameter_annotations: Vec<Vec<Annotation>>,
+    annotations: Vec<Annotation>,
+}
+
+/// A field declared with `.field`.
+struct FieldDecl {
+    index: RawFieldIndex,
+    access_flags: AccessFlags,
+    ty: String,
+    value: Option<EncodedValue>,
+}
+
+struct Parser<'s, 'p> {
+    src: &'s str,
+    pos: usize,
+    pools: &'p mut Pools,
+}
+
+/// Characters that end a token, besides whitespace.
+const DELIMITERS: &[char] = &[',', '{', '}', '(', ')', '=', ':', '"', '\'', '#', '@', ';'];
+
+impl<'s> Parser<'s, '_> {
+    fn error(&self, at: usize, kind: ParseErrorKind) -> ParseError {
+        let before = &self.src[..at];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            kind,
+        }
+    }
+
+    /// The error for an unexpected token at `at`, or the end of the input.
+    fn expected(&self, at: usize, what: &'static str) -> ParseError {
+        let rest = &self.src[at..];
+        let found = rest
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        match found {
+            "" => self.error(at, ParseErrorKind::UnexpectedEnd(what)),
+            found => self.error(at, ParseErrorKind::Expected(what, found.to_owned())),
+        }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    /// Skips whitespace and comments, returning the position of the next token.
+    fn skip(&mut self) -> usize {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with('#') {
+                return self.pos;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip();
+        self.rest().is_empty()
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.skip();
+        self.rest().chars().next()
+    }
+
+    /// Consumes `s` if it's next.
+    fn eat(&mut self, s: &str) -> bool {
+        self.skip();
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &'static str) -> Result<()> {
+        let at = self.skip();
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.expected(at, s))
+        }
+    }
+
+    /// Reads the next word, up to whitespace or a delimiter.
+    fn token(&mut self, what: &'static str) -> Result<(usize, &'s str)> {
+        let at = self.skip();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || DELIMITERS.contains(&c))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.expected(at, what));
+        }
+        self.pos += len;
+        Ok((at, &rest[..len]))
+    }
+
+    /// Consumes the given words if they're next, e.g. `.end method`.
+    fn eat_words(&mut self, words: &[&str]) -> bool {
+        let pos = self.pos;
+        for word in words {
+            if self.token("").map(|(_, token)| token) != Ok(word) {
+                self.pos = pos;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn expect_words(&mut self, words: &[&str], what: &'static str) -> Result<()> {
+        let at = self.skip();
+        if self.eat_words(words) {
+            Ok(())
+        } else {
+            Err(self.expected(at, what))
+        }
+    }
+
+    fn number(&mut self, what: &'static str) -> Result<(usize, Number)> {
+        let (at, token) = self.token(what)?;
+        let number = parse_number(token)
+            .ok_or_else(|| self.error(at, ParseErrorKind::InvalidLiteral(token.to_owned())))?;
+        Ok((at, number))
+    }
+
+    /// An integer of at most `bits` bits.
+    fn integer(&mut self, bits: u32, what: &'static str) -> Result<long> {
+        let (at, number) = self.number(what)?;
+        match number {
+            Number::Integer(value, _) => narrow(value, bits),
+            _ => None,
+        }
+        .ok_or_else(|| self.expected(at, what))
+    }
+
+    fn unsigned(&mut self, what: &'static str) -> Result<uint> {
+        let at = self.skip();
+        let value = self.integer(64, what)?;
+        uint::try_from(value).map_err(|_| self.expected(at, what))
+    }
+
+    fn access_flags(&mut self) -> Result<AccessFlags> {
+        let mut flags = AccessFlags::empty();
+        while self.peek_char().is_some_and(|c| c.is_ascii_lowercase()) {
+            let pos = self.pos;
+            let (at, token) = self.token("access flag")?;
+            // the member name may look like a flag, but is followed by its type
+            if matches!(self.rest().chars().next(), Some(':' | '(')) {
+                self.pos = pos;
+                break;
+            }
+            flags |= access_flag(token).ok_or_else(|| {
+                self.error(at, ParseErrorKind::UnknownAccessFlag(token.to_owned()))
+            })?;
+        }
+        Ok(flags)
+    }
+
+    /// A type descriptor like `I`, `[J` or `Ljava/lang/Object;`.
+    fn descriptor(&mut self) -> Result<&'s str> {
+        let at = self.skip();
+        let rest = self.rest();
+        let dims = rest.len() - rest.trim_start_matches('[').len();
+        let len = match rest[dims..].chars().next() {
+            Some('L') => match rest[dims..].find([';', '\n']) {
+                Some(end) if rest[dims + end..].starts_with(';') => dims + end + 1,
+                _ => return Err(self.expected(at, "type descriptor")),
+            },
+            Some('V' | 'Z' | 'B' | 'S' | 'C' | 'I' | 'J' | 'F' | 'D') => dims + 1,
+            _ => return Err(self.expected(at, "type descriptor")),
+        };
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn ty(&mut self) -> Result<RawTypeIndex> {
+        let descriptor = self.descriptor()?;
+        Ok(self.pools.ty(descriptor))
+    }
+
+    /// The return and parameter types of a prototype like `(IJ)V`.
+    fn proto_types(&mut self) -> Result<(&'s str, Vec<&'s str>)> {
+        self.expect("(")?;
+        let mut parameters = Vec::new();
+        while !self.eat(")") {
+            parameters.push(self.descriptor()?);
+        }
+        Ok((self.descriptor()?, parameters))
+    }
+
+    fn proto(&mut self) -> Result<RawProtoIndex> {
+        let (return_type, parameters) = self.proto_types()?;
+        Ok(self.pools.proto(return_type, &parameters))
+    }
+
+    /// The rest of a member reference after its class, starting at `->`.
+    fn member_of(&mut self, class: &str) -> Result<Member> {
+        self.expect("->")?;
+        let (_, name) = self.token("member name")?;
+        if self.eat(":") {
+            let ty = self.descriptor()?;
+            return Ok(Member::Field(self.pools.field(class, name, ty)));
+        }
+        let proto = self.proto()?;
+        Ok(Member::Method(self.pools.method(class, name, proto)))
+    }
+
+    fn member(&mut self) -> Result<(usize, Member)> {
+        let at = self.skip();
+        let class = self.descriptor()?;
+        Ok((at, self.member_of(class)?))
+    }
+
+    fn field(&mut self) -> Result<RawFieldIndex> {
+        match self.member()? {
+            (_, Member::Field(idx)) => Ok(idx),
+            (at, Member::Method(_)) => Err(self.expected(at, "field reference")),
+        }
+    }
+
+    fn method(&mut self) -> Result<RawMethodIndex> {
+        match self.member()? {
+            (_, Member::Method(idx)) => Ok(idx),
+            (at, Member::Field(_)) => Err(self.expected(at, "method reference")),
+        }
+    }
+
+    /// A method handle like `invoke-static@Lcom/example/Foo;->bar()V`.
+    fn method_handle(&mut self) -> Result<MethodHandleEntry> {
+        let (at, token) = self.token("method handle type")?;
+        let ty =
+            method_handle_type(token).ok_or_else(|| self.expected(at, "method handle type"))?;
+        self.expect("@")?;
+        let accessor = matches!(
+            ty,
+            MethodHandleType::StaticPut
+                | MethodHandleType::StaticGet
+                | MethodHandleType::InstancePut
+                | MethodHandleType::InstanceGet
+        );
+        let member = match accessor {
+            true => self.field()?,
+            false => self.method()?,
+        };
+        Ok(MethodHandleEntry { ty, member })
+    }
+
+    /// The UTF-16 code units of a string or character literal, delimited by `quote`.
+    fn quoted(&mut self, quote: char, what: &'static str) -> Result<Vec<u16>> {
+        let at = self.skip();
+        if !self.rest().starts_with(quote) {
+            return Err(self.expected(at, what));
+        }
+        let mut units = Vec::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        loop {
+            let Some((i, c)) = chars.next() else {
+                return Err(self.error(at, ParseErrorKind::UnexpectedEnd(what)));
+            };
+            let escape_at = self.pos + i;
+            match c {
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(units);
+                }
+                '\n' => return Err(self.error(escape_at, ParseErrorKind::UnexpectedEnd(what))),
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n' as u16,
+                        Some('r') => '\r' as u16,
+                        Some('t') => '\t' as u16,
+                        Some('b') => 0x08,
+                        Some('f') => 0x0c,
+                        Some(c @ ('\'' | '"' | '\\')) => c as u16,
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                            match hex.len() == 4 {
+                                true => u16::from_str_radix(&hex, 16).ok(),
+                                false => None,
+                            }
+                            .ok_or_else(|| self.error(escape_at, ParseErrorKind::InvalidEscape))?
+                        }
+                        _ => return Err(self.error(escape_at, ParseErrorKind::InvalidEscape)),
+                    };
+                    units.push(escaped);
+                }
+                c => units.extend(c.encode_utf16(&mut [0; 2]).iter()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let at = self.skip();
+        let units = self.quoted('"', "string literal")?;
+        String::from_utf16(&units).map_err(|_| self.error(at, ParseErrorKind::InvalidEscape))
+    }
+
+    fn string_idx(&mut self) -> Result<RawStringIndex> {
+        let string = self.string()?;
+        Ok(self.pools.string(&string))
+    }
+
+    fn char(&mut self) -> Result<u16> {
+        let at = self.skip();
+        match self.quoted('\'', "character literal")?[..] {
+            [unit] => Ok(unit),
+            _ => Err(self.error(
+                at,
+                ParseErrorKind::InvalidLiteral(self.src[at..self.pos].to_owned()),
+            )),
+        }
+    }
+
+    fn value(&mut self) -> Result<EncodedValue> {
+        let at = self.skip();
+        match self.rest().chars().next() {
+            Some('"') => return Ok(EncodedValue::String(self.string_idx()?)),
+            Some('\'') => return Ok(EncodedValue::Char(self.char()?)),
+            Some('(') => return Ok(EncodedValue::MethodType(self.proto()?)),
+            Some('{') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if !self.eat("}") {
+                    loop {
+                        values.push(self.value()?);
+                        if self.eat("}") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                return Ok(EncodedValue::Array(values));
+            }
+            Some('L' | '[') => {
+                let class = self.descriptor()?;
+                if !self.rest().starts_with("->") {
+                    return Ok(EncodedValue::Type(self.pools.ty(class)));
+                }
+                return Ok(match self.member_of(class)? {
+                    Member::Field(idx) => EncodedValue::Field(idx),
+                    Member::Method(idx) => EncodedValue::Method(idx),
+                });
+            }
+            _ => {}
+        }
+        let pos = self.pos;
+        let (_, token) = self.token("value")?;
+        Ok(match token {
+            ".subannotation" => {
+                let annotation = self.annotation_body(&[".end", "subannotation"])?;
+                EncodedValue::Annotation(annotation)
+            }
+            ".enum" => EncodedValue::Enum(self.field()?),
+            "null" => EncodedValue::Null,
+            "true" => EncodedValue::Boolean(true),
+            "false" => EncodedValue::Boolean(false),
+            "V" | "Z" | "B" | "S" | "C" | "I" | "J" | "F" | "D" => {
+                EncodedValue::Type(self.pools.ty(token))
+            }
+            _ if self.rest().starts_with('@') => {
+                self.pos = pos;
+                let handle = self.method_handle()?;
+                EncodedValue::MethodHandle(self.pools.method_handle(handle))
+            }
+            _ => {
+                let number = parse_number(token).ok_or_else(|| {
+                    self.error(at, ParseErrorKind::InvalidLiteral(token.to_owned()))
+                })?;
+                let invalid = || self.error(at, ParseErrorKind::InvalidLiteral(token.to_owned()));
+                match number {
+                    Number::Integer(value, Some('t')) => {
+                        EncodedValue::Byte(narrow(value, 8).ok_or_else(invalid)? as i8)
+                    }
+                    Number::Integer(value, Some('s')) => {
+                        EncodedValue::Short(narrow(value, 16).ok_or_else(invalid)? as i16)
+                    }
+                    Number::Integer(value, Some(_)) => EncodedValue::Long(value),
+                    Number::Integer(value, None) => {
+                        EncodedValue::Int(narrow(value, 32).ok_or_else(invalid)? as int)
+                    }
+                    Number::Float(value) => EncodedValue::Float(value),
+                    Number::Double(value) => EncodedValue::Double(value),
+                }
+            }
+        })
+    }
+
+    /// The type and elements of an annotation, up to and including the closing directive.
+    fn annotation_body(&mut self, end: &[&str]) -> Result<EncodedAnnotation> {
+        let type_idx = self.ty()? as ulong;
+        let mut elements = Vec::new();
+        loop {
+            self.skip();
+            if self.rest().starts_with(".end") {
+                self.expect_words(end, "end of annotation")?;
+                break;
+            }
+            let (_, name) = self.token("annotation element")?;
+            self.expect("=")?;
+            let name_idx = self.pools.string(name) as ulong;
+            let value = self.value()?;
+            elements.push(AnnotationElement { name_idx, value });
+        }
+        Ok(EncodedAnnotation {
+            type_idx,
+            size: elements.len() as ulong,
+            elements,
+        })
+    }
+
+    /// Any number of `.annotation` blocks.
+    fn annotations(&mut self) -> Result<Vec<Annotation>> {
+        let mut annotations = Vec::new();
+        while self.eat_words(&[".annotation"]) {
+            let (at, token) = self.token("annotation visibility")?;
+            let visibility = match token {
+                "build" => Visibility::Build,
+                "runtime" => Visibility::Runtime,
+                "system" => Visibility::System,
+                _ => return Err(self.expected(at, "annotation visibility")),
+            };
+            let annotation = self.annotation_body(&[".end", "annotation"])?;
+            annotations.push(Annotation {
+                visibility,
+                annotation,
+            });
+        }
+        Ok(annotations)
+    }
+
+    fn class(mut self) -> Result<ClassItem> {
+        let at = self.skip();
+        self.expect_words(&[".class"], ".class")
+            .map_err(|_| self.error(at, ParseErrorKind::MissingClass))?;
+        let access_flags = self.access_flags()?;
+        let class = self.descriptor()?;
+        let class_idx = self.pools.ty(class);
+        let mut superclass_idx = NO_INDEX;
+        let mut source_file_idx = NO_INDEX;
+        let mut interfaces = Vec::new();
+        let mut annotations = ClassAnnotations::default();
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+
+        while !self.at_end() {
+            let (at, directive) = self.token("directive")?;
+            match directive {
+                ".super" => superclass_idx = self.ty()?,
+                ".source" => source_file_idx = self.string_idx()?,
+                ".implements" => interfaces.push(self.ty()?),
+                ".annotation" => {
+                    self.pos = at;
+                    annotations.class.extend(self.annotations()?);
+                }
+                ".field" => {
+                    let field = self.field_decl(class)?;
+                    let field_annotations = self.annotations()?;
+                    if self.eat_words(&[".end", "field"]) {
+                        annotations.fields.push((field.index, field_annotations));
+                    } else {
+                        // without `.end field`, they belong to the class
+                        annotations.class.extend(field_annotations);
+                    }
+                    fields.push(field);
+                }
+                ".method" => {
+                    let method = self.method_decl(class, at)?;
+                    if !method.annotations.is_empty() {
+                        annotations
+                            .methods
+                            .push((method.index, method.annotations.clone()));
+                    }
+                    if method.parameter_annotations.iter().any(|a| !a.is_empty()) {
+                        annotations
+                            .parameters
+                            .push((method.index, method.parameter_annotations.clone()));
+                    }
+                    methods.push(method);
+                }
+                _ => {
+                    let kind = ParseErrorKind::UnknownDirective(directive.to_owned());
+                    return Err(self.error(at, kind));
+                }
+            }
+        }
+
+        let mut code = BTreeMap::new();
+        let mut direct_methods = Vec::new();
+        let mut virtual_methods = Vec::new();
+        for method in methods {
+            let direct = method
+                .access_flags
+                .intersects(AccessFlags::Static | AccessFlags::Private | AccessFlags::Constructor);
+            let (index, access_flags) = (method.index, method.access_flags);
+            if let Some(method_code) = self.finish_method(method)? {
+                code.insert(index, method_code);
+            }
+            match direct {
+                true => direct_methods.push((index, access_flags)),
+                false => virtual_methods.push((index, access_flags)),
+            }
+        }
+
+        fields.sort_by_key(|field| field.index);
+        let (static_fields, instance_fields): (Vec<_>, Vec<_>) = fields
+            .into_iter()
+            .partition(|field| field.access_flags.contains(AccessFlags::Static));
+        let mut static_values = static_fields
+            .iter()
+            .map(|field| {
+                field
+                    .value
+                    .clone()
+                    .unwrap_or_else(|| EncodedValue::default_for(&field.ty))
+            })
+            .collect::<Vec<_>>();
+        while static_values.last().is_some_and(EncodedValue::is_default) {
+            static_values.pop();
+        }
+
+        let encode_fields = |fields: Vec<FieldDecl>| {
+            let mut previous = 0;
+            fields
+                .into_iter()
+                .map(|field| {
+                    let diff = field.index - previous;
+                    previous = field.index;
+                    EncodedField {
+                        field_idx_diff: diff as ulong,
+                        access_flags: field.access_flags,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let encode_methods = |mut methods: Vec<(RawMethodIndex, AccessFlags)>| {
+            methods.sort_by_key(|(index, _)| *index);
+            let mut previous = 0;
+            methods
+                .into_iter()
+                .map(|(index, access_flags)| {
+                    let diff = index - previous;
+                    previous = index;
+                    EncodedMethod {
+                        method_idx_diff: diff as ulong,
+                        access_flags,
+                        code_off: 0,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let static_fields = encode_fields(static_fields);
+        let instance_fields = encode_fields(instance_fields);
+        let direct_methods = encode_methods(direct_methods);
+        let virtual_methods = encode_methods(virtual_methods);
+        let class_data = match static_fields.is_empty()
+            && instance_fields.is_empty()
+            && direct_methods.is_empty()
+            && virtual_methods.is_empty()
+        {
+            true => None,
+            false => Some(ClassData {
+                static_fields_size: static_fields.len() as ulong,
+                instance_fields_size: instance_fields.len() as ulong,
+                direct_methods_size: direct_methods.len() as ulong,
+                virtual_methods_size: virtual_methods.len() as ulong,
+                static_fields,
+                instance_fields,
+                direct_methods,
+                virtual_methods,
+            }),
+        };
+
+        Ok(ClassItem {
+            def: ClassDef {
+                class_idx,
+                access_flags,
+                superclass_idx,
+                interfaces_off: 0,
+                source_file_idx,
+                annotations_off: 0,
+                class_data_off: 0,
+                static_values_off: 0,
+            },
+            interfaces,
+            annotations,
+            class_data,
+            code,
+            static_values,
+            hiddenapi_flags: None,
+        })
+    }
+
+    /// `.field flags name:Type [= value]`
+    fn field_decl(&mut self, class: &str) -> Result<FieldDecl> {
+        let access_flags = self.access_flags()?;
+        let (_, name) = self.token("field name")?;
+        self.expect(":")?;
+        let ty = self.descriptor()?;
+        let value = match self.eat("=") {
+            true => Some(self.value()?),
+            false => None,
+        };
+        Ok(FieldDecl {
+            index: self.pools.field(class, name, ty),
+            access_flags,
+            ty: ty.to_owned(),
+            value,
+        })
+    }
+
+    /// `.method flags name(Params)Return`, up to and including `.end method`.
+    fn method_decl(&mut self, class: &str, at: usize) -> Result<MethodBuilder> {
+        let access_flags = self.access_flags()?;
+        let (_, name) = self.token("method name")?;
+        let (return_type, parameters) = self.proto_types()?;
+        let proto = self.pools.proto(return_type, &parameters);
+        let mut ins_size = match access_flags.contains(AccessFlags::Static) {
+            true => 0,
+            false => 1,
+        };
+        let mut parameter_registers = Vec::with_capacity(parameters.len());
+        for parameter in &parameters {
+            parameter_registers.push(ins_size);
+            ins_size += if is_wide(parameter) { 2 } else { 1 };
+        }
+        let mut method = MethodBuilder {
+            at,
+            index: self.pools.method(class, name, proto),
+            access_flags,
+            parameter_registers,
+            ins_size,
+            registers_size: None,
+            outs_size: 0,
+            asm: Assembler::new(),
+            labels: HashMap::new(),
+            references: Vec::new(),
+            catches: Vec::new(),
+            debug: Vec::new(),
+            parameter_names: vec![None; parameters.len()],
+            parameter_annotations: vec![Vec::new(); parameters.len()],
+            annotations: Vec::new(),
+        };
+
+        loop {
+            let at = self.skip();
+            if self.eat(":") {
+                let (_, name) = self.token("label name")?;
+                if method.labels.insert(name.to_owned(), at).is_some() {
+                    let kind = ParseErrorKind::DuplicateLabel(name.to_owned());
+                    return Err(self.error(at, kind));
+                }
+                let label = method.asm.named_label(name);
+                method.asm.bind(label);
+                continue;
+            }
+            let (at, token) = self.token("instruction or directive")?;
+            match token {
+                ".registers" | ".locals" => {
+                    let count = self.unsigned("register count")?;
+                    let count = match token {
+                        ".locals" => count + method.ins_size as uint,
+                        _ => count,
+                    };
+                    let count = ushort::try_from(count).map_err(|_| {
+                        self.error(at, ParseErrorKind::InvalidLiteral(count.to_string()))
+                    })?;
+                    method.registers_size = Some(count);
+                }
+                ".param" => self.param(&mut method)?,
+                ".annotation" => {
+                    self.pos = at;
+                    method.annotations.extend(self.annotations()?);
+                }
+                ".end" => {
+                    let (at, what) = self.token("end of method")?;
+                    match what {
+                        "method" => break,
+                        "local" => {
+                            let register = self.register(&method)? as uint;
+                            self.debug(
+                                &mut method,
+                                DebugEvent::Op(DebugInfoOp::EndLocal(register)),
+                            );
+                        }
+                        _ => return Err(self.expected(at, "end of method")),
+                    }
+                }
+                ".restart" => {
+                    self.expect_words(&["local"], "local")?;
+                    let register = self.register(&method)? as uint;
+                    self.debug(
+                        &mut method,
+                        DebugEvent::Op(DebugInfoOp::RestartLocal(register)),
+                    );
+                }
+                ".prologue" => self.debug(&mut method, DebugEvent::Op(DebugInfoOp::SetPrologueEnd)),
+                ".epilogue" => {
+                    self.debug(&mut method, DebugEvent::Op(DebugInfoOp::SetEpilogueBegin))
+                }
+                ".line" => {
+                    let line = self.unsigned("line number")?;
+                    self.debug(&mut method, DebugEvent::Line(line as ulong));
+                }
+                ".source" => {
+                    let name = match self.peek_char() {
+                        Some('"') => Some(self.string_idx()?),
+                        _ => None,
+                    };
+                    self.debug(&mut method, DebugEvent::Op(DebugInfoOp::SetFile(name)));
+                }
+                ".local" => {
+                    let op = self.local(&method)?;
+                    self.debug(&mut method, DebugEvent::Op(op));
+                }
+                ".catch" | ".catchall" => {
+                    let ty = match token {
+                        ".catch" => Some(self.ty()?),
+                        _ => None,
+                    };
+                    self.expect("{")?;
+                    let start = self.label_ref(&mut method)?;
+                    self.expect("..")?;
+                    let end = self.label_ref(&mut method)?;
+                    self.expect("}")?;
+                    let handler = self.label_ref(&mut method)?;
+                    method.catches.push(Catch {
+                        at,
+                        ty,
+                        start,
+                        end,
+                        handler,
+                    });
+                }
+                ".packed-switch" => {
+                    let first_key = self.integer(32, "first key")? as int;
+                    let mut targets = Vec::new();
+                    while !self.eat_words(&[".end", "packed-switch"]) {
+                        targets.push(self.label_ref(&mut method)?);
+                    }
+                    method.asm.packed_switch_payload(first_key, targets);
+                }
+                ".sparse-switch" => {
+                    let mut entries = Vec::new();
+                    while !self.eat_words(&[".end", "sparse-switch"]) {
+                        let key = self.integer(32, "switch key")? as int;
+                        self.expect("->")?;
+                        entries.push((key, self.label_ref(&mut method)?));
+                    }
+                    method.asm.sparse_switch_payload(entries);
+                }
+                ".array-data" => {
+                    let data = self.array_data()?;
+                    method.asm.fill_array_data_payload(data);
+                }
+                _ if token.starts_with('.') => {
+                    return Err(self.error(at, ParseErrorKind::UnknownDirective(token.to_owned())));
+                }
+                _ => self.instruction(&mut method, at, token)?,
+            }
+        }
+        Ok(method)
+    }
+
+    /// `.param pN[, "name"]`, optionally followed by annotations and `.end param`.
+    fn param(&mut self, method: &mut MethodBuilder) -> Result<()> {
+        let (at, token) = self.token("register")?;
+        let invalid = || self.error(at, ParseErrorKind::InvalidRegister(token.to_owned()));
+        let number = token
+            .get(1..)
+            .and_then(|n| n.parse::<ushort>().ok())
+            .ok_or_else(invalid)?;
+        // parameters are numbered from the first `p` register
+        let offset = match (&token[..1], method.registers_size) {
+            ("p", _) => number,
+            ("v", Some(registers_size)) => number
+                .checked_sub(registers_size - method.ins_size.min(registers_size))
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        let idx = method
+            .parameter_registers
+            .iter()
+            .position(|register| *register == offset)
+            .ok_or_else(invalid)?;
+        if self.eat(",") {
+            method.parameter_names[idx] = Some(self.string_idx()?);
+        }
+        let annotations = self.annotations()?;
+        if self.eat_words(&[".end", "param"]) {
+            method.parameter_annotations[idx] = annotations;
+        } else {
+            // without `.end param`, they belong to the method
+            method.annotations.extend(annotations);
+        }
+        Ok(())
+    }
+
+    /// `.local vN[, "name":Type[, "signature"]]`, where a missing name is `null` and a missing type `V`.
+    fn local(&mut self, method: &MethodBuilder) -> Result<DebugInfoOp> {
+        let register = self.register(method)? as uint;
+        if !self.eat(",") {
+            return Ok(DebugInfoOp::StartLocal {
+                register,
+                name: None,
+                ty: None,
+            });
+        }
+        let name = match self.eat_words(&["null"]) {
+            true => None,
+            false => Some(self.string_idx()?),
+        };
+        self.expect(":")?;
+        let ty = match self.descriptor()? {
+            "V" => None,
+            ty => Some(self.pools.ty(ty)),
+        };
+        if !self.eat(",") {
+            return Ok(DebugInfoOp::StartLocal { register, name, ty });
+        }
+        let signature = Some(self.string_idx()?);
+        Ok(DebugInfoOp::StartLocalExtended {
+            register,
+            name,
+            ty,
+            signature,
+        })
+    }
+
+    fn debug(&mut self, method: &mut MethodBuilder, event: DebugEvent) {
+        let label = method.asm.new_label();
+        method.asm.bind(label);
+        method.debug.push((label, event));
+    }
+
+    fn label_ref(&mut self, method: &mut MethodBuilder) -> Result<Label> {
+        let at = self.skip();
+        self.expect(":")?;
+        let (_, name) = self.token("label name")?;
+        method.references.push((name.to_owned(), at));
+        Ok(method.asm.named_label(name))
+    }
+
+    fn register(&mut self, method: &MethodBuilder) -> Result<Reg> {
+        let (at, token) = self.token("register")?;
+        let registers_size = method
+            .registers_size
+            .ok_or_else(|| self.error(at, ParseErrorKind::MissingRegisters))?;
+        let number = token.get(1..).and_then(|n| n.parse::<ushort>().ok());
+        let register = match (token.chars().next(), number) {
+            (Some('v'), Some(n)) => Some(n),
+            (Some('p'), Some(n)) => registers_size
+                .checked_sub(method.ins_size)
+                .and_then(|first| first.checked_add(n)),
+            _ => return Err(self.expected(at, "register")),
+        };
+        register
+            .filter(|register| *register < registers_size)
+            .ok_or_else(|| self.error(at, ParseErrorKind::InvalidRegister(token.to_owned())))
+    }
+
+    /// A register list like `{v0, p1}`.
+    fn register_list(&mut self, method: &MethodBuilder) -> Result<Vec<Reg>> {
+        self.expect("{")?;
+        let mut registers = Vec::new();
+        if self.eat("}") {
+            return Ok(registers);
+        }
+        loop {
+            registers.push(self.register(method)?);
+            if self.eat("}") {
+                return Ok(registers);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// A register range like `{v0 .. v3}`, returning the first register and the count.
+    fn register_range(&mut self, method: &MethodBuilder) -> Result<(Reg, ushort)> {
+        self.expect("{")?;
+        if self.eat("}") {
+            return Ok((0, 0));
+        }
+        let first = self.register(method)?;
+        let at = self.skip();
+        let last = match self.eat("..") {
+            true => self.register(method)?,
+            false => first,
+        };
+        self.expect("}")?;
+        if last < first {
+            let kind = ParseErrorKind::InvalidRegister(self.src[at..self.pos].trim().to_owned());
+            return Err(self.error(at, kind));
+        }
+        Ok((first, last - first + 1))
+    }
+
+    /// The literal operand of `op`, as the bits of the register it's loaded into.
+    fn literal(&mut self, op: Opcode) -> Result<long> {
+        let (at, number) = self.number("literal")?;
+        let wide = matches!(
+            op,
+            Opcode::ConstWide16 | Opcode::ConstWide32 | Opcode::ConstWide | Opcode::ConstWideHigh16
+        );
+        let value = match number {
+            Number::Integer(value, _) if wide => Some(value),
+            Number::Integer(value, _) => narrow(value, 32),
+            Number::Float(value) if !wide => Some(value.to_bits() as int as long),
+            Number::Double(value) if wide => Some(value.to_bits() as long),
+            _ => None,
+        };
+        value.ok_or_else(|| self.expected(at, "literal"))
+    }
+
+    fn reference(&mut self, op: Opcode) -> Result<uint> {
+        Ok(match op.index_kind() {
+            Some(IndexKind::String) => self.string_idx()?,
+            Some(IndexKind::Type) => self.ty()?,
+            Some(IndexKind::Field) => self.field()?,
+            Some(IndexKind::Method) => self.method()?,
+            Some(IndexKind::Proto) => self.proto()?,
+            Some(IndexKind::MethodHandle) => {
+                let handle = self.method_handle()?;
+                self.pools.method_handle(handle)
+            }
+            Some(IndexKind::CallSite) => self.call_site()?,
+            None => {
+                let at = self.skip();
+                self.expect("@")?;
+                self.unsigned("index")
+                    .map_err(|_| self.expected(at, "index"))?
+            }
+        })
+    }
+
+    /// A call site like `call_site_0("name", (I)V, args...)@Lcom/example/Foo;->bootstrap(...)`.
+    fn call_site(&mut self) -> Result<uint> {
+        self.token("call site")?;
+        self.expect("(")?;
+        let method_name = self.string_idx()?;
+        self.expect(",")?;
+        let method_type = self.proto()?;
+        let mut extra_args = Vec::new();
+        while !self.eat(")") {
+            self.expect(",")?;
+            extra_args.push(self.value()?);
+        }
+        self.expect("@")?;
+        let bootstrap = self.method()?;
+        let method_handle = self.pools.method_handle(MethodHandleEntry {
+            ty: MethodHandleType::InvokeStatic,
+            member: bootstrap,
+        });
+        Ok(self.pools.call_site(CallSiteItem {
+            method_handle,
+            method_name,
+            method_type,
+            extra_args,
+        }))
+    }
+
+    /// The elements of `.array-data`, up to and including `.end array-data`.
+    fn array_data(&mut self) -> Result<ArrayData> {
+        let at = self.skip();
+        let width = self.integer(8, "element width")?;
+        let bits = match width {
+            1 | 2 | 4 | 8 => width as u32 * 8,
+            _ => return Err(self.expected(at, "element width")),
+        };
+        let mut elements = Vec::new();
+        while !self.eat_words(&[".end", "array-data"]) {
+            let (at, number) = self.number("array element")?;
+            let value = match number {
+                Number::Integer(value, _) => narrow(value, bits),
+                Number::Float(value) if bits == 32 => Some(value.to_bits() as int as long),
+                Number::Double(value) if bits == 64 => Some(value.to_bits() as long),
+                _ => None,
+            };
+            elements.push(value.ok_or_else(|| self.expected(at, "array element"))?);
+        }
+        Ok(match width {
+            1 => ArrayData::Byte(elements.into_iter().map(|v| v as i8).collect()),
+            2 => ArrayData::Short(elements.into_iter().map(|v| v as i16).collect()),
+            4 => ArrayData::Int(elements.into_iter().map(|v| v as int).collect()),
+            _ => ArrayData::Long(elements),
+        })
+    }
+
+    fn instruction(&mut self, method: &mut MethodBuilder, at: usize, name: &str) -> Result<()> {
+        let op = Opcode::from_name(name)
+            .ok_or_else(|| self.error(at, ParseErrorKind::UnknownInstruction(name.to_owned())))?;
+        let insn = match op.format() {
+            Format::Format10x => Instruction::Format10x { op },
+            Format::Format12x | Format::Format22x | Format::Format32x => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let b = self.register(method)?;
+                match op.format() {
+                    Format::Format12x => Instruction::Format12x { op, a, b },
+                    Format::Format22x => Instruction::Format22x { op, a, b },
+                    _ => Instruction::Format32x { op, a, b },
+                }
+            }
+            Format::Format11x => Instruction::Format11x {
+                op,
+                a: self.register(method)?,
+            },
+            Format::Format11n
+            | Format::Format21s
+            | Format::Format21h
+            | Format::Format31i
+            | Format::Format51l => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let lit = self.literal(op)?;
+                match op.format() {
+                    Format::Format11n => Instruction::Format11n { op, a, lit },
+                    Format::Format21s => Instruction::Format21s { op, a, lit },
+                    Format::Format21h => Instruction::Format21h { op, a, lit },
+                    Format::Format31i => Instruction::Format31i { op, a, lit },
+                    _ => Instruction::Format51l { op, a, lit },
+                }
+            }
+            Format::Format10t | Format::Format20t | Format::Format30t => {
+                let target = self.label_ref(method)?;
+                method.asm.branch(op, &[], target);
+                return Ok(());
+            }
+            Format::Format21t => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let target = self.label_ref(method)?;
+                method.asm.branch(op, &[a], target);
+                return Ok(());
+            }
+            Format::Format22t => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let b = self.register(method)?;
+                self.expect(",")?;
+                let target = self.label_ref(method)?;
+                method.asm.branch(op, &[a, b], target);
+                return Ok(());
+            }
+            Format::Format31t => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let payload = self.label_ref(method)?;
+                method.asm.payload_ref(op, a, payload);
+                return Ok(());
+            }
+            Format::Format21c | Format::Format31c => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let index = self.reference(op)?;
+                match op.format() {
+                    Format::Format21c => Instruction::Format21c { op, a, index },
+                    _ => Instruction::Format31c { op, a, index },
+                }
+            }
+            Format::Format23x => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let b = self.register(method)?;
+                self.expect(",")?;
+                let c = self.register(method)?;
+                Instruction::Format23x { op, a, b, c }
+            }
+            Format::Format22b | Format::Format22s => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let b = self.register(method)?;
+                self.expect(",")?;
+                let lit = self.literal(op)?;
+                match op.format() {
+                    Format::Format22b => Instruction::Format22b { op, a, b, lit },
+                    _ => Instruction::Format22s { op, a, b, lit },
+                }
+            }
+            Format::Format22c => {
+                let a = self.register(method)?;
+                self.expect(",")?;
+                let b = self.register(method)?;
+                self.expect(",")?;
+                let index = self.reference(op)?;
+                Instruction::Format22c { op, a, b, index }
+            }
+            Format::Format35c | Format::Format45cc => {
+                let args = self.register_list(method)?;
+                self.expect(",")?;
+                let index = self.reference(op)?;
+                if name.starts_with("invoke") {
+                    method.outs_size = method.outs_size.max(args.len() as ushort);
+                }
+                match op.format() {
+                    Format::Format35c => Instruction::Format35c { op, args, index },
+                    _ => {
+                        self.expect(",")?;
+                        let proto = self.proto()?;
+                        Instruction::Format45cc {
+                            op,
+                            args,
+                            index,
+                            proto,
+                        }
+                    }
+                }
+            }
+            Format::Format3rc | Format::Format4rcc => {
+                let (first, count) = self.register_range(method)?;
+                self.expect(",")?;
+                let index = self.reference(op)?;
+                if name.starts_with("invoke") {
+                    method.outs_size = method.outs_size.max(count);
+                }
+                match op.format() {
+                    Format::Format3rc => Instruction::Format3rc {
+                        op,
+                        first,
+                        count,
+                        index,
+                    },
+                    _ => {
+                        self.expect(",")?;
+                        let proto = self.proto()?;
+                        Instruction::Format4rcc {
+                            op,
+                            first,
+                            count,
+                            index,
+                            proto,
+                        }
+                    }
+                }
+            }
+        };
+        // report operands that don't fit here, rather than when the method is assembled
+        insn.encode(&mut Vec::new())
+            .map_err(|e| self.error(at, ParseErrorKind::Encode(e)))?;
+        method.asm.push(insn);
+        Ok(())
+    }
+
+    /// Assembles the code of a parsed method, if it has any.
+    fn finish_method(&mut self, method: MethodBuilder) -> Result<Option<MethodCode>> {
+        for (name, at) in &method.references {
+            if !method.labels.contains_key(name) {
+                return Err(self.error(*at, ParseErrorKind::UndefinedLabel(name.clone())));
+            }
+        }
+        let Some(registers_size) = method.registers_size else {
+            return Ok(None);
+        };
+        let (insns, labels) = method
+            .asm
+            .assemble_with_labels()
+            .map_err(|e| self.error(method.at, ParseErrorKind::Assemble(e)))?;
+
+        // group the handlers by the range they cover, in order of appearance
+        let mut ranges: Vec<((uint, uint), usize, EncodedCatchHandler)> = Vec::new();
+        for catch in &method.catches {
+            let (start, end) = (labels[&catch.start], labels[&catch.end]);
+            if start >= end || end - start > ushort::MAX as uint {
+                return Err(self.error(catch.at, ParseErrorKind::InvalidTryRange));
+            }
+            let idx = match ranges.iter().position(|(range, ..)| *range == (start, end)) {
+                Some(idx) => idx,
+                None => {
+                    let handler = EncodedCatchHandler {
+                        size: 0,
+                        handlers: Vec::new(),
+                        catch_all_addr: None,
+                    };
+                    ranges.push(((start, end), catch.at, handler));
+                    ranges.len() - 1
+                }
+            };
+            let handler = &mut ranges[idx].2;
+            let addr = labels[&catch.handler] as ulong;
+            match catch.ty {
+                Some(ty) => handler.handlers.push(EncodedTypeAddrPair {
+                    type_id: ty as ulong,
+                    addr,
+                }),
+                None => {
+                    handler.catch_all_addr.get_or_insert(addr);
+                }
+            }
+        }
+        ranges.sort_by_key(|((start, _), ..)| *start);
+        for pair in ranges.windows(2) {
+            if pair[0].0 .1 > pair[1].0 .0 {
+                return Err(self.error(pair[1].1, ParseErrorKind::OverlappingTries));
+            }
+        }
+        let mut handlers: Vec<EncodedCatchHandler> = Vec::new();
+        let mut handler_indices = Vec::with_capacity(ranges.len());
+        for (_, _, mut handler) in ranges.iter().cloned() {
+            let count = handler.handlers.len() as i64;
+            handler.size = match handler.catch_all_addr {
+                Some(_) => -count,
+                None => count,
+            };
+            let key = |h: &EncodedCatchHandler| {
+                let pairs: Vec<_> = h.handlers.iter().map(|p| (p.type_id, p.addr)).collect();
+                (pairs, h.catch_all_addr)
+            };
+            let idx = match handlers.iter().position(|h| key(h) == key(&handler)) {
+                Some(idx) => idx,
+                None => {
+                    handlers.push(handler);
+                    handlers.len() - 1
+                }
+            };
+            handler_indices.push(idx);
+        }
+        let handlers = EncodedCatchHandlerList::from(handlers);
+        let tries = ranges
+            .iter()
+            .zip(handler_indices)
+            .map(|(((start, end), ..), idx)| TryItem {
+                start_addr: *start,
+                insn_count: (end - start) as ushort,
+                handler_off: handlers.handler_off(idx).unwrap(),
+            })
+            .collect::<Vec<_>>();
+
+        let debug_info =
+            match method.debug.is_empty() && method.parameter_names.iter().all(Option::is_none) {
+                true => None,
+                false => Some(debug_info(&method, &labels)),
+            };
+
+        let code = CodeItem {
+            registers_size,
+            ins_size: method.ins_size,
+            outs_size: method.outs_size,
+            tries_size: tries.len() as ushort,
+            debug_info_off: 0,
+            insns,
+            handlers: (!tries.is_empty()).then_some(handlers),
+            tries,
+        };
+        Ok(Some(MethodCode { code, debug_info }))
+    }
+}
+
+/// Encodes the debug directives of `method` into a state machine program.
+fn debug_info(method: &MethodBuilder, labels: &HashMap<Label, uint>) -> DebugInfoItem {
+    let line_start = method
+        .debug
+        .iter()
+        .find_map(|(_, event)| match event {
+            DebugEvent::Line(line) => Some(*line),
+            DebugEvent::Op(_) => None,
+        })
+        .unwrap_or(0);
+    let mut ops = Vec::new();
+    let mut address = 0;
+    let mut line = line_start;
+    for (label, event) in &method.debug {
+        let target = labels[label];
+        match event {
+            DebugEvent::Line(next) => {
+                let mut line_diff = next.wrapping_sub(line) as int;
+                let mut address_diff = target - address;
+                if DebugInfoOp::special(line_diff, 0).is_none() {
+                    ops.push(DebugInfoOp::AdvanceLine(line_diff));
+                    line_diff = 0;
+                }
+                if DebugInfoOp::special(line_diff, address_diff).is_none() {
+                    ops.push(DebugInfoOp::AdvancePc(address_diff));
+                    address_diff = 0;
+                }
+                ops.push(DebugInfoOp::special(line_diff, address_diff).unwrap());
+                line = *next;
+            }
+            DebugEvent::Op(op) => {
+                if target > address {
+                    ops.push(DebugInfoOp::AdvancePc(target - address));
+                }
+                ops.push(*op);
+            }
+        }
+        address = target;
+    }
+    DebugInfoItem {
+        line_start,
+        parameter_names: method
+            .parameter_names
+            .iter()
+            .map(|name| name.map(|idx| idx as ulong))
+            .collect(),
+        ops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smali::disassemble;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("-0x1"), Some(Number::Integer(-1, None)));
+        assert_eq!(parse_number("0x1f"), Some(Number::Integer(31, None)));
+        assert_eq!(parse_number("0x7ft"), Some(Number::Integer(127, Some('t'))));
+        assert_eq!(
+            parse_number("-0x8000s"),
+            Some(Number::Integer(-0x8000, Some('s')))
+        );
+        assert_eq!(
+            parse_number("-0x8000000000000000L"),
+            Some(Number::Integer(long::MIN, Some('L')))
+        );
+        assert_eq!(parse_number("42"), Some(Number::Integer(42, None)));
+        assert_eq!(parse_number("1.5E-4f"), Some(Number::Float(1.5e-4)));
+        assert_eq!(
+            parse_number("-Infinityf"),
+            Some(Number::Float(f32::NEG_INFINITY))
+        );
+        assert_eq!(parse_number("123.456"), Some(Number::Double(123.456)));
+        assert_eq!(parse_number("2d"), Some(Number::Double(2.0)));
+        assert_eq!(parse_number("x"), None);
+        assert!(matches!(parse_number("NaN"), Some(Number::Double(v)) if v.is_nan()));
+
+        assert_eq!(narrow(0xff, 8), Some(-1));
+        assert_eq!(narrow(-0x80, 8), Some(-0x80));
+        assert_eq!(narrow(0x100, 8), None);
+    }
+
+    #[test]
+    fn dex() {
+        let dex = crate::t::dex!();
+        let mut pools = Pools::new();
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let item = assemble(&disassemble(&class).unwrap(), &mut pools).unwrap();
+            let descriptor = class.descriptor().unwrap();
+            assert_eq!(pools.descriptor(item.def.class_idx), descriptor.as_str());
+            assert_eq!(item.def.access_flags, class.access_flags());
+            assert_eq!(item.interfaces.len(), class.interfaces().unwrap().len());
+            assert_eq!(
+                item.static_values.len(),
+                class.static_values().unwrap().len()
+            );
+
+            let methods = [
+                class.direct_methods().unwrap(),
+                class.virtual_methods().unwrap(),
+            ];
+            for method in methods.iter().flatten() {
+                let method_ref = method.method_ref().unwrap();
+                let parameters = method_ref.proto.parameters.iter().map(|p| p.as_str());
+                let proto = pools.proto(
+                    &method_ref.proto.return_type,
+                    &parameters.collect::<Vec<_>>(),
+                );
+                let idx = pools.method(&descriptor, &method_ref.name, proto);
+                let Some(code) = method.code().unwrap() else {
+                    assert!(!item.code.contains_key(&idx), "{method_ref}");
+                    continue;
+                };
+                let assembled = &item.code[&idx];
+                let ops = |code: &CodeItem| {
+                    code.instructions()
+                        .map(|insn| insn.unwrap().instruction.opcode())
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(ops(&assembled.code), ops(&code), "{method_ref}");
+                assert_eq!(assembled.code.insns.len(), code.insns.len());
+                assert_eq!(assembled.code.registers_size, code.registers_size);
+                assert_eq!(assembled.code.ins_size, code.ins_size);
+                assert_eq!(assembled.code.outs_size, code.outs_size);
+                let tries = |code: &CodeItem| {
+                    code.tries
+                        .iter()
+                        .map(|t| (t.start_addr, t.insn_count))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(tries(&assembled.code), tries(&code));
+
+                let original = method.debug_info_item(&code).unwrap();
+                assert_eq!(assembled.debug_info.is_some(), original.is_some());
+                if let (Some(assembled), Some(original)) = (&assembled.debug_info, original) {
+                    let size = code.insns.len() as uint;
+                    let (a, b) = (assembled.decode(size), original.decode(size));
+                    let positions = |info: &crate::raw::code_item::DebugInfo| {
+                        info.positions
+                            .iter()
+                            .map(|p| (p.address, p.line, p.prologue_end, p.epilogue_begin))
+                            .collect::<Vec<_>>()
+                    };
+                    assert_eq!(positions(&a), positions(&b), "{method_ref}");
+                    let locals = |info: &crate::raw::code_item::DebugInfo| {
+                        info.locals
+                            .iter()
+                            .map(|l| (l.register, l.start_address, l.end_address))
+                            .collect::<Vec<_>>()
+                    };
+                    assert_eq!(locals(&a), locals(&b), "{method_ref}");
+                    assert_eq!(
+                        assembled
+                            .parameter_names
+                            .iter()
+                            .map(Option::is_some)
+                            .collect::<Vec<_>>(),
+                        original
+                            .parameter_names
+                            .iter()
+                            .map(Option::is_some)
+                            .collect::<Vec<_>>()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn code() {
+        let source = r#"
+.class public LFoo;
+.super Ljava/lang/Object;
+
+.field private static final X:J = 0x10L
+
+.method public static sum([I)I
+    .locals 3
+    .param p0, "values"
+
+    .line 7
+    const/4 v0, 0x0
+    array-length v1, p0
+    :loop
+    if-ge v0, v1, :done
+    aget v2, p0, v0
+    add-int/lit8 v0, v0, 0x1   # comments are ignored
+    goto :loop
+    :done
+    .line 9
+    return v0
+.end method
+"#;
+        let mut pools = Pools::new();
+        let item = assemble(source, &mut pools).unwrap();
+        assert_eq!(
+            pools.descriptor(item.def.superclass_idx),
+            "Ljava/lang/Object;"
+        );
+        assert!(matches!(item.static_values[..], [EncodedValue::Long(16)]));
+        let class_data = item.class_data.unwrap();
+        assert_eq!(class_data.static_fields.len(), 1);
+        assert_eq!(class_data.direct_methods.len(), 1);
+
+        let method = &item.code[&(class_data.direct_methods[0].method_idx_diff as uint)];
+        assert_eq!(method.code.registers_size, 4);
+        assert_eq!(method.code.ins_size, 1);
+        let insns = method
+            .code
+            .instructions()
+            .map(|insn| insn.unwrap().instruction)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            insns[2],
+            Instruction::Format22t {
+                op: Opcode::IfGe,
+                a: 0,
+                b: 1,
+                offset: 7
+            }
+        );
+        assert_eq!(
+            insns[3],
+            Instruction::Format23x {
+                op: Opcode::Aget,
+                a: 2,
+                b: 3,
+                c: 0
+            }
+        );
+        assert_eq!(
+            insns[5],
+            Instruction::Format10t {
+                op: Opcode::Goto,
+                offset: -6
+            }
+        );
+        let debug_info = method.debug_info.as_ref().unwrap();
+        assert_eq!(debug_info.line_start, 7);
+        let lines = debug_info
+            .decode(method.code.insns.len() as uint)
+            .positions
+            .iter()
+            .map(|p| (p.address, p.line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![(0, 7), (9, 9)]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| {
+            let err = assemble(source, &mut Pools::new()).unwrap_err();
+            (err.line, err.column, err.kind)
+        };
+        assert_eq!(error(""), (1, 1, ParseErrorKind::MissingClass));
+        assert_eq!(
+            error(".class public LFoo;\n.bogus"),
+            (2, 1, ParseErrorKind::UnknownDirective(".bogus".to_owned()))
+        );
+        assert_eq!(
+            error(".class LFoo;\n.field public x:Q"),
+            (
+                2,
+                17,
+                ParseErrorKind::Expected("type descriptor", "Q".to_owned())
+            )
+        );
+        assert_eq!(
+            error(".class LFoo;\n.field x:Ljava/lang/String; = \"\\q\""),
+            (2, 32, ParseErrorKind::InvalidEscape)
+        );
+
+        let method = |body: &str| {
+            error(&format!(
+                ".class LFoo;\n.method static f(I)V\n    .registers 2\n{body}\n.end method"
+            ))
+        };
+        assert_eq!(
+            method("    frobnicate v0"),
+            (
+                4,
+                5,
+                ParseErrorKind::UnknownInstruction("frobnicate".to_owned())
+            )
+        );
+        assert_eq!(
+            method("    move v0, p1"),
+            (4, 14, ParseErrorKind::InvalidRegister("p1".to_owned()))
+        );
+        assert_eq!(
+            method("    goto :missing"),
+            (4, 10, ParseErrorKind::UndefinedLabel("missing".to_owned()))
+        );
+        assert_eq!(
+            method("    const/4 v0, 0x8"),
+            (
+                4,
+                5,
+                ParseErrorKind::Encode(EncodeError::LiteralOutOfRange(Opcode::Const4, 8))
+            )
+        );
+        assert_eq!(
+            method("    :a\n    nop\n    :a\n    return-void"),
+            (6, 5, ParseErrorKind::DuplicateLabel("a".to_owned()))
+        );
+    }
+}
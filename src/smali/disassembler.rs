@@ -0,0 +1,1320 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    dex::{
+        class::{Class, Field, Method},
+        method_handles::MethodHandleTarget,
+        methods::MethodRef,
+        strings::DexString,
+        DexFile,
+    },
+    raw::{
+        annotations::{Annotation, Visibility},
+        bytecode::{
+            ArrayData, DecodeError, DecodedInstruction, IndexKind, Instruction, Opcode, Reg,
+        },
+        code_item::{CodeItem, DebugInfoItem, DebugInfoOp},
+        encoded_value::{AnnotationElement, EncodedValue},
+        flags::AccessFlags,
+        int, long, uint, ushort,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisassembleError {
+    #[error("error decoding instructions: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("try block at {0} references a missing handler at offset {1}")]
+    InvalidHandler(uint, ushort),
+}
+
+/// Renders `class` in the smali syntax, the way `baksmali` does with its default options.
+///
+/// Labels are named after the address they point to, e.g. `:cond_1c`,
+/// and registers holding the method parameters are written as `p0`, `p1`, etc.
+pub fn disassemble(class: &Class<'_>) -> crate::Result<String> {
+    let mut w = Writer::default();
+    Smali { dex: class.dex() }.class(&mut w, class)?;
+    Ok(w.out)
+}
+
+const CLASS_FLAGS: &[(AccessFlags, &str)] = &[
+    (AccessFlags::Public, "public"),
+    (AccessFlags::Private, "private"),
+    (AccessFlags::Protected, "protected"),
+    (AccessFlags::Static, "static"),
+    (AccessFlags::Final, "final"),
+    (AccessFlags::Interface, "interface"),
+    (AccessFlags::Abstract, "abstract"),
+    (AccessFlags::Synthetic, "synthetic"),
+    (AccessFlags::Annotation, "annotation"),
+    (AccessFlags::Enum, "enum"),
+];
+
+const FIELD_FLAGS: &[(AccessFlags, &str)] = &[
+    (AccessFlags::Public, "public"),
+    (AccessFlags::Private, "private"),
+    (AccessFlags::Protected, "protected"),
+    (AccessFlags::Static, "static"),
+    (AccessFlags::Final, "final"),
+    (AccessFlags::Volatile, "volatile"),
+    (AccessFlags::Transient, "transient"),
+    (AccessFlags::Synthetic, "synthetic"),
+    (AccessFlags::Enum, "enum"),
+];
+
+const METHOD_FLAGS: &[(AccessFlags, &str)] = &[
+    (AccessFlags::Public, "public"),
+    (AccessFlags::Private, "private"),
+    (AccessFlags::Protected, "protected"),
+    (AccessFlags::Static, "static"),
+    (AccessFlags::Final, "final"),
+    (AccessFlags::Synchronized, "synchronized"),
+    (AccessFlags::Bridge, "bridge"),
+    (AccessFlags::Varargs, "varargs"),
+    (AccessFlags::Native, "native"),
+    (AccessFlags::Abstract, "abstract"),
+    (AccessFlags::Strict, "strictfp"),
+    (AccessFlags::Synthetic, "synthetic"),
+    (AccessFlags::Constructor, "constructor"),
+    (AccessFlags::DeclaredSynchronized, "declared-synchronized"),
+];
+
+/// Writes the names of the `flags` in `table`, each followed by a space.
+fn access_flags(flags: AccessFlags, table: &[(AccessFlags, &str)]) -> String {
+    table
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| format!("{name} "))
+        .collect()
+}
+
+// sort order of the items of a method at the same address, as in baksmali
+const PROLOGUE: i32 = -4;
+const SOURCE: i32 = -3;
+const LINE: i32 = -2;
+const LOCAL: i32 = -1;
+const LABEL: i32 = 0;
+const INSTRUCTION: i32 = 100;
+const TRY_END: i32 = 101;
+const CATCH: i32 = 102;
+const BLANK: i32 = i32::MAX;
+
+/// A line (or block of lines) in the body of a method.
+struct Item {
+    address: uint,
+    order: i32,
+    text: String,
+}
+
+/// Accumulates the output, indenting every line that isn't empty.
+#[derive(Default)]
+struct Writer {
+    out: String,
+    indent: usize,
+}
+
+impl Writer {
+    fn write(&mut self, text: impl AsRef<str>) {
+        for c in text.as_ref().chars() {
+            if c != '\n' && (self.out.is_empty() || self.out.ends_with('\n')) {
+                self.out.extend(std::iter::repeat_n(' ', self.indent));
+            }
+            self.out.push(c);
+        }
+    }
+
+    fn indent(&mut self, by: usize) {
+        self.indent += by;
+    }
+
+    fn deindent(&mut self, by: usize) {
+        self.indent -= by;
+    }
+}
+
+/// Names registers, using `pN` for those holding the method parameters.
+struct Registers {
+    registers_size: ushort,
+    ins_size: ushort,
+}
+
+impl Registers {
+    fn name(&self, reg: impl Into<uint>) -> String {
+        let reg = reg.into();
+        let first_parameter = self.registers_size.saturating_sub(self.ins_size) as uint;
+        if self.ins_size > 0 && reg >= first_parameter {
+            format!("p{}", reg - first_parameter)
+        } else {
+            format!("v{reg}")
+        }
+    }
+
+    fn list(&self, regs: &[Reg]) -> String {
+        let regs: Vec<_> = regs.iter().map(|reg| self.name(*reg)).collect();
+        format!("{{{}}}", regs.join(", "))
+    }
+
+    fn range(&self, first: Reg, count: ushort) -> String {
+        if count == 0 {
+            return "{}".to_string();
+        }
+        let last = first as uint + count as uint - 1;
+        format!("{{{} .. {}}}", self.name(first), self.name(last))
+    }
+}
+
+fn label(prefix: &str, address: uint) -> String {
+    format!(":{prefix}{address:x}")
+}
+
+/// Records a label at `offset` code units from `address` and returns its name.
+fn target(
+    labels: &mut BTreeSet<(uint, &'static str)>,
+    prefix: &'static str,
+    address: uint,
+    offset: int,
+) -> String {
+    let target = address.wrapping_add_signed(offset);
+    labels.insert((target, prefix));
+    label(prefix, target)
+}
+
+/// Escapes `s` as the contents of a smali string or character literal.
+fn escape(s: &str) -> String {
+    escape_utf16(s.encode_utf16())
+}
+
+fn escape_utf16(units: impl IntoIterator<Item = u16>) -> String {
+    let mut out = String::new();
+    for unit in units {
+        match unit {
+            0x27 | 0x22 | 0x5c => {
+                out.push('\\');
+                out.push(unit as u8 as char);
+            }
+            0x20..=0x7e => out.push(unit as u8 as char),
+            0x0a => out.push_str("\\n"),
+            0x0d => out.push_str("\\r"),
+            0x09 => out.push_str("\\t"),
+            _ => out.push_str(&format!("\\u{unit:04x}")),
+        }
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+/// Formats `value` as a signed hex literal, with an `L` suffix if it doesn't fit in an `int`.
+fn literal(value: long) -> String {
+    let suffix = if int::try_from(value).is_ok() {
+        ""
+    } else {
+        "L"
+    };
+    format!("{}{suffix}", hex(value))
+}
+
+fn hex(value: long) -> String {
+    if value < 0 {
+        format!("-0x{:x}", value.unsigned_abs())
+    } else {
+        format!("0x{value:x}")
+    }
+}
+
+/// Formats a number given in Rust's shortest `{:e}` notation like Java's `Double.toString`.
+fn java_decimal(scientific: String) -> String {
+    let (sign, scientific) = match scientific.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", scientific.as_str()),
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let digits = mantissa.replace('.', "");
+    if !(-3..7).contains(&exponent) {
+        let fraction = if digits.len() > 1 { &digits[1..] } else { "0" };
+        return format!("{sign}{}.{fraction}E{exponent}", &digits[..1]);
+    }
+    if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        return format!("{sign}0.{zeros}{digits}");
+    }
+    let point = exponent as usize + 1;
+    if digits.len() > point {
+        format!("{sign}{}.{}", &digits[..point], &digits[point..])
+    } else {
+        format!("{sign}{digits:0<point$}.0")
+    }
+}
+
+fn java_float(value: f32) -> String {
+    match value {
+        v if v.is_nan() => "NaN".to_string(),
+        f32::INFINITY => "Infinity".to_string(),
+        f32::NEG_INFINITY => "-Infinity".to_string(),
+        v => java_decimal(format!("{v:e}")),
+    }
+}
+
+fn java_double(value: f64) -> String {
+    match value {
+        v if v.is_nan() => "NaN".to_string(),
+        f64::INFINITY => "Infinity".to_string(),
+        f64::NEG_INFINITY => "-Infinity".to_string(),
+        v => java_decimal(format!("{v:e}")),
+    }
+}
+
+/// Formats `value` like a `DecimalFormat("0.####################E0")`,
+/// then drops the digits after a run of `000` or `999` in the mantissa.
+fn short_scientific(value: String) -> String {
+    let (mantissa, exponent) = value.split_once('e').unwrap_or((&value, "0"));
+    let mut mantissa = mantissa.to_string();
+    if let Some((int, fraction)) = mantissa.split_once('.') {
+        let fraction = fraction.trim_end_matches('0');
+        let fraction = ["000", "999"]
+            .iter()
+            .find_map(|run| fraction.find(run).map(|at| &fraction[..at]))
+            .unwrap_or(fraction);
+        mantissa = match fraction {
+            "" => int.to_string(),
+            _ => format!("{int}.{fraction}"),
+        };
+    }
+    format!("{mantissa}E{exponent}")
+}
+
+/// Whether the bits of `value` more likely represent a `float` than an `int`,
+/// using the same heuristic as baksmali.
+fn is_likely_float(value: int) -> bool {
+    let float = f32::from_bits(value as u32);
+    if value as u32 == 0x7fc00000
+        || float == f32::MAX
+        || float == std::f32::consts::PI
+        || float == std::f32::consts::E
+    {
+        return true;
+    }
+    if value == int::MAX || value == int::MIN {
+        return false;
+    }
+    // likely a resource id
+    let (package, kind, id) = (value >> 24, (value >> 16) & 0xff, value & 0xffff);
+    if (package == 0x7f || package == 1) && kind < 0x1f && id < 0xfff {
+        return false;
+    }
+    if float.is_nan() {
+        return false;
+    }
+    if float.is_infinite() {
+        return true;
+    }
+    let as_float = short_scientific(format!("{:.20e}", float as f64));
+    as_float.len() < short_scientific(format!("{:.20e}", value as f64)).len()
+}
+
+/// Same as [`is_likely_float`] for `double` and `long`.
+fn is_likely_double(value: long) -> bool {
+    let double = f64::from_bits(value as u64);
+    if value as u64 == 0x7ff8000000000000
+        || double == f64::MAX
+        || double == std::f64::consts::PI
+        || double == std::f64::consts::E
+    {
+        return true;
+    }
+    if value == long::MAX || value == long::MIN || double.is_nan() {
+        return false;
+    }
+    if double.is_infinite() {
+        return true;
+    }
+    // format the integer exactly, it may not be representable as a double
+    let digits = value.unsigned_abs().to_string();
+    let sign = if value < 0 { "-" } else { "" };
+    let as_long = short_scientific(format!(
+        "{sign}{}.{}e{}",
+        &digits[..1],
+        &digits[1..],
+        digits.len() - 1
+    ));
+    short_scientific(format!("{double:.20e}")).len() < as_long.len()
+}
+
+/// The comment baksmali appends to `const` instructions whose literal is likely a float.
+fn float_comment(op: Opcode, lit: long) -> String {
+    let wide = matches!(
+        op,
+        Opcode::ConstWide16 | Opcode::ConstWide32 | Opcode::ConstWide | Opcode::ConstWideHigh16
+    );
+    let comment = if wide {
+        if !is_likely_double(lit) {
+            return String::new();
+        }
+        match f64::from_bits(lit as u64) {
+            f64::INFINITY => "Double.POSITIVE_INFINITY".to_string(),
+            f64::NEG_INFINITY => "Double.NEGATIVE_INFINITY".to_string(),
+            v if v.is_nan() => "Double.NaN".to_string(),
+            f64::MAX => "Double.MAX_VALUE".to_string(),
+            std::f64::consts::PI => "Math.PI".to_string(),
+            std::f64::consts::E => "Math.E".to_string(),
+            v => java_double(v),
+        }
+    } else {
+        if !is_likely_float(lit as int) {
+            return String::new();
+        }
+        match f32::from_bits(lit as u32) {
+            f32::INFINITY => "Float.POSITIVE_INFINITY".to_string(),
+            f32::NEG_INFINITY => "Float.NEGATIVE_INFINITY".to_string(),
+            v if v.is_nan() => "Float.NaN".to_string(),
+            f32::MAX => "Float.MAX_VALUE".to_string(),
+            std::f32::consts::PI => "(float)Math.PI".to_string(),
+            std::f32::consts::E => "(float)Math.E".to_string(),
+            v => format!("{}f", java_float(v)),
+        }
+    };
+    format!("    # {comment}")
+}
+
+fn is_wide(descriptor: &str) -> bool {
+    descriptor == "J" || descriptor == "D"
+}
+
+/// A local variable as shown by `.local`, `.end local` and `.restart local`.
+#[derive(Clone, Default)]
+struct Local {
+    name: Option<DexString>,
+    ty: Option<DexString>,
+    signature: Option<DexString>,
+}
+
+impl Local {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.ty.is_none() && self.signature.is_none()
+    }
+}
+
+impl std::fmt::Display for Local {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", quote(name))?,
+            None => write!(f, "null")?,
+        }
+        write!(f, ":{}", self.ty.as_ref().map_or("V", |ty| ty.as_str()))?;
+        if let Some(signature) = &self.signature {
+            write!(f, ", {}", quote(signature))?;
+        }
+        Ok(())
+    }
+}
+
+struct Smali<'a> {
+    dex: &'a DexFile<'a>,
+}
+
+impl Smali<'_> {
+    fn class(&self, w: &mut Writer, class: &Class<'_>) -> crate::Result<()> {
+        let descriptor = class.descriptor()?;
+        w.write(format!(
+            ".class {}{descriptor}\n",
+            access_flags(class.access_flags(), CLASS_FLAGS)
+        ));
+        if let Some(superclass) = class.superclass()? {
+            w.write(format!(".super {superclass}\n"));
+        }
+        if let Some(source_file) = class.source_file()? {
+            w.write(format!(".source {}\n", quote(&source_file)));
+        }
+
+        let interfaces = class.interfaces()?;
+        if !interfaces.is_empty() {
+            w.write("\n# interfaces\n");
+            for interface in interfaces {
+                w.write(format!(".implements {interface}\n"));
+            }
+        }
+
+        let directory = class.annotations()?;
+        let annotations = match &directory {
+            Some(directory) => self.dex.annotation_set(directory.class_annotations_off)?,
+            None => Vec::new(),
+        };
+        if !annotations.is_empty() {
+            w.write("\n\n# annotations\n");
+            self.annotation_set(w, &annotations)?;
+        }
+        let field_annotations: HashMap<_, _> = directory
+            .iter()
+            .flat_map(|directory| &directory.field_annotations)
+            .map(|item| (item.field_idx, item.annotations_off))
+            .collect();
+        let method_annotations: HashMap<_, _> = directory
+            .iter()
+            .flat_map(|directory| &directory.method_annotations)
+            .map(|item| (item.method_idx, item.annotations_off))
+            .collect();
+        let parameter_annotations: HashMap<_, _> = directory
+            .iter()
+            .flat_map(|directory| &directory.parameter_annotations)
+            .map(|item| (item.method_idx, item.annotations_off))
+            .collect();
+
+        let static_fields = class.static_fields()?;
+        let static_values = class.static_values()?;
+        let set_in_clinit = self.fields_set_in_clinit(class, &descriptor)?;
+        if !static_fields.is_empty() {
+            w.write("\n\n# static fields");
+        }
+        for (i, field) in static_fields.iter().enumerate() {
+            w.write("\n");
+            let annotations_off = field_annotations.get(&field.index).copied();
+            let value = static_values.get(i);
+            self.field(w, field, value, &set_in_clinit, annotations_off)?;
+        }
+        let instance_fields = class.instance_fields()?;
+        if !instance_fields.is_empty() {
+            w.write("\n\n# instance fields");
+        }
+        for field in &instance_fields {
+            w.write("\n");
+            let annotations_off = field_annotations.get(&field.index).copied();
+            self.field(w, field, None, &set_in_clinit, annotations_off)?;
+        }
+
+        for (header, methods) in [
+            ("# direct methods", class.direct_methods()?),
+            ("# virtual methods", class.virtual_methods()?),
+        ] {
+            if !methods.is_empty() {
+                w.write(format!("\n\n{header}"));
+            }
+            for method in &methods {
+                w.write("\n");
+                let annotations_off = method_annotations.get(&method.index).copied();
+                let parameters_off = parameter_annotations.get(&method.index).copied();
+                self.method(w, method, annotations_off, parameters_off)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The `(name, type)` of the fields of `class` assigned by its static initializer.
+    fn fields_set_in_clinit(
+        &self,
+        class: &Class<'_>,
+        descriptor: &DexString,
+    ) -> crate::Result<HashSet<(DexString, DexString)>> {
+        let mut fields = HashSet::new();
+        for method in class.direct_methods()? {
+            if method.method_ref()?.name.as_str() != "<clinit>" {
+                continue;
+            }
+            let Some(code) = method.code()? else {
+                continue;
+            };
+            for insn in code.instructions() {
+                let insn = insn.map_err(DisassembleError::from)?;
+                let Instruction::Format21c { op, index, .. } = insn.instruction else {
+                    continue;
+                };
+                if (Opcode::Sput..=Opcode::SputShort).contains(&op) {
+                    let field = self.dex.fields().get(index)?;
+                    if field.class == *descriptor {
+                        fields.insert((field.name.clone(), field.ty.clone()));
+                    }
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    fn field(
+        &self,
+        w: &mut Writer,
+        field: &Field<'_>,
+        mut value: Option<&EncodedValue>,
+        set_in_clinit: &HashSet<(DexString, DexString)>,
+        annotations_off: Option<uint>,
+    ) -> crate::Result<()> {
+        let field_ref = field.field_ref()?;
+        let static_final = AccessFlags::Static | AccessFlags::Final;
+        if let Some(initial) = value {
+            let key = (field_ref.name.clone(), field_ref.ty.clone());
+            if field.access_flags.contains(static_final) && set_in_clinit.contains(&key) {
+                if initial.is_default() {
+                    value = None;
+                } else {
+                    w.write("# The value of this static final field might be set in the static constructor\n");
+                }
+            }
+        }
+        w.write(format!(
+            ".field {}{}:{}",
+            access_flags(field.access_flags, FIELD_FLAGS),
+            field_ref.name,
+            field_ref.ty
+        ));
+        if let Some(value) = value {
+            w.write(" = ");
+            self.value(w, value)?;
+        }
+        w.write("\n");
+        let annotations = self.dex.annotation_set(annotations_off.unwrap_or(0))?;
+        if !annotations.is_empty() {
+            w.indent(4);
+            self.annotation_set(w, &annotations)?;
+            w.deindent(4);
+            w.write(".end field\n");
+        }
+        Ok(())
+    }
+
+    fn method(
+        &self,
+        w: &mut Writer,
+        method: &Method<'_>,
+        annotations_off: Option<uint>,
+        parameters_off: Option<uint>,
+    ) -> crate::Result<()> {
+        let method_ref = method.method_ref()?;
+        w.write(format!(
+            ".method {}{}{}\n",
+            access_flags(method.access_flags, METHOD_FLAGS),
+            method_ref.name,
+            method_ref.proto
+        ));
+        w.indent(4);
+        let code = method.code()?;
+        let debug_info = match &code {
+            Some(code) => method.debug_info_item(code)?,
+            None => None,
+        };
+        if let Some(code) = &code {
+            w.write(format!(".registers {}\n", code.registers_size));
+        }
+        let parameter_annotations = self
+            .dex
+            .annotation_set_ref_list(parameters_off.unwrap_or(0))?;
+        self.parameters(
+            w,
+            method,
+            &method_ref,
+            debug_info.as_ref(),
+            &parameter_annotations,
+        )?;
+        let annotations = self.dex.annotation_set(annotations_off.unwrap_or(0))?;
+        self.annotation_set(w, &annotations)?;
+        if let Some(code) = &code {
+            w.write("\n");
+            let items = self.code(method, &method_ref, code, debug_info.as_ref())?;
+            for item in items {
+                w.write(item.text);
+                w.write("\n");
+            }
+        }
+        w.deindent(4);
+        w.write(".end method\n");
+        Ok(())
+    }
+
+    fn parameters(
+        &self,
+        w: &mut Writer,
+        method: &Method<'_>,
+        method_ref: &MethodRef,
+        debug_info: Option<&DebugInfoItem>,
+        annotations: &[Vec<Annotation>],
+    ) -> crate::Result<()> {
+        let mut register = if method.access_flags.contains(AccessFlags::Static) {
+            0
+        } else {
+            1
+        };
+        for (i, ty) in method_ref.proto.parameters.iter().enumerate() {
+            let name = debug_info.and_then(|info| info.parameter_names.get(i).copied().flatten());
+            let annotations = annotations.get(i).map_or(&[][..], Vec::as_slice);
+            if name.is_some() || !annotations.is_empty() {
+                w.write(format!(".param p{register}"));
+                if let Some(name) = name {
+                    let name = self.dex.strings().get_at(name as uint)?;
+                    w.write(format!(", {}", quote(&name)));
+                }
+                w.write(format!("    # {ty}\n"));
+                if !annotations.is_empty() {
+                    w.indent(4);
+                    self.annotation_set(w, annotations)?;
+                    w.deindent(4);
+                    w.write(".end param\n");
+                }
+            }
+            register += if is_wide(ty) { 2 } else { 1 };
+        }
+        Ok(())
+    }
+
+    /// The labels, debug directives, instructions and try blocks of `code`, in output order.
+    fn code(
+        &self,
+        method: &Method<'_>,
+        method_ref: &MethodRef,
+        code: &CodeItem,
+        debug_info: Option<&DebugInfoItem>,
+    ) -> crate::Result<Vec<Item>> {
+        let registers = Registers {
+            registers_size: code.registers_size,
+            ins_size: code.ins_size,
+        };
+        let instructions = code
+            .instructions()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DisassembleError::from)?;
+        // payload address -> address of the switch referencing it
+        let switches: HashMap<_, _> = instructions
+            .iter()
+            .filter(|insn| matches!(insn.instruction.opcode(), Some(op) if op.is_switch()))
+            .filter_map(|insn| {
+                let payload = insn.instruction.payload_address(insn.offset)?;
+                Some((payload, insn.offset))
+            })
+            .collect();
+
+        let mut items = Vec::new();
+        let mut labels = BTreeSet::new();
+        for (i, insn) in instructions.iter().enumerate() {
+            let text = self.instruction(insn, &registers, &switches, &mut labels)?;
+            items.push(Item {
+                address: insn.offset,
+                order: INSTRUCTION,
+                text,
+            });
+            if i + 1 != instructions.len() {
+                items.push(Item {
+                    address: insn.offset,
+                    order: BLANK,
+                    text: String::new(),
+                });
+            }
+        }
+        self.tries(code, &instructions, &mut labels, &mut items)?;
+        if let Some(debug_info) = debug_info {
+            self.debug_items(method, method_ref, code, debug_info, &registers, &mut items)?;
+        }
+        // labels at the same address are sorted by name
+        items.extend(labels.into_iter().map(|(address, prefix)| Item {
+            address,
+            order: LABEL,
+            text: label(prefix, address),
+        }));
+        items.sort_by_key(|item| (item.address, item.order));
+        Ok(items)
+    }
+
+    fn tries(
+        &self,
+        code: &CodeItem,
+        instructions: &[DecodedInstruction],
+        labels: &mut BTreeSet<(uint, &'static str)>,
+        items: &mut Vec<Item>,
+    ) -> crate::Result<()> {
+        let mut try_ends = BTreeSet::new();
+        for try_item in &code.tries {
+            let start = try_item.start_addr;
+            let end = start + try_item.insn_count as uint;
+            let handler = code
+                .handlers
+                .as_ref()
+                .and_then(|handlers| handlers.handler_at(try_item.handler_off))
+                .ok_or(DisassembleError::InvalidHandler(
+                    start,
+                    try_item.handler_off,
+                ))?;
+            // the end of the block is marked after the last instruction it covers
+            let last = instructions
+                .iter()
+                .rev()
+                .find(|insn| insn.offset < end)
+                .map_or(start, |insn| insn.offset);
+            labels.insert((start, "try_start_"));
+            if try_ends.insert((last, end)) {
+                items.push(Item {
+                    address: last,
+                    order: TRY_END,
+                    text: label("try_end_", end),
+                });
+            }
+            let range = format!(
+                "{{{} .. {}}}",
+                label("try_start_", start),
+                label("try_end_", end)
+            );
+            for pair in &handler.handlers {
+                let address = pair.addr as uint;
+                let ty = self.dex.types().get(pair.type_id as uint)?;
+                labels.insert((address, "catch_"));
+                items.push(Item {
+                    address: last,
+                    order: CATCH,
+                    text: format!(".catch {ty} {range} {}", label("catch_", address)),
+                });
+            }
+            if let Some(address) = handler.catch_all_addr {
+                let address = address as uint;
+                labels.insert((address, "catchall_"));
+                items.push(Item {
+                    address: last,
+                    order: CATCH,
+                    text: format!(".catchall {range} {}", label("catchall_", address)),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn debug_items(
+        &self,
+        method: &Method<'_>,
+        method_ref: &MethodRef,
+        code: &CodeItem,
+        debug_info: &DebugInfoItem,
+        registers: &Registers,
+        items: &mut Vec<Item>,
+    ) -> crate::Result<()> {
+        let string = |idx: Option<uint>| idx.map(|idx| self.dex.strings().get_at(idx)).transpose();
+        let ty = |idx: Option<uint>| idx.map(|idx| self.dex.types().get(idx)).transpose();
+
+        // the parameters are named locals from the start of the method
+        let mut locals: HashMap<uint, Local> = HashMap::new();
+        let is_static = method.access_flags.contains(AccessFlags::Static);
+        let parameters = &method_ref.proto.parameters;
+        let parameters_size = parameters
+            .iter()
+            .map(|ty| if is_wide(ty) { 2 } else { 1 })
+            .sum::<uint>()
+            + if is_static { 0 } else { 1 };
+        let mut register = (code.registers_size as uint).saturating_sub(parameters_size);
+        if !is_static {
+            let this = Local {
+                name: Some(DexString::new("this".to_string())),
+                ty: Some(method_ref.class.clone()),
+                signature: None,
+            };
+            locals.insert(register, this);
+            register += 1;
+        }
+        for (i, parameter) in parameters.iter().enumerate() {
+            let name = debug_info.parameter_names.get(i).copied().flatten();
+            let local = Local {
+                name: string(name.map(|name| name as uint))?,
+                ty: Some(parameter.clone()),
+                signature: None,
+            };
+            locals.insert(register, local);
+            register += if is_wide(parameter) { 2 } else { 1 };
+        }
+
+        let mut address: uint = 0;
+        let mut line = debug_info.line_start as uint;
+        for op in &debug_info.ops {
+            let (address_diff, line_diff) = op.advance();
+            address = address.wrapping_add(address_diff);
+            line = line.wrapping_add_signed(line_diff);
+            let (order, text) = match *op {
+                DebugInfoOp::StartLocal {
+                    register,
+                    name,
+                    ty: t,
+                } => {
+                    let local = Local {
+                        name: string(name)?,
+                        ty: ty(t)?,
+                        signature: None,
+                    };
+                    (
+                        LOCAL,
+                        self.start_local(registers, register, local, &mut locals),
+                    )
+                }
+                DebugInfoOp::StartLocalExtended {
+                    register,
+                    name,
+                    ty: t,
+                    signature,
+                } => {
+                    let local = Local {
+                        name: string(name)?,
+                        ty: ty(t)?,
+                        signature: string(signature)?,
+                    };
+                    (
+                        LOCAL,
+                        self.start_local(registers, register, local, &mut locals),
+                    )
+                }
+                DebugInfoOp::EndLocal(register) => {
+                    let local = locals.get(&register).cloned().unwrap_or_default();
+                    let text = format!(".end local {}", registers.name(register));
+                    (LOCAL, with_local_comment(text, &local))
+                }
+                DebugInfoOp::RestartLocal(register) => {
+                    let local = locals.get(&register).cloned().unwrap_or_default();
+                    let text = format!(".restart local {}", registers.name(register));
+                    (LOCAL, with_local_comment(text, &local))
+                }
+                DebugInfoOp::SetPrologueEnd => (PROLOGUE, ".prologue".to_string()),
+                DebugInfoOp::SetEpilogueBegin => (PROLOGUE, ".epilogue".to_string()),
+                DebugInfoOp::SetFile(name) => match string(name)? {
+                    Some(name) => (SOURCE, format!(".source {}", quote(&name))),
+                    None => (SOURCE, ".source".to_string()),
+                },
+                DebugInfoOp::Special(_) => (LINE, format!(".line {line}")),
+                DebugInfoOp::AdvancePc(_) | DebugInfoOp::AdvanceLine(_) => continue,
+            };
+            items.push(Item {
+                address,
+                order,
+                text,
+            });
+        }
+        Ok(())
+    }
+
+    fn start_local(
+        &self,
+        registers: &Registers,
+        register: uint,
+        local: Local,
+        locals: &mut HashMap<uint, Local>,
+    ) -> String {
+        let mut text = format!(".local {}", registers.name(register));
+        if !local.is_empty() {
+            text.push_str(&format!(", {local}"));
+        }
+        locals.insert(register, local);
+        text
+    }
+
+    fn instruction(
+        &self,
+        insn: &DecodedInstruction,
+        registers: &Registers,
+        switches: &HashMap<uint, uint>,
+        labels: &mut BTreeSet<(uint, &'static str)>,
+    ) -> crate::Result<String> {
+        use Instruction::*;
+        let address = insn.offset;
+        let r = |reg: &Reg| registers.name(*reg);
+        Ok(match &insn.instruction {
+            Format10x { op } => op.to_string(),
+            Format12x { op, a, b } | Format22x { op, a, b } | Format32x { op, a, b } => {
+                format!("{op} {}, {}", r(a), r(b))
+            }
+            Format11x { op, a } => format!("{op} {}", r(a)),
+            Format11n { op, a, lit } => format!("{op} {}, {}", r(a), literal(*lit)),
+            Format21s { op, a, lit }
+            | Format21h { op, a, lit }
+            | Format31i { op, a, lit }
+            | Format51l { op, a, lit } => {
+                let comment = float_comment(*op, *lit);
+                format!("{op} {}, {}{comment}", r(a), literal(*lit))
+            }
+            Format10t { op, offset } | Format20t { op, offset } | Format30t { op, offset } => {
+                format!("{op} {}", target(labels, "goto_", address, *offset))
+            }
+            Format21t { op, a, offset } => {
+                let target = target(labels, "cond_", address, *offset);
+                format!("{op} {}, {target}", r(a))
+            }
+            Format22t { op, a, b, offset } => {
+                let target = target(labels, "cond_", address, *offset);
+                format!("{op} {}, {}, {target}", r(a), r(b))
+            }
+            Format31t { op, a, offset } => {
+                let prefix = match op {
+                    Opcode::PackedSwitch => "pswitch_data_",
+                    Opcode::SparseSwitch => "sswitch_data_",
+                    _ => "array_",
+                };
+                let target = target(labels, prefix, address, *offset);
+                format!("{op} {}, {target}", r(a))
+            }
+            Format21c { op, a, index } | Format31c { op, a, index } => {
+                format!("{op} {}, {}", r(a), self.reference(*op, *index)?)
+            }
+            Format22c { op, a, b, index } => {
+                let reference = self.reference(*op, *index)?;
+                format!("{op} {}, {}, {reference}", r(a), r(b))
+            }
+            Format23x { op, a, b, c } => format!("{op} {}, {}, {}", r(a), r(b), r(c)),
+            Format22b { op, a, b, lit } | Format22s { op, a, b, lit } => {
+                format!("{op} {}, {}, {}", r(a), r(b), literal(*lit))
+            }
+            Format35c { op, args, index } => {
+                let reference = self.reference(*op, *index)?;
+                format!("{op} {}, {reference}", registers.list(args))
+            }
+            Format3rc {
+                op,
+                first,
+                count,
+                index,
+            } => {
+                let reference = self.reference(*op, *index)?;
+                format!("{op} {}, {reference}", registers.range(*first, *count))
+            }
+            Format45cc {
+                op,
+                args,
+                index,
+                proto,
+            } => {
+                let method = self.reference(*op, *index)?;
+                let proto = self.dex.protos().get(*proto)?;
+                format!("{op} {}, {method}, {proto}", registers.list(args))
+            }
+            Format4rcc {
+                op,
+                first,
+                count,
+                index,
+                proto,
+            } => {
+                let method = self.reference(*op, *index)?;
+                let proto = self.dex.protos().get(*proto)?;
+                let range = registers.range(*first, *count);
+                format!("{op} {range}, {method}, {proto}")
+            }
+            PackedSwitchPayload(payload) => {
+                let base = switches.get(&address).copied().unwrap_or(address);
+                let mut text = format!(".packed-switch {}\n", hex(payload.first_key as long));
+                for offset in &payload.targets {
+                    text.push_str(&format!(
+                        "    {}\n",
+                        target(labels, "pswitch_", base, *offset)
+                    ));
+                }
+                text.push_str(".end packed-switch");
+                text
+            }
+            SparseSwitchPayload(payload) => {
+                let base = switches.get(&address).copied().unwrap_or(address);
+                let mut text = ".sparse-switch\n".to_string();
+                for (key, offset) in payload.keys.iter().zip(&payload.targets) {
+                    let target = target(labels, "sswitch_", base, *offset);
+                    text.push_str(&format!("    {} -> {target}\n", hex(*key as long)));
+                }
+                text.push_str(".end sparse-switch");
+                text
+            }
+            FillArrayDataPayload(payload) => {
+                let data = &payload.data;
+                let elements: Vec<String> = match data {
+                    ArrayData::Byte(values) => values
+                        .iter()
+                        .map(|v| format!("{}t", hex(*v as long)))
+                        .collect(),
+                    ArrayData::Short(values) => values
+                        .iter()
+                        .map(|v| format!("{}s", hex(*v as long)))
+                        .collect(),
+                    ArrayData::Int(values) => values.iter().map(|v| hex(*v as long)).collect(),
+                    ArrayData::Long(values) => {
+                        values.iter().map(|v| format!("{}L", hex(*v))).collect()
+                    }
+                };
+                let mut text = format!(".array-data {}\n", data.element_width());
+                for element in elements {
+                    text.push_str(&format!("    {element}\n"));
+                }
+                text.push_str(".end array-data");
+                text
+            }
+        })
+    }
+
+    /// Formats the entry at `index` of the pool referenced by `op`.
+    fn reference(&self, op: Opcode, index: uint) -> crate::Result<String> {
+        let Some(kind) = op.index_kind() else {
+            return Ok(format!("@{index}"));
+        };
+        Ok(match kind {
+            IndexKind::String => quote(&self.dex.strings().get_at(index)?),
+            IndexKind::Type => self.dex.types().get(index)?.to_string(),
+            IndexKind::Field => self.dex.fields().get(index)?.to_string(),
+            IndexKind::Method => self.dex.methods().get(index)?.to_string(),
+            IndexKind::Proto => self.dex.protos().get(index)?.to_string(),
+            IndexKind::MethodHandle => self.dex.method_handles().get(index)?.to_string(),
+            IndexKind::CallSite => self.call_site(index)?,
+        })
+    }
+
+    /// Formats a call site as `call_site_0("name", (I)V, args...)@Lcom/example/Foo;->bootstrap(...)`.
+    fn call_site(&self, index: uint) -> crate::Result<String> {
+        let call_site = self.dex.call_sites().get(index)?;
+        let mut w = Writer::default();
+        w.write(format!(
+            "call_site_{index}({}, {}",
+            quote(&call_site.method_name),
+            call_site.method_type
+        ));
+        for arg in &call_site.extra_args {
+            w.write(", ");
+            self.value(&mut w, arg)?;
+        }
+        let bootstrap = match &call_site.method_handle.target {
+            MethodHandleTarget::Method(method) => method.to_string(),
+            MethodHandleTarget::Field(field) => field.to_string(),
+        };
+        w.write(format!(")@{bootstrap}"));
+        Ok(w.out)
+    }
+
+    fn annotation_set(&self, w: &mut Writer, annotations: &[Annotation]) -> crate::Result<()> {
+        for (i, annotation) in annotations.iter().enumerate() {
+            if i > 0 {
+                w.write("\n");
+            }
+            let visibility = match annotation.visibility {
+                Visibility::Build => "build",
+                Visibility::Runtime => "runtime",
+                Visibility::System => "system",
+            };
+            let ty = self
+                .dex
+                .types()
+                .get(annotation.annotation.type_idx as uint)?;
+            w.write(format!(".annotation {visibility} {ty}\n"));
+            self.annotation_elements(w, &annotation.annotation.elements)?;
+            w.write(".end annotation\n");
+        }
+        Ok(())
+    }
+
+    fn annotation_elements(
+        &self,
+        w: &mut Writer,
+        elements: &[AnnotationElement],
+    ) -> crate::Result<()> {
+        w.indent(4);
+        for element in elements {
+            let name = self.dex.strings().get_at(element.name_idx as uint)?;
+            w.write(format!("{name} = "));
+            self.value(w, &element.value)?;
+            w.write("\n");
+        }
+        w.deindent(4);
+        Ok(())
+    }
+
+    fn value(&self, w: &mut Writer, value: &EncodedValue) -> crate::Result<()> {
+        match value {
+            EncodedValue::Byte(v) => w.write(format!("{}t", hex(*v as long))),
+            EncodedValue::Short(v) => w.write(format!("{}s", hex(*v as long))),
+            EncodedValue::Char(v) => w.write(format!("'{}'", escape_utf16([*v]))),
+            EncodedValue::Int(v) => w.write(hex(*v as long)),
+            EncodedValue::Long(v) => w.write(format!("{}L", hex(*v))),
+            EncodedValue::Float(v) => w.write(format!("{}f", java_float(*v))),
+            EncodedValue::Double(v) => w.write(java_double(*v)),
+            EncodedValue::MethodType(idx) => w.write(self.dex.protos().get(*idx)?.to_string()),
+            EncodedValue::MethodHandle(idx) => {
+                w.write(self.dex.method_handles().get(*idx)?.to_string())
+            }
+            EncodedValue::String(idx) => w.write(quote(&self.dex.strings().get_at(*idx)?)),
+            EncodedValue::Type(idx) => w.write(self.dex.types().get(*idx)?.as_str()),
+            EncodedValue::Field(idx) => w.write(self.dex.fields().get(*idx)?.to_string()),
+            EncodedValue::Method(idx) => w.write(self.dex.methods().get(*idx)?.to_string()),
+            EncodedValue::Enum(idx) => w.write(format!(".enum {}", self.dex.fields().get(*idx)?)),
+            EncodedValue::Array(values) => {
+                if values.is_empty() {
+                    w.write("{}");
+                    return Ok(());
+                }
+                w.write("{\n");
+                w.indent(4);
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        w.write(",\n");
+                    }
+                    self.value(w, value)?;
+                }
+                w.deindent(4);
+                w.write("\n}");
+            }
+            EncodedValue::Annotation(annotation) => {
+                let ty = self.dex.types().get(annotation.type_idx as uint)?;
+                w.write(format!(".subannotation {ty}\n"));
+                self.annotation_elements(w, &annotation.elements)?;
+                w.write(".end subannotation");
+            }
+            EncodedValue::Null => w.write("null"),
+            EncodedValue::Boolean(v) => w.write(v.to_string()),
+        }
+        Ok(())
+    }
+}
+
+fn with_local_comment(mut text: String, local: &Local) -> String {
+    if !local.is_empty() {
+        text.push_str(&format!("    # {local}"));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        assert_eq!(literal(-1), "-0x1");
+        assert_eq!(literal(0x7fffffff), "0x7fffffff");
+        assert_eq!(literal(0x80000000), "0x80000000L");
+        assert_eq!(literal(long::MIN), "-0x8000000000000000L");
+        assert_eq!(escape("a\"b'\\\n\u{e9}"), "a\\\"b\\'\\\\\\n\\u00e9");
+
+        assert_eq!(java_float(1.0), "1.0");
+        assert_eq!(java_float(-0.0), "-0.0");
+        assert_eq!(java_float(0.001), "0.001");
+        assert_eq!(java_float(1.5e-4), "1.5E-4");
+        assert_eq!(java_float(1234567.0), "1234567.0");
+        assert_eq!(java_float(1.0e7), "1.0E7");
+        assert_eq!(java_double(0.1), "0.1");
+        assert_eq!(java_double(123.456), "123.456");
+
+        assert_eq!(float_comment(Opcode::Const, 0x3f800000), "    # 1.0f");
+        assert_eq!(float_comment(Opcode::Const, 0x7f010000), "");
+        assert_eq!(float_comment(Opcode::Const, 1000), "");
+        assert_eq!(
+            float_comment(Opcode::ConstHigh16, 0x7f800000),
+            "    # Float.POSITIVE_INFINITY"
+        );
+        assert_eq!(
+            float_comment(Opcode::ConstWideHigh16, 0x4000000000000000),
+            "    # 2.0"
+        );
+        assert_eq!(float_comment(Opcode::ConstWide16, 100), "");
+    }
+
+    #[test]
+    fn dex() {
+        let dex = crate::t::dex!();
+        let base = dex
+            .class_by_descriptor("Lcom/example/Base;")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            disassemble(&base).unwrap(),
+            r#".class public abstract Lcom/example/Base;
+.super Ljava/lang/Object;
+.source "Base.java"
+
+# interfaces
+.implements Ljava/lang/Runnable;
+
+
+# static fields
+.field public static final CONST:I = 0x2a
+
+.field public static final NAME:Ljava/lang/String; = "hello"
+
+
+# instance fields
+.field public count:I
+
+
+# direct methods
+.method public constructor <init>()V
+    .registers 1
+
+    invoke-direct {p0}, Ljava/lang/Object;-><init>()V
+
+    return-void
+.end method
+
+
+# virtual methods
+.method public abstract run()V
+.end method
+"#
+        );
+
+        let main = dex
+            .class_by_descriptor("Lcom/example/Main;")
+            .unwrap()
+            .unwrap();
+        let smali = disassemble(&main).unwrap();
+        assert!(smali.starts_with(
+            r#".class public Lcom/example/Main;
+.super Lcom/example/Base;
+.source "Main.java"
+
+
+# annotations
+.annotation runtime Lcom/example/Anno;
+    value = "x"
+.end annotation
+"#
+        ));
+        for method in [
+            r#"
+.method public static add(II)I
+    .registers 3
+    .param p0, "a"    # I
+    .param p1, "b"    # I
+
+    .prologue
+    .line 10
+    add-int v0, p0, p1
+
+    .epilogue
+    .line 12
+    return v0
+.end method
+"#,
+            r#"
+.method public static sw(I)I
+    .registers 2
+
+    packed-switch p0, :pswitch_data_8
+
+    :pswitch_3
+    const/4 v0, -0x1
+
+    return v0
+
+    :pswitch_5
+    const/4 v0, 0x1
+
+    return v0
+
+    nop
+
+    :pswitch_data_8
+    .packed-switch 0xa
+        :pswitch_5
+        :pswitch_3
+    .end packed-switch
+.end method
+"#,
+            r#"
+    invoke-virtual {v0, v1}, Ljava/io/PrintStream;->println(Ljava/lang/String;)V
+    :try_end_c
+    .catch Ljava/lang/Exception; {:try_start_2 .. :try_end_c} :catch_d
+    .catchall {:try_start_2 .. :try_end_c} :catchall_f
+
+    .line 12
+    .restart local v0    # "i":I
+    :try_start_c
+    return-void
+"#,
+            r#"
+    const-method-handle v0, static-get@Lcom/example/Base;->CONST:I
+"#,
+        ] {
+            assert!(smali.contains(method), "{method}");
+        }
+    }
+}
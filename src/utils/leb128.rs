@@ -17,6 +17,12 @@ impl Uleb128 {
         self.count
     }
 
+    /// Return how many bytes `value` takes up once encoded
+    #[inline]
+    pub fn len_of(value: u64) -> usize {
+        ((u64::BITS - value.leading_zeros()).max(1) as usize).div_ceil(7)
+    }
+
     /// Read a variable length u64 from `src` at `offset`
     #[inline]
     pub fn read(src: &[u8], offset: &mut usize) -> scroll::Result<u64> {
@@ -60,6 +66,14 @@ impl Sleb128 {
         self.count
     }
 
+    /// Return how many bytes `value` takes up once encoded
+    #[inline]
+    pub fn len_of(value: i64) -> usize {
+        // the magnitude, plus the sign bit
+        let magnitude = if value < 0 { !value } else { value };
+        ((i64::BITS - magnitude.leading_zeros() + 1) as usize).div_ceil(7)
+    }
+
     /// Read a variable length i64 from `src` at `offset`
     #[inline]
     pub fn read(src: &[u8], offset: &mut usize) -> scroll::Result<i64> {
@@ -225,3 +239,32 @@ impl TryIntoCtx for Sleb128 {
         Ok(*offset)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn len_of() {
+        let mut buf = [0u8; 10];
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX] {
+            let len = buf.pwrite(Uleb128 { value, count: 0 }, 0).unwrap();
+            assert_eq!(Uleb128::len_of(value), len, "{value:#x}");
+        }
+        for value in [
+            0,
+            1,
+            -1,
+            0x3f,
+            0x40,
+            -0x40,
+            -0x41,
+            i32::MIN as i64,
+            i64::MAX,
+            i64::MIN,
+        ] {
+            let len = buf.pwrite(Sleb128 { value, count: 0 }, 0).unwrap();
+            assert_eq!(Sleb128::len_of(value), len, "{value:#x}");
+        }
+    }
+}
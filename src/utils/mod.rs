@@ -35,6 +35,18 @@ macro_rules! count_delim {
     };
 }
 
+/// Implements [`EncodedLen`][crate::raw::buffer::EncodedLen] for plain structs
+/// whose fields are written back to back without padding.
+macro_rules! sized_encoded_len {
+    ($($struct:ty),+ $(,)?) => {
+        $(impl crate::raw::buffer::EncodedLen for $struct {
+            fn encoded_len(&self) -> usize {
+                std::mem::size_of::<Self>()
+            }
+        })+
+    };
+}
+
 #[cfg(debug_assertions)]
 macro_rules! assert_sz {
     ($($const:ident; $struct:ty)+) => {
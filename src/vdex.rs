@@ -0,0 +1,410 @@
+//! Vdex files, which ART keeps next to the compiled code of an app.
+//!
+//! They hold the app's dex or compact dex files, their checksums, and the results of verifying them.
+//! Since version 027 (Android 12), a vdex file is described by a table of [`VdexSection`]s.
+//! Earlier versions have a fixed layout, which [`VdexFile`] presents as the same table.
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use scroll::{ctx::TryFromCtx, Pread};
+
+use crate::{
+    dex::DexFile,
+    raw::{compact::COMPACT_MAGIC, header::FILE_SIZE_OFF, ubyte, uint},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum VdexError {
+    #[error("invalid magic bytes")]
+    InvalidMagic,
+    #[error("unsupported vdex version {0}")]
+    UnsupportedVersion(String),
+    #[error("{0:?} section is out of bounds")]
+    SectionOutOfBounds(VdexSection),
+    #[error("dex file {0} is out of bounds")]
+    DexOutOfBounds(usize),
+    #[error("read error: {0}")]
+    Scroll(#[from] scroll::Error),
+}
+
+const MAGIC: &[ubyte; 4] = b"vdex";
+/// Android 8.0
+pub const V006: uint = 6;
+/// Android 8.1
+pub const V010: uint = 10;
+/// Android 9 and 10, adds a separate header for the dex section.
+pub const V019: uint = 19;
+/// Android 11, adds the boot classpath checksums and class loader context.
+pub const V021: uint = 21;
+/// Android 12, replaces the fixed layout with a section table.
+pub const V027: uint = 27;
+
+/// The kinds of sections of a vdex file.
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum VdexSection {
+    /// The checksum of each dex file, as a `uint`.
+    Checksum = 0,
+    /// The dex files, each aligned to 4 bytes.
+    DexFile = 1,
+    /// The verification results of each class, only meaningful to ART.
+    VerifierDeps = 2,
+    TypeLookupTable = 3,
+}
+
+/// An entry of the section table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VdexSectionHeader {
+    /// A [`VdexSection`], unless the file is newer than this crate.
+    pub kind: uint,
+    /// Offset from the start of the file to the section.
+    pub offset: uint,
+    pub size: uint,
+}
+
+impl VdexSectionHeader {
+    pub fn section(&self) -> Option<VdexSection> {
+        VdexSection::from_u32(self.kind)
+    }
+}
+
+impl<'a> TryFromCtx<'a, scroll::Endian> for VdexSectionHeader {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let kind = src.gread_with(offset, ctx)?;
+        let section_offset = src.gread_with(offset, ctx)?;
+        let size = src.gread_with(offset, ctx)?;
+        Ok((
+            Self {
+                kind,
+                offset: section_offset,
+                size,
+            },
+            *offset,
+        ))
+    }
+}
+
+/// A dex or compact dex file embedded in a vdex file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedDex {
+    /// Offset from the start of the vdex file to the header of the dex file.
+    pub offset: uint,
+    pub file_size: uint,
+    /// The checksum of the original dex file, from the [`VdexSection::Checksum`] section.
+    pub checksum: uint,
+    pub compact: bool,
+}
+
+pub struct VdexFile<'a> {
+    src: &'a [u8],
+    version: uint,
+    sections: Vec<VdexSectionHeader>,
+    dex_files: Vec<EmbeddedDex>,
+}
+
+impl<'a> VdexFile<'a> {
+    /// Parses the header and section table of `src`, and finds the dex files it contains.
+    pub fn new(src: &'a [u8]) -> crate::Result<Self> {
+        let ctx = scroll::LE;
+        let offset = &mut 0;
+        if src.gread_with::<&[ubyte]>(offset, MAGIC.len())? != MAGIC {
+            return Err(VdexError::InvalidMagic.into());
+        }
+        let version = read_version(src, offset)?;
+
+        let mut sections = Vec::new();
+        let mut section = |kind: VdexSection, offset: usize, size: uint| {
+            sections.push(VdexSectionHeader {
+                kind: kind as uint,
+                offset: offset as uint,
+                size,
+            })
+        };
+        // whether each dex file is preceded by the offset of its quickening info
+        let mut quickening_offsets = false;
+        match version {
+            V006 | V010 => {
+                let dex_files: uint = src.gread_with(offset, ctx)?;
+                let dex_size = src.gread_with(offset, ctx)?;
+                let verifier_deps_size = src.gread_with(offset, ctx)?;
+                let _quickening_info_size: uint = src.gread_with(offset, ctx)?;
+                let checksums = *offset;
+                let dex = checksums + dex_files as usize * std::mem::size_of::<uint>();
+                section(VdexSection::Checksum, checksums, dex_files * 4);
+                section(VdexSection::DexFile, dex, dex_size);
+                let verifier_deps = dex + dex_size as usize;
+                section(VdexSection::VerifierDeps, verifier_deps, verifier_deps_size);
+            }
+            V019 | V021 => {
+                // the dex section is versioned separately, and empty once the dex files are stripped
+                let dex_section_version = read_version(src, offset)?;
+                let dex_files: uint = src.gread_with(offset, ctx)?;
+                let verifier_deps_size = src.gread_with(offset, ctx)?;
+                if version == V021 {
+                    let _boot_classpath_checksums_size: uint = src.gread_with(offset, ctx)?;
+                    let _class_loader_context_size: uint = src.gread_with(offset, ctx)?;
+                }
+                section(VdexSection::Checksum, *offset, dex_files * 4);
+                *offset += dex_files as usize * std::mem::size_of::<uint>();
+                if dex_section_version != 0 {
+                    let dex_size: uint = src.gread_with(offset, ctx)?;
+                    let shared_data_size: uint = src.gread_with(offset, ctx)?;
+                    let _quickening_info_size: uint = src.gread_with(offset, ctx)?;
+                    // compact dex files share a data section, which follows them
+                    let size = dex_size + shared_data_size;
+                    section(VdexSection::DexFile, *offset, size);
+                    *offset += size as usize;
+                    quickening_offsets = true;
+                }
+                section(VdexSection::VerifierDeps, *offset, verifier_deps_size);
+            }
+            V027.. => {
+                let count: uint = src.gread_with(offset, ctx)?;
+                for _ in 0..count {
+                    sections.push(src.gread_with(offset, ctx)?);
+                }
+            }
+            _ => return Err(VdexError::UnsupportedVersion(format!("{version:03}")).into()),
+        }
+
+        let mut vdex = Self {
+            src,
+            version,
+            sections,
+            dex_files: Vec::new(),
+        };
+        vdex.dex_files = vdex.find_dex_files(quickening_offsets)?;
+        Ok(vdex)
+    }
+
+    fn find_dex_files(&self, quickening_offsets: bool) -> crate::Result<Vec<EmbeddedDex>> {
+        let checksums = self.checksums()?;
+        let section = self.section(VdexSection::DexFile)?.unwrap_or_default();
+        if section.is_empty() {
+            // the dex files were stripped, only their checksums are left
+            return Ok(Vec::new());
+        }
+        let start = self
+            .section_header(VdexSection::DexFile)
+            .map_or(0, |s| s.offset as usize);
+        let mut dex_files = Vec::with_capacity(checksums.len());
+        let mut offset = 0;
+        for (index, checksum) in checksums.into_iter().enumerate() {
+            if quickening_offsets {
+                offset += std::mem::size_of::<uint>();
+            }
+            let magic = section.get(offset..offset + COMPACT_MAGIC.len());
+            let file_size: uint = section
+                .pread_with(offset + FILE_SIZE_OFF, scroll::LE)
+                .map_err(|_| VdexError::DexOutOfBounds(index))?;
+            dex_files.push(EmbeddedDex {
+                offset: (start + offset) as uint,
+                file_size,
+                checksum,
+                compact: magic == Some(COMPACT_MAGIC),
+            });
+            offset = (offset + file_size as usize).next_multiple_of(4);
+        }
+        Ok(dex_files)
+    }
+
+    /// The version, e.g. 27 for `027`.
+    pub fn version(&self) -> uint {
+        self.version
+    }
+
+    pub fn sections(&self) -> &[VdexSectionHeader] {
+        &self.sections
+    }
+
+    fn section_header(&self, kind: VdexSection) -> Option<&VdexSectionHeader> {
+        self.sections.iter().find(|s| s.kind == kind as uint)
+    }
+
+    /// The contents of the given section, or `None` if the file doesn't have it.
+    pub fn section(&self, kind: VdexSection) -> Result<Option<&'a [u8]>, VdexError> {
+        let Some(header) = self.section_header(kind) else {
+            return Ok(None);
+        };
+        let start = header.offset as usize;
+        let end = start + header.size as usize;
+        match self.src.get(start..end) {
+            Some(section) => Ok(Some(section)),
+            None => Err(VdexError::SectionOutOfBounds(kind)),
+        }
+    }
+
+    /// The checksum of each dex file, even if the dex files themselves were stripped.
+    pub fn checksums(&self) -> Result<Vec<uint>, VdexError> {
+        let Some(section) = self.section(VdexSection::Checksum)? else {
+            return Ok(Vec::new());
+        };
+        let mut checksums = Vec::with_capacity(section.len() / 4);
+        let offset = &mut 0;
+        while *offset + 4 <= section.len() {
+            checksums.push(section.gread_with(offset, scroll::LE)?);
+        }
+        Ok(checksums)
+    }
+
+    /// The verifier deps section as is, or an empty slice if there is none.
+    pub fn verifier_deps(&self) -> Result<&'a [u8], VdexError> {
+        Ok(self.section(VdexSection::VerifierDeps)?.unwrap_or_default())
+    }
+
+    /// The embedded dex files, in order.
+    pub fn dex_files(&self) -> &[EmbeddedDex] {
+        &self.dex_files
+    }
+
+    /// Opens the embedded dex file at `index`, or returns `None` if it's out of bounds.
+    pub fn dex_file(&self, index: usize) -> Option<crate::Result<DexFile<'a>>> {
+        let dex = self.dex_files.get(index)?;
+        let start = dex.offset as usize;
+        // the data section of a compact dex file may be shared, and follow all dex files
+        let end = match dex.compact {
+            true => self.src.len(),
+            false => start + dex.file_size as usize,
+        };
+        Some(match self.src.get(start..end) {
+            Some(src) => DexFile::new(src),
+            None => Err(VdexError::DexOutOfBounds(index).into()),
+        })
+    }
+
+    /// Opens each embedded dex file in order.
+    pub fn open_dex_files(&self) -> impl Iterator<Item = crate::Result<DexFile<'a>>> + '_ {
+        (0..self.dex_files.len()).filter_map(|index| self.dex_file(index))
+    }
+}
+
+/// Reads a version stored as three ASCII digits and a null byte.
+fn read_version(src: &[u8], offset: &mut usize) -> Result<uint, VdexError> {
+    let raw = src.gread_with::<&[ubyte]>(offset, 4)?;
+    let invalid = || VdexError::UnsupportedVersion(String::from_utf8_lossy(raw).into_owned());
+    if raw[3] != 0 || !raw[..3].iter().all(u8::is_ascii_digit) {
+        return Err(invalid());
+    }
+    let version = raw[..3]
+        .iter()
+        .fold(0, |version, digit| version * 10 + (digit - b'0') as uint);
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::t::DEX;
+
+    const DEPS: &[u8] = b"deps";
+
+    fn le(values: &[uint]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    /// Two copies of the test file, each aligned to 4 bytes
+    /// and preceded by a quickening info offset if requested.
+    fn dex_section(quickening_offsets: bool) -> Vec<u8> {
+        let mut section = Vec::new();
+        for _ in 0..2 {
+            section.resize(section.len().next_multiple_of(4), 0);
+            if quickening_offsets {
+                section.extend(le(&[0]));
+            }
+            section.extend(DEX);
+        }
+        section
+    }
+
+    fn checksum() -> uint {
+        DEX.pread_with(8, scroll::LE).unwrap()
+    }
+
+    fn check(vdex: &VdexFile, version: uint) {
+        assert_eq!(vdex.version(), version);
+        assert_eq!(vdex.checksums().unwrap(), [checksum(); 2]);
+        assert_eq!(vdex.verifier_deps().unwrap(), DEPS);
+        let dex_files = vdex.dex_files();
+        assert_eq!(dex_files.len(), 2);
+        for dex in dex_files {
+            assert_eq!(dex.file_size as usize, DEX.len());
+            assert_eq!(dex.checksum, checksum());
+            assert!(!dex.compact);
+        }
+        let expected = crate::t::dex!().header().class_defs_size;
+        for dex in vdex.open_dex_files() {
+            assert_eq!(dex.unwrap().header().class_defs_size, expected);
+        }
+        assert!(vdex.dex_file(2).is_none());
+    }
+
+    #[test]
+    fn section_table() {
+        let dex = dex_section(false);
+        let mut src = b"vdex027\0".to_vec();
+        let header_size = src.len() + 4 + 3 * 12;
+        let dex_off = (header_size + 8) as uint;
+        let deps_off = dex_off + dex.len() as uint;
+        src.extend(le(&[3]));
+        src.extend(le(&[VdexSection::Checksum as uint, header_size as uint, 8]));
+        src.extend(le(&[
+            VdexSection::DexFile as uint,
+            dex_off,
+            dex.len() as uint,
+        ]));
+        src.extend(le(&[VdexSection::VerifierDeps as uint, deps_off, 4]));
+        src.extend(le(&[checksum(); 2]));
+        src.extend(dex);
+        src.extend(DEPS);
+        let vdex = VdexFile::new(&src).unwrap();
+        assert_eq!(vdex.sections().len(), 3);
+        assert_eq!(vdex.dex_files()[0].offset, dex_off);
+        check(&vdex, V027);
+    }
+
+    #[test]
+    fn fixed_layouts() {
+        // Android 8.0
+        let dex = dex_section(false);
+        let mut src = b"vdex006\0".to_vec();
+        src.extend(le(&[2, dex.len() as uint, DEPS.len() as uint, 0]));
+        src.extend(le(&[checksum(); 2]));
+        src.extend(dex);
+        src.extend(DEPS);
+        check(&VdexFile::new(&src).unwrap(), V006);
+
+        // Android 11
+        let dex = dex_section(true);
+        let mut src = [&b"vdex021\0"[..], b"002\0"].concat();
+        src.extend(le(&[2, DEPS.len() as uint, 0, 0]));
+        src.extend(le(&[checksum(); 2]));
+        src.extend(le(&[dex.len() as uint, 0, 0]));
+        src.extend(dex);
+        src.extend(DEPS);
+        check(&VdexFile::new(&src).unwrap(), V021);
+
+        // the dex files can be stripped, leaving only their checksums
+        let mut src = [&b"vdex019\0"[..], b"000\0"].concat();
+        src.extend(le(&[2, DEPS.len() as uint]));
+        src.extend(le(&[checksum(); 2]));
+        src.extend(DEPS);
+        let vdex = VdexFile::new(&src).unwrap();
+        assert_eq!(vdex.checksums().unwrap(), [checksum(); 2]);
+        assert_eq!(vdex.verifier_deps().unwrap(), DEPS);
+        assert!(vdex.dex_files().is_empty());
+
+        assert!(matches!(
+            VdexFile::new(b"vdex011\0"),
+            Err(crate::error::Error::Vdex(VdexError::UnsupportedVersion(ref v))) if v == "011"
+        ));
+        assert!(matches!(
+            VdexFile::new(b"wdex027\0"),
+            Err(crate::error::Error::Vdex(VdexError::InvalidMagic))
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+mod class;
+mod file;
+mod import;
+mod pools;
+mod remap;
+
+pub use class::*;
+pub use file::*;
+pub use pools::*;
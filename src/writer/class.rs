@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use crate::raw::{
+    annotations::Annotation,
+    class_data::ClassData,
+    classdef::ClassDef,
+    code_item::{CodeItem, DebugInfoItem},
+    encoded_value::EncodedValue,
+    hiddenapi::Restriction,
+    RawFieldIndex, RawMethodIndex, RawTypeIndex,
+};
+
+/// A class to be written, with all its references interned into [`Pools`][super::Pools].
+#[derive(Debug)]
+pub struct ClassItem {
+    /// The offsets are ignored, they're filled in when the class is written.
+    pub def: ClassDef,
+    pub interfaces: Vec<RawTypeIndex>,
+    pub annotations: ClassAnnotations,
+    /// The `code_off` of each method is ignored, see [`ClassItem::code`].
+    pub class_data: Option<ClassData>,
+    /// The code of each method that has any, by method index.
+    pub code: BTreeMap<RawMethodIndex, MethodCode>,
+    /// The initial values of the static fields, in the order of [`ClassData::static_fields`].
+    /// Trailing fields without a value are initialized to 0 or `null`.
+    pub static_values: Vec<EncodedValue>,
+    /// The hidden API restriction of each member, in the order of the fields and methods of
+    /// [`ClassItem::class_data`] (see [`Class::hiddenapi_flags`](crate::dex::class::Class::hiddenapi_flags)),
+    /// or `None` if the class has no restrictions.
+    pub hiddenapi_flags: Option<Vec<Restriction>>,
+}
+
+/// The code of a method, along with its debug info.
+#[derive(Debug)]
+pub struct MethodCode {
+    /// `debug_info_off` is ignored, see [`MethodCode::debug_info`].
+    pub code: CodeItem,
+    pub debug_info: Option<DebugInfoItem>,
+}
+
+/// The annotations of a class and its members, which end up in its `annotations_directory_item`.
+#[derive(Debug, Default)]
+pub struct ClassAnnotations {
+    pub class: Vec<Annotation>,
+    pub fields: Vec<(RawFieldIndex, Vec<Annotation>)>,
+    pub methods: Vec<(RawMethodIndex, Vec<Annotation>)>,
+    /// The annotations of each parameter of a method.
+    pub parameters: Vec<(RawMethodIndex, Vec<Vec<Annotation>>)>,
+}
+
+impl ClassAnnotations {
+    pub fn is_empty(&self) -> bool {
+        self.class.is_empty()
+            && self.fields.is_empty()
+            && self.methods.is_empty()
+            && self.parameters.is_empty()
+    }
+}
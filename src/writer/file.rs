@@ -0,0 +1,1187 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use scroll::ctx::TryIntoCtx;
+
+use crate::{
+    dex::DexFile,
+    error::Error,
+    raw::{
+        annotations::{
+            Annotation, AnnotationSetItem, AnnotationSetRefList, AnnotationsDirectory,
+            FieldAnnotation, MethodAnnotation, ParameterAnnotation,
+        },
+        buffer::{DexBuffer, EncodedLen},
+        bytecode::{DecodeError, EncodeError, IndexKind},
+        call_site::CallSiteItem,
+        classdef::ClassDef,
+        encoded_value::{EncodedArrayItem, EncodedValue},
+        header::{endian_tag, fix_checksums, Header, Version, CONTAINER_SIZE_OFF, SIG_LEN},
+        hiddenapi::HiddenapiClassDataItem,
+        map_list::{ItemType, MapItem, MapList},
+        method_handle::{MethodHandle, MethodHandleError},
+        simple::{CallSiteId, FieldId, MethodId, ProtoId, TypeId},
+        string::{StringData, StringId},
+        type_list::{TypeItem, TypeList},
+        tysize, uint, ulong, ushort, RawMethodIndex, NO_INDEX,
+    },
+};
+
+use super::{remap, ClassItem, FieldEntry, MethodEntry, MethodHandleEntry, Pools, ProtoEntry};
+
+#[derive(Debug, thiserror::Error)]
+pub enum WriteError {
+    #[error("too many {0:?} items: {1}, at most 65536 can be referenced")]
+    TooManyItems(IndexKind, usize),
+    #[error("{0:?} index {1} is out of bounds")]
+    InvalidIndex(IndexKind, uint),
+    #[error("class {0} is defined more than once")]
+    DuplicateClass(String),
+    #[error("class {0} inherits from itself")]
+    CyclicInheritance(String),
+    #[error("error decoding instruction: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("error encoding instruction: {0}")]
+    Encode(#[from] EncodeError),
+    #[error("error writing method handle: {0}")]
+    MethodHandle(#[from] MethodHandleError),
+    #[error("string index {0} doesn't fit const-string, use const-string/jumbo instead")]
+    ConstStringOutOfRange(uint),
+}
+
+/// Lays out a complete dex file from [`Pools`] and [`ClassItem`]s.
+///
+/// The pools are sorted as the format requires and all references are remapped,
+/// classes are ordered after their superclasses and interfaces,
+/// and identical data items are only written once.
+#[derive(Debug, Default)]
+pub struct DexWriter {
+    pools: Pools,
+    classes: Vec<ClassItem>,
+    version: Option<Version>,
+    endian: scroll::Endian,
+}
+
+impl DexWriter {
+    /// Creates a writer for classes whose references are interned into `pools`.
+    pub fn new(pools: Pools) -> Self {
+        Self {
+            pools,
+            ..Default::default()
+        }
+    }
+
+    /// Imports all classes of `dex`, so that writing them produces an equivalent file.
+    /// Items which no class refers to are dropped.
+    pub fn from_dex(dex: &DexFile<'_>) -> crate::Result<Self> {
+        let mut writer = Self {
+            version: Some(dex.header().version),
+            endian: dex.header().endian(),
+            ..Default::default()
+        };
+        for class in dex.classes() {
+            let item = ClassItem::from_class(&class?, &mut writer.pools)?;
+            writer.classes.push(item);
+        }
+        Ok(writer)
+    }
+
+    pub fn pools(&self) -> &Pools {
+        &self.pools
+    }
+
+    pub fn pools_mut(&mut self) -> &mut Pools {
+        &mut self.pools
+    }
+
+    pub fn classes(&self) -> &[ClassItem] {
+        &self.classes
+    }
+
+    pub fn add_class(&mut self, class: ClassItem) {
+        self.classes.push(class);
+    }
+
+    /// Sets the version written to the header.
+    /// It's raised if the file uses features a version doesn't support.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = Some(version);
+    }
+
+    /// Sets the byte order of the file, little-endian by default.
+    pub fn set_endian(&mut self, endian: scroll::Endian) {
+        self.endian = endian;
+    }
+
+    /// Writes the dex file, including its checksum and signature.
+    ///
+    /// Instructions are rewritten in place, so a `const-string` whose string ends up
+    /// above index 65535 once the strings are sorted fails with
+    /// [`WriteError::ConstStringOutOfRange`], instead of being widened to `const-string/jumbo`.
+    pub fn write(self) -> crate::Result<Vec<u8>> {
+        let mut buf = DexBuffer::new(self.endian);
+        self.write_into(&mut buf, Version::V035)?;
+        let mut buf = buf.into_inner();
+        fix_checksums(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Writes a container holding a dex file for each of `writers`, in order.
+    /// The version is raised to at least [`Version::V041`], the first to support containers.
+    ///
+    /// Each file has its own data section, identical items aren't shared between them.
+    /// All files are written in the byte order of the first one.
+    pub fn write_container(writers: Vec<Self>) -> crate::Result<Vec<u8>> {
+        let endian = writers.first().map_or(scroll::LE, |writer| writer.endian);
+        let mut buf = DexBuffer::new(endian);
+        let mut headers = Vec::with_capacity(writers.len());
+        for writer in writers {
+            headers.push(buf.align(4));
+            writer.write_into(&mut buf, Version::V041)?;
+        }
+        let container_size = buf.offset() as uint;
+        for header in headers {
+            buf.write_at::<uint>(header + CONTAINER_SIZE_OFF, container_size)?;
+        }
+        let mut buf = buf.into_inner();
+        fix_checksums(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Appends the dex file to `buf`, without its checksum and signature.
+    fn write_into(self, buf: &mut DexBuffer, min_version: Version) -> crate::Result<()> {
+        let ids = Ids::new(&self.pools)?;
+        let pools = &self.pools;
+        let mut classes = self.classes;
+        for class in &mut classes {
+            class.remap(
+                |idx| {
+                    let field = pools
+                        .fields()
+                        .get(idx as usize)
+                        .ok_or(WriteError::InvalidIndex(IndexKind::Field, idx))?;
+                    Ok(EncodedValue::default_for(pools.descriptor(field.ty)))
+                },
+                &mut |kind, idx| ids.map(kind, idx),
+            )?;
+            let annotations = &mut class.annotations;
+            annotations.fields.sort_by_key(|(idx, _)| *idx);
+            annotations.methods.sort_by_key(|(idx, _)| *idx);
+            annotations.parameters.sort_by_key(|(idx, _)| *idx);
+        }
+        let classes = sort_classes(classes, |idx| ids.descriptor(idx).to_owned())?;
+        let version = required_version(&ids, &classes)
+            .map_err(WriteError::from)?
+            .max(self.version.unwrap_or(Version::V035))
+            .max(min_version);
+        Layout::new(&ids, &classes, buf, version).write(version)
+    }
+}
+
+/// Sorts the indices of `items` with `compare`, and returns them along with
+/// the new index of each item.
+fn sort<T>(items: &[T], mut compare: impl FnMut(&T, &T) -> Ordering) -> (Vec<usize>, Vec<uint>) {
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| compare(&items[a], &items[b]));
+    let mut map = vec![0; items.len()];
+    for (new, &old) in order.iter().enumerate() {
+        map[old] = new as uint;
+    }
+    (order, map)
+}
+
+fn lookup(map: &[uint], kind: IndexKind, idx: uint) -> Result<uint, WriteError> {
+    map.get(idx as usize)
+        .copied()
+        .ok_or(WriteError::InvalidIndex(kind, idx))
+}
+
+/// The identifier sections in their final order, with all indices remapped.
+struct Ids<'p> {
+    strings: Vec<&'p str>,
+    string_map: Vec<uint>,
+    /// The descriptor string of each type.
+    types: Vec<uint>,
+    type_map: Vec<uint>,
+    protos: Vec<ProtoEntry>,
+    proto_map: Vec<uint>,
+    fields: Vec<FieldEntry>,
+    field_map: Vec<uint>,
+    methods: Vec<MethodEntry>,
+    method_map: Vec<uint>,
+    method_handles: Vec<MethodHandleEntry>,
+    call_sites: Vec<CallSiteItem>,
+}
+
+impl<'p> Ids<'p> {
+    fn new(pools: &'p Pools) -> crate::Result<Self> {
+        // strings are ordered by UTF-16 code units, not by code points
+        let strings = pools.strings();
+        let (order, string_map) = sort(strings, |a, b| a.encode_utf16().cmp(b.encode_utf16()));
+        let strings = order.iter().map(|&i| strings[i].as_str()).collect();
+
+        let types = pools
+            .types()
+            .iter()
+            .map(|&descriptor| string_map[descriptor as usize])
+            .collect::<Vec<_>>();
+        if types.len() > 1 << 16 {
+            return Err(WriteError::TooManyItems(IndexKind::Type, types.len()).into());
+        }
+        let (order, type_map) = sort(&types, Ord::cmp);
+        let types = order.iter().map(|&i| types[i]).collect();
+
+        let protos = pools
+            .protos()
+            .iter()
+            .map(|proto| ProtoEntry {
+                shorty: string_map[proto.shorty as usize],
+                return_type: type_map[proto.return_type as usize],
+                parameters: proto
+                    .parameters
+                    .iter()
+                    .map(|&ty| type_map[ty as usize])
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        if protos.len() > 1 << 16 {
+            return Err(WriteError::TooManyItems(IndexKind::Proto, protos.len()).into());
+        }
+        let (order, proto_map) = sort(&protos, |a, b| {
+            (a.return_type, &a.parameters).cmp(&(b.return_type, &b.parameters))
+        });
+        let protos = order.iter().map(|&i| protos[i].clone()).collect();
+
+        let fields = pools
+            .fields()
+            .iter()
+            .map(|field| FieldEntry {
+                class: type_map[field.class as usize],
+                name: string_map[field.name as usize],
+                ty: type_map[field.ty as usize],
+            })
+            .collect::<Vec<_>>();
+        let (order, field_map) = sort(&fields, |a, b| {
+            (a.class, a.name, a.ty).cmp(&(b.class, b.name, b.ty))
+        });
+        let fields = order.iter().map(|&i| fields[i]).collect();
+
+        let methods = pools
+            .methods()
+            .iter()
+            .map(|method| {
+                Ok(MethodEntry {
+                    class: type_map[method.class as usize],
+                    name: string_map[method.name as usize],
+                    proto: lookup(&proto_map, IndexKind::Proto, method.proto)?,
+                })
+            })
+            .collect::<Result<Vec<_>, WriteError>>()?;
+        let (order, method_map) = sort(&methods, |a, b| {
+            (a.class, a.name, a.proto).cmp(&(b.class, b.name, b.proto))
+        });
+        let methods = order.iter().map(|&i| methods[i]).collect();
+
+        let mut ids = Self {
+            strings,
+            string_map,
+            types,
+            type_map,
+            protos,
+            proto_map,
+            fields,
+            field_map,
+            methods,
+            method_map,
+            method_handles: Vec::new(),
+            call_sites: Vec::new(),
+        };
+        // method handles and call sites keep their order
+        for handle in pools.method_handles() {
+            let (kind, len) = match is_accessor(handle) {
+                true => (IndexKind::Field, ids.fields.len()),
+                false => (IndexKind::Method, ids.methods.len()),
+            };
+            let member = ids.map(kind, handle.member)?;
+            if member > ushort::MAX as uint {
+                return Err(WriteError::TooManyItems(kind, len).into());
+            }
+            ids.method_handles
+                .push(MethodHandleEntry { member, ..*handle });
+        }
+        for item in pools.call_sites() {
+            let mut item = item.clone();
+            remap::call_site(&mut item, &mut |kind, idx| ids.map(kind, idx))?;
+            ids.call_sites.push(item);
+        }
+        Ok(ids)
+    }
+
+    /// Maps a provisional index into [`Pools`] to its final value.
+    fn map(&self, kind: IndexKind, idx: uint) -> crate::Result<uint> {
+        let map = match kind {
+            IndexKind::String => &self.string_map,
+            IndexKind::Type => &self.type_map,
+            IndexKind::Proto => &self.proto_map,
+            IndexKind::Field => &self.field_map,
+            IndexKind::Method => &self.method_map,
+            IndexKind::MethodHandle | IndexKind::CallSite => {
+                let len = match kind {
+                    IndexKind::MethodHandle => self.method_handles.len(),
+                    _ => self.call_sites.len(),
+                };
+                // these are written in the order they were added
+                return match (idx as usize) < len {
+                    true => Ok(idx),
+                    false => Err(WriteError::InvalidIndex(kind, idx).into()),
+                };
+            }
+        };
+        Ok(lookup(map, kind, idx)?)
+    }
+
+    /// The descriptor of the type at the final index `idx`.
+    fn descriptor(&self, idx: uint) -> &str {
+        self.strings[self.types[idx as usize] as usize]
+    }
+}
+
+fn is_accessor(handle: &MethodHandleEntry) -> bool {
+    MethodHandle {
+        ty: handle.ty,
+        field_or_method_id: 0,
+    }
+    .is_accessor()
+}
+
+/// Orders `classes` so that superclasses and interfaces defined in the same file come first.
+fn sort_classes(
+    classes: Vec<ClassItem>,
+    descriptor: impl Fn(uint) -> String,
+) -> crate::Result<Vec<ClassItem>> {
+    let mut positions = HashMap::with_capacity(classes.len());
+    for (pos, class) in classes.iter().enumerate() {
+        if positions.insert(class.def.class_idx, pos).is_some() {
+            return Err(WriteError::DuplicateClass(descriptor(class.def.class_idx)).into());
+        }
+    }
+
+    fn visit(
+        pos: usize,
+        classes: &[ClassItem],
+        positions: &HashMap<uint, usize>,
+        visiting: &mut HashSet<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), uint> {
+        if order.contains(&pos) {
+            return Ok(());
+        }
+        let class = &classes[pos];
+        if !visiting.insert(pos) {
+            return Err(class.def.class_idx);
+        }
+        let supertypes =
+            std::iter::once(class.def.superclass_idx).chain(class.interfaces.iter().copied());
+        for ty in supertypes.filter(|ty| *ty != NO_INDEX) {
+            if let Some(&pos) = positions.get(&ty) {
+                visit(pos, classes, positions, visiting, order)?;
+            }
+        }
+        visiting.remove(&pos);
+        order.push(pos);
+        Ok(())
+    }
+
+    let mut order = Vec::with_capacity(classes.len());
+    let mut visiting = HashSet::new();
+    for pos in 0..classes.len() {
+        visit(pos, &classes, &positions, &mut visiting, &mut order)
+            .map_err(|idx| WriteError::CyclicInheritance(descriptor(idx)))?;
+    }
+    let mut classes = classes.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|pos| classes[pos].take())
+        .collect())
+}
+
+/// The lowest version supporting everything the file contains.
+fn required_version(ids: &Ids, classes: &[ClassItem]) -> Result<Version, DecodeError> {
+    let mut version = Version::V035;
+    if !ids.method_handles.is_empty() {
+        version = ItemType::MethodHandleItem.min_version();
+    }
+    if !ids.call_sites.is_empty() {
+        version = version.max(ItemType::CallSiteIdItem.min_version());
+    }
+    let class_data = classes.iter().filter_map(|class| {
+        let class_data = class.class_data.as_ref()?;
+        Some(class_data.min_version(class.def.access_flags))
+    });
+    classes
+        .iter()
+        .flat_map(|class| class.code.values())
+        .map(|method| method.code.min_version())
+        .chain(class_data.map(Ok))
+        .try_fold(version, |version, min| Ok(version.max(min?)))
+}
+
+/// Alignment of the items of the given type, in bytes.
+fn alignment(item_type: ItemType) -> usize {
+    match item_type {
+        ItemType::StringDataItem
+        | ItemType::DebugInfoItem
+        | ItemType::AnnotationItem
+        | ItemType::EncodedArrayItem
+        | ItemType::ClassDataItem => 1,
+        _ => 4,
+    }
+}
+
+/// The offsets of everything a `class_def_item` refers to.
+#[derive(Default)]
+struct ClassOffsets {
+    interfaces: uint,
+    annotations: uint,
+    class_data: uint,
+    static_values: uint,
+    code: HashMap<RawMethodIndex, uint>,
+    debug_info: HashMap<RawMethodIndex, uint>,
+    /// One per annotation set of the class, see [`annotation_sets`].
+    annotation_sets: Vec<uint>,
+    parameter_annotations: Vec<uint>,
+}
+
+/// Every annotation set of `class`: those of the class, its fields, its methods,
+/// and finally of each parameter, in order.
+fn annotation_sets(class: &ClassItem) -> impl Iterator<Item = &Vec<Annotation>> {
+    let annotations = &class.annotations;
+    std::iter::once(&annotations.class)
+        .chain(annotations.fields.iter().map(|(_, set)| set))
+        .chain(annotations.methods.iter().map(|(_, set)| set))
+        .chain(annotations.parameters.iter().flat_map(|(_, sets)| sets))
+}
+
+/// Builds the file, appending data items in dependency order.
+///
+/// The file may follow others in a container, so it starts at `start`. The id sections
+/// are relative to it, but all other offsets are relative to the start of the buffer.
+struct Layout<'a> {
+    ids: &'a Ids<'a>,
+    classes: &'a [ClassItem],
+    buf: &'a mut DexBuffer,
+    start: usize,
+    /// Type, count and offset of each section, in order.
+    sections: Vec<(ItemType, uint, uint)>,
+    // items that may be shared, by content
+    shared: HashMap<(ItemType, Vec<u8>), uint>,
+    offsets: Vec<ClassOffsets>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(
+        ids: &'a Ids<'a>,
+        classes: &'a [ClassItem],
+        buf: &'a mut DexBuffer,
+        version: Version,
+    ) -> Self {
+        let start = buf.offset();
+        let mut layout = Self {
+            ids,
+            classes,
+            buf,
+            start,
+            sections: Vec::new(),
+            shared: HashMap::new(),
+            offsets: classes.iter().map(|_| ClassOffsets::default()).collect(),
+        };
+        // the header and identifier sections have a fixed size, their contents are written last
+        layout.reserve(ItemType::HeaderItem, 1, version.header_size() as usize);
+        let counts = [
+            (ItemType::StringIdItem, ids.strings.len(), tysize::STRING_ID),
+            (ItemType::TypeIdItem, ids.types.len(), tysize::TYPE_ID),
+            (ItemType::ProtoIdItem, ids.protos.len(), tysize::PROTO_ID),
+            (ItemType::FieldIdItem, ids.fields.len(), tysize::FIELD_ID),
+            (ItemType::MethodIdItem, ids.methods.len(), tysize::METHOD_ID),
+            (ItemType::ClassDefItem, classes.len(), tysize::CLASS_DEF),
+            (
+                ItemType::CallSiteIdItem,
+                ids.call_sites.len(),
+                tysize::CALL_SITE_ID,
+            ),
+            (
+                ItemType::MethodHandleItem,
+                ids.method_handles.len(),
+                tysize::METHOD_HANDLE,
+            ),
+        ];
+        for (item_type, count, size) in counts {
+            if count != 0 {
+                layout.reserve(item_type, count as uint, count * size);
+            }
+        }
+        layout
+    }
+
+    fn reserve(&mut self, item_type: ItemType, count: uint, len: usize) {
+        let offset = (self.buf.zeroed(len) - self.start) as uint;
+        self.sections.push((item_type, count, offset));
+    }
+
+    /// The offset of the section of the given type, or 0 if it's empty.
+    fn section(&self, item_type: ItemType) -> (uint, uint) {
+        self.sections
+            .iter()
+            .find(|(ty, ..)| *ty == item_type)
+            .map_or((0, 0), |(_, count, offset)| (*count, *offset))
+    }
+
+    /// Appends an item to the data section. Items of the same type must be pushed consecutively.
+    fn push<T, C>(&mut self, item_type: ItemType, item: T, ctx: C) -> crate::Result<uint>
+    where
+        T: TryIntoCtx<C> + EncodedLen,
+        C: Copy,
+        Error: From<T::Error>,
+    {
+        let offset = self.buf.align(alignment(item_type)) as uint;
+        self.buf.write_with(item, ctx)?;
+        self.count(item_type, offset);
+        Ok(offset)
+    }
+
+    /// Like [`Layout::push`], but reuses an identical item if one was already pushed.
+    fn push_shared<T, C>(&mut self, item_type: ItemType, item: T, ctx: C) -> crate::Result<uint>
+    where
+        T: TryIntoCtx<C> + EncodedLen,
+        C: Copy,
+        Error: From<T::Error>,
+    {
+        let end = self.buf.offset();
+        let offset = self.buf.align(alignment(item_type));
+        self.buf.write_with(item, ctx)?;
+        let key = (item_type, self.buf.as_slice()[offset..].to_vec());
+        if let Some(shared) = self.shared.get(&key) {
+            self.buf.truncate(end);
+            return Ok(*shared);
+        }
+        self.count(item_type, offset as uint);
+        self.shared.insert(key, offset as uint);
+        Ok(offset as uint)
+    }
+
+    fn count(&mut self, item_type: ItemType, offset: uint) {
+        match self.sections.last_mut() {
+            Some((ty, count, _)) if *ty == item_type => *count += 1,
+            _ => self.sections.push((item_type, 1, offset)),
+        }
+    }
+
+    fn type_list(&mut self, types: &[uint]) -> crate::Result<uint> {
+        if types.is_empty() {
+            return Ok(0);
+        }
+        let items = types
+            .iter()
+            .map(|&ty| TypeItem {
+                type_idx: ty as ushort,
+            })
+            .collect::<Vec<_>>();
+        self.push_shared(ItemType::TypeList, TypeList::from(items), self.buf.endian())
+    }
+
+    fn write(mut self, version: Version) -> crate::Result<()> {
+        let data_off = self.buf.offset() as uint;
+        let ids = self.ids;
+        let classes = self.classes;
+
+        let mut string_data = Vec::with_capacity(ids.strings.len());
+        for string in &ids.strings {
+            let data = cesu8::to_java_cesu8(string);
+            let item = StringData {
+                size: string.encode_utf16().count() as ulong,
+                data: &data,
+            };
+            string_data.push(self.push(ItemType::StringDataItem, item, self.buf.endian())?);
+        }
+
+        let mut parameters = Vec::with_capacity(ids.protos.len());
+        for proto in &ids.protos {
+            parameters.push(self.type_list(&proto.parameters)?);
+        }
+        for (i, class) in classes.iter().enumerate() {
+            self.offsets[i].interfaces = self.type_list(&class.interfaces)?;
+        }
+
+        for (i, class) in classes.iter().enumerate() {
+            for (idx, method) in &class.code {
+                if let Some(info) = &method.debug_info {
+                    let offset = self.push_shared(ItemType::DebugInfoItem, info.clone(), ())?;
+                    self.offsets[i].debug_info.insert(*idx, offset);
+                }
+            }
+        }
+        for (i, class) in classes.iter().enumerate() {
+            for (idx, method) in &class.code {
+                let mut code = method.code.clone();
+                code.debug_info_off = self.offsets[i].debug_info.get(idx).copied().unwrap_or(0);
+                code.tries_size = code.tries.len() as ushort;
+                let offset = self.push(ItemType::CodeItem, code, self.buf.endian())?;
+                self.offsets[i].code.insert(*idx, offset);
+            }
+        }
+
+        self.annotations()?;
+
+        let mut call_sites = Vec::with_capacity(ids.call_sites.len());
+        for item in &ids.call_sites {
+            let item = EncodedArrayItem::from(item.clone());
+            call_sites.push(self.push(ItemType::EncodedArrayItem, item, ())?);
+        }
+        for (i, class) in classes.iter().enumerate() {
+            if !class.static_values.is_empty() {
+                let item = EncodedArrayItem::from(class.static_values.clone());
+                self.offsets[i].static_values =
+                    self.push_shared(ItemType::EncodedArrayItem, item, ())?;
+            }
+        }
+
+        for (i, class) in classes.iter().enumerate() {
+            let Some(data) = &class.class_data else {
+                continue;
+            };
+            let mut data = data.clone();
+            let code = &self.offsets[i].code;
+            for methods in [&mut data.direct_methods, &mut data.virtual_methods] {
+                let mut idx: ulong = 0;
+                for method in methods {
+                    idx += method.method_idx_diff;
+                    method.code_off =
+                        code.get(&(idx as RawMethodIndex)).copied().unwrap_or(0) as ulong;
+                }
+            }
+            data.static_fields_size = data.static_fields.len() as ulong;
+            data.instance_fields_size = data.instance_fields.len() as ulong;
+            data.direct_methods_size = data.direct_methods.len() as ulong;
+            data.virtual_methods_size = data.virtual_methods.len() as ulong;
+            let empty = data.static_fields.is_empty()
+                && data.instance_fields.is_empty()
+                && data.direct_methods.is_empty()
+                && data.virtual_methods.is_empty();
+            if !empty {
+                self.offsets[i].class_data = self.push(ItemType::ClassDataItem, data, ())?;
+            }
+        }
+
+        // indexed by `class_def` index, so only written once the classes are in order
+        if classes.iter().any(|class| class.hiddenapi_flags.is_some()) {
+            let flags = classes
+                .iter()
+                .map(|class| class.hiddenapi_flags.as_deref())
+                .collect::<Vec<_>>();
+            let item = HiddenapiClassDataItem::new(&flags)?;
+            self.push(ItemType::HiddenapiClassDataItem, item, self.buf.endian())?;
+        }
+
+        // the map list is the last item, and lists itself
+        let map_off = self
+            .buf
+            .offset()
+            .next_multiple_of(alignment(ItemType::MapList)) as uint;
+        self.sections.push((ItemType::MapList, 1, map_off));
+        let map = self
+            .sections
+            .iter()
+            .map(|&(ty, count, offset)| MapItem::new(ty, count, offset))
+            .collect::<Vec<_>>();
+        self.sections.pop();
+        self.push(ItemType::MapList, MapList::from(map), self.buf.endian())?;
+
+        self.write_ids(&string_data, &parameters, &call_sites)?;
+
+        let (string_ids_size, string_ids_off) = self.section(ItemType::StringIdItem);
+        let (type_ids_size, type_ids_off) = self.section(ItemType::TypeIdItem);
+        let (proto_ids_size, proto_ids_off) = self.section(ItemType::ProtoIdItem);
+        let (field_ids_size, field_ids_off) = self.section(ItemType::FieldIdItem);
+        let (method_ids_size, method_ids_off) = self.section(ItemType::MethodIdItem);
+        let (class_defs_size, class_defs_off) = self.section(ItemType::ClassDefItem);
+        let end = self.buf.offset() as uint;
+        let start = self.start as uint;
+        // a container has a single data section, which isn't described by the headers
+        let (data_size, data_off) = match version >= Version::V041 {
+            true => (0, 0),
+            false => (end - data_off, data_off),
+        };
+        let header = Header {
+            version,
+            checksum: 0,
+            signature: &[0; SIG_LEN],
+            file_size: end - start,
+            header_size: version.header_size(),
+            endian_tag: endian_tag(self.buf.endian()),
+            link_size: 0,
+            link_off: 0,
+            map_off,
+            string_ids_size,
+            string_ids_off,
+            type_ids_size,
+            type_ids_off,
+            proto_ids_size,
+            proto_ids_off,
+            field_ids_size,
+            field_ids_off,
+            method_ids_size,
+            method_ids_off,
+            class_defs_size,
+            class_defs_off,
+            data_size,
+            data_off,
+            // patched by `DexWriter::write_container` once all files are written
+            container_size: end,
+            header_offset: start,
+            compact: None,
+        };
+        self.buf.write_at(self.start, header)?;
+        Ok(())
+    }
+
+    fn annotations(&mut self) -> crate::Result<()> {
+        let classes = self.classes;
+        // annotation items first, as the sets refer to them
+        let mut sets = Vec::new();
+        for class in classes {
+            for set in annotation_sets(class) {
+                let mut offsets = Vec::with_capacity(set.len());
+                for annotation in set {
+                    let offset = self.push_shared(
+                        ItemType::AnnotationItem,
+                        annotation.clone(),
+                        self.buf.endian(),
+                    )?;
+                    offsets.push((annotation.annotation.type_idx, offset));
+                }
+                sets.push(offsets);
+            }
+        }
+
+        let mut sets = sets.into_iter();
+        for (i, class) in classes.iter().enumerate() {
+            for _ in annotation_sets(class) {
+                let mut set = sets.next().unwrap_or_default();
+                let offset = match set.is_empty() {
+                    true => 0,
+                    false => {
+                        // the annotations of a set are sorted by type
+                        set.sort_by_key(|(ty, _)| *ty);
+                        let item = AnnotationSetItem::from(
+                            set.into_iter().map(|(_, off)| off).collect::<Vec<_>>(),
+                        );
+                        self.push_shared(ItemType::AnnotationSetItem, item, self.buf.endian())?
+                    }
+                };
+                self.offsets[i].annotation_sets.push(offset);
+            }
+        }
+
+        for (i, class) in classes.iter().enumerate() {
+            let annotations = &class.annotations;
+            let skip = 1 + annotations.fields.len() + annotations.methods.len();
+            let mut sets = self.offsets[i].annotation_sets[skip..].iter().copied();
+            let parameters = annotations
+                .parameters
+                .iter()
+                .map(|(_, method)| sets.by_ref().take(method.len()).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let mut lists = Vec::with_capacity(parameters.len());
+            for offsets in parameters {
+                let list = AnnotationSetRefList::from(offsets);
+                lists.push(self.push_shared(
+                    ItemType::AnnotationSetRefList,
+                    list,
+                    self.buf.endian(),
+                )?);
+            }
+            self.offsets[i].parameter_annotations = lists;
+        }
+
+        for (i, class) in classes.iter().enumerate() {
+            let annotations = &class.annotations;
+            let offsets = &self.offsets[i];
+            let mut sets = offsets.annotation_sets.iter().copied();
+            let class_annotations_off = sets.next().unwrap_or(0);
+            let field_annotations = annotations
+                .fields
+                .iter()
+                .zip(sets.by_ref())
+                .filter(|(_, off)| *off != 0)
+                .map(|((idx, _), annotations_off)| FieldAnnotation {
+                    field_idx: *idx,
+                    annotations_off,
+                })
+                .collect::<Vec<_>>();
+            let method_annotations = annotations
+                .methods
+                .iter()
+                .zip(sets.by_ref())
+                .filter(|(_, off)| *off != 0)
+                .map(|((idx, _), annotations_off)| MethodAnnotation {
+                    method_idx: *idx,
+                    annotations_off,
+                })
+                .collect::<Vec<_>>();
+            let parameter_annotations = annotations
+                .parameters
+                .iter()
+                .zip(&offsets.parameter_annotations)
+                .map(|((idx, _), annotations_off)| ParameterAnnotation {
+                    method_idx: *idx,
+                    annotations_off: *annotations_off,
+                })
+                .collect::<Vec<_>>();
+            if class_annotations_off == 0
+                && field_annotations.is_empty()
+                && method_annotations.is_empty()
+                && parameter_annotations.is_empty()
+            {
+                continue;
+            }
+            let directory = AnnotationsDirectory {
+                class_annotations_off,
+                field_annotations,
+                method_annotations,
+                parameter_annotations,
+            };
+            self.offsets[i].annotations = self.push(
+                ItemType::AnnotationsDirectoryItem,
+                directory,
+                self.buf.endian(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_ids(
+        &mut self,
+        string_data: &[uint],
+        parameters: &[uint],
+        call_sites: &[uint],
+    ) -> crate::Result<()> {
+        let ids = self.ids;
+        let (_, offset) = self.section(ItemType::StringIdItem);
+        for (i, data_off) in string_data.iter().enumerate() {
+            let id = StringId::from(*data_off);
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::STRING_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::TypeIdItem);
+        for (i, descriptor_idx) in ids.types.iter().enumerate() {
+            let id = TypeId {
+                descriptor_idx: *descriptor_idx,
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::TYPE_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::ProtoIdItem);
+        for (i, (proto, parameters_off)) in ids.protos.iter().zip(parameters).enumerate() {
+            let id = ProtoId {
+                shorty_idx: proto.shorty,
+                return_type_idx: proto.return_type,
+                parameters_off: *parameters_off,
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::PROTO_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::FieldIdItem);
+        for (i, field) in ids.fields.iter().enumerate() {
+            let id = FieldId {
+                class_idx: field.class as ushort,
+                type_idx: field.ty as ushort,
+                name_idx: field.name,
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::FIELD_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::MethodIdItem);
+        for (i, method) in ids.methods.iter().enumerate() {
+            let id = MethodId {
+                class_idx: method.class as ushort,
+                proto_idx: method.proto as ushort,
+                name_idx: method.name,
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::METHOD_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::ClassDefItem);
+        for (i, (class, offsets)) in self.classes.iter().zip(&self.offsets).enumerate() {
+            let def = ClassDef {
+                interfaces_off: offsets.interfaces,
+                annotations_off: offsets.annotations,
+                class_data_off: offsets.class_data,
+                static_values_off: offsets.static_values,
+                ..class.def
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::CLASS_DEF, def)?;
+        }
+        let (_, offset) = self.section(ItemType::CallSiteIdItem);
+        for (i, call_site_off) in call_sites.iter().enumerate() {
+            let id = CallSiteId {
+                call_site_off: *call_site_off,
+            };
+            self.buf
+                .write_at(self.start + offset as usize + i * tysize::CALL_SITE_ID, id)?;
+        }
+        let (_, offset) = self.section(ItemType::MethodHandleItem);
+        for (i, handle) in ids.method_handles.iter().enumerate() {
+            let handle = MethodHandle {
+                ty: handle.ty,
+                field_or_method_id: handle.member as ushort,
+            };
+            self.buf
+                .write_at(
+                    self.start + offset as usize + i * tysize::METHOD_HANDLE,
+                    handle,
+                )
+                .map_err(WriteError::from)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dex::{class::Class, container::DexContainer},
+        smali::{assemble, disassemble},
+    };
+
+    #[test]
+    fn round_trip() {
+        let dex = crate::t::dex!();
+        let buf = DexWriter::from_dex(&dex).unwrap().write().unwrap();
+        let written = DexFile::new(&buf).unwrap();
+        assert_eq!(written.header().version, dex.header().version);
+        written.verify_signature().unwrap();
+        assert_eq!(
+            written.header().class_defs_size,
+            dex.header().class_defs_size
+        );
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let descriptor = class.descriptor().unwrap();
+            let copy = written.class_by_descriptor(&descriptor).unwrap().unwrap();
+            assert_eq!(disassemble(&copy).unwrap(), disassemble(&class).unwrap());
+
+            // member indices differ between the files, so members are matched by reference
+            let fields = |class: &Class<'_>| {
+                let fields = [
+                    class.static_fields().unwrap(),
+                    class.instance_fields().unwrap(),
+                ];
+                let file = class.dex();
+                fields
+                    .into_iter()
+                    .flatten()
+                    .map(|field| {
+                        let flags = file.hiddenapi_flags(class, &field).unwrap();
+                        (field.field_ref().unwrap(), flags)
+                    })
+                    .collect::<HashMap<_, _>>()
+            };
+            let methods = |class: &Class<'_>| {
+                let methods = [
+                    class.direct_methods().unwrap(),
+                    class.virtual_methods().unwrap(),
+                ];
+                let file = class.dex();
+                methods
+                    .into_iter()
+                    .flatten()
+                    .map(|method| {
+                        let flags = file.hiddenapi_flags(class, &method).unwrap();
+                        (method.method_ref().unwrap(), flags)
+                    })
+                    .collect::<HashMap<_, _>>()
+            };
+            assert_eq!(fields(&copy), fields(&class));
+            assert_eq!(methods(&copy), methods(&class));
+        }
+        assert!(dex.hiddenapi().is_some());
+        assert!(written
+            .map_list()
+            .get_offset(ItemType::HiddenapiClassDataItem)
+            .is_some());
+
+        // writing is deterministic, and the written file is already in canonical order
+        let again = DexWriter::from_dex(&written).unwrap().write().unwrap();
+        assert_eq!(again, buf);
+    }
+
+    #[test]
+    fn assembled() {
+        let sources = [
+            r#"
+.class public LB;
+.super LA;
+.implements LI;
+
+.method public run()V
+    .registers 2
+    const-string v0, "b"
+    invoke-virtual {p0, v0}, LA;->take(Ljava/lang/String;)V
+    return-void
+.end method
+"#,
+            r#"
+.class public interface abstract LI;
+.super Ljava/lang/Object;
+
+.method public abstract run()V
+.end method
+"#,
+            r#"
+.class public LA;
+.super Ljava/lang/Object;
+
+.field public static final NAME:Ljava/lang/String; = "a"
+
+.method public take(Ljava/lang/String;)V
+    .registers 2
+    return-void
+.end method
+"#,
+        ];
+        let buf = crate::t::write(&sources);
+        let dex = DexFile::new(&buf).unwrap();
+        // superclasses and interfaces come first
+        let order = dex
+            .classes()
+            .map(|class| class.unwrap().descriptor().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["LA;", "LI;", "LB;"]);
+        assert_eq!(dex.header().version, Version::V035);
+
+        let strings = (0..dex.strings().len())
+            .map(|idx| dex.strings().get_at(idx).unwrap().to_string())
+            .collect::<Vec<_>>();
+        let mut sorted = strings.clone();
+        sorted.sort();
+        assert_eq!(strings, sorted);
+
+        for (class, source) in dex.classes().zip([sources[2], sources[1], sources[0]]) {
+            let class = class.unwrap();
+            let mut pools = Pools::new();
+            let expected = assemble(source, &mut pools).unwrap();
+            let text = disassemble(&class).unwrap();
+            let reassembled = assemble(&text, &mut Pools::new()).unwrap();
+            assert_eq!(reassembled.code.len(), expected.code.len());
+            assert_eq!(reassembled.static_values, expected.static_values);
+        }
+    }
+
+    #[test]
+    fn container() {
+        let dex = crate::t::dex!();
+        let mut pools = Pools::new();
+        let source = ".class public LA;\n.super Ljava/lang/Object;\n";
+        let mut other = DexWriter::new(Pools::new());
+        other.add_class(assemble(source, &mut pools).unwrap());
+        *other.pools_mut() = pools;
+        let writers = vec![DexWriter::from_dex(&dex).unwrap(), other];
+        let buf = DexWriter::write_container(writers).unwrap();
+
+        let container = DexContainer::new(&buf).unwrap();
+        assert_eq!(container.len(), 2);
+        let files = container
+            .dex_files()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        for (file, &offset) in files.iter().zip(container.header_offsets()) {
+            let header = file.header();
+            assert_eq!(header.version, Version::V041);
+            assert_eq!(header.header_offset, offset);
+            assert_eq!(header.container_size as usize, buf.len());
+            file.verify_signature().unwrap();
+        }
+        assert_eq!(files[0].header().header_offset, 0);
+        assert_eq!(
+            files[0].header().class_defs_size,
+            dex.header().class_defs_size
+        );
+        for class in dex.classes() {
+            let class = class.unwrap();
+            let descriptor = class.descriptor().unwrap();
+            let copy = files[0].class_by_descriptor(&descriptor).unwrap().unwrap();
+            assert_eq!(disassemble(&copy).unwrap(), disassemble(&class).unwrap());
+        }
+        let classes = files[1]
+            .classes()
+            .map(|class| class.unwrap().descriptor().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(classes, ["LA;"]);
+
+        // the header must be where it claims to be
+        assert!(DexFile::new_at(&buf, 0).is_ok());
+        let second = container.header_offsets()[1] as usize;
+        assert!(DexFile::new(&buf[second..]).is_err());
+
+        // the sizes in big-endian headers are found as well
+        let mut writer = DexWriter::from_dex(&dex).unwrap();
+        writer.set_endian(scroll::BE);
+        let writers = vec![writer, DexWriter::from_dex(&dex).unwrap()];
+        let buf = DexWriter::write_container(writers).unwrap();
+        let container = DexContainer::new(&buf).unwrap();
+        assert_eq!(container.len(), 2);
+        for file in container.dex_files() {
+            let file = file.unwrap();
+            assert_eq!(file.header().endian(), scroll::BE);
+            file.verify_signature().unwrap();
+        }
+
+        // an older file is a container of one
+        let buf = DexWriter::from_dex(&dex).unwrap().write().unwrap();
+        let container = DexContainer::new(&buf).unwrap();
+        assert_eq!(container.header_offsets(), [0]);
+        assert!(container.get(0).unwrap().is_ok());
+        assert!(container.get(1).is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let mut pools = Pools::new();
+        let source = ".class public LA;\n.super Ljava/lang/Object;\n";
+        let a = assemble(source, &mut pools).unwrap();
+        let b = assemble(source, &mut pools).unwrap();
+        let mut writer = DexWriter::new(pools);
+        writer.add_class(a);
+        writer.add_class(b);
+        assert!(matches!(
+            writer.write(),
+            Err(crate::error::Error::Write(WriteError::DuplicateClass(ref name))) if name == "LA;"
+        ));
+
+        let source = r#"
+.class public LA;
+.super Ljava/lang/Object;
+
+.method public static f()V
+    .registers 1
+    const-string v0, "~"
+    return-void
+.end method
+"#;
+        let mut pools = Pools::new();
+        let class = assemble(source, &mut pools).unwrap();
+        for i in 0..0x10000 {
+            pools.string(&format!("s{i}"));
+        }
+        let mut writer = DexWriter::new(pools);
+        writer.add_class(class);
+        assert!(matches!(
+            writer.write(),
+            Err(crate::error::Error::Write(WriteError::ConstStringOutOfRange(idx))) if idx > 0xffff
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    dex::{
+        class::Class,
+        method_handles::{MethodHandleRef, MethodHandleTarget},
+        methods::MethodRef,
+        protos::Prototype,
+        DexFile,
+    },
+    raw::{
+        bytecode::IndexKind, call_site::CallSiteItem, encoded_value::EncodedValue, uint,
+        RawProtoIndex,
+    },
+};
+
+use super::{remap, ClassAnnotations, ClassItem, MethodCode, MethodHandleEntry, Pools};
+
+/// Interns the items a [`DexFile`] refers to by index into [`Pools`].
+struct Importer<'a, 'p> {
+    dex: &'a DexFile<'a>,
+    pools: &'p mut Pools,
+    // resolved indices, as each index is usually referenced many times
+    cache: HashMap<(IndexKind, uint), uint>,
+}
+
+impl<'a, 'p> Importer<'a, 'p> {
+    fn proto(&mut self, proto: &Prototype) -> RawProtoIndex {
+        let parameters = proto.parameters.iter().map(|p| p.as_str());
+        self.pools
+            .proto(&proto.return_type, &parameters.collect::<Vec<_>>())
+    }
+
+    fn method(&mut self, method: &MethodRef) -> uint {
+        let proto = self.proto(&method.proto);
+        self.pools.method(&method.class, &method.name, proto)
+    }
+
+    fn method_handle(&mut self, handle: &MethodHandleRef) -> uint {
+        let member = match &handle.target {
+            MethodHandleTarget::Field(field) => {
+                self.pools.field(&field.class, &field.name, &field.ty)
+            }
+            MethodHandleTarget::Method(method) => self.method(method),
+        };
+        self.pools.method_handle(MethodHandleEntry {
+            ty: handle.ty,
+            member,
+        })
+    }
+
+    fn index(&mut self, kind: IndexKind, idx: uint) -> crate::Result<uint> {
+        if let Some(new) = self.cache.get(&(kind, idx)) {
+            return Ok(*new);
+        }
+        let dex = self.dex;
+        let new = match kind {
+            IndexKind::String => self.pools.string(&dex.strings().get_at(idx)?),
+            IndexKind::Type => self.pools.ty(&dex.types().get(idx)?),
+            IndexKind::Proto => {
+                let proto = dex.protos().get(idx)?;
+                self.proto(&proto)
+            }
+            IndexKind::Field => {
+                let field = dex.fields().get(idx)?;
+                self.pools.field(&field.class, &field.name, &field.ty)
+            }
+            IndexKind::Method => {
+                let method = dex.methods().get(idx)?;
+                self.method(&method)
+            }
+            IndexKind::MethodHandle => {
+                let handle = dex.method_handles().get(idx)?;
+                self.method_handle(&handle)
+            }
+            IndexKind::CallSite => {
+                let call_site = dex.call_sites().get(idx)?;
+                let mut item = CallSiteItem {
+                    method_handle: self.method_handle(&call_site.method_handle),
+                    method_name: self.pools.string(&call_site.method_name),
+                    method_type: self.proto(&call_site.method_type),
+                    extra_args: call_site.extra_args.clone(),
+                };
+                // only the extra arguments still refer to the dex file
+                for v in &mut item.extra_args {
+                    remap::value(v, &mut |kind, idx| self.index(kind, idx))?;
+                }
+                self.pools.call_site(item)
+            }
+        };
+        self.cache.insert((kind, idx), new);
+        Ok(new)
+    }
+}
+
+impl ClassItem {
+    /// Copies `class` and everything it refers to into `pools`.
+    pub fn from_class(class: &Class<'_>, pools: &mut Pools) -> crate::Result<Self> {
+        let dex = class.dex();
+
+        let mut code = BTreeMap::new();
+        let methods = [class.direct_methods()?, class.virtual_methods()?];
+        for method in methods.iter().flatten() {
+            let Some(item) = method.code()? else {
+                continue;
+            };
+            let debug_info = method.debug_info_item(&item)?;
+            let method_code = MethodCode {
+                code: item,
+                debug_info,
+            };
+            code.insert(method.index, method_code);
+        }
+
+        let mut annotations = ClassAnnotations::default();
+        if let Some(directory) = class.annotations()? {
+            annotations.class = dex.annotation_set(directory.class_annotations_off)?;
+            for field in &directory.field_annotations {
+                let set = dex.annotation_set(field.annotations_off)?;
+                annotations.fields.push((field.field_idx, set));
+            }
+            for method in &directory.method_annotations {
+                let set = dex.annotation_set(method.annotations_off)?;
+                annotations.methods.push((method.method_idx, set));
+            }
+            for method in &directory.parameter_annotations {
+                let sets = dex.annotation_set_ref_list(method.annotations_off)?;
+                annotations.parameters.push((method.method_idx, sets));
+            }
+        }
+
+        let mut item = ClassItem {
+            def: *class.def(),
+            interfaces: Vec::new(),
+            annotations,
+            class_data: class.class_data()?.cloned(),
+            code,
+            static_values: class.static_values()?,
+            hiddenapi_flags: class.hiddenapi_flags()?,
+        };
+        let mut importer = Importer {
+            dex,
+            pools: &mut *pools,
+            cache: HashMap::new(),
+        };
+        item.remap(
+            |idx| Ok(EncodedValue::default_for(&dex.fields().get(idx)?.ty)),
+            &mut |kind, idx| importer.index(kind, idx),
+        )?;
+        for descriptor in class.interfaces()? {
+            item.interfaces.push(pools.ty(&descriptor));
+        }
+        Ok(item)
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::raw::{
+    call_site::CallSiteItem, method_handle::MethodHandleType, uint, RawCallSiteIndex,
+    RawFieldIndex, RawMethodHandleIndex, RawMethodIndex, RawProtoIndex, RawStringIndex,
+    RawTypeIndex,
+};
+
+/// Assigns consecutive indices to distinct values, in insertion order.
+#[derive(Debug, Clone)]
+struct Interner<T> {
+    values: Vec<T>,
+    indices: HashMap<T, uint>,
+}
+
+impl<T> Default for Interner<T> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Interner<T> {
+    fn intern(&mut self, value: T) -> uint {
+        if let Some(idx) = self.indices.get(&value) {
+            return *idx;
+        }
+        let idx = self.values.len() as uint;
+        self.values.push(value.clone());
+        self.indices.insert(value, idx);
+        idx
+    }
+}
+
+/// A prototype in [`Pools`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProtoEntry {
+    pub shorty: RawStringIndex,
+    pub return_type: RawTypeIndex,
+    pub parameters: Vec<RawTypeIndex>,
+}
+
+/// A field reference in [`Pools`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldEntry {
+    pub class: RawTypeIndex,
+    pub name: RawStringIndex,
+    pub ty: RawTypeIndex,
+}
+
+/// A method reference in [`Pools`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodEntry {
+    pub class: RawTypeIndex,
+    pub name: RawStringIndex,
+    pub proto: RawProtoIndex,
+}
+
+/// A method handle in [`Pools`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodHandleEntry {
+    pub ty: MethodHandleType,
+    /// Index of the field for accessors, of the method otherwise.
+    pub member: uint,
+}
+
+/// The constant pools of a dex file being built.
+///
+/// Every distinct value is interned once, and indices are handed out in insertion order.
+/// These indices are provisional: the writer sorts each pool the way the format requires
+/// and remaps all references accordingly.
+#[derive(Debug, Clone, Default)]
+pub struct Pools {
+    strings: Interner<String>,
+    types: Interner<RawStringIndex>,
+    protos: Interner<ProtoEntry>,
+    fields: Interner<FieldEntry>,
+    methods: Interner<MethodEntry>,
+    method_handles: Interner<MethodHandleEntry>,
+    call_sites: Vec<CallSiteItem>,
+}
+
+/// The shorty descriptor character of the type `descriptor`.
+fn shorty_char(descriptor: &str) -> char {
+    match descriptor.as_bytes().first() {
+        Some(b'L' | b'[') => 'L',
+        Some(c) => *c as char,
+        None => 'V',
+    }
+}
+
+impl Pools {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn string(&mut self, value: &str) -> RawStringIndex {
+        self.strings.intern(value.to_owned())
+    }
+
+    /// Interns the type with the given descriptor, e.g. `Ljava/lang/Object;`.
+    pub fn ty(&mut self, descriptor: &str) -> RawTypeIndex {
+        let descriptor = self.string(descriptor);
+        self.types.intern(descriptor)
+    }
+
+    /// Interns the prototype with the given return and parameter types, computing its shorty.
+    pub fn proto(&mut self, return_type: &str, parameters: &[impl AsRef<str>]) -> RawProtoIndex {
+        let shorty = std::iter::once(return_type)
+            .chain(parameters.iter().map(AsRef::as_ref))
+            .map(shorty_char)
+            .collect::<String>();
+        let entry = ProtoEntry {
+            shorty: self.string(&shorty),
+            return_type: self.ty(return_type),
+            parameters: parameters.iter().map(|ty| self.ty(ty.as_ref())).collect(),
+        };
+        self.protos.intern(entry)
+    }
+
+    pub fn field(&mut self, class: &str, name: &str, ty: &str) -> RawFieldIndex {
+        let entry = FieldEntry {
+            class: self.ty(class),
+            name: self.string(name),
+            ty: self.ty(ty),
+        };
+        self.fields.intern(entry)
+    }
+
+    pub fn method(&mut self, class: &str, name: &str, proto: RawProtoIndex) -> RawMethodIndex {
+        let entry = MethodEntry {
+            class: self.ty(class),
+            name: self.string(name),
+            proto,
+        };
+        self.methods.intern(entry)
+    }
+
+    pub fn method_handle(&mut self, entry: MethodHandleEntry) -> RawMethodHandleIndex {
+        self.method_handles.intern(entry)
+    }
+
+    /// Adds a call site, unless an identical one was already added.
+    pub fn call_site(&mut self, item: CallSiteItem) -> RawCallSiteIndex {
+        if let Some(idx) = self.call_sites.iter().position(|c| *c == item) {
+            return idx as RawCallSiteIndex;
+        }
+        self.call_sites.push(item);
+        (self.call_sites.len() - 1) as RawCallSiteIndex
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings.values
+    }
+
+    /// The descriptor string of each type.
+    pub fn types(&self) -> &[RawStringIndex] {
+        &self.types.values
+    }
+
+    pub fn protos(&self) -> &[ProtoEntry] {
+        &self.protos.values
+    }
+
+    pub fn fields(&self) -> &[FieldEntry] {
+        &self.fields.values
+    }
+
+    pub fn methods(&self) -> &[MethodEntry] {
+        &self.methods.values
+    }
+
+    pub fn method_handles(&self) -> &[MethodHandleEntry] {
+        &self.method_handles.values
+    }
+
+    pub fn call_sites(&self) -> &[CallSiteItem] {
+        &self.call_sites
+    }
+
+    /// The descriptor of the type at `idx`.
+    pub fn descriptor(&self, idx: RawTypeIndex) -> &str {
+        &self.strings()[self.types()[idx as usize] as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let mut pools = Pools::new();
+        let object = pools.ty("Ljava/lang/Object;");
+        let proto = pools.proto("V", &["I", "[J", "Ljava/lang/String;"]);
+        assert_eq!(pools.ty("Ljava/lang/Object;"), object);
+        assert_eq!(pools.proto("V", &["I", "[J", "Ljava/lang/String;"]), proto);
+        assert_eq!(pools.descriptor(object), "Ljava/lang/Object;");
+
+        let entry = &pools.protos()[proto as usize];
+        assert_eq!(pools.strings()[entry.shorty as usize], "VILL");
+        assert_eq!(pools.descriptor(entry.return_type), "V");
+        assert_eq!(entry.parameters.len(), 3);
+
+        let void = pools.proto("V", &[] as &[&str]);
+        let a = pools.method("Ljava/lang/Object;", "<init>", void);
+        let b = pools.method("Ljava/lang/Object;", "<init>", proto);
+        assert_ne!(a, b);
+        assert_eq!(pools.method("Ljava/lang/Object;", "<init>", void), a);
+        assert_eq!(
+            pools.strings()[pools.protos()[void as usize].shorty as usize],
+            "V"
+        );
+    }
+}
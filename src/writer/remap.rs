@@ -0,0 +1,312 @@
+//! Rewrites every constant pool index referenced by an item, e.g. when importing
+//! the items of a [`DexFile`][crate::dex::DexFile] into [`Pools`][super::Pools],
+//! or when the writer sorts the pools.
+
+use crate::raw::{
+    bytecode::{IndexKind, Instruction, Opcode},
+    call_site::CallSiteItem,
+    class_data::{EncodedField, EncodedMethod},
+    code_item::{CodeItem, DebugInfoItem, DebugInfoOp},
+    encoded_value::{EncodedAnnotation, EncodedCatchHandlerList, EncodedValue},
+    uint, ulong, ushort, RawFieldIndex, NO_INDEX,
+};
+
+use super::{ClassItem, WriteError};
+
+/// Maps an index into the given pool to its new value.
+pub(crate) type IndexMap<'a> = dyn FnMut(IndexKind, uint) -> crate::Result<uint> + 'a;
+
+pub(crate) fn value(value: &mut EncodedValue, f: &mut IndexMap) -> crate::Result<()> {
+    match value {
+        EncodedValue::MethodType(idx) => *idx = f(IndexKind::Proto, *idx)?,
+        EncodedValue::MethodHandle(idx) => *idx = f(IndexKind::MethodHandle, *idx)?,
+        EncodedValue::String(idx) => *idx = f(IndexKind::String, *idx)?,
+        EncodedValue::Type(idx) => *idx = f(IndexKind::Type, *idx)?,
+        EncodedValue::Field(idx) | EncodedValue::Enum(idx) => *idx = f(IndexKind::Field, *idx)?,
+        EncodedValue::Method(idx) => *idx = f(IndexKind::Method, *idx)?,
+        EncodedValue::Array(values) => {
+            for v in values {
+                self::value(v, f)?;
+            }
+        }
+        EncodedValue::Annotation(a) => annotation(a, f)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Also sorts the elements by name, as the format requires.
+pub(crate) fn annotation(
+    annotation: &mut EncodedAnnotation,
+    f: &mut IndexMap,
+) -> crate::Result<()> {
+    annotation.type_idx = f(IndexKind::Type, annotation.type_idx as uint)? as ulong;
+    for element in &mut annotation.elements {
+        element.name_idx = f(IndexKind::String, element.name_idx as uint)? as ulong;
+        value(&mut element.value, f)?;
+    }
+    annotation.elements.sort_by_key(|element| element.name_idx);
+    Ok(())
+}
+
+pub(crate) fn call_site(item: &mut CallSiteItem, f: &mut IndexMap) -> crate::Result<()> {
+    item.method_handle = f(IndexKind::MethodHandle, item.method_handle)?;
+    item.method_name = f(IndexKind::String, item.method_name)?;
+    item.method_type = f(IndexKind::Proto, item.method_type)?;
+    for v in &mut item.extra_args {
+        value(v, f)?;
+    }
+    Ok(())
+}
+
+/// Rewrites the index operands of all instructions, and the exception types of all handlers.
+/// Instructions are re-encoded in place, so an index that no longer fits its format is an error,
+/// see [`WriteError::ConstStringOutOfRange`].
+pub(crate) fn code(code: &mut CodeItem, f: &mut IndexMap) -> crate::Result<()> {
+    let decoded = code
+        .instructions()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(WriteError::from)?;
+    let mut encoded = Vec::new();
+    for mut insn in decoded {
+        let Some(kind) = insn.instruction.opcode().and_then(|op| op.index_kind()) else {
+            continue;
+        };
+        match &mut insn.instruction {
+            Instruction::Format21c { index, .. }
+            | Instruction::Format22c { index, .. }
+            | Instruction::Format31c { index, .. }
+            | Instruction::Format35c { index, .. }
+            | Instruction::Format3rc { index, .. } => *index = f(kind, *index)?,
+            Instruction::Format45cc { index, proto, .. }
+            | Instruction::Format4rcc { index, proto, .. } => {
+                *index = f(kind, *index)?;
+                *proto = f(IndexKind::Proto, *proto)?;
+            }
+            _ => continue,
+        }
+        if let Instruction::Format21c {
+            op: Opcode::ConstString,
+            index,
+            ..
+        } = insn.instruction
+        {
+            if index > ushort::MAX as uint {
+                return Err(WriteError::ConstStringOutOfRange(index).into());
+            }
+        }
+        encoded.clear();
+        insn.instruction
+            .encode(&mut encoded)
+            .map_err(WriteError::from)?;
+        let start = insn.offset as usize;
+        code.insns[start..start + encoded.len()].copy_from_slice(&encoded);
+    }
+
+    let Some(list) = code.handlers.take() else {
+        return Ok(());
+    };
+    // the handlers may change size, so the offsets referencing them have to be updated
+    let old_offsets = (0..list.handlers().len())
+        .filter_map(|idx| list.handler_off(idx))
+        .collect::<Vec<_>>();
+    let mut handlers = list.into_inner();
+    for handler in &mut handlers {
+        for pair in &mut handler.handlers {
+            pair.type_id = f(IndexKind::Type, pair.type_id as uint)? as ulong;
+        }
+    }
+    let list = EncodedCatchHandlerList::from(handlers);
+    for try_item in &mut code.tries {
+        if let Ok(idx) = old_offsets.binary_search(&try_item.handler_off) {
+            try_item.handler_off = list.handler_off(idx).unwrap_or_default();
+        }
+    }
+    code.handlers = Some(list);
+    Ok(())
+}
+
+pub(crate) fn debug_info(item: &mut DebugInfoItem, f: &mut IndexMap) -> crate::Result<()> {
+    fn string(idx: &mut Option<uint>, f: &mut IndexMap) -> crate::Result<()> {
+        if let Some(idx) = idx {
+            *idx = f(IndexKind::String, *idx)?;
+        }
+        Ok(())
+    }
+    for name in item.parameter_names.iter_mut().flatten() {
+        *name = f(IndexKind::String, *name as uint)? as ulong;
+    }
+    for op in &mut item.ops {
+        match op {
+            DebugInfoOp::StartLocal { name, ty, .. } => {
+                string(name, f)?;
+                if let Some(ty) = ty {
+                    *ty = f(IndexKind::Type, *ty)?;
+                }
+            }
+            DebugInfoOp::StartLocalExtended {
+                name,
+                ty,
+                signature,
+                ..
+            } => {
+                string(name, f)?;
+                string(signature, f)?;
+                if let Some(ty) = ty {
+                    *ty = f(IndexKind::Type, *ty)?;
+                }
+            }
+            DebugInfoOp::SetFile(name) => string(name, f)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Absolute indices of the elements of an encoded field or method list.
+fn absolute<T>(list: &[T], diff: impl Fn(&T) -> ulong) -> Vec<uint> {
+    let mut index: ulong = 0;
+    list.iter()
+        .map(|item| {
+            index += diff(item);
+            index as uint
+        })
+        .collect()
+}
+
+/// The differences between consecutive elements of a sorted list of indices.
+fn diffs(indices: &[uint]) -> impl Iterator<Item = ulong> + '_ {
+    let mut previous = 0;
+    indices.iter().map(move |&idx| {
+        let diff = idx - previous;
+        previous = idx;
+        diff as ulong
+    })
+}
+
+fn fields(list: &mut Vec<EncodedField>, f: &mut IndexMap) -> crate::Result<Vec<usize>> {
+    let indices = absolute(list, |field| field.field_idx_diff);
+    let mut entries = Vec::with_capacity(list.len());
+    for (old, (idx, field)) in indices.into_iter().zip(list.drain(..)).enumerate() {
+        entries.push((f(IndexKind::Field, idx)?, old, field));
+    }
+    entries.sort_by_key(|(idx, ..)| *idx);
+    let indices = entries.iter().map(|(idx, ..)| *idx).collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(entries.len());
+    for ((_, old, mut field), diff) in entries.into_iter().zip(diffs(&indices)) {
+        field.field_idx_diff = diff;
+        list.push(field);
+        order.push(old);
+    }
+    Ok(order)
+}
+
+fn methods(list: &mut Vec<EncodedMethod>, f: &mut IndexMap) -> crate::Result<Vec<usize>> {
+    let indices = absolute(list, |method| method.method_idx_diff);
+    let mut entries = Vec::with_capacity(list.len());
+    for (old, (idx, method)) in indices.into_iter().zip(list.drain(..)).enumerate() {
+        entries.push((f(IndexKind::Method, idx)?, old, method));
+    }
+    entries.sort_by_key(|(idx, ..)| *idx);
+    let indices = entries.iter().map(|(idx, ..)| *idx).collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(entries.len());
+    for ((_, old, mut method), diff) in entries.into_iter().zip(diffs(&indices)) {
+        method.method_idx_diff = diff;
+        list.push(method);
+        order.push(old);
+    }
+    Ok(order)
+}
+
+impl ClassItem {
+    /// Rewrites every index referenced by this class with `f`, and restores the orders
+    /// the format requires. `default` returns the uninitialized value of a static field,
+    /// given its index before remapping.
+    pub(crate) fn remap(
+        &mut self,
+        default: impl Fn(RawFieldIndex) -> crate::Result<EncodedValue>,
+        f: &mut IndexMap,
+    ) -> crate::Result<()> {
+        let def = &mut self.def;
+        def.class_idx = f(IndexKind::Type, def.class_idx)?;
+        if def.superclass_idx != NO_INDEX {
+            def.superclass_idx = f(IndexKind::Type, def.superclass_idx)?;
+        }
+        if def.source_file_idx != NO_INDEX {
+            def.source_file_idx = f(IndexKind::String, def.source_file_idx)?;
+        }
+        for ty in &mut self.interfaces {
+            *ty = f(IndexKind::Type, *ty)?;
+        }
+
+        if let Some(data) = &mut self.class_data {
+            // the static values follow the order of the fields, so they're sorted along with them
+            let static_fields = absolute(&data.static_fields, |field| field.field_idx_diff);
+            let mut values = std::mem::take(&mut self.static_values);
+            for idx in static_fields.iter().skip(values.len()) {
+                values.push(default(*idx)?);
+            }
+            let static_order = fields(&mut data.static_fields, f)?;
+            let mut values = static_order
+                .iter()
+                .map(|&old| std::mem::replace(&mut values[old], EncodedValue::Null))
+                .collect::<Vec<_>>();
+            while values.last().is_some_and(EncodedValue::is_default) {
+                values.pop();
+            }
+            self.static_values = values;
+            let orders = [
+                static_order,
+                fields(&mut data.instance_fields, f)?,
+                methods(&mut data.direct_methods, f)?,
+                methods(&mut data.virtual_methods, f)?,
+            ];
+            // so do the hidden API flags, which follow all members
+            if let Some(flags) = &mut self.hiddenapi_flags {
+                let mut start = 0;
+                let mut sorted = Vec::with_capacity(flags.len());
+                for order in orders {
+                    sorted.extend(order.iter().filter_map(|old| flags.get(start + old)));
+                    start += order.len();
+                }
+                *flags = sorted;
+            }
+        }
+        for v in &mut self.static_values {
+            value(v, f)?;
+        }
+
+        let code = std::mem::take(&mut self.code);
+        for (idx, mut method) in code {
+            self::code(&mut method.code, f)?;
+            if let Some(info) = &mut method.debug_info {
+                debug_info(info, f)?;
+            }
+            self.code.insert(f(IndexKind::Method, idx)?, method);
+        }
+
+        let annotations = &mut self.annotations;
+        for annotation in annotations.class.iter_mut() {
+            self::annotation(&mut annotation.annotation, f)?;
+        }
+        for (idx, set) in &mut annotations.fields {
+            *idx = f(IndexKind::Field, *idx)?;
+            for annotation in set {
+                self::annotation(&mut annotation.annotation, f)?;
+            }
+        }
+        for (idx, set) in &mut annotations.methods {
+            *idx = f(IndexKind::Method, *idx)?;
+            for annotation in set {
+                self::annotation(&mut annotation.annotation, f)?;
+            }
+        }
+        for (idx, sets) in &mut annotations.parameters {
+            *idx = f(IndexKind::Method, *idx)?;
+            for annotation in sets.iter_mut().flatten() {
+                self::annotation(&mut annotation.annotation, f)?;
+            }
+        }
+        Ok(())
+    }
+}